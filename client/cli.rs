@@ -3,15 +3,23 @@ use std::path::PathBuf;
 use chrono::prelude::*;
 use chrono::Duration;
 use chrono_english::*;
-use structopt::clap::Shell;
+use structopt::clap::{AppSettings, Shell};
 use structopt::StructOpt;
 
+use pueue::duration::parse_duration;
+use pueue::message::ExitCodeFilter;
+use pueue::task::{parse_cpu_list, Progress};
+
+use crate::commands::pick::PICK_ACTIONS;
+
 #[derive(StructOpt, Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum SubCommand {
     /// Enqueue a task for execution.
     Add {
         /// The command that should be added.
-        #[structopt(required = true)]
+        /// Variables like `{{task_id}}`, `{{group}}` or `{{date}}` are expanded, once the task starts.
+        #[structopt(required_unless = "from-yaml")]
         command: Vec<String>,
 
         /// Start the task immediately.
@@ -25,7 +33,7 @@ pub enum SubCommand {
 
         /// Delays enqueueing the task until <delay> elapses. See "enqueue" for accepted formats.
         #[structopt(name = "delay", short, long, conflicts_with = "immediate", parse(try_from_str=parse_delay_until))]
-        delay_until: Option<DateTime<Local>>,
+        delay_until: Option<Delay>,
 
         /// Assign the task to a group. Groups kind of act as separate queues.
         /// I.e. all groups run in parallel and you can specify the amount of parallel tasks for each group.
@@ -33,17 +41,205 @@ pub enum SubCommand {
         #[structopt(name = "group", short, long)]
         group: Option<String>,
 
+        /// Run the task in this directory instead of the current one.
+        /// Relative paths are resolved from the current directory.
+        /// Just like the current directory, it's translated via `client.path_mappings`.
+        #[structopt(short, long)]
+        working_directory: Option<PathBuf>,
+
         /// Start the task once all specified tasks have successfully finished.
         /// As soon as one of the dependencies fails, this task will fail as well.
         #[structopt(name = "after", short, long)]
         dependencies: Vec<usize>,
+
+        /// Start the task once any one of the specified tasks has successfully finished,
+        /// e.g. `--after-any 3,4,5`. This task only fails, if all of them fail.
+        #[structopt(long, use_delimiter = true)]
+        after_any: Vec<usize>,
+
+        /// Only start the task, once one of the specified tasks failed, e.g. to clean up or alert.
+        /// If all of them succeed, the task is skipped.
+        #[structopt(long, use_delimiter = true)]
+        after_failure: Vec<usize>,
+
+        /// Fail the task, if its dependencies aren't fulfilled within this time span since it
+        /// has been added, e.g. `--dependency-timeout 6h`. Supports `s`, `m`, `h` and `d`.
+        #[structopt(long, parse(try_from_str=parse_duration))]
+        dependency_timeout: Option<i64>,
+
+        /// Wait until the group's queue has a free slot, if `max_queued` is reached.
+        /// By default, the task is rejected in this case.
+        #[structopt(long)]
+        wait_for_slot: bool,
+
+        /// Only allow a single queued task with this key.
+        /// If there's already a queued task with the same key, the new task is dropped.
+        #[structopt(long)]
+        unique_key: Option<String>,
+
+        /// Replace an already queued task with the same `--unique-key` instead of dropping the new one.
+        #[structopt(long, requires = "unique-key")]
+        replace: bool,
+
+        /// The amount of resource slots this task occupies.
+        /// Only relevant for groups with a slot pool (`daemon.slots`).
+        #[structopt(long, default_value = "1", validator=min_one)]
+        slots: usize,
+
+        /// A named lock. Tasks with the same lock never run at the same time, even across groups.
+        #[structopt(long)]
+        lock: Option<String>,
+
+        /// Feed the stdout of another task into this task's stdin.
+        /// The task implicitly depends on the given task, i.e. it's only started after it succeeded.
+        #[structopt(long)]
+        stdin_from: Option<usize>,
+
+        /// A glob pattern of files that are produced by this task, e.g. `--artifact 'out/*.csv'`.
+        /// Relative patterns are resolved from the current directory once the task finished.
+        #[structopt(long = "artifact")]
+        artifacts: Vec<String>,
+
+        /// Run the task inside a fresh temporary directory.
+        /// The directory is removed once the task succeeded, but kept on failure for debugging.
+        #[structopt(long)]
+        tempdir: bool,
+
+        /// Run the task inside a container of the given image, e.g. `--container rust:1.70`.
+        /// The current directory is mounted into the container.
+        /// The container runtime can be configured via `daemon.container_runtime`.
+        #[structopt(long)]
+        container: Option<String>,
+
+        /// Execute the task on a remote host via SSH.
+        /// The host has to be configured in the `hosts` section of the config file.
+        #[structopt(long, conflicts_with = "container")]
+        host: Option<String>,
+
+        /// A short, human readable description of the task.
+        #[structopt(short, long)]
+        label: Option<String>,
+
+        /// Queued tasks with a higher priority are started first. Defaults to 0.
+        /// With `daemon.preemption`, running tasks with a lower priority are paused
+        /// to make room for this task.
+        #[structopt(long, default_value = "0", allow_hyphen_values = true)]
+        priority: i32,
+
+        /// Restrict the task to the given CPUs, e.g. `0-3,6`. Only supported on Linux.
+        /// For tasks in containers, this is passed to the container runtime instead.
+        #[structopt(long, validator=cpu_list, conflicts_with = "host")]
+        cpus: Option<String>,
+
+        /// The amount of GPUs this task needs. The task is only started, once enough GPUs
+        /// of the daemon's pool (`daemon.gpus`) are free. They're passed via `CUDA_VISIBLE_DEVICES`.
+        #[structopt(long, default_value = "0")]
+        gpus: usize,
+
+        /// Keep the task waiting in the queue, until the given path exists.
+        /// Relative paths are resolved from the current directory.
+        #[structopt(long)]
+        wait_for_path: Option<PathBuf>,
+
+        /// Keep the task waiting in the queue, until a TCP connection to `host:port` succeeds.
+        /// The task fails, if the host isn't reachable within `daemon.host_check_timeout`.
+        #[structopt(long, validator=host_and_port)]
+        wait_for_host: Option<String>,
+
+        /// Add one task for each combination of the given values, e.g.
+        /// `--param res=720,1080 --param codec=x264,x265 -- encode.sh {res} {codec}`.
+        /// The `{name}` placeholders in the command are replaced by the values.
+        #[structopt(long = "param")]
+        parameters: Vec<String>,
+
+        /// Add an array job with one task for each index of the range, e.g. `--array 1-100`.
+        /// Each task gets its index via the `PUEUE_ARRAY_INDEX` environment variable.
+        /// The array is shown as a single entry by `status`, unless it's expanded.
+        #[structopt(long, parse(try_from_str=parse_array_range), conflicts_with = "unique-key")]
+        array: Option<(usize, usize)>,
+
+        /// Add all tasks of a YAML file at once, either all of them or none.
+        /// Each task has a name and may specify its `command`, `group`, `label`, `path`, `env`,
+        /// `stashed` and the names of the tasks it runs `after`.
+        /// Prints the ids of the added tasks by their names.
+        #[structopt(long, conflicts_with_all = &["command", "array", "parameters"])]
+        from_yaml: Option<PathBuf>,
+
+        /// Don't execute the task, if the same command succeeded in the same directory and
+        /// environment within this time span, e.g. `--cache 24h`. Supports `s`, `m`, `h` and `d`.
+        /// The task is marked as successful right away and gets the output of the cached run.
+        #[structopt(long, parse(try_from_str=parse_duration))]
+        cache: Option<i64>,
+
+        /// A command that stops the task on `pueue kill` instead of SIGTERM, e.g. `docker stop app`.
+        /// It gets the pid of the task via `PUEUE_PID`. The task is still killed with SIGKILL,
+        /// if it doesn't exit within the grace period.
+        #[structopt(long)]
+        stop_command: Option<String>,
+
+        /// A command that's executed, once the task finished, failed or has been killed,
+        /// e.g. to remove scratch directories. It runs with the task's environment and gets
+        /// its result via `PUEUE_RESULT` and `PUEUE_EXIT_CODE`.
+        /// Overwrites the group's default of `daemon.on_exit`.
+        #[structopt(long)]
+        on_exit: Option<String>,
+
+        /// A setup command that's executed right before the command in the same directory
+        /// and environment. If it fails, the task fails without executing the command.
+        #[structopt(long)]
+        before: Option<String>,
+
+        /// Run the command via a login shell of the current `$SHELL`,
+        /// so it sees the same environment as an interactive shell.
+        #[structopt(long)]
+        login_shell: bool,
+
+        /// Load the `.envrc` of the working directory via `direnv` before running the command.
+        /// Requires `direnv` to be installed on the daemon's machine.
+        #[structopt(long)]
+        direnv: bool,
+
+        /// Only pass the given environment variables of the current shell to the task,
+        /// e.g. `--copy-env PATH,HOME`. The daemon's environment isn't passed either.
+        #[structopt(long, use_delimiter = true, conflicts_with = "no-env")]
+        copy_env: Vec<String>,
+
+        /// Don't pass any environment variables of the current shell or the daemon to the task.
+        #[structopt(long)]
+        no_env: bool,
+
+        /// Force line buffering of the command's output via `stdbuf`, so `pueue follow` shows
+        /// the output line by line instead of in big chunks.
+        /// This only affects programs that use the C standard library's buffering.
+        #[structopt(long)]
+        line_buffered: bool,
+    },
+    /// Add a pipeline of stages from a YAML file, e.g. build, test and deploy.
+    /// Each task of a stage depends on all tasks of the previous stage.
+    /// All tasks are tagged with the pipeline's name. See `status --pipeline` and `cancel`.
+    Apply {
+        /// The path to the pipeline file.
+        path: PathBuf,
     },
     /// Remove tasks from the list.
     /// Running or paused tasks need to be killed first.
+    /// Removed tasks are kept in the trash for `daemon.trash_retention` seconds.
     Remove {
-        /// The task ids to be removed.
-        #[structopt(required = true)]
-        task_ids: Vec<usize>,
+        /// The task ids to be removed. Ranges like `3-12` are supported as well.
+        #[structopt(required_unless_one = &["group", "pattern"], parse(try_from_str=parse_task_range))]
+        task_ids: Vec<(usize, usize)>,
+
+        /// Only remove tasks of this group.
+        #[structopt(short, long)]
+        group: Option<String>,
+
+        /// Only remove tasks whose command matches this glob pattern, e.g. `--match 'ffmpeg*'`.
+        #[structopt(name = "pattern", long = "match")]
+        pattern: Option<String>,
+
+        /// Don't ask for confirmation, see `client.confirm_destructive`.
+        #[structopt(short = "y", long)]
+        force: bool,
     },
     /// Switches the queue position of two commands. Only works on queued and stashed commands.
     Switch {
@@ -55,16 +251,36 @@ pub enum SubCommand {
     /// Stashed tasks won't be automatically started.
     /// Either enqueue them, to be normally handled or explicitly start them.
     Stash {
-        /// The id(s) of the tasks you want to stash.
+        /// The id(s) of the tasks you want to stash. Ranges like `3-12` are supported as well.
+        #[structopt(required_unless_one = &["group", "pattern"], parse(try_from_str=parse_task_range))]
+        task_ids: Vec<(usize, usize)>,
+
+        /// Only stash tasks of this group.
+        #[structopt(short, long)]
+        group: Option<String>,
+
+        /// Only stash tasks whose command matches this glob pattern, e.g. `--match 'ffmpeg*'`.
+        #[structopt(name = "pattern", long = "match")]
+        pattern: Option<String>,
+    },
+    /// Move queued or stashed tasks to another group.
+    /// Their position, dependencies and all other properties are kept.
+    Move {
+        /// The id(s) of the tasks you want to move.
         #[structopt(required = true)]
         task_ids: Vec<usize>,
+
+        /// The group the tasks should be moved to.
+        /// Use `default` for the default queue.
+        #[structopt(name = "group", short, long)]
+        group: String,
     },
     /// Enqueue stashed tasks. They'll be handled normally afterwards.
     #[structopt(after_help = "DELAY FORMAT:
 
-    The --delay argument must be either a number of seconds or a \"date expression\" similar to GNU \
-    `date -d` with some extensions. It does not attempt to parse all natural language, but is \
-    incredibly flexible. Here are some supported examples.
+    The --delay argument must be either a duration like `30s`, `15m`, `2h`, `1d` or `1h30m`, \
+    a number of seconds or a \"date expression\" similar to GNU `date -d` with some extensions. \
+    It does not attempt to parse all natural language, but is incredibly flexible. Here are some supported examples.
 
     2020-04-01T18:30:00   // RFC 3339 timestamp
     2020-4-1 18:2:30      // Optional leading zeros
@@ -81,6 +297,15 @@ pub enum SubCommand {
     1d 03:00              // The closest 3:00 after 1 day (24 hours)
     3h                    // 3 hours from now
     3600s                 // 3600 seconds from now
+    1h30m                 // 90 minutes from now
+    in 3 hours            // 3 hours from now
+    tomorrow 9am          // Tomorrow at 09:00
+    next monday           // The closest monday in the future
+    next week             // 1 week at the current time
+    9am                   // The next 09:00, i.e. tomorrow if it already passed today
+
+    Date expressions are echoed back for confirmation, before the task is sent to the daemon. \
+    Disable this via `client.confirm_date_expressions`.
 ")]
     Enqueue {
         /// The id(s) of the tasks you want to enqueue.
@@ -88,7 +313,7 @@ pub enum SubCommand {
 
         /// Delay enqueuing the tasks until <delay> elapses. See DELAY FORMAT below.
         #[structopt(name = "delay", short, long, parse(try_from_str=parse_delay_until))]
-        delay_until: Option<DateTime<Local>>,
+        delay_until: Option<Delay>,
     },
 
     /// Resume operation of specific tasks or groups of tasks.
@@ -118,9 +343,27 @@ pub enum SubCommand {
     /// Restart task(s).
     /// Identical tasks will be created and by default enqueued.
     Restart {
-        /// The tasks you want to restart.
-        #[structopt(required = true)]
-        task_ids: Vec<usize>,
+        /// The tasks you want to restart. Ranges like `3-12` are supported as well.
+        #[structopt(required_unless_one = &["group", "pattern", "failed"], parse(try_from_str=parse_task_range))]
+        task_ids: Vec<(usize, usize)>,
+
+        /// Only restart failed tasks. Without any ids, all failed tasks are restarted,
+        /// e.g. `pueue restart --failed -g ffmpeg` after a transient outage.
+        #[structopt(long)]
+        failed: bool,
+
+        /// Reset the finished tasks and enqueue them again, instead of creating new tasks.
+        /// The tasks keep their ids and their old output is removed.
+        #[structopt(long)]
+        in_place: bool,
+
+        /// Restart all finished tasks of this group, e.g. of `daemon.quarantine_group`.
+        #[structopt(short, long)]
+        group: Option<String>,
+
+        /// Only restart tasks whose command matches this glob pattern, e.g. `--match 'ffmpeg*'`.
+        #[structopt(name = "pattern", long = "match")]
+        pattern: Option<String>,
 
         /// Immediately start the task(s).
         #[structopt(name = "immediate", short, long)]
@@ -172,8 +415,9 @@ pub enum SubCommand {
 
     /// Kill specific running tasks or various groups of tasks.
     Kill {
-        /// The tasks that should be killed.
-        task_ids: Vec<usize>,
+        /// The tasks that should be killed. Ranges like `3-12` are supported as well.
+        #[structopt(parse(try_from_str=parse_task_range))]
+        task_ids: Vec<(usize, usize)>,
 
         /// Kill all running tasks in the default queue. Pause the default queue.
         #[structopt(short, long, group("kill"))]
@@ -191,6 +435,27 @@ pub enum SubCommand {
         /// Useful when working with shell scripts.
         #[structopt(short, long, group("kill"))]
         children: bool,
+
+        /// The time to wait after sending SIGTERM, before the tasks are killed with SIGKILL,
+        /// e.g. `--grace 30s`. Overwrites `daemon.kill_grace_period`. Use 0 to kill right away.
+        #[structopt(long, parse(try_from_str=parse_duration))]
+        grace: Option<i64>,
+
+        /// Only kill tasks whose command matches this glob pattern, e.g. `--match 'ffmpeg*'`.
+        /// Combined with `--group`, only the matching tasks of that group are killed
+        /// and the group isn't paused.
+        #[structopt(name = "pattern", long = "match")]
+        pattern: Option<String>,
+
+        /// Don't ask for confirmation, see `client.confirm_destructive`.
+        #[structopt(short = "y", long)]
+        force: bool,
+    },
+
+    /// Cancel a pipeline. Its running tasks are killed and its pending tasks won't be started.
+    Cancel {
+        /// The name of the pipeline.
+        pipeline: String,
     },
 
     /// Send something to a task. Useful for sending confirmations such as 'y\n'.
@@ -202,14 +467,19 @@ pub enum SubCommand {
         input: String,
     },
 
-    /// Edit the command or path of a stashed or queued task.
-    /// This edits the command of the task by default.
+    /// Edit the command, path, label and group of a stashed or queued task.
+    /// By default, all of them are opened as a small YAML document in your $EDITOR.
+    /// The changes are only applied, if all of them are valid.
     #[structopt(verbatim_doc_comment)]
     Edit {
         /// The id of the task.
         task_id: usize,
 
-        /// Edit the path of the task.
+        /// Only edit the command of the task.
+        #[structopt(short, long, conflicts_with = "path")]
+        command: bool,
+
+        /// Only edit the path of the task.
         #[structopt(short, long)]
         path: bool,
     },
@@ -235,9 +505,119 @@ pub enum SubCommand {
         #[structopt(short, long)]
         json: bool,
 
+        /// Print the tasks as CSV to stdout, e.g. for spreadsheets or `awk`.
+        /// The columns stay stable, new columns are only ever appended.
+        #[structopt(long, conflicts_with_all = &["json", "schedule", "tree"])]
+        csv: bool,
+
         #[structopt(short, long)]
         /// Only show tasks of a specific group
         group: Option<String>,
+
+        /// Only request this many tasks from the daemon.
+        /// Overwrites the `client.status_limit` setting.
+        #[structopt(long)]
+        limit: Option<usize>,
+
+        /// Skip this many tasks. Useful in combination with `--limit`.
+        #[structopt(long, default_value = "0")]
+        offset: usize,
+
+        /// Only show tasks whose command, label or path contain this text.
+        #[structopt(short, long)]
+        query: Option<String>,
+
+        /// Only show tasks of this pipeline, see `apply`.
+        #[structopt(long)]
+        pipeline: Option<String>,
+
+        /// Only show tasks that have been added after this date, e.g. "1 hour ago" or 2021-05-01.
+        #[structopt(long, parse(try_from_str=parse_date_time))]
+        added_after: Option<DateTime<Local>>,
+
+        /// Only show tasks that have been added before this date.
+        #[structopt(long, parse(try_from_str=parse_date_time))]
+        added_before: Option<DateTime<Local>>,
+
+        /// Only show tasks that finished after this date.
+        #[structopt(long, parse(try_from_str=parse_date_time))]
+        finished_after: Option<DateTime<Local>>,
+
+        /// Only show tasks that finished before this date.
+        #[structopt(long, parse(try_from_str=parse_date_time))]
+        finished_before: Option<DateTime<Local>>,
+
+        /// Only show finished tasks with a matching exit code, e.g. `1`, `!=0` or `>=2`.
+        /// Tasks that have been killed by a signal don't have an exit code.
+        #[structopt(long)]
+        exit_code: Option<ExitCodeFilter>,
+
+        /// Sort the tasks of each group by this column. Tasks are sorted by id by default.
+        #[structopt(long, possible_values = &["status", "runtime", "start", "id"])]
+        sort: Option<String>,
+
+        /// Reverse the order of the tasks in each group.
+        #[structopt(long)]
+        reverse: bool,
+
+        /// Only show tasks that are scheduled to be enqueued at a later time.
+        /// The tasks are sorted by the time they're enqueued.
+        #[structopt(long)]
+        schedule: bool,
+
+        /// Show the last line of output of running tasks, e.g. to see the progress at a glance.
+        /// The output is read from the local log files.
+        #[structopt(long)]
+        snippet: bool,
+
+        /// Show the individual tasks of this array job instead of a single entry.
+        #[structopt(long)]
+        expand: Vec<usize>,
+
+        /// Show the tasks of each group as a tree, in which tasks are shown below their dependencies.
+        #[structopt(long, conflicts_with = "json")]
+        tree: bool,
+
+        /// The maximum width of the command column. Overwrites `client.max_command_width`.
+        #[structopt(long)]
+        max_command_width: Option<usize>,
+
+        /// Cut long commands instead of wrapping them onto multiple lines.
+        #[structopt(long, conflicts_with = "wrap")]
+        truncate: bool,
+
+        /// Wrap long commands onto multiple lines, even if `client.truncate_commands` is set.
+        #[structopt(long)]
+        wrap: bool,
+
+        /// Don't wrap or truncate anything, even on terminals.
+        /// Piped output is always printed in full width.
+        #[structopt(long, conflicts_with_all = &["truncate", "max-command-width"])]
+        full_width: bool,
+
+        /// Only print one line per group with the number of queued, running, successful and failed tasks.
+        #[structopt(long, conflicts_with_all = &["csv", "schedule", "tree"])]
+        summary: bool,
+    },
+
+    /// Interactively pick tasks from a fuzzy-searchable list and print their ids.
+    /// Use `Tab` to mark multiple tasks and `Enter` to confirm.
+    Pick {
+        /// Directly apply an action to the picked tasks instead of printing their ids.
+        #[structopt(long, possible_values = PICK_ACTIONS)]
+        then: Option<String>,
+    },
+
+    /// Keep the connection to the daemon open and run pueue subcommands interactively,
+    /// e.g. `status` or `kill 3`. Use `Tab` to complete subcommands, task ids and groups.
+    /// Global options like `--port` can't be changed inside of the shell. Exit with `exit` or `Ctrl+D`.
+    Shell,
+
+    /// Print the ids of all tasks whose command, label or path contain the pattern.
+    /// The ids can be passed to other commands, e.g. `pueue kill $(pueue find ffmpeg)`.
+    Find {
+        /// The text to search for.
+        pattern: String,
     },
 
     /// Display the log output of finished tasks.
@@ -245,12 +625,58 @@ pub enum SubCommand {
     Log {
         /// Specify for which specific tasks you want to see the output.
         task_ids: Vec<usize>,
-        /// Print the current state as json.
-        /// Includes EVERYTHING.
+        /// Print the tasks' metadata and output as json.
+        #[structopt(short, long)]
+        json: bool,
+
+        /// Put stdout and stderr into separate fields of the json output.
+        #[structopt(long, requires = "json")]
+        split: bool,
+
+        /// Only show lines containing this text.
+        /// The logs are searched by the daemon.
+        #[structopt(long)]
+        grep: Option<String>,
+
+        /// The amount of lines shown before and after each match of `--grep`.
+        #[structopt(short = "C", long, default_value = "2")]
+        context: usize,
+
+        /// Remove colors and other terminal escape sequences from the output.
+        /// Progress bars only show their final state.
+        #[structopt(long)]
+        strip_ansi: bool,
+    },
+
+    /// Show everything about a single task, including its log files and previous attempts.
+    Info {
+        /// The id of the task.
+        task_id: usize,
+    },
+
+    /// Display the files that have been produced by a task.
+    /// Artifacts are declared via `add --artifact` and resolved once the task finished.
+    Artifacts {
+        /// The id of the task.
+        task_id: usize,
+
+        /// Print the artifacts as json.
         #[structopt(short, long)]
         json: bool,
     },
 
+    /// Print the dependency graph of the tasks in Graphviz dot format.
+    /// The nodes are colored by the status of the tasks, e.g. `pueue graph | dot -Tsvg > graph.svg`.
+    Graph {
+        /// Only include tasks of a specific group.
+        #[structopt(short, long)]
+        group: Option<String>,
+
+        /// Only include tasks of this pipeline, see `apply`.
+        #[structopt(long)]
+        pipeline: Option<String>,
+    },
+
     /// Follow the output of a currently running task.
     /// This command works like tail -f.
     Follow {
@@ -262,10 +688,39 @@ pub enum SubCommand {
         /// Show stderr instead of stdout.
         #[structopt(short, long)]
         err: bool,
+
+        /// Follow all running tasks at once. Each line is prefixed with the id of its task.
+        /// Tasks that start in the meantime are followed as well.
+        #[structopt(short, long, conflicts_with = "task-id")]
+        all: bool,
+
+        /// Only follow the running tasks of this group. Implies `--all`.
+        #[structopt(short, long, conflicts_with = "task-id")]
+        group: Option<String>,
+
+        /// Remove colors and other terminal escape sequences from the output.
+        /// Without this flag, the output is passed through as it is.
+        #[structopt(long, conflicts_with_all = &["all", "group"])]
+        strip_ansi: bool,
+    },
+
+    /// Revert the most recent `remove`, `clean` or `cancel` by restoring the affected tasks.
+    /// Killed processes aren't started again and the logs of cleaned tasks are gone.
+    Undo,
+
+    /// List, restore or delete removed tasks.
+    Trash {
+        #[structopt(subcommand)]
+        cmd: TrashCommand,
     },
 
     /// Remove all finished tasks from the list (also clears logs).
-    Clean,
+    Clean {
+        /// Only remove tasks that finished successfully.
+        /// Failed and killed tasks are kept for investigation.
+        #[structopt(short, long)]
+        successful_only: bool,
+    },
 
     /// Kill all running tasks, remove all tasks and reset max_task_id.
     Reset {
@@ -273,10 +728,20 @@ pub enum SubCommand {
         /// Useful when working with shell scripts.
         #[structopt(short, long)]
         children: bool,
+
+        /// Don't ask for confirmation, see `client.confirm_destructive`.
+        #[structopt(short = "y", long)]
+        force: bool,
     },
 
     /// Remotely shut down the daemon. Should only be used if the daemon isn't started by a service manager.
-    Shutdown,
+    /// This works over TCP as well, so remote daemons can be stopped cleanly.
+    Shutdown {
+        /// Don't kill the running tasks, but wait for them to finish.
+        /// No new tasks will be started in the meantime.
+        #[structopt(long)]
+        graceful: bool,
+    },
 
     /// Set the amount of allowed parallel tasks.
     Parallel {
@@ -289,6 +754,76 @@ pub enum SubCommand {
         group: Option<String>,
     },
 
+    /// Send a custom message from within a task, e.g. to report its progress.
+    /// The message is stored alongside the task and triggers the `daemon.notify_callback`.
+    Notify {
+        /// The message to send.
+        text: String,
+
+        /// The id of the task the message belongs to.
+        /// Defaults to the `PUEUE_TASK_ID` environment variable, which is set for every task.
+        #[structopt(short, long)]
+        task_id: Option<usize>,
+    },
+
+    /// Report the progress of a task from within the task, e.g. `42%` or `5/120`.
+    /// The progress is shown in `pueue status`.
+    Progress {
+        /// The current progress as percentage or as processed/total items.
+        progress: Progress,
+
+        /// The id of the task. Defaults to the `PUEUE_TASK_ID` environment variable.
+        #[structopt(short, long)]
+        task_id: Option<usize>,
+    },
+
+    /// Wait until tasks are finished. This can be used to block scripts until a batch is done.
+    /// By default, wait until no task of the default group is queued or running anymore.
+    Wait {
+        /// Wait for these specific tasks.
+        task_ids: Vec<usize>,
+
+        /// Wait for all tasks of this group.
+        #[structopt(short, long, conflicts_with = "all")]
+        group: Option<String>,
+
+        /// Wait until no task of any group is queued or running anymore.
+        #[structopt(short, long)]
+        all: bool,
+    },
+
+    /// Pull and execute tasks of a worker group from the daemon.
+    /// This turns the current machine into a remote worker of the daemon you're connected to.
+    /// Worker groups are configured via `daemon.worker_groups`.
+    Worker {
+        /// The worker group to pull tasks from.
+        group: String,
+
+        /// The name of this worker, which is shown in the task's log.
+        #[structopt(short, long)]
+        name: Option<String>,
+    },
+
+    /// Show information about the daemon you're connected to.
+    /// This includes its version, uptime, pid, config path and socket.
+    DaemonInfo {
+        /// Print the information as json.
+        #[structopt(short, long)]
+        json: bool,
+    },
+
+    /// Show statistics about previous runs of each label or command.
+    /// This includes the amount of runs, the mean and 95th percentile runtime and the failure rate.
+    Stats {
+        /// Only show labels and commands that contain this text.
+        #[structopt(short, long)]
+        command: Option<String>,
+
+        /// Print the statistics as json.
+        #[structopt(short, long)]
+        json: bool,
+    },
+
     /// Generates shell completion files.
     /// This can be ignored during normal operations.
     Completions {
@@ -297,13 +832,55 @@ pub enum SubCommand {
         /// The output directory to which the file should be written.
         output_directory: PathBuf,
     },
+
+    /// Generates the man page of the client from its command line definitions.
+    /// This can be ignored during normal operations.
+    GenerateMan {
+        /// The output directory to which the man page should be written.
+        /// Use `pueued --generate-man` for the man page of the daemon.
+        output_directory: PathBuf,
+    },
+
+    /// Print task ids or group names of the daemon for dynamic shell completion.
+    /// This is used by the scripts in `utils/completions`.
+    #[structopt(name = "__complete", setting = AppSettings::Hidden)]
+    Complete {
+        #[structopt(possible_values = &["tasks", "groups"])]
+        kind: String,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum TrashCommand {
+    /// Show all removed tasks that can still be restored.
+    List {
+        /// Print the removed tasks as json.
+        #[structopt(short, long)]
+        json: bool,
+    },
+    /// Put removed tasks back into the task list.
+    Restore {
+        /// The ids of the removed tasks.
+        #[structopt(required = true)]
+        task_ids: Vec<usize>,
+    },
+    /// Delete all removed tasks for good.
+    Empty,
 }
 
 #[derive(StructOpt, Debug)]
 #[structopt(
     name = "Pueue client",
     about = "Interact with the Pueue daemon",
-    author = "Arne Beer <contact@arne.beer>"
+    author = "Arne Beer <contact@arne.beer>",
+    after_help = "EXIT CODES:
+    0   Success.
+    1   The daemon rejected the command or it failed for all given tasks.
+        `status` with a filter, `find` and `log` didn't match any task.
+        `log --grep` didn't find anything.
+        `wait <task_ids>` waited for a task that didn't succeed.
+    2   An error occurred, e.g. invalid arguments or the daemon couldn't be reached.
+"
 )]
 pub struct Opt {
     /// Verbose mode (-v, -vv, -vvv)
@@ -326,23 +903,134 @@ pub struct Opt {
     #[structopt(short, long)]
     pub config: Option<PathBuf>,
 
+    /// Connect to the daemon, even if its version is incompatible with this client.
+    #[structopt(long)]
+    pub force: bool,
+
+    /// Don't print anything and only communicate via the exit code, see EXIT CODES below.
+    /// Confirmation prompts are skipped. Errors are still printed to stderr.
+    #[structopt(short, long)]
+    pub quiet: bool,
+
     #[structopt(subcommand)]
     pub cmd: SubCommand,
 }
 
-fn parse_delay_until(src: &str) -> Result<DateTime<Local>, String> {
-    if let Ok(seconds) = src.parse::<i64>() {
-        let delay_until = Local::now() + Duration::seconds(seconds);
-        return Ok(delay_until);
+/// The point in time of a `--delay`.
+#[derive(Clone, Debug)]
+pub struct Delay {
+    pub time: DateTime<Local>,
+    /// Whether the delay has been given as a date expression like `tomorrow 9am`.
+    /// Those are easy to misinterpret, so they're confirmed before they're used.
+    pub is_expression: bool,
+}
+
+/// Parse a relative duration like `15m` or `1h30m`, or a date expression like `tomorrow 9am`.
+fn parse_delay_until(src: &str) -> Result<Delay, String> {
+    let now = Local::now();
+    let duration = parse_duration(src);
+    if let Ok(seconds) = duration {
+        return Ok(Delay {
+            time: now + Duration::seconds(seconds),
+            is_expression: false,
+        });
+    }
+
+    if let Some(time) = parse_date_expression(src, now) {
+        return Ok(Delay {
+            time,
+            is_expression: true,
+        });
     }
 
-    if let Ok(date_time) = parse_date_string(src, Local::now(), Dialect::Us) {
-        return Ok(date_time);
+    // Something like `5x` was most likely meant to be a duration.
+    let looks_like_duration = src.starts_with(|character: char| character.is_ascii_digit())
+        && !src.contains(|character: char| character.is_whitespace() || character == ':');
+    match duration {
+        Err(error) if looks_like_duration => Err(error),
+        _ => Err(String::from(
+            "could not parse as duration (e.g. 30s, 15m, 2h or 1d) or date expression",
+        )),
     }
+}
+
+/// Parse an inclusive index range like `1-100`. A single index is a range of one.
+fn parse_array_range(src: &str) -> Result<(usize, usize), String> {
+    parse_range(src).ok_or_else(|| format!("Invalid array range {}. Use e.g. 1-100", src))
+}
+
+/// Parse a single task id `3` or an inclusive range of task ids `3-12`.
+fn parse_task_range(src: &str) -> Result<(usize, usize), String> {
+    parse_range(src).ok_or_else(|| format!("Invalid task id or range {}. Use e.g. 3 or 3-12", src))
+}
+
+/// Parse an inclusive range `start-end`. A single number is a range with a single element.
+fn parse_range(src: &str) -> Option<(usize, usize)> {
+    let (start, end) = match src.split_once('-') {
+        Some((start, end)) => (start, end),
+        None => (src, src),
+    };
+    let start: usize = start.trim().parse().ok()?;
+    let end: usize = end.trim().parse().ok()?;
+    if start > end {
+        return None;
+    }
+
+    Some((start, end))
+}
 
-    Err(String::from(
-        "could not parse as seconds or date expression",
-    ))
+/// Resolve a date expression like `tomorrow 9am`, `next monday`, `next week` or `in 3 hours`.
+/// On top of the formats of `chrono_english`, this supports the `in` and `next` prefixes.
+fn parse_date_expression(src: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let expression = src.trim().to_lowercase();
+    // `in 3 hours` is the same as `3 hours`.
+    let expression = expression.strip_prefix("in ").unwrap_or(&expression);
+    if let Ok(seconds) = parse_duration(expression) {
+        return Some(now + Duration::seconds(seconds));
+    }
+
+    let expression = match expression {
+        "noon" => "12:00".to_string(),
+        "midnight" => "tomorrow 00:00".to_string(),
+        "next day" | "next week" | "next month" | "next year" => expression.replace("next", "1"),
+        _ => expression
+            .strip_prefix("today ")
+            .unwrap_or(expression)
+            .to_string(),
+    };
+    let time = parse_date_string(&expression, now, Dialect::Us).ok()?;
+
+    // A time of day that already passed today, e.g. `9am` in the evening, means tomorrow.
+    let is_time_of_day = expression
+        .trim_end_matches("am")
+        .trim_end_matches("pm")
+        .chars()
+        .all(|character| character.is_ascii_digit() || character == ':');
+    // Move by calendar day instead of 24 hours, so the time of day survives DST changes.
+    if time < now && is_time_of_day {
+        let tomorrow = time.naive_local() + Duration::days(1);
+        return Local.from_local_datetime(&tomorrow).earliest();
+    }
+
+    Some(time)
+}
+
+fn parse_date_time(src: &str) -> Result<DateTime<Local>, String> {
+    parse_date_string(src, Local::now(), Dialect::Us)
+        .map_err(|_| String::from("could not parse as date expression"))
+}
+
+/// Validator function. The input string has to be a list of CPUs like `0-3,6`.
+fn cpu_list(value: String) -> Result<(), String> {
+    parse_cpu_list(&value).map(|_| ())
+}
+
+/// Validator function. The input string has to be in the `host:port` format.
+fn host_and_port(value: String) -> Result<(), String> {
+    match value.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+        _ => Err("The address has to be in the `host:port` format".into()),
+    }
 }
 
 /// Validator function. The input string has to be parsable as int and bigger than 0
@@ -1,20 +1,41 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::env::{current_dir, vars};
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context, Result};
+use chrono::Local;
+use crossterm::tty::IsTty;
 use log::error;
+use structopt::StructOpt;
 
+use pueue::log::strip_ansi;
 use pueue::message::*;
 use pueue::platform::socket::*;
 use pueue::protocol::*;
 use pueue::settings::Settings;
+use pueue::task::{EnvironmentMode, Task, TaskStatus};
+use pueue::tls::{client_config, connect};
 
-use crate::cli::{Opt, SubCommand};
+use crate::cli::{Opt, SubCommand, TrashCommand};
+use crate::commands::add::*;
+use crate::commands::complete::*;
 use crate::commands::edit::*;
+use crate::commands::find::*;
+use crate::commands::follow_all::*;
+use crate::commands::get_state;
 use crate::commands::local_follow::*;
+use crate::commands::notify::*;
+use crate::commands::pick::*;
 use crate::commands::restart::*;
+use crate::commands::shell::*;
+use crate::commands::wait::*;
+use crate::commands::worker::*;
 use crate::output::*;
+use crate::output_helper::format_duration;
+
+/// Removing at least this many tasks at once asks for confirmation, see `client.confirm_destructive`.
+const CONFIRM_REMOVE_THRESHOLD: usize = 10;
 
 /// This struct contains the base logic for the client.
 /// The client is responsible for connecting to the daemon, sending instructions
@@ -26,33 +47,36 @@ pub struct Client {
     opt: Opt,
     settings: Settings,
     socket: Socket,
+    /// The exit code of the client, see the EXIT CODES of `pueue --help`.
+    exit_code: i32,
 }
 
 impl Client {
     /// Connect to the daemon, authorize via secret and return a new initialized Client.
     pub async fn new(settings: Settings, opt: Opt) -> Result<Self> {
-        // // Commandline argument overwrites the configuration files values for address
-        // let address = if let Some(address) = opt.address.clone() {
-        //     address
-        // } else {
-        //     settings.client.daemon_address
-        // };
-
         // Commandline argument overwrites the configuration files values for port
-        let (unix_socket_path, port) = {
+        let (unix_socket_path, address) = {
             // Always prefer commandline options
             if let Some(path) = opt.unix_socket_path.clone() {
                 (Some(path), None)
             } else if let Some(port) = opt.port.clone() {
-                (None, Some(port))
+                (None, Some(settings.shared.client_tcp_address(&port)))
             } else if settings.shared.use_unix_socket {
                 (Some(settings.shared.unix_socket_path.clone()), None)
             } else {
-                (None, Some(settings.shared.port.clone()))
+                let port = &settings.shared.port;
+                (None, Some(settings.shared.client_tcp_address(port)))
             }
         };
 
-        let mut socket = get_client(unix_socket_path, port).await?;
+        let use_tcp = address.is_some();
+        let mut socket = get_client(unix_socket_path, address).await?;
+        // Authenticate via certificate, before the secret is sent.
+        if use_tcp {
+            if let Some(config) = client_config(&settings)? {
+                socket = connect(socket, config, settings.shared.client_host()).await?;
+            }
+        }
 
         // Send the secret to the daemon
         // In case everything was successful, we get a short `hello` response from the daemon.
@@ -63,10 +87,38 @@ impl Client {
             bail!("Daemon went away after initial connection. Did you use the correct secret?")
         }
 
+        // Exchange versions, so incompatible daemons are detected early on.
+        let version = env!("CARGO_PKG_VERSION");
+        send_message(Message::Version(version.to_string()), &mut socket).await?;
+        let daemon_version = match receive_message(&mut socket).await {
+            Ok(Message::Version(daemon_version)) => daemon_version,
+            Ok(Message::Failure(text)) => bail!(text),
+            _ => bail!(
+                "The daemon didn't answer the version handshake. \
+                It's probably running an older version of Pueue. Try restarting the daemon."
+            ),
+        };
+        if !versions_are_compatible(version, &daemon_version) {
+            if !opt.force {
+                bail!(
+                    "Client version {} is incompatible with daemon version {}. \
+                    Use `--force` to connect anyway.",
+                    version,
+                    daemon_version
+                );
+            }
+            // Stdout might be parsed, e.g. with `--json`, so warnings go to stderr.
+            eprintln!(
+                "Warning: Client version {} is incompatible with daemon version {}.",
+                version, daemon_version
+            );
+        }
+
         Ok(Client {
             opt,
             settings,
             socket,
+            exit_code: 0,
         })
     }
 
@@ -75,16 +127,81 @@ impl Client {
     /// we can finally start doing stuff.
     ///
     /// The command handling is splitted into "simple" and "complex" commands.
-    pub async fn start(&mut self) -> Result<()> {
+    /// Returns the exit code of the client.
+    pub async fn start(&mut self) -> Result<i32> {
+        if let SubCommand::Shell = self.opt.cmd {
+            self.run_shell().await?;
+        } else {
+            self.run_command().await?;
+        }
+
+        Ok(self.exit_code)
+    }
+
+    async fn run_command(&mut self) -> Result<()> {
+        self.confirm_date_expression()?;
+
         // Return early, if the command has already been handled.
         if self.handle_complex_command().await? {
             return Ok(());
         }
 
         // The handling of "generic" commands is encapsulated in this function.
-        self.handle_simple_command().await?;
+        self.handle_simple_command().await
+    }
 
-        Ok(())
+    /// Read subcommands line by line and run them over the existing connection.
+    /// The exit code of the shell is the one of the last command.
+    async fn run_shell(&mut self) -> Result<()> {
+        let mut editor = LineEditor::default();
+        loop {
+            // Fetch the state before each prompt, so the completion knows about new tasks.
+            let state = get_state(&mut self.socket).await?;
+            let line = match editor.read_line(&Completer::new(&state))? {
+                Some(line) => line,
+                None => return Ok(()),
+            };
+
+            let words = match split_words(&line) {
+                Ok(words) => words,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    continue;
+                }
+            };
+            match words.first().map(String::as_str) {
+                None => continue,
+                Some("exit") | Some("quit") => return Ok(()),
+                _ => (),
+            }
+
+            let opt = match Opt::from_iter_safe(std::iter::once("pueue".to_string()).chain(words)) {
+                Ok(opt) => opt,
+                Err(error) => {
+                    // Clap also uses errors to display the help text.
+                    if error.use_stderr() {
+                        eprintln!("{}", error.message);
+                    } else {
+                        println!("{}", error.message);
+                    }
+                    continue;
+                }
+            };
+            match opt.cmd {
+                SubCommand::Shell => eprintln!("You're already inside of a shell."),
+                SubCommand::Completions { .. } | SubCommand::GenerateMan { .. } => {
+                    eprintln!("This command isn't available inside of a shell.")
+                }
+                cmd => {
+                    self.opt.cmd = cmd;
+                    self.exit_code = 0;
+                    if let Err(error) = self.run_command().await {
+                        eprintln!("Error: {:?}", error);
+                        self.exit_code = 2;
+                    }
+                }
+            }
+        }
     }
 
     /// Handle all complex client-side functionalities.
@@ -98,13 +215,56 @@ impl Client {
     async fn handle_complex_command(&mut self) -> Result<bool> {
         // This match handles all "complex" commands.
         match &self.opt.cmd {
-            SubCommand::Edit { task_id, path } => {
-                let message = edit(&mut self.socket, *task_id, *path).await?;
+            SubCommand::Add {
+                group,
+                working_directory,
+                wait_for_slot,
+                parameters,
+                from_yaml,
+                ..
+            } => {
+                if let Some(path) = from_yaml {
+                    let cwd = self.daemon_path(&working_directory_or_cwd(working_directory)?)?;
+                    add_bundle(&mut self.socket, path, cwd, &self.settings.client).await?;
+                    return Ok(true);
+                }
+                if !parameters.is_empty() {
+                    let message = match self.get_message_from_opt()? {
+                        Message::Add(message) => *message,
+                        _ => unreachable!(),
+                    };
+                    add_parameter_sweep(&mut self.socket, message, parameters, *wait_for_slot)
+                        .await?;
+                    return Ok(true);
+                }
+
+                // Block until the queue of the group has room for another task.
+                // The actual add is then handled like any other simple command.
+                if *wait_for_slot {
+                    wait_for_queue_slot(&mut self.socket, group).await?;
+                }
+                Ok(false)
+            }
+            SubCommand::Apply { path } => {
+                let cwd = self.daemon_path(&current_dir()?)?;
+                apply_pipeline(&mut self.socket, path, cwd, &self.settings.client).await?;
+                Ok(true)
+            }
+            SubCommand::Edit {
+                task_id,
+                command,
+                path,
+            } => {
+                let message = edit(&mut self.socket, *task_id, *command, *path).await?;
                 self.handle_response(message);
                 Ok(true)
             }
             SubCommand::Restart {
                 task_ids,
+                failed,
+                in_place,
+                group,
+                pattern,
                 start_immediately,
                 stashed,
                 edit,
@@ -112,7 +272,9 @@ impl Client {
             } => {
                 restart(
                     &mut self.socket,
-                    task_ids.clone(),
+                    TaskSelection::from_ranges(task_ids, group.clone(), pattern.clone()),
+                    *failed,
+                    *in_place,
                     *start_immediately,
                     *stashed,
                     *edit,
@@ -121,15 +283,96 @@ impl Client {
                 .await?;
                 Ok(true)
             }
-            SubCommand::Follow { task_id, err } => {
+            SubCommand::Info { task_id } => {
+                let state = get_state(&mut self.socket).await?;
+                if !print_task_info(&state, *task_id, &self.settings) {
+                    self.exit_code = 1;
+                }
+                Ok(true)
+            }
+            SubCommand::Artifacts { task_id, json } => {
+                let state = get_state(&mut self.socket).await?;
+                print_artifacts(&state, *task_id, *json);
+                Ok(true)
+            }
+            SubCommand::Graph { group, pipeline } => {
+                let state = get_state(&mut self.socket).await?;
+                print_dependency_graph(&state, group, pipeline);
+                Ok(true)
+            }
+            SubCommand::Complete { kind } => {
+                complete(&mut self.socket, kind).await?;
+                Ok(true)
+            }
+            SubCommand::Pick { then } => {
+                pick(&mut self.socket, then).await?;
+                Ok(true)
+            }
+            SubCommand::Find { pattern } => {
+                if !find(&mut self.socket, pattern).await? {
+                    self.exit_code = 1;
+                }
+                Ok(true)
+            }
+            SubCommand::Notify { text, task_id } => {
+                notify(&mut self.socket, text, task_id).await?;
+                Ok(true)
+            }
+            SubCommand::Progress {
+                progress: value,
+                task_id,
+            } => {
+                progress(&mut self.socket, value, task_id).await?;
+                Ok(true)
+            }
+            SubCommand::Wait {
+                task_ids,
+                group,
+                all,
+            } => {
+                if !wait(&mut self.socket, task_ids, group, *all).await? {
+                    self.exit_code = 1;
+                }
+                Ok(true)
+            }
+            SubCommand::Worker { group, name } => {
+                let name = name
+                    .clone()
+                    .unwrap_or_else(|| format!("worker-{}", std::process::id()));
+                worker(&mut self.socket, group, &name, &self.settings.client).await?;
+                Ok(true)
+            }
+            SubCommand::Follow {
+                err, all, group, ..
+            } if *all || group.is_some() => {
+                // The output of multiple tasks can only be multiplexed from the local log files.
+                if !self.settings.read_local_logs() {
+                    bail!("Following all tasks requires access to the local, unencrypted logs (client.read_local_logs).");
+                }
+                follow_all(
+                    &mut self.socket,
+                    &self.settings.shared.pueue_directory,
+                    group,
+                    *err,
+                )
+                .await?;
+                Ok(true)
+            }
+            SubCommand::Follow {
+                task_id,
+                err,
+                strip_ansi,
+                ..
+            } => {
                 // Simple log output follows for local logs don't need any communication with the daemon.
                 // Thereby we handle this separately over here.
-                if self.settings.client.read_local_logs {
+                if self.settings.read_local_logs() {
                     local_follow(
                         &mut self.socket,
                         self.settings.shared.pueue_directory.clone(),
                         task_id,
                         *err,
+                        *strip_ansi,
                     )
                     .await?;
                     return Ok(true);
@@ -144,6 +387,8 @@ impl Client {
     /// This always follows a singular ping-pong pattern.
     /// One message to the daemon, one response, Done.
     async fn handle_simple_command(&mut self) -> Result<()> {
+        self.confirm_destructive_command().await?;
+
         // Create the message that should be sent to the daemon
         // depending on the given commandline options.
         let message = self.get_message_from_opt()?;
@@ -167,14 +412,27 @@ impl Client {
     ///
     /// If this function returns `Ok(true)`, the parent function will continue to receive
     /// and handle messages from the daemon. Otherwise the client will simply exit.
-    fn handle_response(&self, message: Message) -> bool {
+    fn handle_response(&mut self, message: Message) -> bool {
+        if self.is_failure(&message) {
+            self.exit_code = 1;
+        }
+
         match message {
             Message::Success(text) => print_success(&text),
             Message::Failure(text) => print_error(&text),
-            Message::StatusResponse(state) => print_state(state, &self.opt.cmd, &self.settings),
+            Message::StatusResponse(state) => print_state(*state, &self.opt.cmd, &self.settings),
             Message::LogResponse(task_logs) => print_logs(task_logs, &self.opt.cmd, &self.settings),
+            Message::LogGrepResponse(matches) => print_log_matches(matches, &self.opt.cmd),
+            Message::DaemonInfoResponse(info) => print_daemon_info(info, &self.opt.cmd),
+            Message::StatsResponse(statistics) => print_statistics(statistics, &self.opt.cmd),
+            Message::TrashResponse(trashed) => print_trash(trashed, &self.opt.cmd, &self.settings),
             Message::Stream(text) => {
-                print!("{}", text);
+                match &self.opt.cmd {
+                    SubCommand::Follow {
+                        strip_ansi: true, ..
+                    } => print!("{}", strip_ansi(&text)),
+                    _ => print!("{}", text),
+                }
                 io::stdout().flush().unwrap();
                 return true;
             }
@@ -186,41 +444,194 @@ impl Client {
 
     /// Prints a warning and prompt for given action and tasks.
     /// Returns `Ok(())` if the action was confirmed.
-    fn handle_user_confirmation(&self, action: &str, task_ids: &[usize]) -> Result<()> {
+    fn handle_user_confirmation(&self, action: &str, selection: &TaskSelection) -> Result<()> {
+        let mut targets: Vec<String> = selection
+            .task_ids
+            .iter()
+            .map(|t| format!("task{}", t.to_string()))
+            .collect();
+        targets.extend(
+            selection
+                .ranges
+                .iter()
+                .map(|(start, end)| format!("tasks {}-{}", start, end)),
+        );
+        if let Some(group) = &selection.group {
+            targets.push(format!("tasks of group {}", group));
+        }
+        if let Some(pattern) = &selection.pattern {
+            targets.push(format!("tasks matching '{}'", pattern));
+        }
+
         // printing warning and prompt
+        println!("You are trying to {}: {}", action, targets.join(", "));
+
+        ask_for_confirmation()
+    }
+
+    /// Check whether a response means that the command failed or didn't match anything.
+    fn is_failure(&self, message: &Message) -> bool {
+        match (message, &self.opt.cmd) {
+            (Message::Failure(_), _) => true,
+            (Message::LogGrepResponse(matches), _) => matches.is_empty(),
+            (Message::LogResponse(task_logs), SubCommand::Log { task_ids, .. }) => {
+                !task_ids.is_empty() && task_logs.is_empty()
+            }
+            (
+                Message::StatusResponse(state),
+                SubCommand::Status {
+                    group,
+                    query,
+                    pipeline,
+                    added_after,
+                    added_before,
+                    finished_after,
+                    finished_before,
+                    exit_code,
+                    ..
+                },
+            ) => {
+                let has_filter = group.is_some()
+                    || query.is_some()
+                    || pipeline.is_some()
+                    || added_after.is_some()
+                    || added_before.is_some()
+                    || finished_after.is_some()
+                    || finished_before.is_some()
+                    || exit_code.is_some();
+                // The full state is sent, if only a group is requested.
+                has_filter
+                    && !state
+                        .tasks
+                        .values()
+                        .any(|task| group.is_none() || &task.group == group)
+            }
+            _ => false,
+        }
+    }
+
+    /// Ask for confirmation before commands, which affect lots of tasks at once.
+    /// Remove and kill are already covered by `client.show_confirmation_questions`, if it's set.
+    /// Non-interactive sessions, e.g. scripts, are never prompted.
+    async fn confirm_destructive_command(&mut self) -> Result<()> {
+        if !self.settings.client.confirm_destructive || self.opt.quiet || !io::stdin().is_tty() {
+            return Ok(());
+        }
+        let ask_anyway = !self.settings.client.show_confirmation_questions;
+
+        let (action, selection, threshold) = match &self.opt.cmd {
+            SubCommand::Reset { force: false, .. } => ("reset the daemon and remove", None, 1),
+            SubCommand::Kill {
+                all: true,
+                force: false,
+                ..
+            } if ask_anyway => ("kill", None, 1),
+            SubCommand::Remove {
+                task_ids,
+                group,
+                pattern,
+                force: false,
+            } if ask_anyway => (
+                "remove",
+                Some(TaskSelection::from_ranges(
+                    task_ids,
+                    group.clone(),
+                    pattern.clone(),
+                )),
+                CONFIRM_REMOVE_THRESHOLD,
+            ),
+            _ => return Ok(()),
+        };
+
+        let state = get_state(&mut self.socket).await?;
+        let affected: Vec<&Task> = match (&self.opt.cmd, selection) {
+            (SubCommand::Kill { .. }, _) => state
+                .tasks
+                .values()
+                .filter(|task| matches!(task.status, TaskStatus::Running | TaskStatus::Paused))
+                .collect(),
+            (_, Some(selection)) => state
+                .select_tasks(&selection)
+                .iter()
+                .filter_map(|task_id| state.tasks.get(task_id))
+                .filter(|task| !matches!(task.status, TaskStatus::Running | TaskStatus::Paused))
+                .collect(),
+            (_, None) => state.tasks.values().collect(),
+        };
+        if affected.len() < threshold {
+            return Ok(());
+        }
+
+        // Count the affected tasks of each group.
+        let mut groups: BTreeMap<&str, usize> = BTreeMap::new();
+        for task in affected.iter() {
+            *groups
+                .entry(task.group.as_deref().unwrap_or("default"))
+                .or_default() += 1;
+        }
+        let groups: Vec<String> = groups
+            .iter()
+            .map(|(group, count)| format!("{} in {}", count, group))
+            .collect();
+
         println!(
-            "You are trying to {}: {}",
+            "You are trying to {} {} tasks ({})",
             action,
-            task_ids
-                .iter()
-                .map(|t| format!("task{}", t.to_string()))
-                .collect::<Vec<String>>()
-                .join(", ")
+            affected.len(),
+            groups.join(", ")
         );
+        ask_for_confirmation()
+    }
 
-        let mut input = String::new();
+    /// Translate a path of this machine into the path on the daemon's machine.
+    fn daemon_path(&self, path: &Path) -> Result<String> {
+        let path = path.to_str().with_context(|| {
+            format!(
+                "Cannot parse path {} (Invalid utf8?)",
+                path.to_string_lossy()
+            )
+        })?;
+        self.settings.client.map_path(path)
+    }
 
-        loop {
-            print!("Do you want to continue [Y/n]: ");
-            io::stdout().flush().unwrap();
-            input.clear();
-            io::stdin().read_line(&mut input)?;
-
-            match input.chars().next().unwrap() {
-                'N' | 'n' => {
-                    println!("Aborted!");
-                    std::process::exit(1);
-                }
-                '\n' | 'Y' | 'y' => {
-                    break;
-                }
-                _ => {
-                    continue;
-                }
-            }
+    /// Echo the point in time of a date expression like `--delay 'tomorrow 9am'` and ask for
+    /// confirmation, as those are easy to misinterpret.
+    fn confirm_date_expression(&self) -> Result<()> {
+        let (delay, subject) = match &self.opt.cmd {
+            SubCommand::Add {
+                delay_until: Some(delay),
+                ..
+            } => (delay, "The task"),
+            SubCommand::Enqueue {
+                delay_until: Some(delay),
+                ..
+            } => (delay, "The tasks"),
+            _ => return Ok(()),
+        };
+        if !delay.is_expression
+            || !self.settings.client.confirm_date_expressions
+            || self.opt.quiet
+            || !io::stdin().is_tty()
+        {
+            return Ok(());
         }
 
-        Ok(())
+        let now = Local::now();
+        if delay.time <= now {
+            println!(
+                "{} will be enqueued right away, as {} is in the past",
+                subject,
+                delay.time.format("%Y-%m-%d %H:%M:%S")
+            );
+        } else {
+            println!(
+                "{} will be enqueued on {} (in {})",
+                subject,
+                delay.time.format("%A, %Y-%m-%d %H:%M:%S"),
+                format_duration(delay.time - now)
+            );
+        }
+        ask_for_confirmation()
     }
 
     /// Convert the cli command into the message that's being sent to the server,
@@ -234,37 +645,127 @@ impl Client {
                 group,
                 delay_until,
                 dependencies,
+                after_any,
+                after_failure,
+                dependency_timeout,
+                unique_key,
+                replace,
+                slots,
+                lock,
+                stdin_from,
+                artifacts,
+                tempdir,
+                container,
+                host,
+                label,
+                priority,
+                cpus,
+                gpus,
+                wait_for_path,
+                wait_for_host,
+                array,
+                cache,
+                stop_command,
+                on_exit,
+                before,
+                login_shell,
+                direnv,
+                copy_env,
+                no_env,
+                line_buffered,
+                working_directory,
+                ..
             } => {
                 let cwd_pathbuf = current_dir()?;
-                let cwd = cwd_pathbuf
-                    .to_str()
-                    .context("Cannot parse current working directory (Invalid utf8?)")?;
-
-                let mut envs = HashMap::new();
-                // Save all environment variables for later injection into the started task
-                for (key, value) in vars() {
-                    envs.insert(key, value);
-                }
+                let path = self.daemon_path(&working_directory_or_cwd(working_directory)?)?;
 
-                Ok(Message::Add(AddMessage {
+                let env_mode = if *no_env {
+                    EnvironmentMode::Empty
+                } else if !copy_env.is_empty() {
+                    EnvironmentMode::Only(copy_env.clone())
+                } else {
+                    EnvironmentMode::All
+                };
+                // Save the environment variables for later injection into the started task
+                let envs = env_mode.filter(vars().collect());
+
+                Ok(Message::Add(Box::new(AddMessage {
                     command: command.join(" "),
-                    path: cwd.to_string(),
+                    path,
                     envs,
                     start_immediately: *start_immediately,
                     stashed: *stashed,
                     group: group.clone(),
-                    enqueue_at: *delay_until,
+                    enqueue_at: delay_until.as_ref().map(|delay| client_instant(delay.time)),
                     dependencies: dependencies.to_vec(),
+                    any_dependencies: after_any.to_vec(),
+                    failure_dependencies: after_failure.to_vec(),
+                    dependency_timeout: *dependency_timeout,
                     ignore_aliases: false,
-                }))
+                    unique_key: unique_key.clone(),
+                    replace_duplicate: *replace,
+                    slots: *slots,
+                    lock: lock.clone(),
+                    stdin_from: *stdin_from,
+                    artifacts: artifacts.clone(),
+                    tempdir: *tempdir,
+                    container: container.clone(),
+                    host: host.clone(),
+                    stop_command: stop_command.clone(),
+                    on_exit: on_exit.clone(),
+                    before: before.clone(),
+                    login_shell: *login_shell,
+                    direnv: *direnv,
+                    env_mode,
+                    line_buffered: *line_buffered,
+                    label: label.clone(),
+                    priority: *priority,
+                    cpus: cpus.clone(),
+                    gpus: *gpus,
+                    wait_for_path: wait_for_path
+                        .as_ref()
+                        .map(|path| self.daemon_path(&cwd_pathbuf.join(path)))
+                        .transpose()?,
+                    wait_for_host: wait_for_host.clone(),
+                    array: *array,
+                    array_id: None,
+                    cache: *cache,
+                    restarted_from: None,
+                })))
             }
-            SubCommand::Remove { task_ids } => {
-                if self.settings.client.show_confirmation_questions {
-                    self.handle_user_confirmation("remove", task_ids)?;
+            SubCommand::Remove {
+                task_ids,
+                group,
+                pattern,
+                force,
+            } => {
+                let selection =
+                    TaskSelection::from_ranges(task_ids, group.clone(), pattern.clone());
+                if self.settings.client.show_confirmation_questions && !force && !self.opt.quiet {
+                    self.handle_user_confirmation("remove", &selection)?;
                 }
-                Ok(Message::Remove(task_ids.clone()))
+                Ok(Message::Remove(selection))
+            }
+            SubCommand::Stash {
+                task_ids,
+                group,
+                pattern,
+            } => Ok(Message::Stash(TaskSelection::from_ranges(
+                task_ids,
+                group.clone(),
+                pattern.clone(),
+            ))),
+            SubCommand::Move { task_ids, group } => {
+                let group = if group == "default" {
+                    None
+                } else {
+                    Some(group.clone())
+                };
+                Ok(Message::Move(MoveMessage {
+                    task_ids: task_ids.clone(),
+                    group,
+                }))
             }
-            SubCommand::Stash { task_ids } => Ok(Message::Stash(task_ids.clone())),
             SubCommand::Switch {
                 task_id_1,
                 task_id_2,
@@ -281,7 +782,7 @@ impl Client {
             } => {
                 let message = EnqueueMessage {
                     task_ids: task_ids.clone(),
-                    enqueue_at: *delay_until,
+                    enqueue_at: delay_until.as_ref().map(|delay| client_instant(delay.time)),
                 };
                 Ok(Message::Enqueue(message))
             }
@@ -321,16 +822,32 @@ impl Client {
                 default,
                 all,
                 children,
+                grace,
+                pattern,
+                force,
             } => {
-                if self.settings.client.show_confirmation_questions {
-                    self.handle_user_confirmation("kill", task_ids)?;
+                // A pattern turns the group into a filter instead of killing the whole group.
+                let selection = if pattern.is_some() {
+                    TaskSelection::from_ranges(task_ids, group.clone(), pattern.clone())
+                } else {
+                    TaskSelection::from_ranges(task_ids, None, None)
+                };
+                if self.settings.client.show_confirmation_questions && !force && !self.opt.quiet {
+                    self.handle_user_confirmation("kill", &selection)?;
                 }
+                let (task_ids, group, selection) = if selection.is_plain() {
+                    (selection.task_ids, group.clone(), None)
+                } else {
+                    (Vec::new(), None, Some(selection))
+                };
                 let message = KillMessage {
-                    task_ids: task_ids.clone(),
-                    group: group.clone(),
+                    task_ids,
+                    group,
                     default: *default,
                     all: *all,
                     children: *children,
+                    grace: grace.map(|seconds| seconds as u64),
+                    selection,
                 };
                 Ok(Message::Kill(message))
             }
@@ -348,24 +865,99 @@ impl Client {
                 };
                 Ok(Message::Group(message))
             }
-            SubCommand::Status { .. } => Ok(Message::Status),
+            SubCommand::Status {
+                group,
+                limit,
+                offset,
+                query,
+                pipeline,
+                added_after,
+                added_before,
+                finished_after,
+                finished_before,
+                exit_code,
+                summary,
+                ..
+            } => {
+                // Let the daemon do the pagination, so we don't have to transfer every single task.
+                // The summary has to count all tasks, so it's never paginated.
+                let (limit, offset) = if *summary {
+                    (None, 0)
+                } else {
+                    (limit.or(self.settings.client.status_limit), *offset)
+                };
+                let message = StatusPageMessage {
+                    group: group.clone(),
+                    query: query.clone(),
+                    pipeline: pipeline.clone(),
+                    offset,
+                    limit,
+                    added_after: *added_after,
+                    added_before: *added_before,
+                    finished_after: *finished_after,
+                    finished_before: *finished_before,
+                    exit_code: exit_code.clone(),
+                };
+                let has_time_range = message.added_after.is_some()
+                    || message.added_before.is_some()
+                    || message.finished_after.is_some()
+                    || message.finished_before.is_some();
+                let has_filter = query.is_some() || pipeline.is_some() || exit_code.is_some();
+                if limit.is_none() && offset == 0 && !has_filter && !has_time_range {
+                    return Ok(Message::Status);
+                }
+                Ok(Message::StatusPage(message))
+            }
+            SubCommand::Log {
+                task_ids,
+                grep: Some(pattern),
+                context,
+                ..
+            } => {
+                let message = LogGrepMessage {
+                    task_ids: task_ids.clone(),
+                    pattern: pattern.clone(),
+                    context: *context,
+                };
+                Ok(Message::LogGrep(message))
+            }
             SubCommand::Log { task_ids, .. } => {
                 let message = LogRequestMessage {
                     task_ids: task_ids.clone(),
-                    send_logs: !self.settings.client.read_local_logs,
+                    send_logs: !self.settings.read_local_logs(),
                 };
                 Ok(Message::Log(message))
             }
-            SubCommand::Follow { task_id, err } => {
+            SubCommand::Follow { task_id, err, .. } => {
                 let message = StreamRequestMessage {
                     task_id: *task_id,
                     err: *err,
                 };
                 Ok(Message::StreamRequest(message))
             }
-            SubCommand::Clean => Ok(Message::Clean),
-            SubCommand::Reset { children } => Ok(Message::Reset(*children)),
-            SubCommand::Shutdown => Ok(Message::DaemonShutdown),
+            SubCommand::Clean { successful_only } => Ok(Message::Clean(*successful_only)),
+            SubCommand::Undo => Ok(Message::Undo),
+            SubCommand::Trash { cmd } => match cmd {
+                TrashCommand::List { .. } => Ok(Message::TrashList),
+                TrashCommand::Restore { task_ids } => Ok(Message::TrashRestore(task_ids.clone())),
+                TrashCommand::Empty => Ok(Message::TrashEmpty),
+            },
+            SubCommand::Reset { children, .. } => Ok(Message::Reset(*children)),
+            SubCommand::Shutdown { graceful } => {
+                if self.settings.client.show_confirmation_questions && !self.opt.quiet {
+                    let action = if *graceful {
+                        "shut down the daemon after all running tasks finished"
+                    } else {
+                        "shut down the daemon and kill all running tasks"
+                    };
+                    println!("You are trying to {}", action);
+                    ask_for_confirmation()?;
+                }
+                Ok(Message::DaemonShutdown(*graceful))
+            }
+            SubCommand::DaemonInfo { .. } => Ok(Message::DaemonInfo),
+            SubCommand::Stats { command, .. } => Ok(Message::Stats(command.clone())),
+            SubCommand::Cancel { pipeline } => Ok(Message::CancelPipeline(pipeline.clone())),
             SubCommand::Parallel {
                 parallel_tasks,
                 group,
@@ -377,8 +969,58 @@ impl Client {
                 Ok(Message::Parallel(message))
             }
             SubCommand::Completions { .. } => bail!("Completions have to be handled earlier"),
+            SubCommand::GenerateMan { .. } => bail!("Man pages have to be generated earlier"),
             SubCommand::Restart { .. } => bail!("Restarts have to be handled earlier"),
             SubCommand::Edit { .. } => bail!("Edits have to be handled earlier"),
+            SubCommand::Info { .. } => bail!("Info has to be handled earlier"),
+            SubCommand::Artifacts { .. } => bail!("Artifacts have to be handled earlier"),
+            SubCommand::Graph { .. } => bail!("Graph has to be handled earlier"),
+            SubCommand::Notify { .. } => bail!("Notify has to be handled earlier"),
+            SubCommand::Apply { .. } => bail!("Apply has to be handled earlier"),
+            SubCommand::Progress { .. } => bail!("Progress has to be handled earlier"),
+            SubCommand::Wait { .. } => bail!("Wait has to be handled earlier"),
+            SubCommand::Worker { .. } => bail!("Workers have to be handled earlier"),
+            SubCommand::Find { .. } => bail!("Find has to be handled earlier"),
+            SubCommand::Pick { .. } => bail!("Pick has to be handled earlier"),
+            SubCommand::Shell => bail!("Shell has to be handled earlier"),
+            SubCommand::Complete { .. } => bail!("Complete has to be handled earlier"),
         }
     }
 }
+
+/// Prompt the user until the action is either confirmed or aborted.
+/// Exits the client, if the action has been aborted.
+/// The `--working-directory` resolved from the current directory or the current directory itself.
+fn working_directory_or_cwd(working_directory: &Option<PathBuf>) -> Result<PathBuf> {
+    let cwd = current_dir()?;
+    Ok(match working_directory {
+        Some(path) => cwd.join(path),
+        None => cwd,
+    })
+}
+
+fn ask_for_confirmation() -> Result<()> {
+    let mut input = String::new();
+
+    loop {
+        print!("Do you want to continue [Y/n]: ");
+        io::stdout().flush().unwrap();
+        input.clear();
+        io::stdin().read_line(&mut input)?;
+
+        match input.chars().next().unwrap() {
+            'N' | 'n' => {
+                println!("Aborted!");
+                std::process::exit(1);
+            }
+            '\n' | 'Y' | 'y' => {
+                break;
+            }
+            _ => {
+                continue;
+            }
+        }
+    }
+
+    Ok(())
+}
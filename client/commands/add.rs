@@ -0,0 +1,195 @@
+use std::env::vars;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use async_std::task::sleep;
+
+use pueue::bundle::{Bundle, Pipeline};
+use pueue::message::*;
+use pueue::protocol::*;
+use pueue::settings::Client;
+
+use crate::commands::get_state;
+use crate::output::print_success;
+
+/// Block until the queue of the given group is no longer full.
+/// The daemon rejects new tasks as long as a group's `max_queued` limit is reached,
+/// so we poll the state until there's room for another task.
+pub async fn wait_for_queue_slot(socket: &mut Socket, group: &Option<String>) -> Result<()> {
+    let mut notified = false;
+    loop {
+        let state = get_state(socket).await?;
+        if !state.queue_is_full(group) {
+            return Ok(());
+        }
+
+        if !notified {
+            println!("The queue is full. Waiting for a free slot...");
+            notified = true;
+        }
+        sleep(Duration::from_millis(1000)).await;
+    }
+}
+
+/// Add one task for each combination of the `--param` values.
+/// The `{name}` placeholders in the command are replaced by the respective values.
+///
+/// This is done on the client-side, since each combination is simply a new task.
+/// Adding stops at the first task that's rejected by the daemon.
+pub async fn add_parameter_sweep(
+    socket: &mut Socket,
+    message: AddMessage,
+    parameters: &[String],
+    wait_for_slot: bool,
+) -> Result<()> {
+    for command in expand_parameters(&message.command, parameters)? {
+        if wait_for_slot {
+            wait_for_queue_slot(socket, &message.group).await?;
+        }
+
+        let mut add_message = message.clone();
+        add_message.command = command;
+        send_message(Message::Add(Box::new(add_message)), socket).await?;
+        match receive_message(socket).await? {
+            Message::Success(text) => print_success(&text),
+            Message::Failure(text) => bail!(text),
+            _ => bail!("Received unexpected response while adding tasks"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Add all tasks of a bundle file and print their ids by their names.
+/// The file is parsed on the client-side, so the daemon doesn't need access to it.
+/// `cwd` is the directory of the tasks on the daemon's machine.
+pub async fn add_bundle(
+    socket: &mut Socket,
+    path: &Path,
+    cwd: String,
+    settings: &Client,
+) -> Result<()> {
+    let content = read_to_string(path)
+        .with_context(|| format!("Failed to read bundle file {}", path.to_string_lossy()))?;
+    let bundle = Bundle::from_yaml(&content)?;
+
+    send_bundle(socket, bundle, None, cwd, settings).await
+}
+
+/// Add all stages of a pipeline file and print the ids of its tasks by their names.
+pub async fn apply_pipeline(
+    socket: &mut Socket,
+    path: &Path,
+    cwd: String,
+    settings: &Client,
+) -> Result<()> {
+    let content = read_to_string(path)
+        .with_context(|| format!("Failed to read pipeline file {}", path.to_string_lossy()))?;
+    let pipeline = Pipeline::from_yaml(&content)?;
+
+    send_bundle(
+        socket,
+        pipeline.to_bundle()?,
+        Some(pipeline.name),
+        cwd,
+        settings,
+    )
+    .await
+}
+
+async fn send_bundle(
+    socket: &mut Socket,
+    mut bundle: Bundle,
+    pipeline: Option<String>,
+    cwd: String,
+    settings: &Client,
+) -> Result<()> {
+    // Relative paths are resolved by the daemon, absolute ones are paths of this machine.
+    for task in bundle.tasks.values_mut() {
+        if let Some(path) = task
+            .path
+            .as_ref()
+            .filter(|path| Path::new(path).is_absolute())
+        {
+            task.path = Some(settings.map_path(path)?);
+        }
+    }
+
+    let message = Message::AddBundle(BundleMessage {
+        bundle,
+        path: cwd,
+        envs: vars().collect(),
+        pipeline,
+    });
+    send_message(message, socket).await?;
+    match receive_message(socket).await? {
+        Message::BundleResponse(task_ids) => {
+            for (name, task_id) in task_ids {
+                println!("{}: {}", name, task_id);
+            }
+        }
+        Message::Failure(text) => bail!(text),
+        _ => bail!("Received unexpected response while adding tasks"),
+    }
+
+    Ok(())
+}
+
+/// Expand the cartesian product of all `name=value1,value2` parameters into commands.
+pub fn expand_parameters(command: &str, parameters: &[String]) -> Result<Vec<String>> {
+    let mut commands = vec![command.to_string()];
+    let mut names = Vec::new();
+    for parameter in parameters {
+        let (name, values) = match parameter.split_once('=') {
+            Some(split) => split,
+            None => bail!(
+                "Parameter {} has to be in the `name=value1,value2` format",
+                parameter
+            ),
+        };
+        let placeholder = format!("{{{}}}", name);
+        if names.contains(&name) {
+            bail!("Parameter {} has been specified twice", name);
+        }
+        if !command.contains(&placeholder) {
+            bail!("Parameter {} isn't used in the command", placeholder);
+        }
+        names.push(name);
+
+        commands = commands
+            .iter()
+            .flat_map(|command| {
+                values
+                    .split(',')
+                    .map(|value| command.replace(&placeholder, value))
+                    .collect::<Vec<String>>()
+            })
+            .collect();
+    }
+
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_parameters() {
+        let parameters = vec!["res=720,1080".to_string(), "codec=x264,x265".to_string()];
+        assert_eq!(
+            expand_parameters("encode.sh {res} {codec} out_{res}", &parameters).unwrap(),
+            vec![
+                "encode.sh 720 x264 out_720",
+                "encode.sh 720 x265 out_720",
+                "encode.sh 1080 x264 out_1080",
+                "encode.sh 1080 x265 out_1080",
+            ]
+        );
+
+        assert!(expand_parameters("encode.sh", &parameters).is_err());
+        assert!(expand_parameters("echo {res}", &["res".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+
+use pueue::protocol::*;
+
+use crate::commands::get_state;
+
+/// Print the candidates for dynamic shell completion, one per line.
+/// Each line consists of the value and a short description, separated by a tab.
+/// This is the format fish expects, other shells can simply cut off the description.
+pub async fn complete(socket: &mut Socket, kind: &str) -> Result<()> {
+    let state = get_state(socket).await?;
+
+    match kind {
+        "tasks" => {
+            for (id, task) in state.tasks.iter() {
+                let description = match &task.label {
+                    Some(label) => format!("{} ({})", task.command, label),
+                    None => task.command.clone(),
+                };
+                // Newlines in the command would break the line based format.
+                println!("{}\t{}", id, description.replace('\n', " "));
+            }
+        }
+        _ => {
+            println!(
+                "default\t{} parallel",
+                state.settings.daemon.default_parallel_tasks
+            );
+            for (group, parallel) in state.settings.daemon.groups.iter() {
+                println!("{}\t{} parallel", group, parallel);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -3,6 +3,7 @@ use std::io::{Read, Seek, SeekFrom, Write};
 use std::process::Command;
 
 use anyhow::{Context, Result};
+use serde_derive::{Deserialize, Serialize};
 use tempfile::NamedTempFile;
 
 use pueue::message::*;
@@ -14,8 +15,14 @@ use pueue::protocol::*;
 /// started or manipulated in any way, as long as we're editing.
 ///
 /// After receiving the task information, the user can then edit it in their editor.
-/// Upon exiting the text editor, the line will then be read and sent to the server
-pub async fn edit(socket: &mut Socket, task_id: usize, edit_path: bool) -> Result<Message> {
+/// Upon exiting the text editor, the file will then be read and sent to the server,
+/// which validates the changes and unlocks the task again.
+pub async fn edit(
+    socket: &mut Socket,
+    task_id: usize,
+    edit_command: bool,
+    edit_path: bool,
+) -> Result<Message> {
     // Request the data to edit from the server and issue a task-lock while doing so.
     let init_message = Message::EditRequest(task_id);
     send_message(init_message, socket).await?;
@@ -31,24 +38,86 @@ pub async fn edit(socket: &mut Socket, task_id: usize, edit_path: bool) -> Resul
         return Ok(init_response);
     };
 
-    // Edit either the path or the command, depending on the `path` flag.
-    let mut command = init_response.command;
-    let mut path = init_response.path;
-    if edit_path {
-        path = edit_line(&path)?;
+    let mut edit_message = EditMessage {
+        task_id,
+        command: init_response.command,
+        path: init_response.path,
+        label: init_response.label,
+        group: init_response.group,
+    };
+
+    // Edit either only the path, only the command or everything at once.
+    let edited = if edit_path {
+        edit_line(&edit_message.path).map(|path| EditMessage {
+            path,
+            ..edit_message.clone()
+        })
+    } else if edit_command {
+        edit_line(&edit_message.command).map(|command| EditMessage {
+            command,
+            ..edit_message.clone()
+        })
     } else {
-        command = edit_line(&command)?
+        edit_document(&edit_message)
     };
 
-    // Create a new message with the edited command.
-    let edit_message = Message::Edit(EditMessage {
-        task_id,
-        command,
-        path,
-    });
-    send_message(edit_message, socket).await?;
+    // The task is still locked. Send the unchanged values to unlock it, if the edit failed.
+    let error = match edited {
+        Ok(edited) => {
+            edit_message = edited;
+            None
+        }
+        Err(error) => Some(error),
+    };
+    send_message(Message::Edit(edit_message), socket).await?;
+    let response = receive_message(socket).await?;
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok(response),
+    }
+}
+
+/// The fields of a task that can be edited at once.
+#[derive(Deserialize, Serialize)]
+struct EditDocument {
+    command: String,
+    path: String,
+    label: Option<String>,
+    group: String,
+}
+
+/// Let the user edit all fields of a task as a YAML document in $EDITOR.
+fn edit_document(message: &EditMessage) -> Result<EditMessage> {
+    let document = EditDocument {
+        command: message.command.clone(),
+        path: message.path.clone(),
+        label: message.label.clone(),
+        group: message
+            .group
+            .clone()
+            .unwrap_or_else(|| "default".to_string()),
+    };
+    let content = format!(
+        "# Edit task {} and close the editor to apply the changes.\n{}",
+        message.task_id,
+        serde_yaml::to_string(&document)?
+    );
+
+    let content = edit_line(&content)?;
+    let document: EditDocument =
+        serde_yaml::from_str(&content).context("The edited task isn't valid YAML")?;
 
-    receive_message(socket).await
+    Ok(EditMessage {
+        task_id: message.task_id,
+        command: document.command,
+        path: document.path,
+        label: document.label,
+        group: match document.group.as_str() {
+            "default" => None,
+            _ => Some(document.group),
+        },
+    })
 }
 
 /// This function allows the user to edit a task's command or path.
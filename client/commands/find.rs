@@ -0,0 +1,33 @@
+use anyhow::{bail, Result};
+
+use pueue::message::*;
+use pueue::protocol::*;
+
+/// Print the ids of all tasks that match the pattern, separated by spaces.
+/// The search is done by the daemon, so we don't have to transfer the whole state.
+/// Returns whether any task matched.
+pub async fn find(socket: &mut Socket, pattern: &str) -> Result<bool> {
+    let message = StatusPageMessage {
+        group: None,
+        query: Some(pattern.to_string()),
+        pipeline: None,
+        offset: 0,
+        limit: None,
+        added_after: None,
+        added_before: None,
+        finished_after: None,
+        finished_before: None,
+        exit_code: None,
+    };
+    send_message(Message::StatusPage(message), socket).await?;
+
+    let state = match receive_message(socket).await? {
+        Message::StatusResponse(state) => *state,
+        _ => bail!("Received unexpected response from daemon"),
+    };
+
+    let ids: Vec<String> = state.tasks.keys().map(|id| id.to_string()).collect();
+    println!("{}", ids.join(" "));
+
+    Ok(!ids.is_empty())
+}
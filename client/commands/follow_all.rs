@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_std::task::sleep;
+use chrono::Local;
+use crossterm::style::Color;
+use crossterm::tty::IsTty;
+
+use pueue::encryption::LogFile;
+use pueue::log::get_log_file_handles;
+use pueue::protocol::Socket;
+use pueue::task::Task;
+
+use crate::commands::get_state;
+use crate::output_helper::style_text;
+
+/// The colors of the task id prefixes. Tasks are assigned a color by their id.
+const COLORS: [Color; 6] = [
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Red,
+];
+
+/// A running task, whose output is being followed.
+struct FollowedTask {
+    handle: LogFile,
+    /// The last line of the output, if it isn't finished yet.
+    partial_line: Vec<u8>,
+}
+
+/// Follow the output of all running tasks, optionally only of a single group.
+/// Every line is prefixed with the id of the task it belongs to.
+/// Tasks that are started in the meantime are picked up as well.
+pub async fn follow_all(
+    socket: &mut Socket,
+    pueue_directory: &str,
+    group: &Option<String>,
+    err: bool,
+) -> Result<()> {
+    let is_tty = io::stdout().is_tty();
+    let mut followed: BTreeMap<usize, FollowedTask> = BTreeMap::new();
+    // Tasks that already finished, but have been followed. They're never attached again.
+    let mut done: BTreeSet<usize> = BTreeSet::new();
+    // Short tasks might start and finish between two refreshes.
+    // Their output is printed, as long as they started after this command.
+    let started_at = Local::now();
+    let mut last_refresh: Option<Instant> = None;
+    loop {
+        // Look for new and finished tasks about once a second.
+        if last_refresh.is_none_or(|time| time.elapsed() >= Duration::from_secs(1)) {
+            last_refresh = Some(Instant::now());
+            let state = get_state(socket).await?;
+            let running: Vec<usize> = state
+                .tasks
+                .iter()
+                .filter(|(_, task)| task.is_running())
+                .filter(|(_, task)| in_group(task, group))
+                .map(|(id, _)| *id)
+                .collect();
+
+            // Print the remaining output of finished tasks, before they're dropped.
+            let finished: Vec<usize> = followed
+                .keys()
+                .filter(|id| !running.contains(id))
+                .cloned()
+                .collect();
+            for task_id in finished {
+                if let Some(mut task) = followed.remove(&task_id) {
+                    print_new_output(task_id, &mut task, is_tty, true)?;
+                }
+                done.insert(task_id);
+            }
+
+            // Print the output of tasks that finished before they could be attached.
+            let missed: Vec<usize> = state
+                .tasks
+                .iter()
+                .filter(|(id, task)| task.is_done() && !done.contains(id))
+                .filter(|(_, task)| task.start.is_some_and(|start| start >= started_at))
+                .filter(|(_, task)| in_group(task, group))
+                .map(|(id, _)| *id)
+                .collect();
+            for task_id in missed {
+                let mut task = open_task(task_id, pueue_directory, err)?;
+                print_new_output(task_id, &mut task, is_tty, true)?;
+                done.insert(task_id);
+            }
+
+            for task_id in running {
+                if followed.contains_key(&task_id) {
+                    continue;
+                }
+                followed.insert(task_id, open_task(task_id, pueue_directory, err)?);
+            }
+        }
+
+        for (task_id, task) in followed.iter_mut() {
+            print_new_output(*task_id, task, is_tty, false)?;
+        }
+
+        sleep(Duration::from_millis(100)).await;
+    }
+}
+
+fn in_group(task: &Task, group: &Option<String>) -> bool {
+    match group {
+        Some(group) => task.group.as_deref().unwrap_or("default") == group,
+        None => true,
+    }
+}
+
+fn open_task(task_id: usize, pueue_directory: &str, err: bool) -> Result<FollowedTask> {
+    let (stdout, stderr) = get_log_file_handles(task_id, pueue_directory, None)?;
+    Ok(FollowedTask {
+        handle: if err { stderr } else { stdout },
+        partial_line: Vec::new(),
+    })
+}
+
+/// Print all complete lines a task wrote since the last call.
+/// On `flush`, the last unfinished line is printed as well.
+fn print_new_output(
+    task_id: usize,
+    task: &mut FollowedTask,
+    is_tty: bool,
+    flush: bool,
+) -> Result<()> {
+    task.handle.read_to_end(&mut task.partial_line)?;
+
+    let prefix = style_text(
+        &format!("[{}]", task_id),
+        is_tty,
+        Some(COLORS[task_id % COLORS.len()]),
+        None,
+    );
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    while let Some(position) = task.partial_line.iter().position(|byte| *byte == b'\n') {
+        let line: Vec<u8> = task.partial_line.drain(..=position).collect();
+        write!(stdout, "{} ", prefix)?;
+        stdout.write_all(&line)?;
+    }
+    if flush && !task.partial_line.is_empty() {
+        write!(stdout, "{} ", prefix)?;
+        stdout.write_all(&task.partial_line)?;
+        writeln!(stdout)?;
+        task.partial_line.clear();
+    }
+    stdout.flush()?;
+
+    Ok(())
+}
@@ -10,6 +10,7 @@ pub async fn local_follow(
     pueue_directory: String,
     task_id: &Option<usize>,
     err: bool,
+    strip_ansi: bool,
 ) -> Result<()> {
     // The user can specify the id of the task they want to follow
     // If the id isn't specified and there's only a single running task, this task will be used.
@@ -44,7 +45,7 @@ pub async fn local_follow(
         }
     };
 
-    follow_task_logs(pueue_directory, task_id, err);
+    follow_task_logs(pueue_directory, task_id, err, strip_ansi);
 
     Ok(())
 }
@@ -4,9 +4,18 @@ use pueue::message::Message;
 use pueue::protocol::*;
 use pueue::state::State;
 
+pub mod add;
+pub mod complete;
 pub mod edit;
+pub mod find;
+pub mod follow_all;
 pub mod local_follow;
+pub mod notify;
+pub mod pick;
 pub mod restart;
+pub mod shell;
+pub mod wait;
+pub mod worker;
 
 // This is a helper function for easy retrieval of the current daemon state.
 // The current daemon state is often needed in more complex commands.
@@ -18,7 +27,7 @@ pub async fn get_state(socket: &mut Socket) -> Result<State> {
     let message = receive_message(socket).await?;
 
     match message {
-        Message::StatusResponse(state) => Ok(state),
+        Message::StatusResponse(state) => Ok(*state),
         _ => unreachable!(),
     }
 }
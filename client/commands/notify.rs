@@ -0,0 +1,52 @@
+use std::env;
+
+use anyhow::{bail, Result};
+
+use pueue::message::*;
+use pueue::protocol::*;
+use pueue::task::Progress;
+
+/// Send a custom message of a task to the daemon.
+/// Nothing is printed on success, since this usually runs inside of a task and would end up in its log.
+pub async fn notify(socket: &mut Socket, text: &str, task_id: &Option<usize>) -> Result<()> {
+    let message = NotifyMessage {
+        task_id: task_id_or_env(task_id)?,
+        text: text.to_string(),
+    };
+    send_message(Message::Notify(message), socket).await?;
+    receive_silent_response(socket).await
+}
+
+/// Report the progress of a task to the daemon, which is shown in `pueue status`.
+pub async fn progress(
+    socket: &mut Socket,
+    progress: &Progress,
+    task_id: &Option<usize>,
+) -> Result<()> {
+    let message = ProgressMessage {
+        task_id: task_id_or_env(task_id)?,
+        progress: progress.clone(),
+    };
+    send_message(Message::Progress(message), socket).await?;
+    receive_silent_response(socket).await
+}
+
+/// Commands called from within a task default to the id of that task.
+fn task_id_or_env(task_id: &Option<usize>) -> Result<usize> {
+    if let Some(task_id) = task_id {
+        return Ok(*task_id);
+    }
+
+    match env::var("PUEUE_TASK_ID").map(|id| id.parse()) {
+        Ok(Ok(task_id)) => Ok(task_id),
+        _ => bail!("Couldn't find PUEUE_TASK_ID. Specify the task via --task-id."),
+    }
+}
+
+async fn receive_silent_response(socket: &mut Socket) -> Result<()> {
+    match receive_message(socket).await? {
+        Message::Success(_) => Ok(()),
+        Message::Failure(text) => bail!(text),
+        _ => bail!("Received unexpected response from daemon"),
+    }
+}
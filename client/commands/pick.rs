@@ -0,0 +1,242 @@
+use std::collections::BTreeSet;
+use std::io::{self, Stderr, Write};
+
+use anyhow::{bail, Result};
+use crossterm::cursor::{self, MoveTo};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use pueue::message::*;
+use pueue::protocol::*;
+
+use crate::commands::get_state;
+
+/// All actions that can be directly applied to the picked tasks via `pick --then`.
+pub const PICK_ACTIONS: &[&str] = &["kill", "remove", "start", "pause", "stash", "enqueue"];
+
+/// Show a fuzzy-searchable list of all tasks and let the user pick some of them.
+/// The ids of the picked tasks are either printed or passed to the given action.
+///
+/// The list is drawn on stderr, so the output can be used in subshells, e.g. `pueue log $(pueue pick)`.
+pub async fn pick(socket: &mut Socket, then: &Option<String>) -> Result<()> {
+    let state = get_state(socket).await?;
+    if state.tasks.is_empty() {
+        bail!("Task list is empty. Add tasks with `pueue add -- [cmd]`");
+    }
+
+    let entries: Vec<(usize, String)> = state
+        .tasks
+        .iter()
+        .map(|(id, task)| {
+            let label = match &task.label {
+                Some(label) => format!("[{}] ", label),
+                None => String::new(),
+            };
+            let text = format!("{:>4}  {:<8}  {}{}", id, task.status, label, task.command);
+            (*id, text)
+        })
+        .collect();
+
+    let task_ids = run_picker(&entries)?;
+    if task_ids.is_empty() {
+        return Ok(());
+    }
+
+    let action = match then {
+        Some(action) => action,
+        None => {
+            let ids: Vec<String> = task_ids.iter().map(|id| id.to_string()).collect();
+            println!("{}", ids.join(" "));
+            return Ok(());
+        }
+    };
+
+    send_message(action_message(action, task_ids)?, socket).await?;
+    match receive_message(socket).await? {
+        Message::Success(text) => println!("{}", text),
+        Message::Failure(text) => println!("{}", text),
+        _ => println!("Received unexpected response from daemon"),
+    }
+
+    Ok(())
+}
+
+/// Build the message for an action of `pick --then`.
+fn action_message(action: &str, task_ids: Vec<usize>) -> Result<Message> {
+    let message = match action {
+        "kill" => Message::Kill(KillMessage {
+            task_ids,
+            group: None,
+            default: false,
+            all: false,
+            children: false,
+            grace: None,
+            selection: None,
+        }),
+        "remove" => Message::Remove(task_ids.into()),
+        "start" => Message::Start(StartMessage {
+            task_ids,
+            group: None,
+            all: false,
+            children: false,
+        }),
+        "pause" => Message::Pause(PauseMessage {
+            task_ids,
+            group: None,
+            wait: false,
+            all: false,
+            children: false,
+        }),
+        "stash" => Message::Stash(task_ids.into()),
+        "enqueue" => Message::Enqueue(EnqueueMessage {
+            task_ids,
+            enqueue_at: None,
+        }),
+        _ => bail!("Unknown action {}", action),
+    };
+
+    Ok(message)
+}
+
+/// Take over the terminal, run the picker and restore the terminal afterwards.
+fn run_picker(entries: &[(usize, String)]) -> Result<Vec<usize>> {
+    let mut stderr = io::stderr();
+    terminal::enable_raw_mode()?;
+    execute!(stderr, EnterAlternateScreen, cursor::Hide)?;
+
+    let result = picker_loop(&mut stderr, entries);
+
+    execute!(stderr, cursor::Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    result
+}
+
+/// Handle all key events until the user either picks tasks or aborts.
+/// `Tab` marks multiple tasks, `Enter` picks the marked tasks or the current one.
+fn picker_loop(stderr: &mut Stderr, entries: &[(usize, String)]) -> Result<Vec<usize>> {
+    let mut query = String::new();
+    let mut selected = 0;
+    let mut marked = BTreeSet::new();
+
+    loop {
+        let matches = filter_entries(entries, &query);
+        if selected >= matches.len() {
+            selected = matches.len().saturating_sub(1);
+        }
+        draw(stderr, &query, &matches, entries.len(), selected, &marked)?;
+
+        let KeyEvent { code, modifiers } = match event::read()? {
+            Event::Key(key) => key,
+            _ => continue,
+        };
+        match code {
+            KeyCode::Esc => return Ok(Vec::new()),
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(Vec::new())
+            }
+            KeyCode::Enter => {
+                if marked.is_empty() {
+                    return Ok(matches
+                        .get(selected)
+                        .map(|(id, _)| vec![*id])
+                        .unwrap_or_default());
+                }
+                return Ok(marked.into_iter().collect());
+            }
+            KeyCode::Tab => {
+                if let Some((id, _)) = matches.get(selected) {
+                    if !marked.remove(id) {
+                        marked.insert(*id);
+                    }
+                    selected += 1;
+                }
+            }
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Down => selected += 1,
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(character) => query.push(character),
+            _ => {}
+        }
+    }
+}
+
+fn draw(
+    stderr: &mut Stderr,
+    query: &str,
+    matches: &[&(usize, String)],
+    total: usize,
+    selected: usize,
+    marked: &BTreeSet<usize>,
+) -> Result<()> {
+    let (width, height) = terminal::size()?;
+    let visible = (height as usize).saturating_sub(2).max(1);
+    // Scroll down, if the selected entry isn't visible.
+    let scroll = selected.saturating_sub(visible - 1);
+
+    queue!(
+        stderr,
+        Clear(ClearType::All),
+        MoveTo(0, 0),
+        Print(format!("> {}", query)),
+        MoveTo(0, 1),
+        Print(format!("  {}/{}", matches.len(), total))
+    )?;
+    for (row, (id, text)) in matches.iter().skip(scroll).take(visible).enumerate() {
+        let pointer = if row + scroll == selected { '>' } else { ' ' };
+        let mark = if marked.contains(id) { '*' } else { ' ' };
+        let line: String = format!("{}{} {}", pointer, mark, text)
+            .chars()
+            .take(width as usize)
+            .collect();
+        queue!(stderr, MoveTo(0, row as u16 + 2), Print(line))?;
+    }
+    stderr.flush()?;
+
+    Ok(())
+}
+
+/// Return all entries that fuzzy match the query, the best matches first.
+fn filter_entries<'a>(entries: &'a [(usize, String)], query: &str) -> Vec<&'a (usize, String)> {
+    let mut matches: Vec<(usize, &(usize, String))> = entries
+        .iter()
+        .filter_map(|entry| fuzzy_score(query, &entry.1).map(|score| (score, entry)))
+        .collect();
+    // The sort is stable, so entries with the same score stay ordered by id.
+    matches.sort_by_key(|(score, _)| *score);
+
+    matches.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Check whether all characters of the pattern appear in the text in the same order.
+/// Returns the length of the matched span, i.e. lower scores are better matches.
+fn fuzzy_score(pattern: &str, text: &str) -> Option<usize> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut first = None;
+    let mut position = 0;
+    for character in pattern.to_lowercase().chars() {
+        let index = text[position..].iter().position(|c| *c == character)? + position;
+        first.get_or_insert(index);
+        position = index + 1;
+    }
+
+    Some(first.map(|first| position - first).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("ffm", "ffmpeg -i in.mp4"), Some(3));
+        assert_eq!(fuzzy_score("FMP", "ffmpeg"), Some(4));
+        assert_eq!(fuzzy_score("fg", "ffmpeg"), Some(6));
+        assert_eq!(fuzzy_score("gf", "ffmpeg"), None);
+    }
+}
@@ -11,9 +11,15 @@ use crate::commands::get_state;
 /// is create from the existing task in the state.
 ///
 /// This is done on the client-side, so we can easily edit the task before restarting it.
+///
+/// If `failed` is set, only failed tasks are restarted. Without any other selection, that's all of them.
+/// Tasks that are restarted `in_place` are reset by the daemon and keep their ids.
+#[allow(clippy::too_many_arguments)]
 pub async fn restart(
     socket: &mut Socket,
-    task_ids: Vec<usize>,
+    selection: TaskSelection,
+    failed: bool,
+    in_place: bool,
     start_immediately: bool,
     stashed: bool,
     edit_command: bool,
@@ -26,14 +32,27 @@ pub async fn restart(
     };
 
     let mut state = get_state(socket).await?;
-    let (matching, mismatching) = state.tasks_in_statuses(vec![TaskStatus::Done], Some(task_ids));
+    let task_ids = if failed && selection == TaskSelection::default() {
+        state.tasks.keys().cloned().collect()
+    } else {
+        state.select_tasks(&selection)
+    };
+    if task_ids.is_empty() {
+        bail!("No tasks match the given selection");
+    }
+    let (mut matching, mismatching) =
+        state.tasks_in_statuses(vec![TaskStatus::Done], Some(task_ids));
+    if failed {
+        // Successful tasks aren't an error, they simply don't need a restart.
+        matching.retain(|task_id| state.tasks.get(task_id).unwrap().failed());
+        if matching.is_empty() && mismatching.is_empty() {
+            bail!("There are no failed tasks to restart");
+        }
+    }
 
-    // Go through all Done commands we found and restart them
+    let mut to_restart = Vec::new();
     for task_id in &matching {
         let task = state.tasks.get(task_id).unwrap();
-        let mut new_task = Task::from_task(task);
-        new_task.status = new_status.clone();
-
         // Path and command can be edited, if the use specified the -e or -p flag.
         let mut command = task.command.clone();
         let mut path = task.path.clone();
@@ -43,20 +62,93 @@ pub async fn restart(
         if edit_path {
             path = edit_line(&path)?;
         }
+        to_restart.push(TaskToRestart {
+            task_id: *task_id,
+            command,
+            path,
+        });
+    }
+
+    if in_place {
+        if to_restart.is_empty() {
+            println!("Couldn't restart tasks: {:?}", mismatching);
+            return Ok(());
+        }
+        let message = Message::Restart(RestartMessage {
+            tasks: to_restart,
+            start_immediately,
+            stashed,
+        });
+        send_message(message, socket).await?;
+        match receive_message(socket).await? {
+            Message::Success(text) => println!("{}", text),
+            Message::Failure(text) => bail!(text),
+            _ => println!("Received unexpected response from daemon"),
+        }
+        if !mismatching.is_empty() {
+            println!("Couldn't restart tasks: {:?}", mismatching);
+        }
+        return Ok(());
+    }
+
+    // Go through all Done commands we found and restart them
+    for TaskToRestart {
+        task_id,
+        command,
+        path,
+    } in to_restart
+    {
+        let task = state.tasks.get(&task_id).unwrap();
+        let mut new_task = Task::from_task(task);
+        new_task.status = new_status.clone();
 
         // Create a AddMessage to add the task to the daemon from the
         // updated info and the old task.
-        let add_task_message = Message::Add(AddMessage {
+        let add_task_message = Message::Add(Box::new(AddMessage {
             command,
             path,
             envs: task.envs.clone(),
             start_immediately,
             stashed,
-            group: task.group.clone(),
+            // Quarantined tasks are restarted in the group they failed in.
+            group: if task.quarantined {
+                task.original_group.clone()
+            } else {
+                task.group.clone()
+            },
             enqueue_at: None,
             dependencies: Vec::new(),
+            any_dependencies: Vec::new(),
+            failure_dependencies: Vec::new(),
+            dependency_timeout: None,
             ignore_aliases: true,
-        });
+            unique_key: task.unique_key.clone(),
+            replace_duplicate: false,
+            slots: task.slots,
+            lock: task.lock.clone(),
+            stdin_from: task.stdin_from,
+            artifacts: task.artifact_patterns.clone(),
+            tempdir: task.use_tempdir,
+            container: task.container.clone(),
+            host: task.host.clone(),
+            stop_command: task.stop_command.clone(),
+            on_exit: task.on_exit.clone(),
+            before: task.before.clone(),
+            login_shell: task.login_shell,
+            direnv: task.direnv,
+            env_mode: task.env_mode.clone(),
+            line_buffered: task.line_buffered,
+            label: task.label.clone(),
+            priority: task.priority,
+            cpus: task.cpus.clone(),
+            gpus: task.gpus,
+            wait_for_path: None,
+            wait_for_host: None,
+            array: task.array_index.map(|index| (index, index)),
+            array_id: task.array_id,
+            cache: None,
+            restarted_from: Some(task.id),
+        }));
 
         // Send the cloned task to the daemon and abort on any Failure messages.
         send_message(add_task_message, socket).await?;
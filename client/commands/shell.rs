@@ -0,0 +1,350 @@
+use std::io::{self, BufRead, Stdout, Write};
+
+use anyhow::{bail, Result};
+use crossterm::cursor::MoveLeft;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::queue;
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::tty::IsTty;
+use structopt::clap::AppSettings;
+use structopt::StructOpt;
+
+use pueue::state::State;
+
+use crate::cli::Opt;
+
+const PROMPT: &str = "pueue> ";
+
+/// The maximum amount of lines that are remembered by the shell.
+const HISTORY_SIZE: usize = 1000;
+
+/// A minimal line editor for `pueue shell` with a history and completion of
+/// subcommands, task ids and groups.
+///
+/// If stdin or stdout isn't a terminal, lines are read as they are, e.g. `pueue shell < commands`.
+pub struct LineEditor {
+    history: Vec<String>,
+    interactive: bool,
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        LineEditor {
+            history: Vec::new(),
+            interactive: io::stdin().is_tty() && io::stdout().is_tty(),
+        }
+    }
+}
+
+impl LineEditor {
+    /// Read the next line. Returns `None`, if the input ended, e.g. via `Ctrl+D`.
+    pub fn read_line(&mut self, completer: &Completer) -> Result<Option<String>> {
+        if !self.interactive {
+            let mut line = String::new();
+            if io::stdin().lock().read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            return Ok(Some(line.trim_end_matches(&['\r', '\n'][..]).to_string()));
+        }
+
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        let result = self.edit_loop(&mut stdout, completer);
+        terminal::disable_raw_mode()?;
+        // Commands print their output below the prompt.
+        println!();
+
+        let line = result?;
+        if let Some(line) = &line {
+            self.add_to_history(line);
+        }
+
+        Ok(line)
+    }
+
+    fn add_to_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map(String::as_str) == Some(line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        if self.history.len() > HISTORY_SIZE {
+            self.history.remove(0);
+        }
+    }
+
+    /// Handle all key events until the user either confirms the line or ends the input.
+    fn edit_loop(&self, stdout: &mut Stdout, completer: &Completer) -> Result<Option<String>> {
+        let mut line: Vec<char> = Vec::new();
+        let mut cursor = 0;
+        // The position while browsing the history. `history.len()` is the line that's being edited.
+        let mut history_index = self.history.len();
+        let mut edited = Vec::new();
+
+        loop {
+            draw(stdout, &line, cursor)?;
+
+            let KeyEvent { code, modifiers } = match event::read()? {
+                Event::Key(key) => key,
+                _ => continue,
+            };
+            let control = modifiers.contains(KeyModifiers::CONTROL);
+            match code {
+                KeyCode::Enter => return Ok(Some(line.into_iter().collect())),
+                KeyCode::Char('c') if control => {
+                    // Abort the current line, just like a normal shell.
+                    queue!(stdout, Print("^C\r\n"))?;
+                    line.clear();
+                    cursor = 0;
+                    history_index = self.history.len();
+                }
+                KeyCode::Char('d') if control && line.is_empty() => return Ok(None),
+                KeyCode::Char('u') if control => {
+                    line.drain(..cursor);
+                    cursor = 0;
+                }
+                // Ignore all other shortcuts instead of inserting their characters.
+                KeyCode::Char(_) if control => {}
+                KeyCode::Char(character) => {
+                    line.insert(cursor, character);
+                    cursor += 1;
+                }
+                KeyCode::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    line.remove(cursor);
+                }
+                KeyCode::Delete if cursor < line.len() => {
+                    line.remove(cursor);
+                }
+                KeyCode::Left => cursor = cursor.saturating_sub(1),
+                KeyCode::Right => cursor = (cursor + 1).min(line.len()),
+                KeyCode::Home => cursor = 0,
+                KeyCode::End => cursor = line.len(),
+                KeyCode::Up if history_index > 0 => {
+                    if history_index == self.history.len() {
+                        edited = line.clone();
+                    }
+                    history_index -= 1;
+                    line = self.history[history_index].chars().collect();
+                    cursor = line.len();
+                }
+                KeyCode::Down if history_index < self.history.len() => {
+                    history_index += 1;
+                    line = match self.history.get(history_index) {
+                        Some(entry) => entry.chars().collect(),
+                        None => edited.clone(),
+                    };
+                    cursor = line.len();
+                }
+                KeyCode::Tab => complete_word(stdout, &mut line, &mut cursor, completer)?,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Redraw the prompt and the current line and place the cursor.
+fn draw(stdout: &mut Stdout, line: &[char], cursor: usize) -> Result<()> {
+    let text: String = line.iter().collect();
+    queue!(
+        stdout,
+        Print("\r"),
+        Clear(ClearType::CurrentLine),
+        Print(PROMPT),
+        Print(text)
+    )?;
+    if cursor < line.len() {
+        queue!(stdout, MoveLeft((line.len() - cursor) as u16))?;
+    }
+    stdout.flush()?;
+
+    Ok(())
+}
+
+/// Complete the word in front of the cursor.
+/// If there are several candidates, their common prefix is inserted. If that doesn't
+/// add anything, all candidates are listed below the prompt.
+fn complete_word(
+    stdout: &mut Stdout,
+    line: &mut Vec<char>,
+    cursor: &mut usize,
+    completer: &Completer,
+) -> Result<()> {
+    let start = line[..*cursor]
+        .iter()
+        .rposition(|character| character.is_whitespace())
+        .map(|position| position + 1)
+        .unwrap_or(0);
+    let word: String = line[start..*cursor].iter().collect();
+    let before: String = line[..start].iter().collect();
+    let previous_words: Vec<&str> = before.split_whitespace().collect();
+
+    let candidates = completer.candidates(&previous_words, &word);
+    let completion = match candidates.as_slice() {
+        [] => return Ok(()),
+        [candidate] => format!("{} ", candidate),
+        _ => common_prefix(&candidates).to_string(),
+    };
+
+    if completion.len() > word.len() {
+        for character in completion[word.len()..].chars() {
+            line.insert(*cursor, character);
+            *cursor += 1;
+        }
+    } else {
+        queue!(
+            stdout,
+            Print("\r\n"),
+            Print(candidates.join("  ")),
+            Print("\r\n")
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Return the longest prefix all candidates have in common.
+fn common_prefix<'a>(candidates: &[&'a str]) -> &'a str {
+    let first = candidates[0];
+    let mut length = first.len();
+    for candidate in candidates.iter().skip(1) {
+        length = first
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, left), right)| left == right)
+            .map(|((index, character), _)| index + character.len_utf8())
+            .last()
+            .unwrap_or(0)
+            .min(length);
+    }
+
+    &first[..length]
+}
+
+/// All values that can be completed in the shell.
+/// The task ids and groups are taken from the state that's fetched before each prompt.
+pub struct Completer {
+    subcommands: Vec<String>,
+    task_ids: Vec<String>,
+    groups: Vec<String>,
+}
+
+impl Completer {
+    pub fn new(state: &State) -> Self {
+        let app = Opt::clap();
+        let mut subcommands: Vec<String> = app
+            .p
+            .subcommands
+            .iter()
+            .filter(|subcommand| !subcommand.p.is_set(AppSettings::Hidden))
+            .map(|subcommand| subcommand.p.meta.name.clone())
+            .collect();
+        subcommands.push("exit".to_string());
+        subcommands.sort();
+
+        let mut groups = vec!["default".to_string()];
+        groups.extend(state.groups.keys().cloned());
+
+        Completer {
+            subcommands,
+            task_ids: state.tasks.keys().map(|id| id.to_string()).collect(),
+            groups,
+        }
+    }
+
+    /// Return all candidates for the word that's currently typed.
+    /// The first word is a subcommand, the value of `-g/--group` is a group and
+    /// everything else is completed as a task id.
+    fn candidates(&self, previous_words: &[&str], word: &str) -> Vec<&str> {
+        let values = match previous_words.last() {
+            None => &self.subcommands,
+            Some(&"-g") | Some(&"--group") => &self.groups,
+            Some(_) if word.starts_with('-') => return Vec::new(),
+            Some(_) => &self.task_ids,
+        };
+
+        values
+            .iter()
+            .map(String::as_str)
+            .filter(|value| value.starts_with(word))
+            .collect()
+    }
+}
+
+/// Split a line into words like a posix shell would.
+/// Words can be quoted with single or double quotes and characters can be escaped with a backslash.
+pub fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // Empty quotes are a word as well, e.g. `pueue add -l '' ls`.
+    let mut in_word = false;
+    let mut quote = None;
+    let mut characters = line.chars();
+
+    while let Some(character) = characters.next() {
+        match (quote, character) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => word.push(character),
+            (Some(_), '\\') | (None, '\\') => match characters.next() {
+                Some(escaped) => {
+                    // Inside of double quotes, only a few characters can be escaped.
+                    if quote.is_some() && !matches!(escaped, '"' | '\\' | '$' | '`') {
+                        word.push('\\');
+                    }
+                    word.push(escaped);
+                    in_word = true;
+                }
+                None => bail!("The line ends with an unfinished escape sequence"),
+            },
+            (Some(_), _) => word.push(character),
+            (None, '\'') | (None, '"') => {
+                quote = Some(character);
+                in_word = true;
+            }
+            (None, _) if character.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, _) => {
+                word.push(character);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        bail!("The line contains an unclosed quote");
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        let words = split_words(r#"add -l "my label" -- echo 'a  b' c\ d "" "\$HOME\n""#).unwrap();
+        assert_eq!(
+            words,
+            vec!["add", "-l", "my label", "--", "echo", "a  b", "c d", "", "$HOME\\n"]
+        );
+
+        assert!(split_words("add 'unclosed").is_err());
+        assert!(split_words("status   ").unwrap() == vec!["status"]);
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        assert_eq!(common_prefix(&["10", "11", "12"]), "1");
+        assert_eq!(common_prefix(&["restart", "reset", "remove"]), "re");
+        assert_eq!(common_prefix(&["status", "stash"]), "sta");
+        assert_eq!(common_prefix(&["kill", "log"]), "");
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use async_std::task::sleep;
+
+use pueue::protocol::Socket;
+use pueue::task::{Task, TaskResult};
+
+use crate::commands::get_state;
+
+/// Block until the given tasks finished.
+/// Without any task ids, wait until no task of the group is queued or running anymore.
+/// With `all`, wait until the whole queue is drained, i.e. no task of any group is left.
+/// Returns whether all of the given tasks succeeded. Groups are always considered successful.
+pub async fn wait(
+    socket: &mut Socket,
+    task_ids: &[usize],
+    group: &Option<String>,
+    all: bool,
+) -> Result<bool> {
+    let group = group.clone().unwrap_or_else(|| "default".into());
+    loop {
+        let state = get_state(socket).await?;
+
+        let missing: Vec<&usize> = task_ids
+            .iter()
+            .filter(|id| !state.tasks.contains_key(id))
+            .collect();
+        if !missing.is_empty() {
+            bail!("There are no tasks with the ids {:?}", missing);
+        }
+
+        let is_waited_for = |(id, task): &(&usize, &Task)| {
+            if !task_ids.is_empty() {
+                return task_ids.contains(id);
+            }
+            all || task.group.as_deref().unwrap_or("default") == group
+        };
+        let pending = state
+            .tasks
+            .iter()
+            .filter(is_waited_for)
+            .filter(|(_, task)| task.is_pending())
+            .count();
+
+        if pending == 0 {
+            let succeeded = task_ids
+                .iter()
+                .all(|id| matches!(state.tasks[id].result, Some(TaskResult::Success)));
+            return Ok(succeeded);
+        }
+
+        sleep(Duration::from_millis(1000)).await;
+    }
+}
@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::process::{Child, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use async_std::task::sleep;
+
+use pueue::message::*;
+use pueue::protocol::*;
+use pueue::settings::Client;
+use pueue::shell::{compile_shell_command, prepend_setup_command};
+use pueue::task::{EnvironmentMode, Task};
+
+/// The interval in which the worker sends the output of its task to the daemon.
+/// This also tells the daemon, that it's still executing the task.
+/// It has to be a lot shorter than the daemon's `daemon.worker_timeout`.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Turn this machine into a remote worker of the daemon we're connected to.
+/// Tasks of the given worker group are continuously pulled, executed locally
+/// and their result and output is reported back to the daemon.
+pub async fn worker(socket: &mut Socket, group: &str, name: &str, settings: &Client) -> Result<()> {
+    println!("Pulling tasks of group '{}' as worker '{}'", group, name);
+    loop {
+        let message = WorkerPullMessage {
+            worker: name.to_string(),
+            group: group.to_string(),
+        };
+        send_message(Message::WorkerPull(message), socket).await?;
+
+        let message = match receive_message(socket).await? {
+            Message::WorkerTask(message) => *message,
+            Message::Failure(text) => bail!(text),
+            // There's currently nothing to do.
+            _ => {
+                sleep(Duration::from_millis(2000)).await;
+                continue;
+            }
+        };
+
+        let task = &message.task;
+        println!("Executing task {}: {}", task.id, message.command);
+        let report = match execute_task(socket, &message, name, settings).await? {
+            Some(report) => report,
+            // The task has been killed in the meantime, there's nothing to report.
+            None => continue,
+        };
+        send_message(Message::WorkerReport(report), socket).await?;
+
+        match receive_message(socket).await? {
+            Message::Success(text) => println!("{}", text),
+            Message::Failure(text) => println!("Failed to report task {}: {}", task.id, text),
+            _ => println!("Received unexpected response from daemon"),
+        }
+    }
+}
+
+/// Execute a pulled task like the daemon would and collect its output.
+/// The task's directory is translated via `client.path_mappings`, if the worker has any.
+/// While the task runs, heartbeats with its latest output are sent to the daemon.
+/// Returns `None`, if the task has been killed on the daemon in the meantime.
+async fn execute_task(
+    socket: &mut Socket,
+    message: &WorkerTaskMessage,
+    name: &str,
+    settings: &Client,
+) -> Result<Option<WorkerReportMessage>> {
+    let task = &message.task;
+    let path = settings
+        .unmap_path(&task.path)
+        .unwrap_or_else(|| task.path.clone());
+    if !Path::new(&path).is_dir() {
+        let error = format!("The directory {} doesn't exist on worker {}", path, name);
+        return Ok(Some(spawn_failure(task, name, error)));
+    }
+
+    let mut stdout = tempfile::tempfile()?;
+    let mut stderr = tempfile::tempfile()?;
+    // The setup command runs in the same shell, so it can prepare the environment.
+    let command_string = prepend_setup_command(task.before.as_deref(), &message.command, None);
+    let mut command = compile_shell_command(&command_string);
+    // Tasks with a selective environment don't inherit the worker's environment.
+    if task.env_mode != EnvironmentMode::All {
+        command.env_clear();
+    }
+    command
+        .current_dir(&path)
+        .envs(&message.envs)
+        .stdin(Stdio::null())
+        .stdout(stdout.try_clone()?)
+        .stderr(stderr.try_clone()?);
+    // Run the task in its own process group, so it can be killed along with its children.
+    #[cfg(not(windows))]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            let error = format!("Failed to spawn task on worker {}: {}", name, err);
+            return Ok(Some(spawn_failure(task, name, error)));
+        }
+    };
+
+    let mut output = Output::default();
+    let mut last_heartbeat = Instant::now();
+    let exit_code = loop {
+        if let Some(exit_status) = child.try_wait()? {
+            break exit_status.code();
+        }
+
+        if last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+            let heartbeat = WorkerHeartbeatMessage {
+                task_id: task.id,
+                worker: name.to_string(),
+                stdout: output.read_stdout(&mut stdout)?,
+                stderr: output.read_stderr(&mut stderr)?,
+            };
+            send_message(Message::WorkerHeartbeat(heartbeat), socket).await?;
+            if let Message::Failure(text) = receive_message(socket).await? {
+                println!("{}. Killing it.", text);
+                kill_process_group(&mut child)?;
+                return Ok(None);
+            }
+            last_heartbeat = Instant::now();
+        }
+
+        sleep(Duration::from_millis(200)).await;
+    };
+
+    Ok(Some(WorkerReportMessage {
+        task_id: task.id,
+        worker: name.to_string(),
+        exit_code,
+        stdout: output.read_stdout(&mut stdout)?,
+        stderr: output.read_stderr(&mut stderr)?,
+        spawn_error: None,
+    }))
+}
+
+/// Kill the task's shell and all processes it spawned.
+#[cfg(not(windows))]
+fn kill_process_group(child: &mut Child) -> Result<()> {
+    use nix::sys::signal::{killpg, Signal};
+    use nix::unistd::Pid;
+
+    killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL)?;
+    child.wait()?;
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn kill_process_group(child: &mut Child) -> Result<()> {
+    child.kill()?;
+    child.wait()?;
+
+    Ok(())
+}
+
+/// How much of the output of a task has already been sent to the daemon.
+#[derive(Default)]
+struct Output {
+    stdout_offset: u64,
+    stderr_offset: u64,
+}
+
+impl Output {
+    fn read_stdout(&mut self, file: &mut File) -> Result<Vec<u8>> {
+        read_new_output(file, &mut self.stdout_offset)
+    }
+
+    fn read_stderr(&mut self, file: &mut File) -> Result<Vec<u8>> {
+        read_new_output(file, &mut self.stderr_offset)
+    }
+}
+
+/// Read the output of a task from its temporary file, that has been written since the last read.
+fn read_new_output(file: &mut File, offset: &mut u64) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    file.seek(SeekFrom::Start(*offset))?;
+    file.read_to_end(&mut output)?;
+    *offset += output.len() as u64;
+
+    Ok(output)
+}
+
+fn spawn_failure(task: &Task, name: &str, error: String) -> WorkerReportMessage {
+    WorkerReportMessage {
+        task_id: task.id,
+        worker: name.to_string(),
+        exit_code: None,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        spawn_error: Some(error),
+    }
+}
@@ -2,6 +2,7 @@ use anyhow::Result;
 use simplelog::{Config, LevelFilter, SimpleLogger};
 use structopt::StructOpt;
 
+use pueue::man::write_man_page;
 use pueue::settings::Settings;
 
 pub mod cli;
@@ -14,9 +15,30 @@ use crate::cli::{Opt, SubCommand};
 use crate::client::Client;
 
 #[async_std::main]
-async fn main() -> Result<()> {
+async fn main() {
+    let exit_code = match run().await {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            eprintln!("Error: {:?}", error);
+            2
+        }
+    };
+    std::process::exit(exit_code);
+}
+
+/// Run the client and return its exit code, see the EXIT CODES of `pueue --help`.
+async fn run() -> Result<i32> {
     // Parse commandline options.
-    let opt = Opt::from_args();
+    // Invalid arguments are errors like any other, see the EXIT CODES of `pueue --help`.
+    let opt = match Opt::from_iter_safe(std::env::args_os()) {
+        Ok(opt) => opt,
+        // The help and the version are reported as errors as well.
+        Err(error) if !error.use_stderr() => error.exit(),
+        Err(error) => {
+            eprintln!("{}", error.message);
+            return Ok(2);
+        }
+    };
 
     if let SubCommand::Completions {
         shell,
@@ -25,7 +47,12 @@ async fn main() -> Result<()> {
     {
         let mut clap = Opt::clap();
         clap.gen_completions("pueue", *shell, output_directory);
-        return Ok(());
+        return Ok(0);
+    }
+
+    if let SubCommand::GenerateMan { output_directory } = &opt.cmd {
+        write_man_page(Opt::clap(), "pueue", output_directory)?;
+        return Ok(0);
     }
 
     // Set the verbosity level of the logger.
@@ -40,9 +67,44 @@ async fn main() -> Result<()> {
     // Try to read settings from the configuration file.
     let settings = Settings::new(true, &opt.config)?;
 
+    if opt.quiet {
+        silence_stdout()?;
+    }
+
     // Create client to talk with the daemon and connect.
     let mut client = Client::new(settings, opt).await?;
-    client.start().await?;
+    client.start().await
+}
+
+/// Discard everything that's printed to stdout, so `--quiet` doesn't have to be handled
+/// by every single command.
+#[cfg(not(target_os = "windows"))]
+fn silence_stdout() -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let null = std::fs::OpenOptions::new().write(true).open("/dev/null")?;
+    nix::unistd::dup2(null.as_raw_fd(), std::io::stdout().as_raw_fd())?;
+
+    Ok(())
+}
+
+/// Point the standard output handle of the process to `NUL`.
+/// Rust looks up that handle on every write, so nothing is printed afterwards.
+#[cfg(target_os = "windows")]
+fn silence_stdout() -> Result<()> {
+    use std::os::windows::io::IntoRawHandle;
+
+    use windows_sys::Win32::System::Console::{SetStdHandle, STD_OUTPUT_HANDLE};
+
+    let null = std::fs::OpenOptions::new().write(true).open("NUL")?;
+    // The handle is used until the client exits, so it's never closed.
+    let handle = null.into_raw_handle();
+    if unsafe { SetStdHandle(STD_OUTPUT_HANDLE, handle as isize) } == 0 {
+        anyhow::bail!(
+            "Failed to discard stdout: {}",
+            std::io::Error::last_os_error()
+        );
+    }
 
     Ok(())
 }
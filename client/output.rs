@@ -1,22 +1,27 @@
 use std::collections::BTreeMap;
-use std::io;
+use std::io::{self, Read, Write};
 use std::string::ToString;
 use std::thread::sleep;
 use std::time::Duration;
 
 use anyhow::Result;
+use chrono::prelude::*;
 use comfy_table::presets::UTF8_HORIZONTAL_BORDERS_ONLY;
 use comfy_table::*;
 use crossterm::tty::IsTty;
+use serde_derive::Serialize;
 use snap::read::FrameDecoder;
 
-use pueue::log::{get_log_file_handles, get_log_paths};
-use pueue::message::TaskLogMessage;
+use pueue::log::{get_log_file_handles, get_log_paths, read_last_line, read_log_files, strip_ansi};
+use pueue::message::{DaemonInfoMessage, LogMatch, TaskLogMessage};
+use pueue::redact::redact_task;
 use pueue::settings::Settings;
 use pueue::state::State;
+use pueue::stats::Statistics;
 use pueue::task::{Task, TaskResult, TaskStatus};
+use pueue::trash::TrashedTask;
 
-use crate::cli::SubCommand;
+use crate::cli::{SubCommand, TrashCommand};
 use crate::output_helper::*;
 
 pub fn print_success(message: &str) {
@@ -30,14 +35,65 @@ pub fn print_error(message: &str) {
 }
 
 /// Print the current state of the daemon in a nicely formatted table.
-pub fn print_state(state: State, cli_command: &SubCommand, settings: &Settings) {
-    let (json, group_only) = match cli_command {
-        SubCommand::Status { json, group } => (*json, group.clone()),
-        _ => panic!(
-            "Got wrong Subcommand {:?} in print_state. This shouldn't happen",
-            cli_command
-        ),
-    };
+pub fn print_state(mut state: State, cli_command: &SubCommand, settings: &Settings) {
+    let is_tty = io::stdout().is_tty();
+    let (json, csv, group_only, sort, reverse, schedule, snippet, expand, tree, summary, layout) =
+        match cli_command {
+            SubCommand::Status {
+                json,
+                csv,
+                group,
+                sort,
+                reverse,
+                schedule,
+                snippet,
+                expand,
+                tree,
+                max_command_width,
+                truncate,
+                wrap,
+                full_width,
+                summary,
+                ..
+            } => (
+                *json,
+                *csv,
+                group.clone(),
+                sort.as_deref(),
+                *reverse,
+                *schedule,
+                *snippet,
+                expand,
+                *tree,
+                *summary,
+                TableLayout::new(
+                    settings,
+                    is_tty,
+                    *full_width,
+                    *max_command_width,
+                    *truncate,
+                    *wrap,
+                ),
+            ),
+            _ => panic!(
+                "Got wrong Subcommand {:?} in print_state. This shouldn't happen",
+                cli_command
+            ),
+        };
+
+    for task in state.tasks.values_mut() {
+        redact_task(task, &settings.shared.redacted_envs);
+    }
+
+    if schedule {
+        print_schedule(state, json, &group_only);
+        return;
+    }
+
+    if summary {
+        print_summary(&state, json, &group_only);
+        return;
+    }
 
     // If the json flag is specified, print the state as json and exit.
     if json {
@@ -45,7 +101,10 @@ pub fn print_state(state: State, cli_command: &SubCommand, settings: &Settings)
         return;
     }
 
-    let is_tty = io::stdout().is_tty();
+    if csv {
+        print_csv(&state.tasks, &group_only, sort, reverse);
+        return;
+    }
 
     // Don't show default queue headline if a single group is requested
     if group_only.is_none() {
@@ -60,9 +119,11 @@ pub fn print_state(state: State, cli_command: &SubCommand, settings: &Settings)
 
     // Skip default queue, if a single group is requested
     if group_only.is_none() {
-        let default_tasks = get_default_tasks(&state.tasks);
-        if !default_tasks.is_empty() {
-            print_table(&default_tasks, settings);
+        let default_tasks = collapse_arrays(&get_default_tasks(&state.tasks), expand);
+        if !default_tasks.is_empty() && tree {
+            print_tree(&default_tasks, is_tty);
+        } else if !default_tasks.is_empty() {
+            print_table(&default_tasks, sort, reverse, snippet, &layout, settings);
         }
     }
 
@@ -75,61 +136,429 @@ pub fn print_state(state: State, cli_command: &SubCommand, settings: &Settings)
             }
         }
         println!("{}", get_group_headline(&group, &state, is_tty));
-        print_table(&tasks, settings);
+        let tasks = collapse_arrays(&tasks, expand);
+        if tree {
+            print_tree(&tasks, is_tty);
+        } else {
+            print_table(&tasks, sort, reverse, snippet, &layout, settings);
+        }
+    }
+}
+
+/// Print all tasks with a future enqueue time across all groups, the next one first.
+/// The task counts of a single group for `status --summary`.
+#[derive(Serialize)]
+struct GroupSummary {
+    group: String,
+    parallel_tasks: usize,
+    paused: bool,
+    queued: usize,
+    running: usize,
+    success: usize,
+    failed: usize,
+}
+
+/// Print one line per group with its task counts, limit and state, without listing any tasks.
+fn print_summary(state: &State, json: bool, group_only: &Option<String>) {
+    let mut groups = vec![(
+        "default".to_string(),
+        state.settings.daemon.default_parallel_tasks,
+        !state.running,
+    )];
+    for (name, running) in state.groups.iter() {
+        let parallel = *state.settings.daemon.groups.get(name).unwrap_or(&1);
+        groups.push((name.clone(), parallel, !running));
+    }
+
+    let summaries: Vec<GroupSummary> = groups
+        .into_iter()
+        .filter(|(name, _, _)| match group_only {
+            Some(group) => group == name,
+            None => true,
+        })
+        .map(|(name, parallel_tasks, paused)| {
+            let mut summary = GroupSummary {
+                group: name,
+                parallel_tasks,
+                paused,
+                queued: 0,
+                running: 0,
+                success: 0,
+                failed: 0,
+            };
+            for task in state.tasks.values() {
+                if task.group.as_deref().unwrap_or("default") != summary.group {
+                    continue;
+                }
+                match task.status {
+                    TaskStatus::Queued | TaskStatus::Stashed | TaskStatus::Locked => {
+                        summary.queued += 1
+                    }
+                    TaskStatus::Running | TaskStatus::Paused => summary.running += 1,
+                    TaskStatus::Done if task.failed() => summary.failed += 1,
+                    TaskStatus::Done if task.result == Some(TaskResult::Success) => {
+                        summary.success += 1
+                    }
+                    _ => (),
+                }
+            }
+            summary
+        })
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string(&summaries).unwrap());
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .load_preset(UTF8_HORIZONTAL_BORDERS_ONLY)
+        .set_header(vec![
+            Cell::new("Group"),
+            Cell::new("State"),
+            Cell::new("Parallel"),
+            Cell::new("Queued"),
+            Cell::new("Running"),
+            Cell::new("Success"),
+            Cell::new("Failed"),
+        ]);
+
+    for summary in summaries {
+        let (state_text, state_color) = if summary.paused {
+            ("paused", Color::Yellow)
+        } else {
+            ("running", Color::Green)
+        };
+        let mut failed = Cell::new(summary.failed.to_string());
+        if summary.failed > 0 {
+            failed = failed.fg(Color::Red);
+        }
+        table.add_row(vec![
+            Cell::new(&summary.group),
+            Cell::new(state_text).fg(state_color),
+            Cell::new(summary.parallel_tasks.to_string()),
+            Cell::new(summary.queued.to_string()),
+            Cell::new(summary.running.to_string()),
+            Cell::new(summary.success.to_string()),
+            failed,
+        ]);
+    }
+
+    println!("{}", table);
+}
+
+fn print_schedule(state: State, json: bool, group_only: &Option<String>) {
+    let mut tasks: Vec<Task> = state
+        .tasks
+        .into_values()
+        .filter(|task| task.enqueue_at.is_some())
+        .filter(|task| group_only.is_none() || group_only == &task.group)
+        .collect();
+    tasks.sort_by_key(|task| task.enqueue_at);
+
+    if json {
+        println!("{}", serde_json::to_string(&tasks).unwrap());
+        return;
+    }
+
+    if tasks.is_empty() {
+        println!("No tasks are scheduled. Schedule tasks with `pueue add --delay [time] -- [cmd]`");
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .load_preset(UTF8_HORIZONTAL_BORDERS_ONLY)
+        .set_header(vec![
+            Cell::new("Index"),
+            Cell::new("Enqueue At"),
+            Cell::new("In"),
+            Cell::new("Group"),
+            Cell::new("Command"),
+        ]);
+
+    let now = Local::now();
+    for task in tasks {
+        // Checked by the filter above.
+        let enqueue_at = task.enqueue_at.unwrap();
+        table.add_row(vec![
+            Cell::new(task.id.to_string()),
+            Cell::new(enqueue_at.format("%Y-%m-%d %H:%M:%S")),
+            Cell::new(format_duration(enqueue_at - now)),
+            Cell::new(task.group.as_deref().unwrap_or("default")),
+            Cell::new(&task.command),
+        ]);
+    }
+
+    println!("{}", table);
+}
+
+/// The columns of `status --csv`. New columns are only ever appended.
+const CSV_COLUMNS: &[&str] = &[
+    "id",
+    "group",
+    "status",
+    "result",
+    "exit_code",
+    "label",
+    "command",
+    "path",
+    "created_at",
+    "start",
+    "end",
+    "runtime_seconds",
+    "signal",
+];
+
+/// Print all tasks as CSV with a header line, e.g. for spreadsheets or `awk`.
+fn print_csv(
+    tasks: &BTreeMap<usize, Task>,
+    group_only: &Option<String>,
+    sort: Option<&str>,
+    reverse: bool,
+) {
+    println!("{}", CSV_COLUMNS.join(","));
+    let format_time = |time: Option<DateTime<Local>>| time.map(|time| time.to_rfc3339());
+    for (id, task) in sort_tasks(tasks, sort, reverse) {
+        let group = task.group.clone().unwrap_or_else(|| "default".into());
+        if group_only
+            .as_ref()
+            .is_some_and(|group_only| group_only != &group)
+        {
+            continue;
+        }
+
+        let exit_code = match &task.result {
+            Some(TaskResult::Success) => Some(0),
+            Some(TaskResult::Failed(exit_code)) => Some(*exit_code),
+            _ => None,
+        };
+        let runtime = task.start.map(|start| {
+            let end = task.end.unwrap_or_else(Local::now);
+            (end - start).num_seconds().to_string()
+        });
+        let fields = vec![
+            Some(id.to_string()),
+            Some(group),
+            Some(task.status.to_string()),
+            task.result.as_ref().map(|result| result.to_string()),
+            exit_code.map(|exit_code| exit_code.to_string()),
+            task.label.clone(),
+            Some(task.command.clone()),
+            Some(task.path.clone()),
+            format_time(Some(task.created_at)),
+            format_time(task.start),
+            format_time(task.end),
+            runtime,
+            task.signal.map(|signal| signal.to_string()),
+        ];
+        let fields: Vec<String> = fields
+            .into_iter()
+            .map(|field| escape_csv(&field.unwrap_or_default()))
+            .collect();
+        println!("{}", fields.join(","));
+    }
+}
+
+/// Quote a CSV field, if it contains separators, quotes or line breaks.
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Determine the human readable task status representation and the respective color.
+fn get_status_text(task: &Task) -> (String, Color) {
+    let status_string = task.status.to_string();
+    match task.status {
+        TaskStatus::Running if task.overdue => {
+            (format!("{} (overdue)", status_string), Color::Yellow)
+        }
+        TaskStatus::Running => (status_string, Color::Green),
+        TaskStatus::Paused | TaskStatus::Locked => (status_string, Color::White),
+        TaskStatus::Queued if task.is_waiting() => ("Waiting".to_string(), Color::Yellow),
+        TaskStatus::Done => match &task.result {
+            Some(TaskResult::Success) if task.cached_from.is_some() => {
+                ("Success (cached)".to_string(), Color::Green)
+            }
+            Some(TaskResult::Success) => (TaskResult::Success.to_string(), Color::Green),
+            Some(TaskResult::DependencyFailed) => ("Dependency failed".to_string(), Color::Red),
+            Some(TaskResult::Skipped) => (TaskResult::Skipped.to_string(), Color::DarkGrey),
+            Some(TaskResult::DependencyTimeout) => ("Dependency timeout".to_string(), Color::Red),
+            Some(TaskResult::FailedToSpawn(_)) => ("Failed to spawn".to_string(), Color::Red),
+            Some(result) => (result.to_string(), Color::Red),
+            None => panic!("Got a 'Done' task without a task result. Please report this bug."),
+        },
+        _ => (status_string, Color::Yellow),
+    }
+}
+
+/// Print the tasks as a tree, in which each task is shown below the tasks it depends on.
+/// Tasks with several dependencies are shown below each of them, but their subtree only once.
+/// Queued tasks that still wait for their dependencies are greyed out.
+fn print_tree(tasks: &BTreeMap<usize, Task>, is_tty: bool) {
+    let mut printed = Vec::new();
+    let roots: Vec<usize> = tasks
+        .values()
+        .filter(|task| !task.dependencies.iter().any(|id| tasks.contains_key(id)))
+        .map(|task| task.id)
+        .collect();
+    for id in roots {
+        print_tree_node(tasks, id, "", None, &mut printed, is_tty);
+    }
+    println!();
+}
+
+fn print_tree_node(
+    tasks: &BTreeMap<usize, Task>,
+    id: usize,
+    prefix: &str,
+    last: Option<bool>,
+    printed: &mut Vec<usize>,
+    is_tty: bool,
+) {
+    let task = &tasks[&id];
+    let (branch, child_prefix) = match last {
+        None => ("", String::new()),
+        Some(true) => ("└─ ", format!("{}   ", prefix)),
+        Some(false) => ("├─ ", format!("{}│  ", prefix)),
+    };
+
+    let name = task.label.as_ref().unwrap_or(&task.command);
+    if printed.contains(&id) {
+        println!("{}{}{} {} (see above)", prefix, branch, id, name);
+        return;
+    }
+    printed.push(id);
+
+    let blocked = task.is_queued()
+        && task.dependencies.iter().any(|dependency| {
+            tasks
+                .get(dependency)
+                .is_some_and(|dependency| dependency.result != Some(TaskResult::Success))
+        });
+    let (status, color) = get_status_text(task);
+    let line = if blocked {
+        let text = format!("{} {} {}", id, status, name);
+        style_text(&text, is_tty, Some(Color::DarkGrey), None)
+    } else {
+        let status = style_text(&status, is_tty, Some(color), None);
+        format!("{} {} {}", id, status, name)
+    };
+    println!("{}{}{}", prefix, branch, line);
+
+    let children: Vec<usize> = tasks
+        .values()
+        .filter(|child| child.dependencies.contains(&id))
+        .map(|child| child.id)
+        .collect();
+    for (index, child) in children.iter().enumerate() {
+        let last = index == children.len() - 1;
+        print_tree_node(tasks, *child, &child_prefix, Some(last), printed, is_tty);
     }
 }
 
 /// Print some tasks into a nicely formatted table
-fn print_table(tasks: &BTreeMap<usize, Task>, settings: &Settings) {
-    let (has_delayed_tasks, has_dependencies) = has_special_columns(tasks);
+fn print_table(
+    tasks: &BTreeMap<usize, Task>,
+    sort: Option<&str>,
+    reverse: bool,
+    snippet: bool,
+    layout: &TableLayout,
+    settings: &Settings,
+) {
+    let (has_delayed_tasks, has_dependencies, has_artifacts, has_labels, has_progress, has_eta) =
+        has_special_columns(tasks);
 
     // Create table header row
     let mut headers = vec![Cell::new("Index"), Cell::new("Status")];
+    if has_progress {
+        headers.push(Cell::new("Progress"));
+    }
+    if has_eta {
+        headers.push(Cell::new("ETA"));
+    }
+    if has_labels {
+        headers.push(Cell::new("Label"));
+    }
     if has_delayed_tasks {
         headers.push(Cell::new("Enqueue At"));
     }
     if has_dependencies {
         headers.push(Cell::new("Deps"));
     }
+    let command_column = headers.len() + 1;
     headers.append(&mut vec![
         Cell::new("Exitcode"),
         Cell::new("Command"),
         Cell::new("Path"),
-        Cell::new("Start"),
-        Cell::new("End"),
     ]);
+    let time_columns = get_time_columns(settings);
+    for column in time_columns.iter() {
+        headers.push(Cell::new(column.header()));
+    }
+    if has_artifacts {
+        headers.push(Cell::new("Artifacts"));
+    }
+    if snippet {
+        headers.push(Cell::new("Output"));
+    }
 
     // Initialize comfy table.
+    let arrangement = if layout.full_width {
+        ContentArrangement::Disabled
+    } else {
+        ContentArrangement::Dynamic
+    };
     let mut table = Table::new();
     table
-        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_content_arrangement(arrangement)
         .load_preset(UTF8_HORIZONTAL_BORDERS_ONLY)
         .set_header(headers);
+    if let Some(width) = layout.command_width {
+        if let Some(column) = table.get_column_mut(command_column) {
+            // The constraint includes the padding on both sides of the cell.
+            column.set_constraint(ColumnConstraint::MaxWidth(width as u16 + 2));
+        }
+    }
 
     // Add rows one by one.
-    for (id, task) in tasks {
+    for (id, task) in sort_tasks(tasks, sort, reverse) {
         let mut row = Row::new();
         if let Some(height) = settings.client.max_status_lines {
             row.max_height(height);
         }
         row.add_cell(Cell::new(&id.to_string()));
 
-        // Determine the human readable task status representation and the respective color.
-        let status_string = task.status.to_string();
-        let (status_text, color) = match task.status {
-            TaskStatus::Running => (status_string, Color::Green),
-            TaskStatus::Paused | TaskStatus::Locked => (status_string, Color::White),
-            TaskStatus::Done => match &task.result {
-                Some(TaskResult::Success) => (TaskResult::Success.to_string(), Color::Green),
-                Some(TaskResult::DependencyFailed) => ("Dependency failed".to_string(), Color::Red),
-                Some(TaskResult::FailedToSpawn(_)) => ("Failed to spawn".to_string(), Color::Red),
-                Some(result) => (result.to_string(), Color::Red),
-                None => panic!("Got a 'Done' task without a task result. Please report this bug."),
-            },
-            _ => (status_string, Color::Yellow),
+        let (status_text, color) = get_status_text(task);
+        let status_text = if task.output_truncated {
+            format!("{} (truncated)", status_text)
+        } else {
+            status_text
         };
         row.add_cell(Cell::new(status_text).fg(color));
 
+        if has_progress {
+            let progress = match &task.progress {
+                Some(progress) if task.is_running() => progress.to_string(),
+                _ => String::new(),
+            };
+            row.add_cell(Cell::new(progress));
+        }
+
+        if has_eta {
+            row.add_cell(Cell::new(format_eta(task)));
+        }
+
+        if has_labels {
+            row.add_cell(Cell::new(task.label.as_deref().unwrap_or("")));
+        }
+
         if has_delayed_tasks {
             if let Some(enqueue_at) = task.enqueue_at {
                 row.add_cell(Cell::new(enqueue_at.format("%Y-%m-%d\n%H:%M:%S")));
@@ -139,42 +568,63 @@ fn print_table(tasks: &BTreeMap<usize, Task>, settings: &Settings) {
         }
 
         if has_dependencies {
-            let text = task
-                .dependencies
-                .iter()
-                .map(|id| id.to_string())
-                .collect::<Vec<String>>()
-                .join(", ");
+            let join = |ids: &[usize], separator: &str| {
+                ids.iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<String>>()
+                    .join(separator)
+            };
+            let mut text = join(&task.dependencies, ", ");
+            if !task.any_dependencies.is_empty() {
+                if !text.is_empty() {
+                    text.push_str(", ");
+                }
+                text.push_str(&format!("any of {}", join(&task.any_dependencies, "|")));
+            }
+            if !task.failure_dependencies.is_empty() {
+                if !text.is_empty() {
+                    text.push_str(", ");
+                }
+                text.push_str(&format!(
+                    "failure of {}",
+                    join(&task.failure_dependencies, "|")
+                ));
+            }
             row.add_cell(Cell::new(text));
         }
 
         // Match the color of the exit code.
-        // If the exit_code is none, it has been killed by the task handler.
-        let exit_code_cell = match task.result {
-            Some(TaskResult::Success) => Cell::new("0").fg(Color::Green),
-            Some(TaskResult::Failed(code)) => Cell::new(&code.to_string()).fg(Color::Red),
+        // If the exit_code is none, it has been killed by the task handler or a signal.
+        let exit_code_cell = match (&task.result, task.signal) {
+            (Some(TaskResult::Success), _) => Cell::new("0").fg(Color::Green),
+            (Some(TaskResult::Failed(code)), _) => Cell::new(&code.to_string()).fg(Color::Red),
+            (Some(TaskResult::Killed), Some(signal)) => {
+                Cell::new(format!("signal {}", signal)).fg(Color::Red)
+            }
             _ => Cell::new(""),
         };
         row.add_cell(exit_code_cell);
 
         // Add command and path.
-        row.add_cell(Cell::new(&task.command));
+        row.add_cell(Cell::new(layout.format_command(&task.command)));
         row.add_cell(Cell::new(&task.path));
 
-        // Add start time, if already set.
-        if let Some(start) = task.start {
-            let formatted = start.format("%H:%M").to_string();
-            row.add_cell(Cell::new(&formatted));
-        } else {
-            row.add_cell(Cell::new(""));
+        // Add the configured time columns, if the respective times are already set.
+        for column in time_columns.iter() {
+            row.add_cell(Cell::new(column.format(task)));
         }
 
-        // Add finish time, if already set.
-        if let Some(end) = task.end {
-            let formatted = end.format("%H:%M").to_string();
-            row.add_cell(Cell::new(&formatted));
-        } else {
-            row.add_cell(Cell::new(""));
+        if has_artifacts {
+            row.add_cell(Cell::new(task.artifacts.len().to_string()));
+        }
+
+        if snippet {
+            let line = if task.is_running() {
+                read_last_line(*id, &settings.shared.pueue_directory, None).unwrap_or_default()
+            } else {
+                String::new()
+            };
+            row.add_cell(Cell::new(line));
         }
 
         table.add_row(row);
@@ -184,6 +634,245 @@ fn print_table(tasks: &BTreeMap<usize, Task>, settings: &Settings) {
     println!("{}", table);
 }
 
+/// Print the artifacts of a single task.
+/// Print the tasks and their dependencies as a Graphviz digraph.
+/// Edges point from a dependency to the tasks that wait for it.
+pub fn print_dependency_graph(state: &State, group: &Option<String>, pipeline: &Option<String>) {
+    let tasks: BTreeMap<usize, &Task> = state
+        .tasks
+        .iter()
+        .filter(|(_, task)| group.is_none() || group == &task.group)
+        .filter(|(_, task)| pipeline.is_none() || pipeline == &task.pipeline)
+        .map(|(id, task)| (*id, task))
+        .collect();
+
+    println!("digraph pueue {{");
+    println!("    node [shape=box, style=filled];");
+    for (id, task) in tasks.iter() {
+        let color = match (&task.status, &task.result) {
+            (TaskStatus::Running, _) => "lightblue",
+            (TaskStatus::Paused, _) => "orange",
+            (TaskStatus::Done, Some(TaskResult::Success)) => "palegreen",
+            (TaskStatus::Done, Some(TaskResult::Skipped)) => "lightgrey",
+            (TaskStatus::Done, _) => "salmon",
+            _ => "white",
+        };
+        let status = match &task.result {
+            Some(result) => result.to_string(),
+            None => task.status.to_string(),
+        };
+        let name = task
+            .label
+            .as_ref()
+            .unwrap_or(&task.command)
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"");
+        println!(
+            "    {} [label=\"{}: {}\\n{}\", fillcolor={}];",
+            id, id, name, status, color
+        );
+    }
+    for (id, task) in tasks.iter() {
+        for dependency in task.dependencies.iter() {
+            if tasks.contains_key(dependency) {
+                println!("    {} -> {};", dependency, id);
+            }
+        }
+        // Only one of these dependencies has to succeed.
+        for dependency in task.any_dependencies.iter() {
+            if tasks.contains_key(dependency) {
+                println!("    {} -> {} [style=dashed];", dependency, id);
+            }
+        }
+        // This task only runs, if the dependency fails.
+        for dependency in task.failure_dependencies.iter() {
+            if tasks.contains_key(dependency) {
+                println!("    {} -> {} [color=red];", dependency, id);
+            }
+        }
+    }
+    println!("}}");
+}
+
+pub fn print_artifacts(state: &State, task_id: usize, json: bool) {
+    let task = match state.tasks.get(&task_id) {
+        Some(task) => task,
+        None => {
+            print_error(&format!("There's no task with id {}", task_id));
+            return;
+        }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string(&task.artifacts).unwrap());
+        return;
+    }
+
+    if task.artifact_patterns.is_empty() {
+        println!("Task {} doesn't declare any artifacts", task_id);
+        return;
+    }
+    if !task.is_done() {
+        println!(
+            "Artifacts of task {} are resolved once it finished",
+            task_id
+        );
+        return;
+    }
+    if task.artifacts.is_empty() {
+        println!("Task {} didn't produce any artifacts", task_id);
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .load_preset(UTF8_HORIZONTAL_BORDERS_ONLY)
+        .set_header(vec![Cell::new("Path"), Cell::new("Size")]);
+    for artifact in task.artifacts.iter() {
+        table.add_row(vec![
+            Cell::new(&artifact.path),
+            Cell::new(artifact.size.to_string()),
+        ]);
+    }
+    println!("{}", table);
+}
+
+/// Print general information about the daemon.
+pub fn print_daemon_info(info: DaemonInfoMessage, cli_command: &SubCommand) {
+    let json = match cli_command {
+        SubCommand::DaemonInfo { json } => *json,
+        _ => panic!(
+            "Got wrong Subcommand {:?} in print_daemon_info. This shouldn't happen",
+            cli_command
+        ),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string(&info).unwrap());
+        return;
+    }
+
+    let uptime = Local::now().signed_duration_since(info.start);
+    println!("Version: {}", info.version);
+    println!(
+        "Uptime: {}h {}m {}s (since {})",
+        uptime.num_hours(),
+        uptime.num_minutes() % 60,
+        uptime.num_seconds() % 60,
+        info.start.to_rfc2822()
+    );
+    println!("Pid: {}", info.pid);
+    match info.config_path {
+        Some(path) => println!("Config: {}", path),
+        None => println!("Config: default locations"),
+    }
+    println!("Pueue directory: {}", info.pueue_directory);
+    println!("Socket: {}", info.socket);
+    println!("Connected clients: {}", info.clients);
+    match info.state_file_size {
+        Some(size) => println!("State file size: {} bytes", size),
+        None => println!("State file size: unknown"),
+    }
+}
+
+/// Print the run statistics of all labels and commands in a table.
+/// Print all removed tasks that can still be restored via `pueue trash restore`.
+pub fn print_trash(mut trashed: Vec<TrashedTask>, cli_command: &SubCommand, settings: &Settings) {
+    let json = match cli_command {
+        SubCommand::Trash {
+            cmd: TrashCommand::List { json },
+        } => *json,
+        _ => panic!(
+            "Got wrong Subcommand {:?} in print_trash. This shouldn't happen",
+            cli_command
+        ),
+    };
+
+    for trashed_task in trashed.iter_mut() {
+        redact_task(&mut trashed_task.task, &settings.shared.redacted_envs);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string(&trashed).unwrap());
+        return;
+    }
+
+    if trashed.is_empty() {
+        println!("The trash is empty.");
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .load_preset(UTF8_HORIZONTAL_BORDERS_ONLY)
+        .set_header(vec![
+            Cell::new("Index"),
+            Cell::new("Status"),
+            Cell::new("Command"),
+            Cell::new("Group"),
+            Cell::new("Removed"),
+        ]);
+    for TrashedTask { task, removed_at } in trashed.iter() {
+        table.add_row(vec![
+            Cell::new(task.id.to_string()),
+            Cell::new(task.status.to_string()),
+            Cell::new(&task.command),
+            Cell::new(task.group.as_deref().unwrap_or("default")),
+            Cell::new(removed_at.format("%F %T").to_string()),
+        ]);
+    }
+    println!("{}", table);
+}
+
+pub fn print_statistics(statistics: Statistics, cli_command: &SubCommand) {
+    let json = match cli_command {
+        SubCommand::Stats { json, .. } => *json,
+        _ => panic!(
+            "Got wrong Subcommand {:?} in print_statistics. This shouldn't happen",
+            cli_command
+        ),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string(&statistics).unwrap());
+        return;
+    }
+
+    if statistics.is_empty() {
+        println!("There are no statistics yet. They're collected once tasks finish.");
+        return;
+    }
+
+    let format_seconds = |seconds: Option<i64>| match seconds {
+        Some(seconds) => format_duration(chrono::Duration::seconds(seconds)),
+        None => "-".to_string(),
+    };
+
+    let mut table = Table::new();
+    table
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .load_preset(UTF8_HORIZONTAL_BORDERS_ONLY)
+        .set_header(vec![
+            Cell::new("Label/Command"),
+            Cell::new("Runs"),
+            Cell::new("Mean"),
+            Cell::new("P95"),
+            Cell::new("Failure rate"),
+        ]);
+    for (key, run_statistics) in statistics.iter() {
+        table.add_row(vec![
+            Cell::new(key),
+            Cell::new(run_statistics.runs.to_string()),
+            Cell::new(format_seconds(run_statistics.mean())),
+            Cell::new(format_seconds(run_statistics.p95())),
+            Cell::new(format!("{:.0}%", run_statistics.failure_rate() * 100.0)),
+        ]);
+    }
+    println!("{}", table);
+}
+
 /// Print the log ouput of finished tasks.
 /// Either print the logs of every task
 /// or only print the logs of the specified tasks.
@@ -192,15 +881,29 @@ pub fn print_logs(
     cli_command: &SubCommand,
     settings: &Settings,
 ) {
-    let (json, task_ids) = match cli_command {
-        SubCommand::Log { json, task_ids } => (*json, task_ids.clone()),
+    let (json, task_ids, strip_ansi, split) = match cli_command {
+        SubCommand::Log {
+            json,
+            task_ids,
+            strip_ansi,
+            split,
+            ..
+        } => (*json, task_ids.clone(), *strip_ansi, *split),
         _ => panic!(
             "Got wrong Subcommand {:?} in print_log. This shouldn't happen",
             cli_command
         ),
     };
+    for task_log in task_logs.values_mut() {
+        redact_task(&mut task_log.task, &settings.shared.redacted_envs);
+    }
+
     if json {
-        println!("{}", serde_json::to_string(&task_logs).unwrap());
+        let exports: BTreeMap<usize, LogExport> = task_logs
+            .iter()
+            .map(|(id, task_log)| (*id, LogExport::new(task_log, settings, strip_ansi, split)))
+            .collect();
+        println!("{}", serde_json::to_string(&exports).unwrap());
         return;
     }
 
@@ -216,7 +919,7 @@ pub fn print_logs(
 
     let mut task_iter = task_logs.iter_mut().peekable();
     while let Some((_, mut task_log)) = task_iter.next() {
-        print_log(&mut task_log, settings);
+        print_log(&mut task_log, settings, strip_ansi);
 
         // Add a newline if there is another task that's going to be printed.
         if let Some((_, task_log)) = task_iter.peek() {
@@ -229,8 +932,133 @@ pub fn print_logs(
     }
 }
 
+/// The metadata and output of a task for `log --json`.
+#[derive(Serialize)]
+struct LogExport {
+    id: usize,
+    command: String,
+    path: String,
+    group: String,
+    label: Option<String>,
+    status: TaskStatus,
+    result: Option<TaskResult>,
+    exit_code: Option<i32>,
+    created_at: DateTime<Local>,
+    start: Option<DateTime<Local>>,
+    end: Option<DateTime<Local>>,
+    /// The runtime in seconds.
+    duration: Option<f64>,
+    /// Stdout followed by stderr, unless the streams are split.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stderr: Option<String>,
+}
+
+impl LogExport {
+    fn new(task_log: &TaskLogMessage, settings: &Settings, strip: bool, split: bool) -> LogExport {
+        let task = &task_log.task;
+        let (stdout, stderr) = if settings.read_local_logs() {
+            read_log_files(task.id, &settings.shared.pueue_directory, None).unwrap_or_default()
+        } else {
+            (
+                decompress_output(&task_log.stdout),
+                decompress_output(&task_log.stderr),
+            )
+        };
+        let (stdout, stderr) = if strip {
+            (strip_ansi(&stdout), strip_ansi(&stderr))
+        } else {
+            (stdout, stderr)
+        };
+        let exit_code = match &task.result {
+            Some(TaskResult::Success) => Some(0),
+            Some(TaskResult::Failed(exit_code)) => Some(*exit_code),
+            _ => None,
+        };
+        let duration = match (
+            task.start,
+            task.end.or_else(|| task.start.map(|_| Local::now())),
+        ) {
+            (Some(start), Some(end)) => Some((end - start).num_milliseconds() as f64 / 1000.0),
+            _ => None,
+        };
+
+        LogExport {
+            id: task.id,
+            command: task.command.clone(),
+            path: task.path.clone(),
+            group: task.group.clone().unwrap_or_else(|| "default".into()),
+            label: task.label.clone(),
+            status: task.status.clone(),
+            result: task.result.clone(),
+            exit_code,
+            created_at: task.created_at,
+            start: task.start,
+            end: task.end,
+            duration,
+            output: (!split).then(|| stdout.clone() + &stderr),
+            stdout: split.then_some(stdout),
+            stderr: split.then_some(stderr),
+        }
+    }
+}
+
+fn decompress_output(bytes: &Option<Vec<u8>>) -> String {
+    let mut output = Vec::new();
+    if let Some(bytes) = bytes {
+        let _ = FrameDecoder::new(bytes.as_slice()).read_to_end(&mut output);
+    }
+
+    String::from_utf8_lossy(&output).to_string()
+}
+
+/// Print the results of `log --grep`.
+/// Each block of lines is prefixed with the task id, the stream and the line number.
+pub fn print_log_matches(matches: BTreeMap<usize, Vec<LogMatch>>, cli_command: &SubCommand) {
+    let json = match cli_command {
+        SubCommand::Log { json, .. } => *json,
+        _ => panic!(
+            "Got wrong Subcommand {:?} in print_log_matches. This shouldn't happen",
+            cli_command
+        ),
+    };
+    if json {
+        println!("{}", serde_json::to_string(&matches).unwrap());
+        return;
+    }
+
+    if matches.is_empty() {
+        println!("No matches found");
+        return;
+    }
+
+    let is_tty = io::stdout().is_tty();
+    let mut first = true;
+    for (task_id, task_matches) in matches {
+        for log_match in task_matches {
+            if !first {
+                println!("--");
+            }
+            first = false;
+
+            let stream = if log_match.stderr { "stderr" } else { "stdout" };
+            for (offset, line) in log_match.lines.iter().enumerate() {
+                let prefix = format!("{}:{}:{}:", task_id, stream, log_match.start + offset);
+                println!(
+                    "{} {}",
+                    style_text(&prefix, is_tty, Some(Color::Green), None),
+                    line
+                );
+            }
+        }
+    }
+}
+
 /// Print the log of a single task.
-pub fn print_log(task_log: &mut TaskLogMessage, settings: &Settings) {
+pub fn print_log(task_log: &mut TaskLogMessage, settings: &Settings, strip_ansi: bool) {
     let task = &task_log.task;
     // We only show logs of finished or running tasks.
     if !vec![TaskStatus::Done, TaskStatus::Running, TaskStatus::Paused].contains(&task.status) {
@@ -247,7 +1075,82 @@ pub fn print_log(task_log: &mut TaskLogMessage, settings: &Settings) {
         None,
         Some(Attribute::Bold),
     );
-    let (exit_status, color) = match &task.result {
+    let (exit_status, color) = describe_result(&task.result);
+    let status_text = style_text(&exit_status, is_tty, Some(color), None);
+    println!("{} {}", task_text, status_text);
+
+    // Print command and path.
+    if let Some(label) = &task.label {
+        println!("Label: {}", label);
+    }
+    println!("Command: {}", task.command);
+    println!("Path: {}", task.path);
+    if task.quarantined {
+        let group = task.original_group.as_deref().unwrap_or("default");
+        println!("Original group: {}", group);
+    }
+    if let Some(worker) = &task.worker {
+        println!("Worker: {}", worker);
+    }
+    if let Some(host) = &task.host {
+        println!("Host: {}", host);
+    }
+    if let Some(container) = &task.container {
+        println!("Container: {}", container);
+    }
+    if !task.assigned_gpus.is_empty() {
+        let gpus: Vec<String> = task
+            .assigned_gpus
+            .iter()
+            .map(|gpu| gpu.to_string())
+            .collect();
+        println!("GPUs: {}", gpus.join(","));
+    }
+    if let Some(tempdir) = &task.tempdir {
+        println!("Temporary directory: {}", tempdir);
+    }
+    if let Some(before) = &task.before {
+        println!("Setup command: {}", before);
+    }
+    if let Some(cached_from) = task.cached_from {
+        println!("Cached: The output is taken from task {}", cached_from);
+    }
+
+    if let Some(start) = task.start {
+        println!("Start: {}", start.to_rfc2822());
+    }
+    if let Some(end) = task.end {
+        println!("End: {}", end.to_rfc2822());
+    }
+    if !task.notifications.is_empty() {
+        println!("Notifications:");
+        for notification in task.notifications.iter() {
+            println!(
+                "    {} {}",
+                notification.time.format("%H:%M:%S"),
+                notification.text
+            );
+        }
+    }
+    if !task.artifacts.is_empty() {
+        println!("Artifacts:");
+        for artifact in task.artifacts.iter() {
+            println!("    {} ({} bytes)", artifact.path, artifact.size);
+        }
+    }
+
+    if settings.read_local_logs() {
+        print_local_log_output(task_log.task.id, settings, is_tty, strip_ansi);
+    } else if task_log.stdout.is_some() && task_log.stderr.is_some() {
+        print_task_output_from_daemon(task_log, is_tty, strip_ansi);
+    } else {
+        println!("Logs requested from pueue daemon, but none received. Please report this bug.");
+    }
+}
+
+/// Describe the result of a task for humans, e.g. `failed with exit code 1`.
+fn describe_result(result: &Option<TaskResult>) -> (String, Color) {
+    match result {
         Some(TaskResult::Success) => ("completed successfully".into(), Color::Green),
         Some(TaskResult::Failed(exit_code)) => {
             (format!("failed with exit code {}", exit_code), Color::Red)
@@ -255,36 +1158,129 @@ pub fn print_log(task_log: &mut TaskLogMessage, settings: &Settings) {
         Some(TaskResult::FailedToSpawn(err)) => (format!("failed to spawn: {}", err), Color::Red),
         Some(TaskResult::Killed) => ("killed by system or user".into(), Color::Red),
         Some(TaskResult::DependencyFailed) => ("dependency failed".into(), Color::Red),
+        Some(TaskResult::Skipped) => ("skipped, since no dependency failed".into(), Color::White),
+        Some(TaskResult::DependencyTimeout) => (
+            "failed, since its dependencies didn't finish in time".into(),
+            Color::Red,
+        ),
         None => ("running".into(), Color::White),
+    }
+}
+
+/// Print everything that's known about a single task, including its previous attempts.
+/// Returns whether the task exists.
+pub fn print_task_info(state: &State, task_id: usize, settings: &Settings) -> bool {
+    let mut task = match state.tasks.get(&task_id) {
+        Some(task) => task.clone(),
+        None => {
+            print_error(&format!("There's no task with id {}", task_id));
+            return false;
+        }
     };
-    let status_text = style_text(&exit_status, is_tty, Some(color), None);
-    println!("{} {}", task_text, status_text);
+    redact_task(&mut task, &settings.shared.redacted_envs);
+    let is_tty = io::stdout().is_tty();
 
-    // Print command and path.
+    let title = style_text(
+        &format!("Task {}", task.id),
+        is_tty,
+        None,
+        Some(Attribute::Bold),
+    );
+    let status = match task.status {
+        TaskStatus::Done => describe_result(&task.result).0,
+        _ => task.status.to_string(),
+    };
+    println!("{}: {}", title, status);
+
+    if let Some(label) = &task.label {
+        println!("Label: {}", label);
+    }
     println!("Command: {}", task.command);
     println!("Path: {}", task.path);
+    println!("Group: {}", task.group.as_deref().unwrap_or("default"));
+    if task.quarantined {
+        let group = task.original_group.as_deref().unwrap_or("default");
+        println!("Original group: {}", group);
+    }
+    println!("Environment variables: {}", task.envs.len());
+    if task.priority != 0 {
+        println!("Priority: {}", task.priority);
+    }
+
+    let format_ids = |ids: &[usize]| {
+        ids.iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    if !task.dependencies.is_empty() {
+        println!("Dependencies: {}", format_ids(&task.dependencies));
+    }
+    if !task.any_dependencies.is_empty() {
+        println!("Any of: {}", format_ids(&task.any_dependencies));
+    }
+    if !task.failure_dependencies.is_empty() {
+        println!(
+            "After failure of: {}",
+            format_ids(&task.failure_dependencies)
+        );
+    }
 
+    println!("Added: {}", task.created_at.to_rfc2822());
+    if let Some(enqueue_at) = task.enqueue_at {
+        println!("Enqueue at: {}", enqueue_at.to_rfc2822());
+    }
     if let Some(start) = task.start {
         println!("Start: {}", start.to_rfc2822());
     }
     if let Some(end) = task.end {
         println!("End: {}", end.to_rfc2822());
     }
+    if let (Some(start), Some(end)) = (task.start, task.end) {
+        println!("Duration: {}", format_duration(end - start));
+    }
+    match (&task.result, task.signal) {
+        (_, Some(signal)) => println!("Signal: {}", signal),
+        (Some(TaskResult::Success), _) => println!("Exit code: 0"),
+        (Some(TaskResult::Failed(exit_code)), _) => println!("Exit code: {}", exit_code),
+        _ => (),
+    }
 
-    if settings.client.read_local_logs {
-        print_local_log_output(task_log.task.id, settings, is_tty);
-    } else if task_log.stdout.is_some() && task_log.stderr.is_some() {
-        print_task_output_from_daemon(task_log, is_tty);
-    } else {
-        println!("Logs requested from pueue daemon, but none received. Please report this bug.");
+    let (stdout_path, stderr_path) = get_log_paths(task.id, &settings.shared.pueue_directory);
+    println!("Stdout log: {}", stdout_path.display());
+    println!("Stderr log: {}", stderr_path.display());
+
+    // Follow the chain of restarts, as long as the previous tasks haven't been removed.
+    let mut previous = task.restarted_from;
+    let mut attempts = Vec::new();
+    while let Some(previous_task) = previous.and_then(|id| state.tasks.get(&id)) {
+        attempts.push(previous_task);
+        previous = previous_task.restarted_from;
     }
+    if !attempts.is_empty() {
+        println!("Previous attempts:");
+        for attempt in attempts {
+            let time = match attempt.start {
+                Some(start) => start.format("%F %T").to_string(),
+                None => "never started".to_string(),
+            };
+            println!(
+                "    Task {}: {} ({})",
+                attempt.id,
+                describe_result(&attempt.result).0,
+                time
+            );
+        }
+    }
+
+    true
 }
 
 /// The daemon didn't send any log output, thereby we didn't request any.
 /// If that's the case, read the log files from the local pueue directory
-pub fn print_local_log_output(task_id: usize, settings: &Settings, is_tty: bool) {
+pub fn print_local_log_output(task_id: usize, settings: &Settings, is_tty: bool, strip_ansi: bool) {
     let (mut stdout_log, mut stderr_log) =
-        match get_log_file_handles(task_id, &settings.shared.pueue_directory) {
+        match get_log_file_handles(task_id, &settings.shared.pueue_directory, None) {
             Ok((stdout, stderr)) => (stdout, stderr),
             Err(err) => {
                 println!("Failed to get log file handles: {}", err);
@@ -295,28 +1291,28 @@ pub fn print_local_log_output(task_id: usize, settings: &Settings, is_tty: bool)
     // without having to load anything into memory.
     let mut stdout = io::stdout();
 
-    if let Ok(metadata) = stdout_log.metadata() {
-        if metadata.len() != 0 {
+    if let Ok(size) = stdout_log.size() {
+        if size != 0 {
             println!(
                 "\n{}",
                 style_text("stdout:", is_tty, Some(Color::Green), Some(Attribute::Bold))
             );
 
-            if let Err(err) = io::copy(&mut stdout_log, &mut stdout) {
+            if let Err(err) = copy_output(&mut stdout_log, &mut stdout, strip_ansi) {
                 println!("Failed reading local stdout log file: {}", err);
             };
         }
     }
 
-    if let Ok(metadata) = stderr_log.metadata() {
-        if metadata.len() != 0 {
+    if let Ok(size) = stderr_log.size() {
+        if size != 0 {
             // Add a spacer line between stdout and stderr
             println!(
                 "\n{}",
                 style_text("stderr:", is_tty, Some(Color::Red), Some(Attribute::Bold))
             );
 
-            if let Err(err) = io::copy(&mut stderr_log, &mut stdout) {
+            if let Err(err) = copy_output(&mut stderr_log, &mut stdout, strip_ansi) {
                 println!("Failed reading local stderr log file: {}", err);
             };
         }
@@ -326,16 +1322,16 @@ pub fn print_local_log_output(task_id: usize, settings: &Settings, is_tty: bool)
 /// Prints log output received from the daemon.
 /// We can safely call .unwrap() on stdout and stderr in here, since this
 /// branch is always called after ensuring that both are `Some`.
-pub fn print_task_output_from_daemon(task_log: &TaskLogMessage, is_tty: bool) {
+pub fn print_task_output_from_daemon(task_log: &TaskLogMessage, is_tty: bool, strip_ansi: bool) {
     // Save whether stdout was printed, so we can add a newline between outputs.
     if !task_log.stdout.as_ref().unwrap().is_empty() {
-        if let Err(err) = print_remote_task_output(&task_log, is_tty, true) {
+        if let Err(err) = print_remote_task_output(&task_log, is_tty, true, strip_ansi) {
             println!("Error while parsing stdout: {}", err);
         }
     }
 
     if !task_log.stderr.as_ref().unwrap().is_empty() {
-        if let Err(err) = print_remote_task_output(&task_log, is_tty, false) {
+        if let Err(err) = print_remote_task_output(&task_log, is_tty, false, strip_ansi) {
             println!("Error while parsing stderr: {}", err);
         };
     }
@@ -346,6 +1342,7 @@ pub fn print_remote_task_output(
     task_log: &TaskLogMessage,
     is_tty: bool,
     stdout: bool,
+    strip_ansi: bool,
 ) -> Result<()> {
     let (pre_text, color, bytes) = if stdout {
         ("stdout: ", Color::Green, task_log.stdout.as_ref().unwrap())
@@ -362,11 +1359,23 @@ pub fn print_remote_task_output(
 
     let stdout = io::stdout();
     let mut write = stdout.lock();
-    io::copy(&mut decompressor, &mut write)?;
+    copy_output(&mut decompressor, &mut write, strip_ansi)?;
 
     Ok(())
 }
 
+/// Copy a task's output to the terminal, optionally without escape sequences.
+fn copy_output(reader: &mut impl Read, writer: &mut impl Write, strip: bool) -> io::Result<()> {
+    if !strip {
+        io::copy(reader, writer)?;
+        return Ok(());
+    }
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    writer.write_all(strip_ansi(&String::from_utf8_lossy(&bytes)).as_bytes())
+}
+
 /// Follow the log ouput of running task.
 ///
 /// If no task is specified, this will check for the following cases:
@@ -374,8 +1383,9 @@ pub fn print_remote_task_output(
 /// - No running task: Print an error that there are no running tasks
 /// - Single running task: Follow the output of that task
 /// - Multiple running tasks: Print out the list of possible tasks to follow.
-pub fn follow_task_logs(pueue_directory: String, task_id: usize, stderr: bool) {
-    let (stdout_handle, stderr_handle) = match get_log_file_handles(task_id, &pueue_directory) {
+pub fn follow_task_logs(pueue_directory: String, task_id: usize, stderr: bool, strip: bool) {
+    let (stdout_handle, stderr_handle) = match get_log_file_handles(task_id, &pueue_directory, None)
+    {
         Ok((stdout, stderr)) => (stdout, stderr),
         Err(err) => {
             println!("Failed to get log file handles: {}", err);
@@ -390,6 +1400,8 @@ pub fn follow_task_logs(pueue_directory: String, task_id: usize, stderr: bool) {
     // Stdout handler to directly write log file output to io::stdout
     // without having to load anything into memory.
     let mut stdout = io::stdout();
+    // Incomplete lines are kept back until they're finished, when escape sequences are removed.
+    let mut pending = Vec::new();
     loop {
         // Check whether the file still exists. Exit if it doesn't.
         if !handle_path.exists() {
@@ -397,7 +1409,20 @@ pub fn follow_task_logs(pueue_directory: String, task_id: usize, stderr: bool) {
             return;
         }
         // Read the next chunk of text from the last position.
-        if let Err(err) = io::copy(&mut handle, &mut stdout) {
+        let result = if strip {
+            handle.read_to_end(&mut pending).and_then(|_| {
+                let end = match pending.iter().rposition(|byte| *byte == b'\n') {
+                    Some(position) => position + 1,
+                    None => return Ok(()),
+                };
+                let lines: Vec<u8> = pending.drain(..end).collect();
+                stdout.write_all(strip_ansi(&String::from_utf8_lossy(&lines)).as_bytes())?;
+                stdout.flush()
+            })
+        } else {
+            io::copy(&mut handle, &mut stdout).map(|_| ())
+        };
+        if let Err(err) = result {
             println!("Error while reading file: {}", err);
             return;
         };
@@ -1,9 +1,12 @@
 use std::collections::BTreeMap;
 
+use chrono::prelude::*;
+use chrono::Duration;
 use crossterm::style::{style, Attribute, Color};
 
+use pueue::settings::Settings;
 use pueue::state::State;
-use pueue::task::Task;
+use pueue::task::{Task, TaskResult, TaskStatus};
 
 /// This is a simple small helper function with the purpose of easily styling text,
 /// while also prevent styling if we're printing to a non-tty output.
@@ -30,34 +33,241 @@ pub fn style_text(
     styled.to_string()
 }
 
-pub fn has_special_columns(tasks: &BTreeMap<usize, Task>) -> (bool, bool) {
+pub fn has_special_columns(tasks: &BTreeMap<usize, Task>) -> (bool, bool, bool, bool, bool, bool) {
     // Check whether there are any delayed tasks.
     // In case there are, we need to add another column to the table.
     let has_delayed_tasks = tasks.iter().any(|(_id, task)| task.enqueue_at.is_some());
 
     // Check whether there are any tasks with dependencies.
     // In case there are, we need to add another column to the table.
-    let has_dependencies = tasks
+    let has_dependencies = tasks.iter().any(|(_id, task)| {
+        !task.dependencies.is_empty()
+            || !task.any_dependencies.is_empty()
+            || !task.failure_dependencies.is_empty()
+    });
+
+    // Check whether there are any tasks that produced artifacts.
+    // In case there are, we need to add another column to the table.
+    let has_artifacts = tasks.iter().any(|(_id, task)| !task.artifacts.is_empty());
+
+    // Check whether there are any tasks with a label.
+    // In case there are, we need to add another column to the table.
+    let has_labels = tasks.iter().any(|(_id, task)| task.label.is_some());
+
+    // Check whether there are any running tasks that reported their progress.
+    // In case there are, we need to add another column to the table.
+    let has_progress = tasks
+        .iter()
+        .any(|(_id, task)| task.is_running() && task.progress.is_some());
+
+    // Check whether there are any running tasks with an estimated runtime.
+    // In case there are, we need to add another column to the table.
+    let has_eta = tasks
+        .iter()
+        .any(|(_id, task)| task.is_running() && task.expected_runtime.is_some());
+
+    (
+        has_delayed_tasks,
+        has_dependencies,
+        has_artifacts,
+        has_labels,
+        has_progress,
+        has_eta,
+    )
+}
+
+/// The optional time columns of the status table.
+pub enum TimeColumn {
+    Added,
+    Start,
+    End,
+    Duration,
+}
+
+impl TimeColumn {
+    pub fn header(&self) -> &'static str {
+        match self {
+            TimeColumn::Added => "Added",
+            TimeColumn::Start => "Start",
+            TimeColumn::End => "End",
+            TimeColumn::Duration => "Duration",
+        }
+    }
+
+    /// Format the respective time of a task.
+    /// The duration of running tasks is measured up to now.
+    pub fn format(&self, task: &Task) -> String {
+        let time = match self {
+            TimeColumn::Added => Some(task.created_at),
+            TimeColumn::Start => task.start,
+            TimeColumn::End => task.end,
+            TimeColumn::Duration => {
+                return match task.start {
+                    Some(start) => format_duration(task.end.unwrap_or_else(Local::now) - start),
+                    None => String::new(),
+                }
+            }
+        };
+
+        time.map(|time| time.format("%H:%M").to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Get the time columns from the `client.status_time_columns` setting.
+/// Unknown columns are ignored.
+pub fn get_time_columns(settings: &Settings) -> Vec<TimeColumn> {
+    settings
+        .client
+        .status_time_columns
         .iter()
-        .any(|(_id, task)| !task.dependencies.is_empty());
+        .filter_map(|column| match column.as_str() {
+            "added" => Some(TimeColumn::Added),
+            "start" => Some(TimeColumn::Start),
+            "end" => Some(TimeColumn::End),
+            "duration" => Some(TimeColumn::Duration),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The width long commands are cut at, if they're truncated without a `max_command_width`.
+const DEFAULT_TRUNCATE_WIDTH: usize = 50;
+
+/// How the command column of the status table is laid out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableLayout {
+    /// Don't wrap or truncate any column, not even to fit the terminal.
+    pub full_width: bool,
+    /// The maximum width of the command column.
+    pub command_width: Option<usize>,
+    /// Cut long commands at the `command_width` instead of wrapping them.
+    pub truncate: bool,
+}
+
+impl TableLayout {
+    /// Combine the settings and flags of `pueue status`.
+    /// Piped output is always printed in full width, so nothing gets lost.
+    pub fn new(
+        settings: &Settings,
+        is_tty: bool,
+        full_width: bool,
+        max_command_width: Option<usize>,
+        truncate: bool,
+        wrap: bool,
+    ) -> Self {
+        if full_width || !is_tty {
+            return TableLayout {
+                full_width: true,
+                command_width: None,
+                truncate: false,
+            };
+        }
+
+        let truncate = truncate || (settings.client.truncate_commands && !wrap);
+        let mut command_width = max_command_width.or(settings.client.max_command_width);
+        if truncate && command_width.is_none() {
+            command_width = Some(DEFAULT_TRUNCATE_WIDTH);
+        }
+        TableLayout {
+            full_width: false,
+            command_width,
+            truncate,
+        }
+    }
+
+    /// The command as it's shown in the command column.
+    pub fn format_command(&self, command: &str) -> String {
+        match self.command_width {
+            Some(width) if self.truncate => truncate_text(command, width),
+            _ => command.to_string(),
+        }
+    }
+}
+
+/// Cut a text down to its first line and at most `width` characters, marked by a trailing `…`.
+pub fn truncate_text(text: &str, width: usize) -> String {
+    let first_line = text.lines().next().unwrap_or("");
+    let is_cut = first_line.chars().count() > width || first_line.len() < text.trim_end().len();
+    if !is_cut {
+        return first_line.to_string();
+    }
+
+    let mut truncated: String = first_line.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Format a duration in a short human readable form, e.g. `1h 02m 03s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Sort tasks by the given column of `pueue status --sort`.
+/// Tasks with the same value stay ordered by their id.
+pub fn sort_tasks<'a>(
+    tasks: &'a BTreeMap<usize, Task>,
+    sort: Option<&str>,
+    reverse: bool,
+) -> Vec<(&'a usize, &'a Task)> {
+    let mut sorted: Vec<(&usize, &Task)> = tasks.iter().collect();
+    match sort {
+        Some("status") => sorted.sort_by_key(|(_, task)| status_rank(&task.status)),
+        Some("start") => sorted.sort_by_key(|(_, task)| task.start),
+        Some("runtime") => {
+            // Tasks that are still running are measured up to now.
+            let now = Local::now();
+            sorted.sort_by_key(|(_, task)| {
+                task.start
+                    .map(|start| task.end.unwrap_or(now).signed_duration_since(start))
+            })
+        }
+        _ => {}
+    }
+    if reverse {
+        sorted.reverse();
+    }
+
+    sorted
+}
 
-    (has_delayed_tasks, has_dependencies)
+/// The order of tasks when sorting by status, active tasks first.
+fn status_rank(status: &TaskStatus) -> usize {
+    match status {
+        TaskStatus::Running => 0,
+        TaskStatus::Paused => 1,
+        TaskStatus::Locked => 2,
+        TaskStatus::Queued => 3,
+        TaskStatus::Stashed => 4,
+        TaskStatus::Done => 5,
+    }
 }
 
 /// Return a nicely formatted headline that's displayed at the start of `pueue status`
 pub fn get_default_headline(state: &State, is_tty: bool) -> String {
     // Print the current daemon state.
-    let daemon_status_text = if state.running {
+    let daemon_status_text = if state.running && is_outside_run_window(state, "default") {
+        style_text("outside of run window", is_tty, Some(Color::Yellow), None)
+    } else if state.running {
         style_text("running", is_tty, Some(Color::Green), None)
     } else {
         style_text("paused", is_tty, Some(Color::Yellow), None)
     };
     let parallel = state.settings.daemon.default_parallel_tasks;
     format!(
-        "{} ({} parallel): {}",
+        "{} ({} parallel): {}{}",
         style_text("Default queue", is_tty, None, Some(Attribute::Bold)),
         parallel,
-        daemon_status_text
+        daemon_status_text,
+        format_drain_estimate(state, None, parallel)
     )
 }
 
@@ -67,25 +277,102 @@ pub fn get_group_headline(group: &str, state: &State, is_tty: bool) -> String {
     let group_text = style(format!("Group \"{}\"", group)).attribute(Attribute::Bold);
 
     let parallel = state.settings.daemon.groups.get(group).unwrap();
+    let estimate = format_drain_estimate(state, Some(group), *parallel);
 
     // Print the current state of the group.
-    if *state.groups.get(group).unwrap() {
+    let running = *state.groups.get(group).unwrap();
+    if running && is_outside_run_window(state, group) {
+        format!(
+            "{} ({} parallel): {}{}",
+            group_text,
+            parallel,
+            style_text("outside of run window", is_tty, Some(Color::Yellow), None),
+            estimate,
+        )
+    } else if running {
         format!(
-            "{} ({} parallel): {}",
+            "{} ({} parallel): {}{}",
             group_text,
             parallel,
             style_text("running", is_tty, Some(Color::Green), None),
+            estimate,
         )
     } else {
         format!(
-            "{} ({} parallel): {}",
+            "{} ({} parallel): {}{}",
             group_text,
             parallel,
             style_text("paused", is_tty, Some(Color::Yellow), None),
+            estimate,
         )
     }
 }
 
+/// Check whether a group has a run window, which is currently closed.
+fn is_outside_run_window(state: &State, group: &str) -> bool {
+    match state.settings.daemon.run_windows.get(group) {
+        Some(window) => !window.contains(Local::now()),
+        None => false,
+    }
+}
+
+/// The remaining runtime of a running task, based on its estimated runtime.
+pub fn remaining_runtime(task: &Task) -> Option<Duration> {
+    let expected = Duration::seconds(task.expected_runtime?);
+    let elapsed = Local::now() - task.start?;
+    Some((expected - elapsed).max(Duration::zero()))
+}
+
+/// The remaining time and the elapsed share of the estimated runtime of a running task.
+pub fn format_eta(task: &Task) -> String {
+    let (expected, start) = match (task.expected_runtime, task.start) {
+        (Some(expected), Some(start)) if task.is_running() => (expected, start),
+        _ => return String::new(),
+    };
+    let elapsed = (Local::now() - start).num_seconds();
+    let percentage = if expected > 0 {
+        elapsed * 100 / expected
+    } else {
+        100
+    };
+
+    match remaining_runtime(task) {
+        Some(remaining) if remaining > Duration::zero() => {
+            format!("~{} ({}%)", format_duration(remaining), percentage)
+        }
+        _ => format!("overdue ({}%)", percentage),
+    }
+}
+
+/// Estimate, when all queued and running tasks of a group are finished.
+/// This is only possible, if there's an estimate for every single one of them.
+fn format_drain_estimate(state: &State, group: Option<&str>, parallel: usize) -> String {
+    let mut total = Duration::zero();
+    let mut pending = 0;
+    for task in state.tasks.values() {
+        if task.group.as_deref() != group || !task.is_pending() {
+            continue;
+        }
+        let remaining = if task.is_running() {
+            remaining_runtime(task)
+        } else {
+            task.expected_runtime.map(Duration::seconds)
+        };
+        match remaining {
+            Some(remaining) => total = total + remaining,
+            None => return String::new(),
+        }
+        pending += 1;
+    }
+
+    if pending == 0 {
+        return String::new();
+    }
+    // Roughly assume that the tasks are evenly distributed on all slots.
+    let slots = parallel.clamp(1, pending) as i32;
+    format!(", drained in ~{}", format_duration(total / slots))
+}
+
 /// Get all tasks that aren't assigned to a group
 /// Those tasks are displayed first.
 pub fn get_default_tasks(tasks: &BTreeMap<usize, Task>) -> BTreeMap<usize, Task> {
@@ -120,3 +407,96 @@ pub fn sort_tasks_by_group(
 
     sorted_task_groups
 }
+
+/// Show each array job as a single entry, unless it's explicitly expanded.
+/// The entry is represented by the first task of the array, whose status summarizes the array:
+/// It's running as long as any task is running and done, once all tasks are done.
+///
+/// Only the newest task of each index is counted, so a restarted index replaces the old one.
+pub fn collapse_arrays(tasks: &BTreeMap<usize, Task>, expand: &[usize]) -> BTreeMap<usize, Task> {
+    let mut collapsed = BTreeMap::new();
+    let mut arrays: BTreeMap<usize, BTreeMap<usize, &Task>> = BTreeMap::new();
+    for (id, task) in tasks.iter() {
+        match (task.array_id, task.array_index) {
+            (Some(array_id), Some(index)) if !expand.contains(&array_id) => {
+                arrays.entry(array_id).or_default().insert(index, task);
+            }
+            (Some(_), Some(index)) => {
+                let mut task = task.clone();
+                task.command = format!("[index {}] {}", index, task.command);
+                collapsed.insert(*id, task);
+            }
+            _ => {
+                collapsed.insert(*id, task.clone());
+            }
+        }
+    }
+
+    for (array_id, indices) in arrays {
+        let first_id = indices.values().map(|task| task.id).min().unwrap();
+        let mut entry = tasks.get(&first_id).unwrap().clone();
+
+        let succeeded = indices.values().filter(|task| task_succeeded(task)).count();
+        let failed = indices
+            .values()
+            .filter(|task| task.is_done() && !task_succeeded(task))
+            .count();
+        let running = indices
+            .values()
+            .filter(|task| matches!(task.status, TaskStatus::Running | TaskStatus::Paused))
+            .count();
+
+        entry.status = if running > 0 {
+            TaskStatus::Running
+        } else if succeeded + failed < indices.len() {
+            TaskStatus::Queued
+        } else {
+            TaskStatus::Done
+        };
+        entry.result = if entry.status == TaskStatus::Done {
+            indices
+                .values()
+                .find(|task| !task_succeeded(task))
+                .map_or(Some(TaskResult::Success), |task| task.result.clone())
+        } else {
+            None
+        };
+        entry.progress = None;
+        entry.overdue = false;
+        entry.start = indices.values().filter_map(|task| task.start).min();
+        entry.end = if entry.status == TaskStatus::Done {
+            indices.values().filter_map(|task| task.end).max()
+        } else {
+            None
+        };
+        entry.command = format!(
+            "[array {}: {} tasks, {} succeeded, {} failed, {} running] {}",
+            array_id,
+            indices.len(),
+            succeeded,
+            failed,
+            running,
+            entry.command
+        );
+        collapsed.insert(first_id, entry);
+    }
+
+    collapsed
+}
+
+fn task_succeeded(task: &Task) -> bool {
+    matches!(task.result, Some(TaskResult::Success))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_text() {
+        assert_eq!(truncate_text("ls -al", 10), "ls -al");
+        assert_eq!(truncate_text("ffmpeg -i input.mkv", 10), "ffmpeg -i…");
+        assert_eq!(truncate_text("echo a\necho b", 10), "echo a…");
+        assert_eq!(truncate_text("echo a\n", 10), "echo a");
+    }
+}
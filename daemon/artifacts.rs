@@ -0,0 +1,141 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use pueue::pattern::wildcard_match;
+use pueue::task::{Artifact, Task};
+
+/// Resolve the artifact patterns of a task into a list of existing files.
+/// Relative patterns are resolved from the working directory of the task.
+/// For tasks with a temporary directory, that's the temporary directory.
+///
+/// Supported wildcards are `*` and `?` inside of a path component
+/// and `**` as a full component, which matches any amount of directories.
+pub fn collect_artifacts(task: &Task) -> Vec<Artifact> {
+    let mut paths = BTreeSet::new();
+    for pattern in task.artifact_patterns.iter() {
+        let pattern = Path::new(task.working_directory()).join(pattern);
+
+        // Everything up to the first component with a wildcard is a fixed path prefix.
+        let mut root = PathBuf::new();
+        let mut components = Vec::new();
+        for component in pattern.components() {
+            let part = component.as_os_str().to_string_lossy().to_string();
+            if components.is_empty() && !has_wildcard(&part) {
+                root.push(component);
+            } else {
+                components.push(part);
+            }
+        }
+
+        expand(&root, &components, &mut paths);
+    }
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let metadata = fs::metadata(&path).ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            Some(Artifact {
+                path: path.to_string_lossy().to_string(),
+                size: metadata.len(),
+            })
+        })
+        .collect()
+}
+
+/// Recursively walk the filesystem and collect all files matching the remaining components.
+fn expand(path: &Path, components: &[String], matches: &mut BTreeSet<PathBuf>) {
+    let (component, rest) = match components.split_first() {
+        Some(split) => split,
+        None => {
+            if path.is_file() {
+                matches.insert(path.to_path_buf());
+            }
+            return;
+        }
+    };
+
+    if !has_wildcard(component) {
+        expand(&path.join(component), rest, matches);
+        return;
+    }
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    // `**` matches the current directory as well as every directory below it.
+    if component == "**" {
+        expand(path, rest, matches);
+        for entry in entries.flatten() {
+            // Don't follow symlinks to prevent endless loops.
+            if entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false) {
+                expand(&entry.path(), components, matches);
+            }
+        }
+        return;
+    }
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if wildcard_match(component, &name) {
+            expand(&entry.path(), rest, matches);
+        }
+    }
+}
+
+fn has_wildcard(text: &str) -> bool {
+    text.contains('*') || text.contains('?')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pueue::task::TaskStatus;
+
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.txt", "output.txt"));
+        assert!(wildcard_match("out?ut.*", "output.log"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("a*b*c", "aXbYbZc"));
+        assert!(!wildcard_match("*.txt", "output.log"));
+        assert!(!wildcard_match("?", ""));
+        assert!(!wildcard_match("a*b", "aXbY"));
+    }
+
+    #[test]
+    fn test_collect_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("out/nested")).unwrap();
+        fs::write(dir.path().join("out/result.txt"), "12345").unwrap();
+        fs::write(dir.path().join("out/nested/deep.txt"), "1").unwrap();
+        fs::write(dir.path().join("out/nested/ignored.log"), "1").unwrap();
+
+        let mut task = Task::new(
+            "true".into(),
+            dir.path().to_string_lossy().to_string(),
+            HashMap::new(),
+            None,
+            TaskStatus::Queued,
+            None,
+            Vec::new(),
+        );
+        task.artifact_patterns = vec!["out/**/*.txt".into()];
+
+        let artifacts = collect_artifacts(&task);
+        let names: Vec<String> = artifacts
+            .iter()
+            .map(|artifact| artifact.path.replace(&task.path, ""))
+            .collect();
+        assert_eq!(names, vec!["/out/nested/deep.txt", "/out/result.txt"]);
+        assert_eq!(artifacts[1].size, 5);
+    }
+}
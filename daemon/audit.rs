@@ -0,0 +1,134 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::Local;
+use log::warn;
+
+use pueue::message::Message;
+
+/// Append a received message to the audit log inside the pueue directory.
+/// Each line consists of the timestamp, the client and a summary of the message.
+///
+/// Failing to write the audit log is only logged, the message is still processed.
+pub fn audit(pueue_directory: &str, client: &str, message: &Message, denied: bool) {
+    if let Err(error) = write_entry(pueue_directory, client, message, denied) {
+        warn!("Failed to write audit log: {}", error);
+    }
+}
+
+fn write_entry(pueue_directory: &str, client: &str, message: &Message, denied: bool) -> Result<()> {
+    let path = Path::new(pueue_directory).join("audit.log");
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    let mut summary = summarize(message);
+    if denied {
+        summary.push_str(" (denied)");
+    }
+    writeln!(
+        file,
+        "{}\t{}\t{}",
+        Local::now().to_rfc3339(),
+        client,
+        summary
+    )?;
+
+    Ok(())
+}
+
+/// Summarize a message in a single line.
+/// Environment variables, input and output of tasks are left out,
+/// since they might contain secrets and can get rather large.
+fn summarize(message: &Message) -> String {
+    let summary = match message {
+        Message::Add(message) => format!(
+            "Add {:?} in {} (group {})",
+            message.command,
+            message.path,
+            message.group.as_deref().unwrap_or("default")
+        ),
+        Message::AddBundle(message) => format!(
+            "AddBundle of tasks {:?} in {} (pipeline {})",
+            message.bundle.tasks.keys().collect::<Vec<_>>(),
+            message.path,
+            message.pipeline.as_deref().unwrap_or("none")
+        ),
+        Message::Send(message) => format!("Send input to task {}", message.task_id),
+        Message::WorkerReport(message) => format!(
+            "WorkerReport of task {} by {} with exit code {:?}",
+            message.task_id, message.worker, message.exit_code
+        ),
+        message => format!("{:?}", message),
+    };
+
+    // Keep every entry on a single line.
+    summary.replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pueue::message::{AddMessage, SendMessage};
+    use pueue::task::EnvironmentMode;
+
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let mut envs = HashMap::new();
+        envs.insert("TOKEN".to_string(), "hidden".to_string());
+        let message = Message::Add(Box::new(AddMessage {
+            command: "echo 'a\nb'".into(),
+            path: "/tmp".into(),
+            envs,
+            start_immediately: false,
+            stashed: false,
+            group: None,
+            enqueue_at: None,
+            dependencies: Vec::new(),
+            any_dependencies: Vec::new(),
+            failure_dependencies: Vec::new(),
+            dependency_timeout: None,
+            ignore_aliases: false,
+            unique_key: None,
+            replace_duplicate: false,
+            slots: 1,
+            lock: None,
+            stdin_from: None,
+            artifacts: Vec::new(),
+            tempdir: false,
+            container: None,
+            host: None,
+            stop_command: None,
+            on_exit: None,
+            before: None,
+            login_shell: false,
+            direnv: false,
+            env_mode: EnvironmentMode::All,
+            line_buffered: false,
+            label: None,
+            priority: 0,
+            cpus: None,
+            gpus: 0,
+            wait_for_path: None,
+            wait_for_host: None,
+            array: None,
+            array_id: None,
+            cache: None,
+            restarted_from: None,
+        }));
+        assert_eq!(
+            summarize(&message),
+            "Add \"echo 'a\\nb'\" in /tmp (group default)"
+        );
+
+        let message = Message::Send(SendMessage {
+            task_id: 3,
+            input: "password".into(),
+        });
+        assert_eq!(summarize(&message), "Send input to task 3");
+        assert_eq!(summarize(&Message::Reset(false)), "Reset(false)");
+    }
+}
@@ -1,7 +1,44 @@
 use std::path::PathBuf;
 
+use anyhow::{Context, Result};
 use structopt::StructOpt;
 
+#[derive(StructOpt, Debug)]
+pub enum SubCommand {
+    /// Manage the Windows service of the daemon.
+    Service {
+        #[structopt(subcommand)]
+        action: ServiceAction,
+    },
+
+    /// Install a launchd user agent on macOS, which starts the daemon at login.
+    /// The agent uses the current binary and the given config file.
+    InstallLaunchd,
+
+    /// Unload and remove the launchd user agent.
+    UninstallLaunchd,
+
+    /// Print a systemd service unit for the current binary and config file.
+    /// By default, the unit is a system unit, which runs the daemon as the current user.
+    GenerateSystemd {
+        /// Generate a user unit instead, e.g. for `~/.config/systemd/user/pueued.service`.
+        #[structopt(long)]
+        user: bool,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum ServiceAction {
+    /// Register the daemon as a Windows service, which automatically starts on boot.
+    /// The service uses the current binary and the given config file.
+    Install,
+    /// Stop and remove the Windows service.
+    Uninstall,
+    /// Run the daemon as a Windows service.
+    /// This is invoked by the Windows service control manager and shouldn't be called manually.
+    Run,
+}
+
 #[derive(StructOpt, Debug)]
 #[structopt(
     name = "Pueue daemon",
@@ -27,4 +64,36 @@ pub struct Opt {
     /// This ignores all other config files.
     #[structopt(short, long)]
     pub config: Option<PathBuf>,
+
+    /// Write the man page of the daemon into this directory and exit.
+    #[structopt(long)]
+    pub generate_man: Option<PathBuf>,
+
+    /// Start, even if the pid file claims that another daemon is running for the pueue directory.
+    /// Use this to take over from a stale pid file after a crash.
+    #[structopt(long)]
+    pub replace: bool,
+
+    #[structopt(subcommand)]
+    pub cmd: Option<SubCommand>,
+}
+
+impl Opt {
+    /// The command line, with which a service manager should start the current daemon.
+    /// It contains the absolute paths of the current binary and config file.
+    pub fn service_arguments(&self) -> Result<Vec<String>> {
+        let mut arguments = vec![std::env::current_exe()?.to_string_lossy().to_string()];
+        if let Some(config) = &self.config {
+            let config = config
+                .canonicalize()
+                .context("Failed to find the config file")?;
+            arguments.push("--config".into());
+            arguments.push(config.to_string_lossy().to_string());
+        }
+        if self.verbose > 0 {
+            arguments.push(format!("-{}", "v".repeat(self.verbose as usize)));
+        }
+
+        Ok(arguments)
+    }
 }
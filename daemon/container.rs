@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Result};
+
+use crate::task_handler::ProcessAction;
+
+/// The name of the container a task is executed in.
+/// It's used to address the container when pausing or killing the task.
+pub fn container_name(task_id: usize) -> String {
+    format!("pueue_task_{}", task_id)
+}
+
+/// Compile the command that runs a task inside of a container.
+/// The working directory of the task is mounted into the container at the same location,
+/// while the output of the container is attached to the task's log files as usual.
+/// The CPUs of `add --cpus` and the task's environment are passed to the runtime,
+/// since they have to apply inside of the container.
+pub fn compile_container_command(
+    runtime: &str,
+    task_id: usize,
+    image: &str,
+    command_string: &str,
+    working_directory: &Path,
+    cpus: Option<&str>,
+    envs: &BTreeMap<String, String>,
+) -> Command {
+    let directory = working_directory.to_string_lossy();
+    let mut command = Command::new(runtime);
+    command
+        .arg("run")
+        .arg("--rm")
+        .arg("--interactive")
+        .arg("--name")
+        .arg(container_name(task_id))
+        .arg("--volume")
+        .arg(format!("{}:{}", directory, directory))
+        .arg("--workdir")
+        .arg(directory.to_string());
+    if let Some(cpus) = cpus {
+        command.arg("--cpuset-cpus").arg(cpus);
+    }
+    for (key, value) in envs {
+        command.arg("--env").arg(format!("{}={}", key, value));
+    }
+    command.arg(image).arg("sh").arg("-c").arg(command_string);
+
+    command
+}
+
+/// Pause, resume or kill the container of a task via the container runtime.
+pub fn send_action_to_container(
+    runtime: &str,
+    task_id: usize,
+    action: &ProcessAction,
+) -> Result<()> {
+    let (subcommand, args): (&str, &[&str]) = match action {
+        ProcessAction::Pause => ("pause", &[]),
+        ProcessAction::Resume => ("unpause", &[]),
+        ProcessAction::Terminate => ("kill", &["--signal", "TERM"]),
+        ProcessAction::Kill => ("kill", &[]),
+    };
+
+    let output = Command::new(runtime)
+        .arg(subcommand)
+        .args(args)
+        .arg(container_name(task_id))
+        .output()?;
+
+    if !output.status.success() {
+        bail!(
+            "Failed to {} container of task {}: {}",
+            subcommand,
+            task_id,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_command() {
+        let mut envs = BTreeMap::new();
+        envs.insert("PUEUE_TASK_ID".to_string(), "3".to_string());
+        envs.insert("CUDA_VISIBLE_DEVICES".to_string(), "0,1".to_string());
+
+        let command = compile_container_command(
+            "docker",
+            3,
+            "alpine",
+            "echo test",
+            Path::new("/tmp/work"),
+            Some("0-1"),
+            &envs,
+        );
+        let arguments: Vec<_> = command
+            .get_args()
+            .map(|argument| argument.to_string_lossy().to_string())
+            .collect();
+
+        assert_eq!(command.get_program(), "docker");
+        assert_eq!(
+            arguments,
+            vec![
+                "run",
+                "--rm",
+                "--interactive",
+                "--name",
+                "pueue_task_3",
+                "--volume",
+                "/tmp/work:/tmp/work",
+                "--workdir",
+                "/tmp/work",
+                "--cpuset-cpus",
+                "0-1",
+                "--env",
+                "CUDA_VISIBLE_DEVICES=0,1",
+                "--env",
+                "PUEUE_TASK_ID=3",
+                "alpine",
+                "sh",
+                "-c",
+                "echo test",
+            ]
+        );
+    }
+}
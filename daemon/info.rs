@@ -0,0 +1,41 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::prelude::*;
+
+use pueue::message::*;
+use pueue::state::SharedState;
+
+/// Runtime information about the daemon, which isn't part of the state.
+pub struct RuntimeInfo {
+    pub start: DateTime<Local>,
+    /// A description of the socket the daemon is listening on.
+    pub socket: String,
+    /// The amount of currently connected clients.
+    pub clients: AtomicUsize,
+}
+
+/// Invoked when calling `pueue daemon-info`.
+/// Return some general information about the daemon, which is mostly useful for debugging.
+pub fn get_daemon_info(info: &RuntimeInfo, state: &SharedState) -> Message {
+    let state = state.lock().unwrap();
+    let pueue_directory = state.settings.shared.pueue_directory.clone();
+    let state_file_size = fs::metadata(Path::new(&pueue_directory).join("state.json"))
+        .map(|metadata| metadata.len())
+        .ok();
+
+    Message::DaemonInfoResponse(DaemonInfoMessage {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        start: info.start,
+        pid: std::process::id(),
+        config_path: state
+            .config_path()
+            .as_ref()
+            .map(|path| path.to_string_lossy().to_string()),
+        pueue_directory,
+        socket: info.socket.clone(),
+        clients: info.clients.load(Ordering::SeqCst),
+        state_file_size,
+    })
+}
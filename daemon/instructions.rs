@@ -1,25 +1,38 @@
 use std::collections::BTreeMap;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
 use std::sync::mpsc::Sender;
+use std::time::Instant;
 
-use log::debug;
+use chrono::prelude::*;
+use chrono::Duration;
+use log::{debug, info, warn};
 
-use pueue::log::{clean_log_handles, read_and_compress_log_files};
+use pueue::log::*;
 use pueue::message::*;
-use pueue::state::SharedState;
-use pueue::task::{Task, TaskStatus};
+use pueue::state::{SharedState, State};
+use pueue::task::{Notification, Task, TaskResult, TaskStatus};
 
 use crate::aliasing::insert_alias;
 use crate::response_helper::*;
+use crate::task_handler::task_environment;
+use crate::template::render_command;
 
 static SENDER_ERR: &str = "Failed to send message to task handler thread";
+static NO_MATCHING_TASKS: &str = "No tasks match the given selection";
 
 pub fn handle_message(message: Message, sender: &Sender<Message>, state: &SharedState) -> Message {
     match message {
-        Message::Add(message) => add_task(message, sender, state),
-        Message::Remove(task_ids) => remove(task_ids, state),
+        Message::Add(message) => add_task(*message, sender, state),
+        Message::AddBundle(message) => add_bundle(message, state),
+        Message::CancelPipeline(pipeline) => cancel_pipeline(pipeline, sender, state),
+        Message::Remove(selection) => remove(selection, state),
         Message::Switch(message) => switch(message, state),
-        Message::Stash(task_ids) => stash(task_ids, state),
+        Message::Stash(selection) => stash(selection, state),
         Message::Enqueue(message) => enqueue(message, state),
+        Message::Move(message) => move_tasks(message, state),
+        Message::Restart(message) => restart_in_place(message, sender, state),
 
         Message::Start(message) => start(message, sender, state),
         Message::Pause(message) => pause(message, sender, state),
@@ -30,12 +43,26 @@ pub fn handle_message(message: Message, sender: &Sender<Message>, state: &Shared
         Message::Edit(message) => edit(message, state),
         Message::Group(message) => group(message, state),
 
-        Message::Clean => clean(state),
+        Message::Clean(successful_only) => clean(state, successful_only),
+        Message::Undo => undo(state),
+        Message::TrashList => Message::TrashResponse(state.lock().unwrap().trashed_tasks()),
+        Message::TrashRestore(task_ids) => restore_from_trash(task_ids, state),
+        Message::TrashEmpty => empty_trash(state),
         Message::Reset(children) => reset(sender, children),
         Message::Status => get_status(state),
+        Message::StatusPage(message) => get_status_page(message, state),
+        Message::StatusSince(revision) => get_status_since(revision, state),
         Message::Log(message) => get_log(message, state),
+        Message::LogGrep(message) => grep_logs(message, state),
         Message::Parallel(message) => set_parallel_tasks(message, state),
-        Message::DaemonShutdown => shutdown(sender, state),
+        Message::DaemonShutdown(graceful) => shutdown(sender, state, graceful),
+        Message::Version(_) => Message::Version(env!("CARGO_PKG_VERSION").to_string()),
+        Message::WorkerPull(message) => worker_pull(message, state),
+        Message::WorkerReport(message) => worker_report(message, state),
+        Message::WorkerHeartbeat(message) => worker_heartbeat(message, state),
+        Message::Notify(message) => notify(message, sender, state),
+        Message::Progress(message) => set_progress(message, state),
+        Message::Stats(filter) => get_statistics(filter, state),
         _ => create_failure_message("Not implemented yet"),
     }
 }
@@ -52,10 +79,19 @@ fn add_task(message: AddMessage, sender: &Sender<Message>, state: &SharedState)
 
     let mut state = state.lock().unwrap();
 
+    // A task that reads the output of another task implicitly depends on it.
+    let mut dependencies = message.dependencies;
+    if let Some(stdin_from) = message.stdin_from {
+        if !dependencies.contains(&stdin_from) {
+            dependencies.push(stdin_from);
+        }
+    }
+
     // Ensure that specified dependencies actually exist.
-    let not_found: Vec<_> = message
-        .dependencies
+    let not_found: Vec<_> = dependencies
         .iter()
+        .chain(message.any_dependencies.iter())
+        .chain(message.failure_dependencies.iter())
         .filter(|id| !state.tasks.contains_key(id))
         .collect();
     if !not_found.is_empty() {
@@ -65,6 +101,15 @@ fn add_task(message: AddMessage, sender: &Sender<Message>, state: &SharedState)
         ));
     }
 
+    // The task would never be started otherwise.
+    let gpu_pool = state.settings.daemon.gpus.len();
+    if message.gpus > gpu_pool {
+        return create_failure_message(format!(
+            "The task needs {} GPUs, but the daemon only manages {}. See `daemon.gpus`.",
+            message.gpus, gpu_pool
+        ));
+    }
+
     let command = if !message.ignore_aliases {
         // Check if there exists an alias for the given command
         insert_alias(message.command)
@@ -73,15 +118,40 @@ fn add_task(message: AddMessage, sender: &Sender<Message>, state: &SharedState)
     };
 
     // Create a new task and add it to the state.
-    let task = Task::new(
+    let mut task = Task::new(
         command,
         message.path,
         message.envs,
         message.group,
         starting_status,
-        message.enqueue_at,
-        message.dependencies,
+        message.enqueue_at.map(|time| time.with_timezone(&Local)),
+        dependencies,
     );
+    task.unique_key = message.unique_key;
+    task.slots = message.slots;
+    task.lock = message.lock;
+    task.stdin_from = message.stdin_from;
+    task.artifact_patterns = message.artifacts;
+    task.use_tempdir = message.tempdir;
+    task.container = message.container;
+    task.host = message.host;
+    task.stop_command = message.stop_command;
+    task.on_exit = message.on_exit;
+    task.before = message.before;
+    task.any_dependencies = message.any_dependencies;
+    task.failure_dependencies = message.failure_dependencies;
+    task.dependency_timeout = message.dependency_timeout;
+    task.login_shell = message.login_shell;
+    task.direnv = message.direnv;
+    task.env_mode = message.env_mode;
+    task.line_buffered = message.line_buffered;
+    task.label = message.label;
+    task.priority = message.priority;
+    task.cpus = message.cpus;
+    task.gpus = message.gpus;
+    task.wait_for_path = message.wait_for_path;
+    task.wait_for_host = message.wait_for_host;
+    task.restarted_from = message.restarted_from;
 
     // Create a new group in case the user used a unknown group.
     if let Some(group) = &task.group {
@@ -93,7 +163,76 @@ fn add_task(message: AddMessage, sender: &Sender<Message>, state: &SharedState)
         }
     }
 
-    let task_id = state.add_task(task);
+    // Remote hosts need to be configured beforehand.
+    if let Some(host) = &task.host {
+        if !state.settings.hosts.contains_key(host) {
+            return create_failure_message(format!(
+                "Tried to create task with unknown host '{}'",
+                host
+            ));
+        }
+    }
+
+    // Tasks that need more slots than the group's slot pool provides would never be started.
+    let group_key = task.group.as_deref().unwrap_or("default");
+    if let Some(pool) = state.settings.daemon.slots.get(group_key) {
+        if task.slots > *pool {
+            return create_failure_message(format!(
+                "Task needs {} slots, but group '{}' only has {} slots",
+                task.slots, group_key, pool
+            ));
+        }
+    }
+
+    if let Some((first, last)) = message.array {
+        return add_array(
+            task,
+            first..=last,
+            message.array_id,
+            message.start_immediately,
+            sender,
+            &mut state,
+        );
+    }
+
+    // Skip the execution, if the same task recently succeeded.
+    if let Some(window) = message.cache {
+        if let Some(cached_id) = find_cached_run(&state, &task, window) {
+            return add_cached_task(task, cached_id, &mut state);
+        }
+    }
+
+    // Check whether there's already a queued task with the same unique key.
+    let duplicate_id = task
+        .unique_key
+        .as_ref()
+        .and_then(|key| state.queued_task_with_key(key));
+
+    let task_id = match duplicate_id {
+        // Drop the new task and keep the one that's already queued.
+        Some(duplicate_id) if !message.replace_duplicate => {
+            return create_success_message(format!(
+                "Task {} with the same unique key is already queued. The new task has been dropped.",
+                duplicate_id
+            ));
+        }
+        // Replace the queued task in-place, so it keeps its id and position in the queue.
+        Some(duplicate_id) => {
+            state.replace_task(duplicate_id, task);
+            duplicate_id
+        }
+        None => {
+            // Reject the task, if there are already too many pending tasks in this group.
+            if state.queue_is_full(&task.group) {
+                return create_failure_message(format!(
+                    "The queue of group '{}' is full. Try again later or use `--wait-for-slot`",
+                    task.group.as_deref().unwrap_or("default")
+                ));
+            }
+
+            state.add_task(task)
+        }
+    };
 
     // Notify the task handler, in case the client wants to start the task immediately.
     if message.start_immediately {
@@ -105,7 +244,13 @@ fn add_task(message: AddMessage, sender: &Sender<Message>, state: &SharedState)
             .expect(SENDER_ERR);
     }
     // Create the customized response for the client.
-    let message = if let Some(enqueue_at) = message.enqueue_at {
+    let message = if duplicate_id.is_some() {
+        format!(
+            "Queued task with the same unique key replaced (id {}).",
+            task_id
+        )
+    } else if let Some(enqueue_at) = message.enqueue_at {
+        // The time is shown in the client's timezone, which may differ from ours.
         format!(
             "New task added (id {}). It will be enqueued at {}",
             task_id,
@@ -119,11 +264,277 @@ fn add_task(message: AddMessage, sender: &Sender<Message>, state: &SharedState)
     create_success_message(message)
 }
 
+/// Find the most recent successful run of the same command in the same directory and
+/// environment, which finished within the last `window` seconds.
+/// Cached tasks point to the run that has actually been executed.
+fn find_cached_run(state: &State, task: &Task, window: i64) -> Option<usize> {
+    let since = Local::now() - Duration::seconds(window);
+    state
+        .tasks
+        .values()
+        .filter(|other| other.result == Some(TaskResult::Success))
+        .filter(|other| other.end.is_some_and(|end| end >= since))
+        .filter(|other| {
+            other.command == task.command && other.path == task.path && other.envs == task.envs
+        })
+        .max_by_key(|other| other.end)
+        .map(|other| other.cached_from.unwrap_or(other.id))
+}
+
+/// Add a task that's immediately marked as successful due to a cached run.
+/// It gets a copy of the cached run's output, if its logs still exist.
+fn add_cached_task(mut task: Task, cached_id: usize, state: &mut State) -> Message {
+    let now = Local::now();
+    task.status = TaskStatus::Done;
+    task.result = Some(TaskResult::Success);
+    task.start = Some(now);
+    task.end = Some(now);
+    task.cached_from = Some(cached_id);
+    let task_id = state.add_task(task);
+
+    let pueue_directory = &state.settings.shared.pueue_directory;
+    let (cached_stdout, cached_stderr) = get_log_paths(cached_id, pueue_directory);
+    let (stdout, stderr) = get_log_paths(task_id, pueue_directory);
+    if let Err(error) = fs::copy(cached_stdout, stdout).and(fs::copy(cached_stderr, stderr)) {
+        debug!(
+            "Failed to copy logs of cached task {}: {}",
+            cached_id, error
+        );
+    }
+    state.save();
+
+    create_success_message(format!(
+        "New task added (id {}). It has been marked as successful, since task {} is cached.",
+        task_id, cached_id
+    ))
+}
+
+/// Add one copy of the given task for each index of an array job.
+/// New array jobs are identified by the id of their first task.
+fn add_array(
+    task: Task,
+    indices: RangeInclusive<usize>,
+    mut array_id: Option<usize>,
+    start_immediately: bool,
+    sender: &Sender<Message>,
+    state: &mut State,
+) -> Message {
+    let total = indices.clone().count();
+    let mut task_ids = Vec::new();
+    for index in indices {
+        if state.queue_is_full(&task.group) {
+            break;
+        }
+
+        let mut task = task.clone();
+        task.array_id = array_id;
+        task.array_index = Some(index);
+        let task_id = state.add_task(task);
+        if array_id.is_none() {
+            array_id = Some(task_id);
+            state.task_mut(task_id).unwrap().array_id = array_id;
+        }
+        task_ids.push(task_id);
+    }
+
+    let array_id = match array_id {
+        Some(array_id) if !task_ids.is_empty() => array_id,
+        _ => {
+            return create_failure_message(format!(
+                "The queue of group '{}' is full. Try again later or use `--wait-for-slot`",
+                task.group.as_deref().unwrap_or("default")
+            ))
+        }
+    };
+
+    if start_immediately {
+        sender
+            .send(Message::Start(StartMessage {
+                task_ids: task_ids.clone(),
+                ..Default::default()
+            }))
+            .expect(SENDER_ERR);
+    }
+    state.save();
+
+    let mut message = format!(
+        "New array job added (id {}, tasks {} to {}).",
+        array_id,
+        task_ids[0],
+        task_ids[task_ids.len() - 1]
+    );
+    if task_ids.len() < total {
+        message.push_str(&format!(
+            " The queue is full, only {} of {} tasks have been added.",
+            task_ids.len(),
+            total
+        ));
+    }
+    create_success_message(message)
+}
+
+/// Invoked when calling `pueue add --from-yaml` or `pueue apply`.
+/// All tasks of the bundle are validated first, so either all of them are added or none.
+fn add_bundle(message: BundleMessage, state: &SharedState) -> Message {
+    let mut state = state.lock().unwrap();
+    let bundle = message.bundle;
+    let names = match bundle.ordered_names() {
+        Ok(names) => names,
+        Err(error) => return create_failure_message(error.to_string()),
+    };
+
+    // Tasks of pipelines are shown and canceled by the pipeline's name.
+    if let Some(pipeline) = &message.pipeline {
+        let active = state
+            .tasks
+            .values()
+            .any(|task| task.pipeline.as_ref() == Some(pipeline) && !task.is_done());
+        if active {
+            return create_failure_message(format!(
+                "Pipeline {} still has unfinished tasks. Cancel it first.",
+                pipeline
+            ));
+        }
+    }
+
+    let mut tasks_per_group: BTreeMap<&Option<String>, usize> = BTreeMap::new();
+    for (name, task) in bundle.tasks.iter() {
+        if let Some(group) = &task.group {
+            if !state.groups.contains_key(group) {
+                return create_failure_message(format!(
+                    "Task {} uses unknown group '{}'",
+                    name, group
+                ));
+            }
+        }
+        *tasks_per_group.entry(&task.group).or_default() += 1;
+    }
+
+    // Queued and stashed tasks both count towards the `max_queued` limit of their group.
+    for (group, count) in tasks_per_group {
+        if let Some(free) = state.free_queue_slots(group) {
+            if free < count {
+                return create_failure_message(format!(
+                    "The queue of group '{}' is full. The bundle adds {} tasks to it, but only {} more fit",
+                    group.as_deref().unwrap_or("default"),
+                    count,
+                    free
+                ));
+            }
+        }
+    }
+
+    let mut task_ids = BTreeMap::new();
+    for name in names {
+        let bundle_task = bundle.tasks.get(&name).unwrap();
+        let mut envs = message.envs.clone();
+        envs.extend(bundle_task.env.clone());
+        let path = match &bundle_task.path {
+            Some(path) => Path::new(&message.path)
+                .join(path)
+                .to_string_lossy()
+                .to_string(),
+            None => message.path.clone(),
+        };
+        let status = if bundle_task.stashed {
+            TaskStatus::Stashed
+        } else {
+            TaskStatus::Queued
+        };
+        let dependencies = bundle_task
+            .after
+            .iter()
+            .map(|dependency| task_ids[dependency])
+            .collect();
+
+        let mut task = Task::new(
+            insert_alias(bundle_task.command.clone()),
+            path,
+            envs,
+            bundle_task.group.clone(),
+            status,
+            None,
+            dependencies,
+        );
+        task.label = bundle_task.label.clone();
+        if message.pipeline.is_some() {
+            task.pipeline = message.pipeline.clone();
+            task.label = Some(task.label.unwrap_or_else(|| name.clone()));
+        }
+        let task_id = state.add_task(task);
+        task_ids.insert(name, task_id);
+    }
+    state.save();
+
+    Message::BundleResponse(task_ids)
+}
+
+/// Invoked when calling `pueue cancel`.
+/// Pending tasks of the pipeline are marked as killed, so they're never started.
+/// Running tasks are killed by the task handler.
+fn cancel_pipeline(pipeline: String, sender: &Sender<Message>, state: &SharedState) -> Message {
+    let mut state = state.lock().unwrap();
+    let mut canceled = Vec::new();
+    let mut killed = Vec::new();
+    let mut previous = Vec::new();
+    for (id, task) in state.tasks.iter_mut() {
+        if task.pipeline.as_ref() != Some(&pipeline) {
+            continue;
+        }
+        match task.status {
+            TaskStatus::Queued | TaskStatus::Stashed => {
+                previous.push(task.clone());
+                task.status = TaskStatus::Done;
+                task.result = Some(TaskResult::Killed);
+                task.end = Some(Local::now());
+                canceled.push(*id);
+            }
+            TaskStatus::Running | TaskStatus::Paused => killed.push(*id),
+            _ => (),
+        }
+    }
+    for task_id in canceled.iter() {
+        state.mark_changed(*task_id);
+    }
+
+    if canceled.is_empty() && killed.is_empty() {
+        return create_failure_message(format!(
+            "Pipeline {} doesn't have any unfinished tasks",
+            pipeline
+        ));
+    }
+    state.journal("cancel", previous);
+    state.save();
+
+    if !killed.is_empty() {
+        sender
+            .send(Message::Kill(KillMessage {
+                task_ids: killed.clone(),
+                group: None,
+                default: false,
+                all: false,
+                children: false,
+                grace: None,
+                selection: None,
+            }))
+            .expect(SENDER_ERR);
+    }
+
+    create_success_message(format!(
+        "Pipeline {} is being canceled. Killed tasks: {:?}, canceled tasks: {:?}",
+        pipeline, killed, canceled
+    ))
+}
+
 /// Invoked when calling `pueue remove`.
 /// Remove tasks from the queue.
 /// We have to ensure that those tasks aren't running!
-fn remove(task_ids: Vec<usize>, state: &SharedState) -> Message {
+fn remove(selection: TaskSelection, state: &SharedState) -> Message {
     let mut state = state.lock().unwrap();
+    let task_ids = state.select_tasks(&selection);
+    if task_ids.is_empty() {
+        return create_failure_message(NO_MATCHING_TASKS);
+    }
     let statuses = vec![
         TaskStatus::Queued,
         TaskStatus::Stashed,
@@ -132,13 +543,16 @@ fn remove(task_ids: Vec<usize>, state: &SharedState) -> Message {
     ];
     let (not_running, running) = state.tasks_in_statuses(statuses, Some(task_ids));
 
-    for task_id in &not_running {
-        state.tasks.remove(task_id);
-    }
+    let removed: Vec<Task> = not_running
+        .iter()
+        .filter_map(|task_id| state.remove_task(*task_id))
+        .collect();
+    state.trash_tasks(removed.clone());
+    state.journal("remove", removed);
+    state.save();
 
     let text = "Tasks removed from list";
-    let response = compile_task_response(text, not_running, running);
-    create_success_message(response)
+    create_task_response(text, not_running, running)
 }
 
 /// Invoked when calling `pueue switch`.
@@ -154,8 +568,8 @@ fn switch(message: SwitchMessage, state: &SharedState) -> Message {
     }
 
     // Get the tasks. Expect them to be there, since we found no mismatch
-    let mut first_task = state.tasks.remove(&task_ids[0]).unwrap();
-    let mut second_task = state.tasks.remove(&task_ids[1]).unwrap();
+    let mut first_task = state.remove_task(task_ids[0]).unwrap();
+    let mut second_task = state.remove_task(task_ids[1]).unwrap();
 
     // Switch task ids
     let temp_id = first_task.id;
@@ -165,6 +579,7 @@ fn switch(message: SwitchMessage, state: &SharedState) -> Message {
     // Put tasks back in again
     state.tasks.insert(first_task.id, first_task);
     state.tasks.insert(second_task.id, second_task);
+    state.save();
 
     create_success_message("Tasks have been switched")
 }
@@ -172,9 +587,13 @@ fn switch(message: SwitchMessage, state: &SharedState) -> Message {
 /// Invoked when calling `pueue stash`.
 /// Stash specific queued tasks.
 /// They won't be executed until they're enqueued or explicitely started.
-fn stash(task_ids: Vec<usize>, state: &SharedState) -> Message {
+fn stash(selection: TaskSelection, state: &SharedState) -> Message {
     let (matching, mismatching) = {
         let mut state = state.lock().unwrap();
+        let task_ids = state.select_tasks(&selection);
+        if task_ids.is_empty() {
+            return create_failure_message(NO_MATCHING_TASKS);
+        }
         let (matching, mismatching) =
             state.tasks_in_statuses(vec![TaskStatus::Queued, TaskStatus::Locked], Some(task_ids));
 
@@ -186,8 +605,125 @@ fn stash(task_ids: Vec<usize>, state: &SharedState) -> Message {
     };
 
     let text = "Tasks are stashed";
-    let response = compile_task_response(text, matching, mismatching);
-    create_success_message(response)
+    create_task_response(text, matching, mismatching)
+}
+
+/// Invoked when calling `pueue move`.
+/// Move queued or stashed tasks to another group.
+/// Queued tasks aren't moved, if the queue of the target group is already full.
+fn move_tasks(message: MoveMessage, state: &SharedState) -> Message {
+    let (matching, mismatching) = {
+        let mut state = state.lock().unwrap();
+        if let Some(group) = &message.group {
+            if !state.groups.contains_key(group) {
+                return create_failure_message(format!("Group {} doesn't exists", group));
+            }
+        }
+
+        let (mut matching, mut mismatching) = state.tasks_in_statuses(
+            vec![TaskStatus::Queued, TaskStatus::Stashed, TaskStatus::Locked],
+            Some(message.task_ids),
+        );
+
+        // Tasks that would overflow the queue of the target group stay where they are.
+        let mut full = Vec::new();
+        for task_id in &matching {
+            let is_queued = state.tasks.get(task_id).unwrap().is_queued();
+            let already_there = state.tasks.get(task_id).unwrap().group == message.group;
+            if is_queued && !already_there && state.queue_is_full(&message.group) {
+                full.push(*task_id);
+                continue;
+            }
+
+            let task = state.task_mut(*task_id).unwrap();
+            task.group = message.group.clone();
+        }
+        matching.retain(|task_id| !full.contains(task_id));
+        mismatching.extend(full);
+        mismatching.sort_unstable();
+
+        if !matching.is_empty() {
+            state.save();
+        }
+
+        (matching, mismatching)
+    };
+
+    let text = format!(
+        "Tasks are moved to group '{}'",
+        message.group.as_deref().unwrap_or("default")
+    );
+    create_task_response(&text, matching, mismatching)
+}
+
+/// Invoked when calling `pueue restart --in-place`.
+/// Reset finished tasks, so they're executed again under their old ids.
+fn restart_in_place(
+    message: RestartMessage,
+    sender: &Sender<Message>,
+    state: &SharedState,
+) -> Message {
+    let (matching, mismatching) = {
+        let mut state = state.lock().unwrap();
+        let task_ids = message.tasks.iter().map(|task| task.task_id).collect();
+        let (matching, mismatching) =
+            state.tasks_in_statuses(vec![TaskStatus::Done], Some(task_ids));
+
+        let new_status = if message.stashed {
+            TaskStatus::Stashed
+        } else {
+            TaskStatus::Queued
+        };
+        let pueue_directory = state.settings.shared.pueue_directory.clone();
+        for to_restart in message
+            .tasks
+            .iter()
+            .filter(|task| matching.contains(&task.task_id))
+        {
+            let task = state.task_mut(to_restart.task_id).unwrap();
+            task.command = to_restart.command.clone();
+            task.path = to_restart.path.clone();
+            // Quarantined tasks are restarted in the group they failed in.
+            if task.quarantined {
+                task.group = task.original_group.take();
+                task.quarantined = false;
+            }
+            task.status = new_status.clone();
+            task.prev_status = new_status.clone();
+            // Like a restarted copy, the task counts as freshly added.
+            // Otherwise, its `dependency_timeout` would already be expired.
+            task.created_at = Local::now();
+            task.result = None;
+            task.start = None;
+            task.end = None;
+            task.signal = None;
+            task.output_truncated = false;
+            task.overdue = false;
+            task.progress = None;
+            task.artifacts = Vec::new();
+            task.assigned_gpus = Vec::new();
+            task.tempdir = None;
+            task.worker = None;
+            clean_log_handles(task.id, &pueue_directory);
+        }
+
+        if !matching.is_empty() {
+            state.save();
+        }
+
+        (matching, mismatching)
+    };
+
+    if message.start_immediately && !matching.is_empty() {
+        sender
+            .send(Message::Start(StartMessage {
+                task_ids: matching.clone(),
+                ..Default::default()
+            }))
+            .expect(SENDER_ERR);
+    }
+
+    create_task_response("Tasks are restarted in place", matching, mismatching)
 }
 
 /// Invoked when calling `pueue enqueue`.
@@ -201,7 +737,10 @@ fn enqueue(message: EnqueueMessage, state: &SharedState) -> Message {
         );
 
         for task_id in &matching {
-            state.set_enqueue_at(*task_id, message.enqueue_at);
+            state.set_enqueue_at(
+                *task_id,
+                message.enqueue_at.map(|time| time.with_timezone(&Local)),
+            );
             state.change_status(*task_id, TaskStatus::Queued);
         }
 
@@ -217,8 +756,7 @@ fn enqueue(message: EnqueueMessage, state: &SharedState) -> Message {
         String::from("Tasks are enqueued")
     };
 
-    let response = compile_task_response(text.as_str(), matching, mismatching);
-    create_success_message(response)
+    create_task_response(text.as_str(), matching, mismatching)
 }
 
 /// Invoked when calling `pueue start`.
@@ -236,13 +774,12 @@ fn start(message: StartMessage, sender: &Sender<Message>, state: &SharedState) -
         .send(Message::Start(message.clone()))
         .expect(SENDER_ERR);
     if !message.task_ids.is_empty() {
-        let response = task_response_helper(
+        return task_response_helper(
             "Tasks are being started",
             message.task_ids,
             vec![TaskStatus::Paused, TaskStatus::Queued, TaskStatus::Stashed],
             state,
         );
-        return create_success_message(response);
     }
 
     if let Some(group) = &message.group {
@@ -269,13 +806,12 @@ fn pause(message: PauseMessage, sender: &Sender<Message>, state: &SharedState) -
         .send(Message::Pause(message.clone()))
         .expect(SENDER_ERR);
     if !message.task_ids.is_empty() {
-        let response = task_response_helper(
+        return task_response_helper(
             "Tasks are being paused",
             message.task_ids,
             vec![TaskStatus::Running],
             state,
         );
-        return create_success_message(response);
     }
     if let Some(group) = &message.group {
         create_success_message(format!("Group {} is being paused.", group))
@@ -288,19 +824,26 @@ fn pause(message: PauseMessage, sender: &Sender<Message>, state: &SharedState) -
 
 /// Invoked when calling `pueue kill`.
 /// Forward the kill message to the task handler, which then kills the process.
-fn kill(message: KillMessage, sender: &Sender<Message>, state: &SharedState) -> Message {
+fn kill(mut message: KillMessage, sender: &Sender<Message>, state: &SharedState) -> Message {
+    // Resolve the selection, so the task handler only has to deal with plain ids.
+    if let Some(selection) = message.selection.take() {
+        message.task_ids = state.lock().unwrap().select_tasks(&selection);
+        if message.task_ids.is_empty() {
+            return create_failure_message(NO_MATCHING_TASKS);
+        }
+    }
+
     sender
         .send(Message::Kill(message.clone()))
         .expect(SENDER_ERR);
 
     if !message.task_ids.is_empty() {
-        let response = task_response_helper(
+        return task_response_helper(
             "Tasks are being killed",
             message.task_ids,
             vec![TaskStatus::Running, TaskStatus::Paused],
             state,
         );
-        return create_success_message(response);
     }
 
     if let Some(group) = &message.group {
@@ -341,7 +884,7 @@ fn send(message: SendMessage, sender: &Sender<Message>, state: &SharedState) ->
 fn edit_request(task_id: usize, state: &SharedState) -> Message {
     // Check whether the task exists and is queued/stashed. Abort if that's not the case.
     let mut state = state.lock().unwrap();
-    match state.tasks.get_mut(&task_id) {
+    match state.task_mut(task_id) {
         Some(task) => {
             if !task.is_queued() {
                 return create_failure_message("You can only edit a queued/stashed task");
@@ -353,6 +896,8 @@ fn edit_request(task_id: usize, state: &SharedState) -> Message {
                 task_id: task.id,
                 command: task.command.clone(),
                 path: task.path.clone(),
+                label: task.label.clone(),
+                group: task.group.clone(),
             };
             Message::EditResponse(message)
         }
@@ -361,25 +906,63 @@ fn edit_request(task_id: usize, state: &SharedState) -> Message {
 }
 
 /// Invoked after closing the editor on `pueue edit`.
-/// Now we actually update the message with the updated command from the client.
+/// Now we actually update the task with the edited values from the client.
+/// Either all changes are applied or none of them, in which case the task is simply unlocked.
 fn edit(message: EditMessage, state: &SharedState) -> Message {
     // Check whether the task exists and is locked. Abort if that's not the case
     let mut state = state.lock().unwrap();
-    match state.tasks.get_mut(&message.task_id) {
-        Some(task) => {
-            if !(task.status == TaskStatus::Locked) {
-                return create_failure_message("Task is no longer locked.");
-            }
+    let (status, prev_status, group) = match state.tasks.get(&message.task_id) {
+        Some(task) => (
+            task.status.clone(),
+            task.prev_status.clone(),
+            task.group.clone(),
+        ),
+        None => {
+            return create_failure_message(format!(
+                "Task to edit has gone away: {}",
+                message.task_id
+            ))
+        }
+    };
+    if status != TaskStatus::Locked {
+        return create_failure_message("Task is no longer locked.");
+    }
 
-            task.status = task.prev_status.clone();
-            task.command = message.command.clone();
-            task.path = message.path.clone();
-            state.save();
+    let error = if message.command.trim().is_empty() {
+        Some("The command can't be empty".to_string())
+    } else if !Path::new(&message.path).is_dir() {
+        Some(format!("The path {} isn't a directory", message.path))
+    } else if let Some(group) = message
+        .group
+        .as_ref()
+        .filter(|group| !state.groups.contains_key(*group))
+    {
+        Some(format!("Group {} doesn't exists", group))
+    } else if prev_status == TaskStatus::Queued
+        && group != message.group
+        && state.queue_is_full(&message.group)
+    {
+        Some(format!(
+            "The queue of group {} is full",
+            message.group.as_deref().unwrap_or("default")
+        ))
+    } else {
+        None
+    };
 
-            create_success_message("Command has been updated")
-        }
-        None => create_failure_message(format!("Task to edit has gone away: {}", message.task_id)),
+    let task = state.task_mut(message.task_id).unwrap();
+    task.status = prev_status;
+    if let Some(error) = error {
+        return create_failure_message(format!("{}. The task hasn't been changed.", error));
     }
+
+    task.command = message.command;
+    task.path = message.path;
+    task.label = message.label.filter(|label| !label.is_empty());
+    task.group = message.group;
+    state.save();
+
+    create_success_message("Task has been updated")
 }
 
 /// Invoked on `pueue groups`.
@@ -445,19 +1028,61 @@ fn group(message: GroupMessage, state: &SharedState) -> Message {
 
 /// Invoked when calling `pueue clean`.
 /// Remove all failed or done tasks from the state.
-fn clean(state: &SharedState) -> Message {
+fn clean(state: &SharedState, successful_only: bool) -> Message {
     let mut state = state.lock().unwrap();
     state.backup();
     let (matching, _) = state.tasks_in_statuses(vec![TaskStatus::Done], None);
 
+    let mut removed = Vec::new();
     for task_id in &matching {
-        let _ = state.tasks.remove(task_id).unwrap();
+        if successful_only && state.tasks[task_id].failed() {
+            continue;
+        }
+        removed.push(state.remove_task(*task_id).unwrap());
         clean_log_handles(*task_id, &state.settings.shared.pueue_directory);
     }
+    state.journal("clean", removed);
 
     state.save();
 
-    create_success_message("All finished tasks have been removed")
+    if successful_only {
+        create_success_message("All successfully finished tasks have been removed")
+    } else {
+        create_success_message("All finished tasks have been removed")
+    }
+}
+
+/// Invoked when calling `pueue undo`.
+/// Restore the tasks of the most recent remove, clean or pipeline cancel.
+/// Running processes aren't affected and the logs of cleaned tasks are gone for good.
+fn undo(state: &SharedState) -> Message {
+    let mut state = state.lock().unwrap();
+    match state.undo() {
+        Some((operation, restored)) if restored.is_empty() => create_failure_message(format!(
+            "None of the tasks of the last {} could be restored, they have been changed since",
+            operation
+        )),
+        Some((operation, restored)) => {
+            let text = format!("Undid {}, restored tasks", operation);
+            create_success_message(compile_task_response(&text, restored, Vec::new()))
+        }
+        None => create_failure_message("Nothing to undo"),
+    }
+}
+
+/// Invoked when calling `pueue trash restore`.
+/// Put removed tasks back into the task list.
+fn restore_from_trash(task_ids: Vec<usize>, state: &SharedState) -> Message {
+    let (restored, missing) = state.lock().unwrap().restore_from_trash(task_ids);
+    let text = "Tasks restored from the trash";
+    create_task_response(text, restored, missing)
+}
+
+/// Invoked when calling `pueue trash empty`.
+/// Delete all removed tasks for good.
+fn empty_trash(state: &SharedState) -> Message {
+    let count = state.lock().unwrap().empty_trash();
+    create_success_message(format!("Deleted {} tasks from the trash", count))
 }
 
 /// Invoked when calling `pueue reset`.
@@ -472,7 +1097,36 @@ fn reset(sender: &Sender<Message>, children: bool) -> Message {
 /// Return the current state.
 fn get_status(state: &SharedState) -> Message {
     let state = state.lock().unwrap().clone();
-    Message::StatusResponse(state)
+    Message::StatusResponse(Box::new(state))
+}
+
+/// Invoked when calling `pueue status` with a limit, offset or query and by `pueue find`.
+/// Only send a single page of tasks.
+fn get_status_page(message: StatusPageMessage, state: &SharedState) -> Message {
+    let state = state.lock().unwrap();
+    Message::StatusResponse(Box::new(state.page(&message)))
+}
+
+/// Invoked by clients that keep a copy of the state, e.g. TUIs or watch-mode clients.
+/// Only send the changes since the given revision.
+/// The full state is sent, if those changes aren't known.
+fn get_status_since(revision: u64, state: &SharedState) -> Message {
+    let mut state = state.lock().unwrap();
+    // Include changes, which haven't been saved yet.
+    state.track_changes();
+    let delta = match state.changes_since(revision) {
+        Some(delta) => delta,
+        None => StatusDeltaMessage {
+            revision: state.revision,
+            full: Some(state.clone()),
+            changed: BTreeMap::new(),
+            removed: Vec::new(),
+            running: state.running,
+            groups: state.groups.clone(),
+        },
+    };
+
+    Message::StatusDelta(Box::new(delta))
 }
 
 /// Invoked when calling `pueue log`.
@@ -493,7 +1147,8 @@ fn get_log(message: LogRequestMessage, state: &SharedState) -> Message {
             // This isn't as efficient as sending the raw compressed data directly,
             // but it's a lot more convenient for now.
             let (stdout, stderr) = if message.send_logs {
-                match read_and_compress_log_files(*task_id, &state.settings.shared.pueue_directory)
+                let pueue_directory = &state.settings.shared.pueue_directory;
+                match read_and_compress_log_files(*task_id, pueue_directory, state.log_key.as_ref())
                 {
                     Ok((stdout, stderr)) => (Some(stdout), Some(stderr)),
                     Err(err) => {
@@ -518,6 +1173,48 @@ fn get_log(message: LogRequestMessage, state: &SharedState) -> Message {
     Message::LogResponse(tasks)
 }
 
+/// Invoked when calling `pueue log --grep`.
+/// Search the log files of all requested tasks, so clients don't have to download them.
+fn grep_logs(message: LogGrepMessage, state: &SharedState) -> Message {
+    let (task_ids, pueue_directory, key) = {
+        let state = state.lock().unwrap();
+        // Search the logs of all tasks that already started, if no specific task id is specified.
+        let task_ids: Vec<usize> = if message.task_ids.is_empty() {
+            state
+                .tasks
+                .iter()
+                .filter(|(_, task)| task.is_running() || task.is_done())
+                .map(|(id, _)| *id)
+                .collect()
+        } else {
+            message.task_ids
+        };
+        (
+            task_ids,
+            state.settings.shared.pueue_directory.clone(),
+            state.log_key.clone(),
+        )
+    };
+
+    let mut results = BTreeMap::new();
+    for task_id in task_ids {
+        // Tasks without log files didn't run yet or their logs have been removed.
+        if let Ok(matches) = grep_log_files(
+            task_id,
+            &pueue_directory,
+            key.as_ref(),
+            &message.pattern,
+            message.context,
+        ) {
+            if !matches.is_empty() {
+                results.insert(task_id, matches);
+            }
+        }
+    }
+
+    Message::LogGrepResponse(results)
+}
+
 /// Set the parallel tasks for either a specific group or the global default.
 fn set_parallel_tasks(message: ParallelMessage, state: &SharedState) -> Message {
     let mut state = state.lock().unwrap();
@@ -560,11 +1257,14 @@ fn set_parallel_tasks(message: ParallelMessage, state: &SharedState) -> Message
 /// Next, the DaemonShutdown Message will be forwarded to the TaskHandler.
 /// The TaskHandler then gracefully shuts down all child processes
 /// and exits with std::proces::exit(0).
-fn shutdown(sender: &Sender<Message>, state: &SharedState) -> Message {
-    // Remove the unix socket
-    {
+///
+/// On a graceful shutdown, the TaskHandler waits for all running tasks to finish instead.
+/// The socket is kept until then, so clients can still check the progress.
+fn shutdown(sender: &Sender<Message>, state: &SharedState, graceful: bool) -> Message {
+    let running = {
         let state = state.lock().unwrap();
-        if state.settings.shared.use_unix_socket {
+        // Remove the unix socket
+        if !graceful && state.settings.listens_on_unix_socket() {
             let path = &state.settings.shared.unix_socket_path;
             debug!("Check if a unit socket exists.");
             if std::path::PathBuf::from(&path).exists() {
@@ -572,10 +1272,219 @@ fn shutdown(sender: &Sender<Message>, state: &SharedState) -> Message {
             }
             debug!("Removed the unix socket.");
         }
-    }
+
+        state
+            .tasks
+            .values()
+            .filter(|task| task.status == TaskStatus::Running || task.status == TaskStatus::Paused)
+            .count()
+    };
 
     // Notify the task handler
-    sender.send(Message::DaemonShutdown).expect(SENDER_ERR);
+    sender
+        .send(Message::DaemonShutdown(graceful))
+        .expect(SENDER_ERR);
 
+    if graceful && running > 0 {
+        return create_success_message(format!(
+            "Daemon shuts down as soon as {} running tasks finished. No new tasks will be started.",
+            running
+        ));
+    }
     create_success_message("Daemon is shutting down")
 }
+
+/// Invoked by remote workers via `pueue worker`.
+/// Hand the next ready task of a worker group to the worker and mark it as running.
+fn worker_pull(message: WorkerPullMessage, state: &SharedState) -> Message {
+    let mut state = state.lock().unwrap();
+    if !state.settings.daemon.worker_groups.contains(&message.group) {
+        return create_failure_message(format!(
+            "Group '{}' isn't configured as a worker group",
+            message.group
+        ));
+    }
+
+    // Don't hand out any tasks while the group is paused.
+    if !state.groups.get(&message.group).cloned().unwrap_or(false) {
+        return create_success_message("Group is paused");
+    }
+
+    let next_task_id = state
+        .tasks
+        .iter()
+        .filter(|(_, task)| task.status == TaskStatus::Queued)
+        .filter(|(_, task)| task.group.as_ref() == Some(&message.group))
+        .filter(|(_, task)| !task.is_waiting())
+        // Check whether all dependencies for this task are fulfilled.
+        .find(|(_, task)| state.dependencies_fulfilled(task))
+        .map(|(id, _)| *id);
+
+    let task_id = match next_task_id {
+        Some(task_id) => task_id,
+        None => return create_success_message("No task available"),
+    };
+
+    // The worker sends the output with its heartbeats, which is appended to fresh log files.
+    let pueue_directory = state.settings.shared.pueue_directory.clone();
+    if let Err(err) = create_log_file_handles(task_id, &pueue_directory, state.log_key.as_ref()) {
+        return create_failure_message(format!("Failed to create log files: {}", err));
+    }
+
+    let task = state.task_mut(task_id).unwrap();
+    task.status = TaskStatus::Running;
+    task.start = Some(Local::now());
+    task.worker = Some(message.worker.clone());
+    let task = task.clone();
+    state.worker_heartbeats.insert(task_id, Instant::now());
+    state.save();
+
+    // Workers execute a single task at a time, so there's only one worker slot.
+    let command = render_command(&task, 0);
+    let (log_path, _) = get_log_paths(task_id, &pueue_directory);
+    let mut envs = task_environment(&task, &message.group, 0, &log_path);
+    // The log files only exist on the daemon.
+    envs.remove("PUEUE_LOG_PATH");
+
+    info!(
+        "Task {} has been pulled by worker {}",
+        task_id, message.worker
+    );
+    Message::WorkerTask(Box::new(WorkerTaskMessage {
+        task,
+        command,
+        envs,
+    }))
+}
+
+/// Invoked by remote workers via `pueue worker`, once a pulled task has finished.
+/// Save the result and the output of the task, as if it had been executed locally.
+fn worker_report(message: WorkerReportMessage, state: &SharedState) -> Message {
+    let mut state = state.lock().unwrap();
+    let pueue_directory = state.settings.shared.pueue_directory.clone();
+    let task = match state.task_mut(message.task_id) {
+        Some(task) => task,
+        None => return create_failure_message("Task has been removed in the meantime"),
+    };
+
+    if task.status != TaskStatus::Running || task.worker.as_ref() != Some(&message.worker) {
+        return create_failure_message(format!(
+            "Task {} isn't executed by worker {}",
+            message.task_id, message.worker
+        ));
+    }
+
+    task.result = match (&message.spawn_error, message.exit_code) {
+        (Some(error), _) => Some(TaskResult::FailedToSpawn(error.clone())),
+        (None, Some(0)) => Some(TaskResult::Success),
+        (None, Some(exit_code)) => Some(TaskResult::Failed(exit_code)),
+        (None, None) => Some(TaskResult::Killed),
+    };
+    task.status = TaskStatus::Done;
+    task.end = Some(Local::now());
+    let task = task.clone();
+    state.worker_heartbeats.remove(&message.task_id);
+    state.record_run(&task);
+
+    // Write the rest of the output of the task into the usual log files.
+    let written = append_to_log_files(
+        message.task_id,
+        &pueue_directory,
+        state.log_key.as_ref(),
+        &message.stdout,
+        &message.stderr,
+    );
+    state.save();
+
+    if let Err(err) = written {
+        return create_failure_message(format!("Failed to write task output: {}", err));
+    }
+
+    create_success_message(format!("Saved result of task {}", message.task_id))
+}
+
+/// Invoked by remote workers via `pueue worker`, while they execute a pulled task.
+/// Tasks of workers that stop sending heartbeats are failed by the task handler.
+fn worker_heartbeat(message: WorkerHeartbeatMessage, state: &SharedState) -> Message {
+    let mut state = state.lock().unwrap();
+    let held = state.tasks.get(&message.task_id).is_some_and(|task| {
+        task.status == TaskStatus::Running && task.worker.as_ref() == Some(&message.worker)
+    });
+    // The task has been killed, removed or timed out in the meantime.
+    if !held {
+        return create_failure_message(format!(
+            "Task {} isn't executed by worker {} anymore",
+            message.task_id, message.worker
+        ));
+    }
+
+    state
+        .worker_heartbeats
+        .insert(message.task_id, Instant::now());
+
+    let pueue_directory = &state.settings.shared.pueue_directory;
+    if let Err(err) = append_to_log_files(
+        message.task_id,
+        pueue_directory,
+        state.log_key.as_ref(),
+        &message.stdout,
+        &message.stderr,
+    ) {
+        // The worker would kill the task on a failure, so only the output is lost.
+        warn!(
+            "Failed to write output of task {}: {}",
+            message.task_id, err
+        );
+    }
+    create_success_message("Heartbeat received")
+}
+
+/// Invoked when calling `pueue notify` from within a task.
+/// Store the message alongside the task and let the task handler fire the notify callback.
+fn notify(message: NotifyMessage, sender: &Sender<Message>, state: &SharedState) -> Message {
+    let mut state = state.lock().unwrap();
+    let task = match state.task_mut(message.task_id) {
+        Some(task) => task,
+        None => {
+            return create_failure_message(format!("There's no task with id {}", message.task_id))
+        }
+    };
+
+    task.notifications.push(Notification {
+        time: Local::now(),
+        text: message.text.clone(),
+    });
+    state.save();
+
+    sender.send(Message::Notify(message)).expect(SENDER_ERR);
+
+    create_success_message("Notification sent")
+}
+
+/// Invoked when calling `pueue progress` from within a task.
+/// The state isn't saved, since tasks might report their progress very frequently.
+/// The progress is still part of the next status update, as the task is marked as changed.
+fn set_progress(message: ProgressMessage, state: &SharedState) -> Message {
+    let mut state = state.lock().unwrap();
+    match state.task_mut(message.task_id) {
+        Some(task) => {
+            task.progress = Some(message.progress);
+            create_success_message("Progress updated")
+        }
+        None => create_failure_message(format!("There's no task with id {}", message.task_id)),
+    }
+}
+
+/// Invoked when calling `pueue stats`.
+/// Return the run statistics of all labels and commands that contain the filter.
+fn get_statistics(filter: Option<String>, state: &SharedState) -> Message {
+    let state = state.lock().unwrap();
+    let statistics = state
+        .statistics
+        .iter()
+        .filter(|(key, _)| filter.as_ref().is_none_or(|filter| key.contains(filter)))
+        .map(|(key, statistics)| (key.clone(), statistics.clone()))
+        .collect();
+
+    Message::StatsResponse(statistics)
+}
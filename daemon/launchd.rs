@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::cli::Opt;
+
+const LABEL: &str = "pueued";
+
+/// Invoked when calling `pueued install-launchd`.
+/// Write a user LaunchAgent for the current binary and config and load it,
+/// so the daemon is started at login.
+pub fn install_launchd(opt: &Opt) -> Result<()> {
+    ensure_macos()?;
+    let path = plist_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, compile_plist(&opt.service_arguments()?))?;
+    launchctl("load", &path)?;
+
+    println!("Installed and loaded the LaunchAgent at {:?}", path);
+    Ok(())
+}
+
+/// Invoked when calling `pueued uninstall-launchd`.
+/// Unload the LaunchAgent, which stops the daemon, and remove it.
+pub fn uninstall_launchd() -> Result<()> {
+    ensure_macos()?;
+    let path = plist_path()?;
+    if !path.exists() {
+        bail!("There's no LaunchAgent at {:?}", path);
+    }
+    launchctl("unload", &path)?;
+    fs::remove_file(&path)?;
+
+    println!("Unloaded and removed the LaunchAgent at {:?}", path);
+    Ok(())
+}
+
+/// Compile the plist of the LaunchAgent.
+/// The daemon is restarted if it crashes, but not after `pueue shutdown`.
+fn compile_plist(arguments: &[String]) -> String {
+    let arguments: String = arguments
+        .iter()
+        .map(|argument| format!("        <string>{}</string>\n", escape(argument)))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+{}    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+</dict>
+</plist>
+"#,
+        LABEL, arguments
+    )
+}
+
+fn plist_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("Couldn't resolve home dir"))?;
+    Ok(home
+        .join("Library/LaunchAgents")
+        .join(format!("{}.plist", LABEL)))
+}
+
+fn launchctl(subcommand: &str, path: &PathBuf) -> Result<()> {
+    let output = Command::new("launchctl")
+        .arg(subcommand)
+        .arg("-w")
+        .arg(path)
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "`launchctl {}` failed: {}",
+            subcommand,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+fn ensure_macos() -> Result<()> {
+    if !cfg!(target_os = "macos") {
+        bail!("launchd is only available on macOS.");
+    }
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_plist() {
+        let plist = compile_plist(&["/usr/bin/pueued".into(), "--config".into(), "a&b".into()]);
+        assert!(
+            plist.contains("<string>/usr/bin/pueued</string>\n        <string>--config</string>")
+        );
+        assert!(plist.contains("<string>a&amp;b</string>"));
+    }
+}
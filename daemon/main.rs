@@ -4,45 +4,92 @@ use std::process::Command;
 use std::sync::mpsc::channel;
 use std::sync::{Arc, Mutex};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use simplelog::{Config, LevelFilter, SimpleLogger};
 use structopt::StructOpt;
 
+use pueue::encryption::LogKey;
+use pueue::man::write_man_page;
 use pueue::message::Message;
 use pueue::settings::Settings;
 use pueue::state::State;
 
-use crate::cli::Opt;
+use crate::cli::{Opt, SubCommand};
+use crate::launchd::{install_launchd, uninstall_launchd};
+use crate::pid::create_pid_file;
 use crate::socket::accept_incoming;
+use crate::systemd::generate_systemd;
 use crate::task_handler::TaskHandler;
 
 mod aliasing;
+mod artifacts;
+mod audit;
 mod cli;
+mod container;
+mod info;
 mod instructions;
+mod launchd;
+mod pid;
 mod platform;
+mod quota;
+mod reachability;
+mod remote;
 mod response_helper;
+#[cfg(not(windows))]
+mod signals;
 mod socket;
 mod streaming;
+mod systemd;
 mod task_handler;
+mod template;
 
 #[async_std::main]
 async fn main() -> Result<()> {
     // Parse commandline options.
     let opt = Opt::from_args();
 
+    if let Some(output_directory) = &opt.generate_man {
+        return write_man_page(Opt::clap(), "pueued", output_directory);
+    }
+
+    if let Some(SubCommand::Service { action }) = &opt.cmd {
+        #[cfg(windows)]
+        return platform::service::handle_service(action, &opt);
+        #[cfg(not(windows))]
+        bail!(
+            "Windows services are only supported on Windows. Failed to {:?} the service.",
+            action
+        );
+    }
+
+    match &opt.cmd {
+        Some(SubCommand::InstallLaunchd) => return install_launchd(&opt),
+        Some(SubCommand::UninstallLaunchd) => return uninstall_launchd(),
+        Some(SubCommand::GenerateSystemd { user }) => return generate_systemd(&opt, *user),
+        _ => (),
+    }
+
     if opt.daemonize {
         fork_daemon(&opt)?;
     }
 
-    // Set the verbosity level of the logger.
-    let level = match opt.verbose {
+    SimpleLogger::init(log_level(opt.verbose), Config::default()).unwrap();
+
+    run(opt).await
+}
+
+/// Set the verbosity level of the logger.
+fn log_level(verbose: u8) -> LevelFilter {
+    match verbose {
         0 => LevelFilter::Error,
         1 => LevelFilter::Warn,
         2 => LevelFilter::Info,
         _ => LevelFilter::Debug,
-    };
-    SimpleLogger::init(level, Config::default()).unwrap();
+    }
+}
 
+/// Read the settings, start the TaskHandler and handle incoming connections.
+async fn run(opt: Opt) -> Result<()> {
     // Try to read settings from the configuration file.
     let settings = match Settings::read(false, &opt.config) {
         Ok(settings) => settings,
@@ -62,9 +109,20 @@ async fn main() -> Result<()> {
         }
     };
 
+    for (group, window) in settings.daemon.run_windows.iter() {
+        window
+            .check()
+            .with_context(|| format!("Invalid run window of group {}", group))?;
+    }
+
     init_directories(&settings.shared.pueue_directory);
+    if settings.daemon.private_logs {
+        restrict_log_directory(&settings.shared.pueue_directory)?;
+    }
+    create_pid_file(&settings.shared.pueue_directory, opt.replace)?;
 
-    let state = State::new(&settings, opt.config.clone());
+    let mut state = State::new(&settings, opt.config.clone());
+    state.log_key = LogKey::from_settings(&settings.daemon)?;
     let state = Arc::new(Mutex::new(state));
 
     let (sender, receiver) = channel();
@@ -79,17 +137,26 @@ async fn main() -> Result<()> {
     let sender_clone = sender.clone();
     ctrlc::set_handler(move || {
         // Clean up the unix socket if we're using it and it exists.
-        if settings.shared.use_unix_socket && std::path::PathBuf::from(&unix_socket_path).exists() {
+        if settings.listens_on_unix_socket() && std::path::PathBuf::from(&unix_socket_path).exists()
+        {
             std::fs::remove_file(&unix_socket_path)
                 .expect("Failed to remove unix socket on shutdown");
         }
 
         // Notify the task handler
         sender_clone
-            .send(Message::DaemonShutdown)
+            .send(Message::DaemonShutdown(false))
             .expect("Failed to send Message to TaskHandler on Shutdown");
     })?;
 
+    // SIGUSR1 pauses and SIGUSR2 resumes all groups.
+    #[cfg(not(windows))]
+    signals::handle_pause_signals(sender.clone())?;
+
+    // Stop requests of the Windows service control manager shut down the daemon.
+    #[cfg(windows)]
+    platform::service::set_shutdown_sender(sender.clone());
+
     let orig_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
         // invoke the default handler and exit the process
@@ -142,6 +209,27 @@ fn init_directories(path: &str) {
     }
 }
 
+/// Only allow the user of the daemon to access the task logs.
+/// Clients of other users then have to request logs from the daemon instead of reading them.
+/// Root and anyone with access to the raw disk can still read them, unless they're encrypted.
+#[cfg(not(windows))]
+fn restrict_log_directory(path: &str) -> Result<()> {
+    use std::fs::{set_permissions, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+
+    let logs_dir = Path::new(path).join("task_logs");
+    set_permissions(&logs_dir, Permissions::from_mode(0o700))?;
+
+    Ok(())
+}
+
+/// Windows uses ACLs instead of file modes, which aren't handled yet.
+/// Refuse to start instead of silently leaving the logs readable.
+#[cfg(windows)]
+fn restrict_log_directory(_path: &str) -> Result<()> {
+    bail!("`daemon.private_logs` isn't supported on Windows yet. Please disable it.")
+}
+
 /// This is a simple and cheap custom fork method.
 /// Simply spawn a new child with identical arguments and exit right away.
 fn fork_daemon(opt: &Opt) -> Result<()> {
@@ -156,6 +244,10 @@ fn fork_daemon(opt: &Opt) -> Result<()> {
         arguments.push("-".to_string() + &" ".repeat(opt.verbose as usize));
     }
 
+    if opt.replace {
+        arguments.push("--replace".to_string());
+    }
+
     Command::new("pueued").args(&arguments).spawn()?;
 
     println!("Pueued is now running in the background");
@@ -0,0 +1,47 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+
+use crate::platform::process_helper::process_exists;
+
+fn pid_path(pueue_directory: &str) -> PathBuf {
+    Path::new(pueue_directory).join("pueued.pid")
+}
+
+/// Make sure that there's only a single daemon per pueue directory.
+/// If another daemon is still running, refuse to start, unless `replace` is set.
+/// A pid file of a daemon which no longer exists is simply replaced.
+pub fn create_pid_file(pueue_directory: &str, replace: bool) -> Result<()> {
+    let path = pid_path(pueue_directory);
+    if let Ok(content) = fs::read_to_string(&path) {
+        match content.trim().parse::<u32>() {
+            Ok(pid) if pid != std::process::id() && process_exists(pid) => {
+                if !replace {
+                    bail!(
+                        "Pueued is already running with pid {} for this directory.\n\
+                        If this is a stale pid file after a crash, start with --replace.",
+                        pid
+                    );
+                }
+                warn!("Replacing the pid file of daemon {}", pid);
+            }
+            Ok(pid) => info!("Removing stale pid file of daemon {}", pid),
+            Err(_) => warn!("Replacing invalid pid file at {:?}", path),
+        }
+    }
+
+    fs::write(&path, std::process::id().to_string())
+        .context(format!("Failed to write pid file at {:?}", path))?;
+
+    Ok(())
+}
+
+/// Remove the pid file on shutdown.
+pub fn cleanup_pid_file(pueue_directory: &str) {
+    let path = pid_path(pueue_directory);
+    if let Err(error) = fs::remove_file(&path) {
+        warn!("Failed to remove pid file at {:?}: {:?}", path, error);
+    }
+}
@@ -1,5 +1,8 @@
 use std::convert::TryInto;
-use std::process::{Child, Command};
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus};
 
 use anyhow::{bail, Result};
 use log::{debug, info, warn};
@@ -9,15 +12,90 @@ use nix::{
 };
 use procfs::process::{all_processes, Process};
 
+pub use pueue::shell::{compile_shell_command, prepend_setup_command};
+
 use crate::task_handler::ProcessAction;
 
-pub fn compile_shell_command(command_string: &str) -> Command {
-    let mut command = Command::new("sh");
-    command.arg("-c").arg(command_string);
+/// Execute the command via a login shell and/or `direnv exec`, so it sees the same environment
+/// as an interactive shell in the task's working directory.
+/// `direnv` loads the `.envrc` first, then the login shell loads the user's profile.
+///
+/// With `line_buffered`, everything is wrapped in `stdbuf`, which is inherited by all
+/// processes the shell spawns.
+pub fn compile_environment_command(
+    command_string: &str,
+    login_shell: Option<&str>,
+    direnv_directory: Option<&Path>,
+    line_buffered: bool,
+) -> Command {
+    let mut arguments: Vec<OsString> = Vec::new();
+    if line_buffered {
+        arguments.push("stdbuf".into());
+        arguments.push("-oL".into());
+        arguments.push("-eL".into());
+    }
+    if let Some(directory) = direnv_directory {
+        arguments.push("direnv".into());
+        arguments.push("exec".into());
+        arguments.push(directory.into());
+    }
+    match login_shell {
+        Some(shell) => {
+            arguments.push(shell.into());
+            arguments.push("-l".into());
+        }
+        None => arguments.push("sh".into()),
+    }
+    arguments.push("-c".into());
+    arguments.push(command_string.into());
+
+    let mut command = Command::new(&arguments[0]);
+    command.args(&arguments[1..]);
 
     command
 }
 
+/// Restrict the process to the given CPUs, once it's spawned.
+#[cfg(target_os = "linux")]
+pub fn set_cpu_affinity(command: &mut Command, cpus: &[usize]) -> io::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    use nix::sched::{sched_setaffinity, CpuSet};
+
+    let mut cpu_set = CpuSet::new();
+    for cpu in cpus {
+        cpu_set.set(*cpu).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid CPU {}", cpu))
+        })?;
+    }
+
+    // This runs in the forked child right before the command is executed.
+    unsafe {
+        command.pre_exec(move || {
+            sched_setaffinity(Pid::from_raw(0), &cpu_set).map_err(io::Error::other)
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_cpu_affinity(_command: &mut Command, _cpus: &[usize]) -> io::Result<()> {
+    Err(io::Error::other("CPU affinity is only supported on Linux"))
+}
+
+/// Check whether a process with the given pid exists.
+pub fn process_exists(pid: u32) -> bool {
+    Process::new(pid as i32).is_ok()
+}
+
+/// The signal that terminated a process, if it didn't exit by itself.
+pub fn get_exit_signal(exit_status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+
+    exit_status.signal()
+}
+
 /// Send a signal to one of Pueue's child process handles.
 /// We need a special since there exists some inconsistent behavior.
 ///
@@ -228,6 +306,7 @@ fn get_child_processes(pid: i32) -> Vec<Process> {
 
 fn get_signal_from_action(action: &ProcessAction) -> Signal {
     match action {
+        ProcessAction::Terminate => Signal::SIGTERM,
         ProcessAction::Kill => Signal::SIGKILL,
         ProcessAction::Pause => Signal::SIGSTOP,
         ProcessAction::Resume => Signal::SIGCONT,
@@ -259,6 +338,25 @@ mod tests {
         assert!(ecode.success());
     }
 
+    #[test]
+    /// A failing setup command prevents the task's command and passes on its exit code.
+    fn test_setup_command() {
+        let command_string = prepend_setup_command(Some("exit 3"), "echo 'this is a test'", None);
+        let ecode = compile_shell_command(&command_string)
+            .status()
+            .expect("Failed to spawn setup command");
+        assert_eq!(ecode.code(), Some(3));
+
+        let command_string = prepend_setup_command(Some("true"), "exit 4", Some("/bin/bash"));
+        let ecode = compile_shell_command(&command_string)
+            .status()
+            .expect("Failed to spawn setup command");
+        assert_eq!(ecode.code(), Some(4));
+
+        let command_string = prepend_setup_command(Some("setup"), "task", Some("/usr/bin/fish"));
+        assert_eq!(command_string, "begin\nsetup\nend\nor exit $status\ntask");
+    }
+
     #[test]
     /// Ensure a `sh -c` command will be properly killed without detached processes.
     fn test_shell_command_is_killed() {
@@ -1,5 +1,8 @@
 use std::convert::TryInto;
-use std::process::{Child, Command};
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus};
 
 use anyhow::Result;
 use log::debug;
@@ -8,15 +11,56 @@ use nix::{
     unistd::Pid,
 };
 
+pub use pueue::shell::{compile_shell_command, prepend_setup_command};
+
 use crate::task_handler::ProcessAction;
 
-pub fn compile_shell_command(command_string: &str) -> Command {
-    let mut command = Command::new("sh");
-    command.arg("-c").arg(command_string);
+/// Execute the command via a login shell and/or `direnv exec`, so it sees the same environment
+/// as an interactive shell in the task's working directory.
+/// `direnv` loads the `.envrc` first, then the login shell loads the user's profile.
+///
+/// With `line_buffered`, everything is wrapped in `stdbuf`, which is inherited by all
+/// processes the shell spawns.
+pub fn compile_environment_command(
+    command_string: &str,
+    login_shell: Option<&str>,
+    direnv_directory: Option<&Path>,
+    line_buffered: bool,
+) -> Command {
+    let mut arguments: Vec<OsString> = Vec::new();
+    if line_buffered {
+        arguments.push("stdbuf".into());
+        arguments.push("-oL".into());
+        arguments.push("-eL".into());
+    }
+    if let Some(directory) = direnv_directory {
+        arguments.push("direnv".into());
+        arguments.push("exec".into());
+        arguments.push(directory.into());
+    }
+    match login_shell {
+        Some(shell) => {
+            arguments.push(shell.into());
+            arguments.push("-l".into());
+        }
+        None => arguments.push("sh".into()),
+    }
+    arguments.push("-c".into());
+    arguments.push(command_string.into());
+
+    let mut command = Command::new(&arguments[0]);
+    command.args(&arguments[1..]);
 
     command
 }
 
+/// The signal that terminated a process, if it didn't exit by itself.
+pub fn get_exit_signal(exit_status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+
+    exit_status.signal()
+}
+
 /// Send a signal to one of Pueue's child process handles.
 /// We need a special since we assume that there's also a `sh -c` around the actuall process.
 pub fn send_signal_to_child(
@@ -45,6 +89,21 @@ pub fn kill_child(task_id: usize, child: &mut Child, _kill_children: bool) -> bo
     }
 }
 
+/// CPU affinity is only supported on Linux.
+pub fn set_cpu_affinity(_command: &mut Command, _cpus: &[usize]) -> io::Result<()> {
+    Err(io::Error::other("CPU affinity is only supported on Linux"))
+}
+
+/// Check whether a process with the given pid exists.
+/// `EPERM` means, that the process exists, but belongs to another user.
+pub fn process_exists(pid: u32) -> bool {
+    match signal::kill(Pid::from_raw(pid.try_into().unwrap()), None) {
+        Ok(()) => true,
+        Err(nix::Error::Sys(nix::errno::Errno::EPERM)) => true,
+        Err(_) => false,
+    }
+}
+
 /// Send a signal to a unix process.
 fn send_signal_to_process(
     pid: u32,
@@ -60,6 +119,7 @@ fn send_signal_to_process(
 
 fn get_signal_from_action(action: &ProcessAction) -> Signal {
     match action {
+        ProcessAction::Terminate => Signal::SIGTERM,
         ProcessAction::Kill => Signal::SIGKILL,
         ProcessAction::Pause => Signal::SIGSTOP,
         ProcessAction::Resume => Signal::SIGCONT,
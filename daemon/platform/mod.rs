@@ -13,3 +13,6 @@ pub use self::macos::process_helper;
 
 #[cfg(target_os = "windows")]
 pub use self::windows::process_helper;
+
+#[cfg(target_os = "windows")]
+pub use self::windows::service;
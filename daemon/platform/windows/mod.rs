@@ -1 +1,2 @@
 pub mod process_helper;
+pub mod service;
@@ -1,18 +1,47 @@
 use anyhow::{bail, Result};
-use std::process::{Child, Command};
+use std::io;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus};
+
+pub use pueue::shell::{compile_shell_command, prepend_setup_command};
 
 use crate::task_handler::ProcessAction;
 use log::info;
 
-pub fn compile_shell_command(command_string: &str) -> Command {
-    // Chain two `powershell` commands, one that sets the output encoding to utf8 and then the user provided one.
-    let mut command = Command::new("powershell");
-    command.arg("-c").arg(format!(
-        "[Console]::OutputEncoding = [Text.UTF8Encoding]::UTF8; {}",
-        command_string
-    ));
+/// Login shells, `direnv` and `stdbuf` aren't supported on Windows.
+/// The command is executed as usual.
+pub fn compile_environment_command(
+    command_string: &str,
+    _login_shell: Option<&str>,
+    _direnv_directory: Option<&Path>,
+    _line_buffered: bool,
+) -> Command {
+    compile_shell_command(command_string)
+}
+
+/// Processes aren't terminated by signals on Windows.
+pub fn get_exit_signal(_exit_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+/// CPU affinity is only supported on Linux.
+pub fn set_cpu_affinity(_command: &mut Command, _cpus: &[usize]) -> io::Result<()> {
+    Err(io::Error::other("CPU affinity is only supported on Linux"))
+}
+
+/// Check whether a process with the given pid exists.
+pub fn process_exists(pid: u32) -> bool {
+    let output = Command::new("tasklist")
+        .arg("/FI")
+        .arg(format!("PID eq {}", pid))
+        .arg("/NH")
+        .output();
 
-    command
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()),
+        // Rather refuse to start a second daemon, if we can't check.
+        Err(_) => true,
+    }
 }
 
 /// Send a signal to a windows process.
@@ -24,6 +53,7 @@ pub fn send_signal_to_child(
     match action {
         ProcessAction::Pause => bail!("Pause is not yet supported on windows."),
         ProcessAction::Resume => bail!("Resume is not yet supported on windows."),
+        ProcessAction::Terminate => bail!("Terminate is not yet supported on windows."),
         ProcessAction::Kill => bail!("Kill is not yet supported on windows."),
     }
 }
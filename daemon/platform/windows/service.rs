@@ -0,0 +1,198 @@
+use std::ffi::OsString;
+use std::fs::{create_dir_all, OpenOptions};
+use std::path::Path;
+use std::process::Command;
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use log::{error, info};
+use simplelog::{Config, WriteLogger};
+use structopt::StructOpt;
+use windows_service::service::{
+    ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{
+    self, ServiceControlHandlerResult, ServiceStatusHandle,
+};
+use windows_service::{define_windows_service, service_dispatcher};
+
+use pueue::message::Message;
+use pueue::settings::Settings;
+
+use crate::cli::{Opt, ServiceAction};
+
+const SERVICE_NAME: &str = "pueued";
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// The sender to the TaskHandler, which is used to shut down the daemon on stop requests.
+static SHUTDOWN_SENDER: Mutex<Option<Sender<Message>>> = Mutex::new(None);
+/// The status handle of the running service. `None`, if we don't run as a service.
+static STATUS_HANDLE: Mutex<Option<ServiceStatusHandle>> = Mutex::new(None);
+
+/// Invoked when calling `pueued service [install|uninstall|run]`.
+pub fn handle_service(action: &ServiceAction, opt: &Opt) -> Result<()> {
+    match action {
+        ServiceAction::Install => install(opt),
+        ServiceAction::Uninstall => uninstall(),
+        ServiceAction::Run => run(),
+    }
+}
+
+/// Register the service with the service control manager.
+/// The service is started automatically on boot and uses the current binary and config.
+fn install(opt: &Opt) -> Result<()> {
+    let mut bin_path: Vec<String> = opt
+        .service_arguments()?
+        .iter()
+        .map(|argument| format!("\"{}\"", argument))
+        .collect();
+    bin_path.push("service run".into());
+    let bin_path = bin_path.join(" ");
+
+    run_sc(&[
+        "create",
+        SERVICE_NAME,
+        "binPath=",
+        &bin_path,
+        "start=",
+        "auto",
+        "DisplayName=",
+        "Pueue daemon",
+    ])?;
+    run_sc(&[
+        "description",
+        SERVICE_NAME,
+        "The daemon of the pueue task queue.",
+    ])?;
+
+    println!(
+        "Installed the {} service. Start it with `sc.exe start {}`",
+        SERVICE_NAME, SERVICE_NAME
+    );
+    Ok(())
+}
+
+/// Stop the service, if it's running, and remove it from the service control manager.
+fn uninstall() -> Result<()> {
+    // The service might not be running, which is fine.
+    let _ = run_sc(&["stop", SERVICE_NAME]);
+    run_sc(&["delete", SERVICE_NAME])?;
+
+    println!("Uninstalled the {} service.", SERVICE_NAME);
+    Ok(())
+}
+
+/// Connect to the service control manager.
+/// This blocks until the service has been stopped.
+fn run() -> Result<()> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main).context(
+        "Failed to connect to the service control manager.\n\
+        `pueued service run` is only supposed to be called by the service control manager.",
+    )?;
+
+    Ok(())
+}
+
+/// Remember the sender to the TaskHandler, so stop requests can shut down the daemon.
+pub fn set_shutdown_sender(sender: Sender<Message>) {
+    *SHUTDOWN_SENDER.lock().unwrap() = Some(sender);
+}
+
+/// Tell the service control manager, that the daemon stopped.
+/// This has to be called right before the daemon exits.
+pub fn report_stopped() {
+    set_status(ServiceState::Stopped, ServiceExitCode::NO_ERROR);
+}
+
+/// The entry point of the service, which is called by the service control manager.
+fn service_main(_arguments: Vec<OsString>) {
+    let handle = match service_control_handler::register(SERVICE_NAME, control_handler) {
+        Ok(handle) => handle,
+        Err(_) => return,
+    };
+    *STATUS_HANDLE.lock().unwrap() = Some(handle);
+    set_status(ServiceState::Running, ServiceExitCode::NO_ERROR);
+
+    let exit_code = match run_daemon() {
+        Ok(()) => ServiceExitCode::NO_ERROR,
+        Err(err) => {
+            error!("Daemon stopped with error: {:?}", err);
+            ServiceExitCode::ServiceSpecific(1)
+        }
+    };
+    set_status(ServiceState::Stopped, exit_code);
+}
+
+/// Run the daemon as usual.
+/// Since services don't have a console, logs are written to `pueued.log` in the pueue directory.
+fn run_daemon() -> Result<()> {
+    let opt = Opt::from_args();
+    let settings = Settings::new(false, &opt.config)?;
+    create_dir_all(&settings.shared.pueue_directory)?;
+    let log_file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(Path::new(&settings.shared.pueue_directory).join("pueued.log"))?;
+    WriteLogger::init(crate::log_level(opt.verbose), Config::default(), log_file)?;
+
+    async_std::task::block_on(crate::run(opt))
+}
+
+/// Handle control requests of the service control manager.
+/// Stop and shutdown requests shut down the daemon, just like `pueue shutdown`.
+fn control_handler(control: ServiceControl) -> ServiceControlHandlerResult {
+    match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            info!("Received stop request from the service control manager.");
+            set_status(ServiceState::StopPending, ServiceExitCode::NO_ERROR);
+            if let Some(sender) = SHUTDOWN_SENDER.lock().unwrap().as_ref() {
+                let _ = sender.send(Message::DaemonShutdown(false));
+            }
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    }
+}
+
+fn set_status(state: ServiceState, exit_code: ServiceExitCode) {
+    let handle = match *STATUS_HANDLE.lock().unwrap() {
+        Some(handle) => handle,
+        None => return,
+    };
+
+    let controls_accepted = if state == ServiceState::Running {
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+    } else {
+        ServiceControlAccept::empty()
+    };
+    let status = ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted,
+        exit_code,
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    };
+    if let Err(err) = handle.set_service_status(status) {
+        error!("Failed to report the service status: {}", err);
+    }
+}
+
+fn run_sc(arguments: &[&str]) -> Result<()> {
+    let output = Command::new("sc.exe").args(arguments).output()?;
+    if !output.status.success() {
+        // `sc.exe` prints its errors to stdout.
+        bail!(
+            "`sc.exe {}` failed: {}",
+            arguments[0],
+            String::from_utf8_lossy(&output.stdout).trim()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use std::io::{Read, Write};
+use std::thread;
+
+use log::{error, warn};
+
+use pueue::encryption::LogFile;
+use pueue::state::SharedState;
+
+/// Copy the output of a process into its log file, e.g. to encrypt it, until the limit is reached.
+///
+/// Once the limit is exceeded, a marker is appended to the log and the task is flagged.
+/// The remaining output is still read, so the process doesn't block on a full pipe,
+/// but it's no longer written to disk.
+pub fn spawn_log_copy<R: Read + Send + 'static>(
+    task_id: usize,
+    mut source: R,
+    mut log: LogFile,
+    limit: Option<u64>,
+    state: SharedState,
+) {
+    let limit = limit.unwrap_or(u64::MAX);
+    thread::spawn(move || {
+        let mut buffer = [0; 8192];
+        let mut written: u64 = 0;
+        let mut truncated = false;
+        loop {
+            let read = match source.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(error) => {
+                    error!("Failed to read output of task {}: {}", task_id, error);
+                    break;
+                }
+            };
+            if truncated {
+                continue;
+            }
+
+            let allowed = (limit - written).min(read as u64) as usize;
+            if let Err(error) = log.write_all(&buffer[..allowed]) {
+                error!("Failed to write log of task {}: {}", task_id, error);
+                break;
+            }
+            written += allowed as u64;
+
+            if allowed < read {
+                truncated = true;
+                warn!("Output of task {} exceeded {} bytes", task_id, limit);
+                let _ = write!(log, "\n[pueue] output truncated after {} bytes\n", limit);
+
+                let mut state = state.lock().unwrap();
+                if let Some(task) = state.task_mut(task_id) {
+                    task.output_truncated = true;
+                }
+            }
+        }
+    });
+}
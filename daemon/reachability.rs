@@ -0,0 +1,42 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The state of the reachability checks of a task that waits for a host via `add --wait-for-host`.
+pub struct HostCheck {
+    /// The point in time the task started waiting. Used for the overall timeout.
+    pub since: Instant,
+    /// The point in time of the last connection attempt.
+    pub last_attempt: Option<Instant>,
+    /// The currently running connection attempt.
+    pub attempt: Option<JoinHandle<bool>>,
+}
+
+impl HostCheck {
+    pub fn new(since: Instant) -> Self {
+        HostCheck {
+            since,
+            last_attempt: None,
+            attempt: None,
+        }
+    }
+}
+
+/// Try to open a TCP connection to the given `host:port` in a separate thread.
+/// Name resolution and connecting may take a while,
+/// which would otherwise block the task handler.
+pub fn spawn_connection_attempt(address: String, timeout: Duration) -> JoinHandle<bool> {
+    thread::spawn(move || {
+        let addresses = match address.to_socket_addrs() {
+            Ok(addresses) => addresses,
+            Err(_) => return false,
+        };
+
+        for address in addresses {
+            if TcpStream::connect_timeout(&address, timeout).is_ok() {
+                return true;
+            }
+        }
+        false
+    })
+}
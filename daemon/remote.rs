@@ -0,0 +1,99 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use shell_escape::unix::escape;
+
+use pueue::settings::Host;
+
+/// Compile the command that executes a task on a remote host via SSH.
+///
+/// Queueing, logging and status handling still happens locally,
+/// since the output of the remote process is forwarded by the local `ssh` process.
+/// Signals are sent to the local `ssh` process. A terminal is allocated on the remote host,
+/// so the remote processes get a SIGHUP, as soon as the connection is closed.
+/// Due to that terminal, stderr of the remote process ends up in its stdout.
+///
+/// `ssh` doesn't forward the local environment, so the task's environment is exported
+/// on the remote host.
+pub fn compile_ssh_command(
+    host: &Host,
+    command_string: &str,
+    envs: &BTreeMap<String, String>,
+) -> Command {
+    let mut command = Command::new("ssh");
+    // Never prompt for passwords or host keys, since there's no one to answer.
+    command.arg("-o").arg("BatchMode=yes");
+    command.arg("-tt");
+    if let Some(port) = host.port {
+        command.arg("-p").arg(port.to_string());
+    }
+
+    let destination = match &host.user {
+        Some(user) => format!("{}@{}", user, host.address),
+        None => host.address.clone(),
+    };
+    command.arg(destination);
+    command.arg(remote_command(host, command_string, envs));
+
+    command
+}
+
+/// The shell command that's executed on the remote host.
+fn remote_command(host: &Host, command_string: &str, envs: &BTreeMap<String, String>) -> String {
+    let mut remote_command = String::new();
+
+    // Variables, whose names aren't valid in a shell, can't be exported.
+    let exports: Vec<String> = envs
+        .iter()
+        .filter(|(key, _)| is_variable_name(key))
+        .map(|(key, value)| format!("{}={}", key, escape(Cow::from(value.as_str()))))
+        .collect();
+    if !exports.is_empty() {
+        remote_command.push_str(&format!("export {}\n", exports.join(" ")));
+    }
+
+    if let Some(directory) = &host.directory {
+        remote_command.push_str(&format!(
+            "cd {} || exit $?\n",
+            escape(Cow::from(directory.as_str()))
+        ));
+    }
+    remote_command.push_str(command_string);
+
+    remote_command
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_command() {
+        let host = Host {
+            address: "build".into(),
+            user: None,
+            port: None,
+            directory: Some("/srv/my builds; rm -rf".into()),
+        };
+        let mut envs = BTreeMap::new();
+        envs.insert("PUEUE_TASK_ID".to_string(), "3".to_string());
+        envs.insert("NAME".to_string(), "it's me".to_string());
+        envs.insert("BASH_FUNC_x%%".to_string(), "() { :; }".to_string());
+
+        assert_eq!(
+            remote_command(&host, "make", &envs),
+            "export NAME='it'\\''s me' PUEUE_TASK_ID=3\n\
+             cd '/srv/my builds; rm -rf' || exit $?\n\
+             make"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use pueue::message::{create_failure_message, create_success_message, Message};
 use pueue::state::SharedState;
 use pueue::task::TaskStatus;
 
@@ -6,14 +7,30 @@ pub fn task_response_helper(
     task_ids: Vec<usize>,
     statuses: Vec<TaskStatus>,
     state: &SharedState,
-) -> String {
+) -> Message {
     // Get all matching/mismatching task_ids for all given ids and statuses.
     let (matching, mismatching) = {
         let mut state = state.lock().unwrap();
         state.tasks_in_statuses(statuses, Some(task_ids))
     };
 
-    compile_task_response(message, matching, mismatching)
+    create_task_response(message, matching, mismatching)
+}
+
+/// Create the response for instructions with multiple task ids.
+/// It's a failure, if the instruction couldn't be executed for any of the tasks.
+pub fn create_task_response(
+    message: &str,
+    matching: Vec<usize>,
+    mismatching: Vec<usize>,
+) -> Message {
+    let failed = matching.is_empty();
+    let response = compile_task_response(message, matching, mismatching);
+    if failed {
+        create_failure_message(response)
+    } else {
+        create_success_message(response)
+    }
 }
 
 /// Compile a response for instructions with multiple tasks ids
@@ -0,0 +1,43 @@
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use anyhow::Result;
+use log::info;
+use signal_hook::iterator::Signals;
+use signal_hook::{SIGUSR1, SIGUSR2};
+
+use pueue::message::*;
+
+/// Pause all groups on SIGUSR1 and resume them on SIGUSR2.
+/// This behaves like `pueue pause --all` and `pueue start --all`,
+/// which allows system hooks to control the queue without a client.
+pub fn handle_pause_signals(sender: Sender<Message>) -> Result<()> {
+    let signals = Signals::new([SIGUSR1, SIGUSR2])?;
+
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let message = match signal {
+                SIGUSR1 => {
+                    info!("Received SIGUSR1. Pausing all groups.");
+                    Message::Pause(PauseMessage {
+                        all: true,
+                        ..Default::default()
+                    })
+                }
+                SIGUSR2 => {
+                    info!("Received SIGUSR2. Resuming all groups.");
+                    Message::Start(StartMessage {
+                        all: true,
+                        ..Default::default()
+                    })
+                }
+                _ => continue,
+            };
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -1,42 +1,109 @@
+use std::sync::atomic::Ordering;
 use std::sync::mpsc::Sender;
+use std::sync::Arc;
 
 use anyhow::{bail, Result};
 use async_std::task;
+use chrono::prelude::*;
 use log::{debug, info, warn};
 
 use pueue::message::*;
 use pueue::protocol::*;
 use pueue::state::SharedState;
+use pueue::tls::{daemon_config, TlsListener};
 
+use crate::audit::audit;
 use crate::cli::Opt;
+use crate::info::{get_daemon_info, RuntimeInfo};
 use crate::instructions::handle_message;
 use crate::streaming::handle_follow;
 
-/// Poll the unix listener and accept new incoming connections.
-/// Create a new future to handle the message and spawn it.
+/// Bind all configured listeners and accept new incoming connections on each of them.
+/// The daemon can listen on the unix socket and a tcp port at the same time.
 pub async fn accept_incoming(sender: Sender<Message>, state: SharedState, opt: Opt) -> Result<()> {
-    let (unix_socket_path, port) = {
+    let (unix_socket_path, address, tls_config) = {
         let state = state.lock().unwrap();
         let shared = &state.settings.shared;
+        let (use_unix_socket, use_tcp) = state.settings.daemon_listeners()?;
 
-        // Return the unix socket path, if we're supposed to use it.
-        if shared.use_unix_socket {
-            (Some(shared.unix_socket_path.clone()), None)
+        // Commandline argument overwrites the configuration files values for port.
+        let port = if let Some(port) = opt.port.clone() {
+            port
         } else {
-            // Otherwise use tcp sockets and a given port
-            // Commandline argument overwrites the configuration files values for port.
-            let port = if let Some(port) = opt.port.clone() {
-                port
-            } else {
-                shared.port.clone()
-            };
-
-            (None, Some(port))
-        }
+            shared.port.clone()
+        };
+
+        (
+            Some(shared.unix_socket_path.clone()).filter(|_| use_unix_socket),
+            Some(shared.tcp_address(&port)).filter(|_| use_tcp),
+            daemon_config(&state.settings)?,
+        )
     };
 
-    let listener = get_listener(unix_socket_path, port).await?;
+    let mut listeners = Vec::new();
+    let mut sockets = Vec::new();
+    if let Some(path) = unix_socket_path {
+        listeners.push(get_listener(Some(path.clone()), None).await?);
+
+        // Allow other users to connect, e.g. members of a shared group.
+        #[cfg(not(windows))]
+        {
+            let state = state.lock().unwrap();
+            let shared = &state.settings.shared;
+            set_socket_permissions(&path, &shared.unix_socket_mode, &shared.unix_socket_group)?;
+        }
+
+        if is_abstract_socket(&path) {
+            sockets.push(format!("abstract unix socket @{}", &path[1..]));
+        } else {
+            sockets.push(format!("unix socket at {}", path));
+        }
+    }
+    if let Some(address) = address {
+        let listener = get_listener(None, Some(address.clone())).await?;
+        // Clients have to authenticate via certificate, before they may send the secret.
+        if let Some(config) = tls_config {
+            listeners.push(Box::new(TlsListener::new(listener, config)));
+            sockets.push(format!("tcp with tls at {}", address));
+        } else {
+            listeners.push(listener);
+            sockets.push(format!("tcp at {}", address));
+        }
+    }
+
+    let runtime_info = Arc::new(RuntimeInfo {
+        start: Local::now(),
+        socket: sockets.join(", "),
+        clients: Default::default(),
+    });
 
+    // Each listener accepts connections in its own task.
+    let handles: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| {
+            task::spawn(accept_connections(
+                listener,
+                sender.clone(),
+                state.clone(),
+                runtime_info.clone(),
+            ))
+        })
+        .collect();
+    for handle in handles {
+        handle.await?;
+    }
+
+    Ok(())
+}
+
+/// Poll the listener and accept new incoming connections.
+/// Create a new future to handle the message and spawn it.
+async fn accept_connections(
+    listener: Listener,
+    sender: Sender<Message>,
+    state: SharedState,
+    runtime_info: Arc<RuntimeInfo>,
+) -> Result<()> {
     loop {
         // Poll if we have a new incoming connection.
         let socket = listener.accept().await?;
@@ -44,8 +111,11 @@ pub async fn accept_incoming(sender: Sender<Message>, state: SharedState, opt: O
         // Start a new task for the request
         let sender_clone = sender.clone();
         let state_clone = state.clone();
+        let info_clone = runtime_info.clone();
         task::spawn(async move {
-            let _result = handle_incoming(socket, sender_clone, state_clone).await;
+            info_clone.clients.fetch_add(1, Ordering::SeqCst);
+            let _result = handle_incoming(socket, sender_clone, state_clone, &info_clone).await;
+            info_clone.clients.fetch_sub(1, Ordering::SeqCst);
         });
     }
 }
@@ -57,6 +127,7 @@ async fn handle_incoming(
     mut socket: Socket,
     sender: Sender<Message>,
     state: SharedState,
+    runtime_info: &RuntimeInfo,
 ) -> Result<()> {
     // Receive the secret once and check, whether the client is allowed to connect
     let payload_bytes = receive_bytes(&mut socket).await?;
@@ -70,38 +141,207 @@ async fn handle_incoming(
     let secret = String::from_utf8(payload_bytes)?;
 
     // Return immediately, if we got a wrong secret from the client.
-    {
+    // Clients with the read-only secret may only issue read operations.
+    let read_only = {
         let state = state.lock().unwrap();
-        if secret != state.settings.shared.secret {
+        let read_only_secret = &state.settings.daemon.read_only_secret;
+        if secret == state.settings.shared.secret {
+            false
+        } else if read_only_secret.as_ref() == Some(&secret) {
+            true
+        } else {
             warn!("Received invalid secret: {}", secret);
             bail!("Received invalid secret");
         }
-    }
+    };
     // Send a super short `ok` byte to the client, so it knows that the secret has been accepted.
     send_bytes(b"hello", &mut socket).await?;
 
     // Save the directory for convenience purposes and to prevent continuously
     // locking the state in the streaming loop.
-    let pueue_directory = {
+    let (pueue_directory, audit_log) = {
         let state = state.lock().unwrap();
-        state.settings.shared.pueue_directory.clone()
+        (
+            state.settings.shared.pueue_directory.clone(),
+            state.settings.daemon.audit_log,
+        )
+    };
+    // Identify the client and its credentials in the audit log.
+    let client = if read_only {
+        format!("{} (read-only)", socket.peer())
+    } else {
+        socket.peer()
     };
 
     loop {
         // Receive the actual instruction from the client
-        let message = receive_message(&mut socket).await?;
+        let payload_bytes = receive_bytes(&mut socket).await?;
+
+        // Didn't receive any bytes. The client disconnected.
+        if payload_bytes.is_empty() {
+            info!("Client went away");
+            return Ok(());
+        }
+
+        // Let clients with an incompatible protocol know what's going on,
+        // instead of simply dropping the connection.
+        let message: Message = match bincode::deserialize(&payload_bytes) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("Failed to deserialize message: {}", err);
+                let response = create_failure_message(format!(
+                    "The daemon couldn't understand your message. \
+                    Your client is probably incompatible with the daemon's version {}",
+                    env!("CARGO_PKG_VERSION")
+                ));
+                send_message(response, &mut socket).await?;
+                bail!("Received message with incompatible format");
+            }
+        };
         debug!("Received instruction: {:?}", message);
 
-        let response = if let Message::StreamRequest(message) = message {
+        let read_operation = is_read_operation(&message);
+        // Workers poll continuously, which would flood the audit log.
+        let polling = matches!(
+            message,
+            Message::WorkerPull(_) | Message::WorkerHeartbeat(_)
+        );
+        if audit_log && !read_operation && !polling {
+            audit(&pueue_directory, &client, &message, read_only);
+        }
+
+        if read_only && !read_operation {
+            let response = create_failure_message(
+                "Permission denied. This client is only allowed to read the state and logs.",
+            );
+            send_message(response, &mut socket).await?;
+            continue;
+        }
+
+        let mut response = match message {
             // The client requested the output of a task.
             // Since we allow streaming, this needs to be handled seperately.
-            handle_follow(&pueue_directory, &mut socket, &state, message).await?
-        } else {
+            Message::StreamRequest(message) => {
+                handle_follow(&pueue_directory, &mut socket, &state, message).await?
+            }
+            // Runtime information is only known to the socket handling.
+            Message::DaemonInfo => get_daemon_info(runtime_info, &state),
             // Process a normal message.
-            handle_message(message, &sender, &state)
+            message => handle_message(message, &sender, &state),
         };
 
         // Respond to the client.
+        remove_secrets(&mut response);
         send_message(response, &mut socket).await?;
     }
 }
+
+/// Clients never need the secrets of the daemon. Remove them from all responses that contain
+/// the state, so clients with the read-only secret can't obtain the full-access secret.
+/// The key of the logs is removed as well, since clients read encrypted logs via the daemon.
+fn remove_secrets(message: &mut Message) {
+    let state = match message {
+        Message::StatusResponse(state) => state,
+        Message::StatusDelta(delta) => match &mut delta.full {
+            Some(state) => state,
+            None => return,
+        },
+        _ => return,
+    };
+
+    state.settings.shared.secret.clear();
+    state.settings.daemon.read_only_secret = None;
+    state.settings.daemon.log_key = None;
+}
+
+/// Check whether a message only reads the state or logs without changing anything.
+/// Only these messages are allowed for clients with the read-only secret.
+fn is_read_operation(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::Status
+            | Message::StatusPage(_)
+            | Message::StatusSince(_)
+            | Message::Log(_)
+            | Message::LogGrep(_)
+            | Message::StreamRequest(_)
+            | Message::DaemonInfo
+            | Message::Stats(_)
+            | Message::TrashList
+            | Message::Version(_)
+    )
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use std::fs;
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+
+    use async_std::os::unix::net::UnixStream;
+
+    use pueue::settings::Settings;
+    use pueue::state::State;
+
+    use super::*;
+
+    #[async_std::test]
+    async fn test_read_only_client_gets_no_secrets() {
+        let directory = tempfile::tempdir().unwrap();
+        let config_path = directory.path().join("pueue.yml");
+        let config = format!(
+            "shared:\n  pueue_directory: {}\n  secret: full-access-secret\n\
+            daemon:\n  read_only_secret: read-only-secret\n",
+            directory.path().to_string_lossy()
+        );
+        fs::write(&config_path, config).unwrap();
+        let settings = Settings::new(true, &Some(config_path)).unwrap();
+        let state = Arc::new(Mutex::new(State::new(&settings, None)));
+
+        let (sender, _receiver) = channel();
+        let (mut client, daemon): (Socket, Socket) = {
+            let (client, daemon) = UnixStream::pair().unwrap();
+            (Box::new(client), Box::new(daemon))
+        };
+        let runtime_info = Arc::new(RuntimeInfo {
+            start: Local::now(),
+            socket: "test".into(),
+            clients: Default::default(),
+        });
+        task::spawn(async move { handle_incoming(daemon, sender, state, &runtime_info).await });
+
+        send_bytes(b"read-only-secret", &mut client).await.unwrap();
+        assert_eq!(receive_bytes(&mut client).await.unwrap(), b"hello");
+
+        let page = StatusPageMessage {
+            group: None,
+            query: None,
+            pipeline: None,
+            offset: 0,
+            limit: None,
+            added_after: None,
+            added_before: None,
+            finished_after: None,
+            finished_before: None,
+            exit_code: None,
+        };
+        // An unknown revision results in the full state.
+        let requests = vec![
+            Message::Status,
+            Message::StatusPage(page),
+            Message::StatusSince(u64::MAX),
+        ];
+        for request in requests {
+            send_message(request, &mut client).await.unwrap();
+            let response = receive_bytes(&mut client).await.unwrap();
+            let text = String::from_utf8_lossy(&response);
+            assert!(!text.contains("full-access-secret"));
+            assert!(!text.contains("read-only-secret"));
+
+            match bincode::deserialize(&response).unwrap() {
+                Message::StatusResponse(_) | Message::StatusDelta(_) => {}
+                message => panic!("Received unexpected response {:?}", message),
+            }
+        }
+    }
+}
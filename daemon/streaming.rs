@@ -1,16 +1,24 @@
-use std::fs::File;
 use std::io::Read;
 use std::time::Duration;
 
 use anyhow::Result;
 use async_std::task::sleep;
 
+use pueue::encryption::LogFile;
 use pueue::log::*;
 use pueue::message::*;
 use pueue::protocol::{send_message, Socket};
 use pueue::state::SharedState;
 
+/// The maximum amount of bytes that's read from the log file and sent at once.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
 /// Handle the continuous stream of a message.
+///
+/// The log is read in chunks of at most [STREAM_CHUNK_SIZE] bytes and the next chunk is only
+/// read, once the previous one has been written to the socket. Since writes only complete as fast
+/// as the client consumes them, slow connections throttle the reading instead of piling up the
+/// output of fast tasks in memory.
 pub async fn handle_follow(
     pueue_directory: &str,
     socket: &mut Socket,
@@ -52,8 +60,9 @@ pub async fn handle_follow(
     };
 
     // The client requested streaming of stdout.
-    let mut handle: File;
-    match get_log_file_handles(task_id, pueue_directory) {
+    let key = state.lock().unwrap().log_key.clone();
+    let mut handle: LogFile;
+    match get_log_file_handles(task_id, pueue_directory, key.as_ref()) {
         Err(_) => {
             return Ok(create_failure_message(
                 "Couldn't find output files for task. Maybe it finished? Try `log`",
@@ -74,6 +83,9 @@ pub async fn handle_follow(
     let (out_path, err_path) = get_log_paths(task_id, pueue_directory);
     let handle_path = if message.err { err_path } else { out_path };
 
+    // Bytes of a multi-byte character, which has been cut in half by the end of the last chunk.
+    let mut incomplete = Vec::new();
+    let mut chunk = vec![0; STREAM_CHUNK_SIZE];
     loop {
         // Check whether the file still exists. Exit if it doesn't.
         if !handle_path.exists() {
@@ -82,16 +94,70 @@ pub async fn handle_follow(
             ));
         }
         // Read the next chunk of text from the last position.
-        let mut buffer = Vec::new();
-
-        if let Err(err) = handle.read_to_end(&mut buffer) {
-            return Ok(create_failure_message(format!("Error: {}", err)));
+        let read = match handle.read(&mut chunk) {
+            Ok(read) => read,
+            Err(err) => return Ok(create_failure_message(format!("Error: {}", err))),
         };
-        let text = String::from_utf8_lossy(&buffer).to_string();
+        let mut buffer = std::mem::take(&mut incomplete);
+        buffer.extend_from_slice(&chunk[..read]);
+        incomplete = split_off_incomplete_char(&mut buffer);
+
+        // Sending only finishes, once the client received most of the chunk.
+        if !buffer.is_empty() {
+            let text = String::from_utf8_lossy(&buffer).to_string();
+            send_message(Message::Stream(text), socket).await?;
+        }
+
+        // Only wait for new output, once we caught up with the log file.
+        if read < STREAM_CHUNK_SIZE {
+            sleep(Duration::from_millis(1000)).await;
+        }
+    }
+}
+
+/// Remove the bytes of an incomplete UTF-8 character at the end of the buffer and return them.
+/// Invalid bytes anywhere else are kept, they're replaced while converting the chunk to text.
+fn split_off_incomplete_char(buffer: &mut Vec<u8>) -> Vec<u8> {
+    // A UTF-8 character has at most 4 bytes, so only the last 3 bytes can be incomplete.
+    let tail_start = buffer.len().saturating_sub(3);
+    for start in tail_start..buffer.len() {
+        match std::str::from_utf8(&buffer[start..]) {
+            // The end of the buffer is valid, nothing's missing.
+            Ok(_) => return Vec::new(),
+            // The missing bytes of the character will follow with the next chunk.
+            Err(error) if error.error_len().is_none() && error.valid_up_to() == 0 => {
+                return buffer.split_off(start)
+            }
+            Err(_) => continue,
+        }
+    }
+
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_off_incomplete_char() {
+        // `ä` is encoded as two bytes.
+        let text = "abcä".as_bytes();
+        let mut buffer = text[..3].to_vec();
+        assert!(split_off_incomplete_char(&mut buffer).is_empty());
+        assert_eq!(buffer, b"abc");
+
+        let mut buffer = text[..4].to_vec();
+        assert_eq!(split_off_incomplete_char(&mut buffer), vec![text[3]]);
+        assert_eq!(buffer, b"abc");
+
+        let mut buffer = text.to_vec();
+        assert!(split_off_incomplete_char(&mut buffer).is_empty());
+        assert_eq!(buffer, text);
 
-        // Send the new chunk and wait for 1 second.
-        let response = Message::Stream(text);
-        send_message(response, socket).await?;
-        sleep(Duration::from_millis(1000)).await;
+        // Invalid bytes aren't held back forever.
+        let mut buffer = vec![b'a', 0xff];
+        assert!(split_off_incomplete_char(&mut buffer).is_empty());
+        assert_eq!(buffer, vec![b'a', 0xff]);
     }
 }
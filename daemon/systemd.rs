@@ -0,0 +1,86 @@
+use std::env;
+
+use anyhow::Result;
+
+use crate::cli::Opt;
+
+/// Invoked when calling `pueued generate-systemd`.
+/// Print a service unit for the current binary and config.
+pub fn generate_systemd(opt: &Opt, user: bool) -> Result<()> {
+    let arguments = opt.service_arguments()?;
+    let system_user = if user { None } else { env::var("USER").ok() };
+    print!(
+        "{}",
+        compile_unit(&arguments, system_user, env::var("PATH").ok())
+    );
+
+    Ok(())
+}
+
+/// Compile a service unit for the given command line.
+/// System units run the daemon as the given user, user units are run by the user's service manager.
+/// The daemon is restarted if it crashes, but not after `pueue shutdown`.
+fn compile_unit(arguments: &[String], system_user: Option<String>, path: Option<String>) -> String {
+    let exec_start: Vec<String> = arguments.iter().map(|argument| quote(argument)).collect();
+
+    let mut unit = String::from(
+        "[Unit]\n\
+        Description=Pueue Daemon - CLI process scheduler and manager\n",
+    );
+    if system_user.is_some() {
+        unit.push_str("After=network.target\n");
+    }
+
+    unit.push_str("\n[Service]\n");
+    unit.push_str(&format!("ExecStart={}\n", exec_start.join(" ")));
+    unit.push_str("Restart=on-failure\n");
+    if let Some(user) = &system_user {
+        unit.push_str(&format!("User={}\n", user));
+    }
+    // Tasks should find the same programs as in the user's shell.
+    if let Some(path) = path {
+        unit.push_str(&format!(
+            "Environment={}\n",
+            quote(&format!("PATH={}", path))
+        ));
+    }
+
+    unit.push_str("\n[Install]\n");
+    if system_user.is_some() {
+        unit.push_str("WantedBy=multi-user.target\n");
+    } else {
+        unit.push_str("WantedBy=default.target\n");
+    }
+
+    unit
+}
+
+/// Quote a value, so systemd neither splits it nor expands specifiers or variables.
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_unit() {
+        let arguments = vec!["/usr/bin/pueued".into(), "-vv".into()];
+        let unit = compile_unit(&arguments, None, Some("/usr/bin:$HOME/bin".into()));
+        assert!(unit.contains("ExecStart=\"/usr/bin/pueued\" \"-vv\"\n"));
+        assert!(unit.contains("Environment=\"PATH=/usr/bin:$$HOME/bin\"\n"));
+        assert!(unit.contains("WantedBy=default.target\n"));
+        assert!(!unit.contains("User="));
+
+        let unit = compile_unit(&arguments, Some("arne".into()), None);
+        assert!(unit.contains("User=arne\n"));
+        assert!(unit.contains("WantedBy=multi-user.target\n"));
+    }
+}
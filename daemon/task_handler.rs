@@ -1,22 +1,38 @@
-use std::collections::{BTreeMap, HashMap};
-use std::io::Write;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::remove_dir_all;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process::Child;
 use std::process::Stdio;
 use std::sync::mpsc::Receiver;
-use std::thread::sleep;
-use std::time::Duration;
+use std::thread::{self, sleep};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use chrono::prelude::*;
 use handlebars::Handlebars;
 use log::{debug, error, info, warn};
+use tempfile::Builder;
 
+use pueue::encryption::{LogFile, LogKey};
 use pueue::log::*;
 use pueue::message::*;
-use pueue::state::SharedState;
-use pueue::task::{Task, TaskResult, TaskStatus};
-
+use pueue::redact::redact_command;
+use pueue::settings::Host;
+use pueue::shell::posix_setup_command;
+use pueue::state::{SharedState, State};
+use pueue::task::{parse_cpu_list, EnvironmentMode, Notification, Task, TaskResult, TaskStatus};
+use pueue::window::{RunWindow, WindowEndAction};
+
+use crate::artifacts::collect_artifacts;
+use crate::container::*;
+use crate::pid::cleanup_pid_file;
 use crate::platform::process_helper::*;
+use crate::quota::spawn_log_copy;
+use crate::reachability::*;
+use crate::remote::compile_ssh_command;
+use crate::template::render_command;
 
 pub struct TaskHandler {
     state: SharedState,
@@ -24,10 +40,68 @@ pub struct TaskHandler {
     children: BTreeMap<usize, Child>,
     callbacks: Vec<Child>,
     reset: bool,
+    /// Set on a graceful shutdown. No new tasks are started and the daemon exits,
+    /// as soon as all running tasks finished.
+    graceful_shutdown: bool,
+    /// The points in time at which tasks have been started during the last minute, by group.
+    start_history: HashMap<String, Vec<Instant>>,
+    /// The point in time at which each group started its last task.
+    /// Groups that haven't started a task for the longest time are preferred.
+    last_started: HashMap<String, Instant>,
+    /// All named locks that are currently held, with the id of the task that holds them.
+    held_locks: HashMap<String, usize>,
+    /// The ids of all running tasks that are executed inside of a container.
+    containers: HashSet<usize>,
+    /// Whether any task was queued or running during the last iteration.
+    /// Used to detect the moment the whole queue has been drained.
+    queue_busy: bool,
+    /// The reachability checks of all queued tasks that wait for a host.
+    host_checks: HashMap<usize, HostCheck>,
+    /// Whether the run window of each group was open during the last iteration.
+    window_open: HashMap<String, bool>,
+    /// The ids of all tasks that have been paused at the end of their group's run window.
+    window_paused: HashSet<usize>,
+    /// The ids of all tasks that have been paused to make room for a task with a higher priority.
+    /// In contrast to tasks paused by the user, they don't occupy a slot of their group
+    /// and are resumed automatically, once there's room again.
+    preempted: HashSet<usize>,
+    /// The ids of all tasks that are known to be `Done`.
+    /// Used to detect the moment a task reaches its final state, no matter how it ended.
+    done: HashSet<usize>,
+    /// The group and slot index of each running task, which is exported as `PUEUE_WORKER`.
+    /// Each task gets the lowest index that isn't used by another task of its group.
+    worker_slots: HashMap<usize, (String, usize)>,
+    /// Tasks that got a SIGTERM, with the point in time at which they're killed with SIGKILL
+    /// and whether their children are killed as well.
+    terminating: HashMap<usize, (Instant, bool)>,
     // Some static settings that are extracted from `state.settings` for convenience purposes.
     pueue_directory: String,
     callback: Option<String>,
     pause_on_failure: bool,
+    max_starts_per_minute: HashMap<String, usize>,
+    max_parallel_tasks: Option<usize>,
+    preemption: bool,
+    run_windows: HashMap<String, RunWindow>,
+    clean_tempdirs: bool,
+    container_runtime: String,
+    hosts: HashMap<String, Host>,
+    redacted_envs: Vec<String>,
+    max_output_bytes: Option<u64>,
+    successful_log_lines: Option<usize>,
+    drained_callback: Option<String>,
+    overdue_factor: Option<f64>,
+    host_check_interval: Duration,
+    host_check_timeout: Option<Duration>,
+    notify_callback: Option<String>,
+    kill_grace_period: u64,
+    on_exit: HashMap<String, String>,
+    line_buffered: bool,
+    quarantine_group: Option<String>,
+    max_consecutive_failures: HashMap<String, usize>,
+    /// The amount of tasks that failed in a row for each group.
+    failure_streaks: HashMap<String, usize>,
+    worker_timeout: Duration,
+    log_key: Option<LogKey>,
 }
 
 /// Pueue directly interacts with processes.
@@ -38,6 +112,8 @@ pub struct TaskHandler {
 pub enum ProcessAction {
     Pause,
     Resume,
+    /// Ask the process to exit, which gives it the chance to clean up.
+    Terminate,
     Kill,
 }
 
@@ -45,12 +121,72 @@ impl TaskHandler {
     pub fn new(state: SharedState, receiver: Receiver<Message>) -> Self {
         // Extract some static settings we often need.
         // This prevents locking the State all the time.
-        let (pueue_directory, callback, pause_on_failure) = {
+        let (
+            pueue_directory,
+            callback,
+            pause_on_failure,
+            max_starts_per_minute,
+            max_parallel_tasks,
+            preemption,
+            run_windows,
+            clean_tempdirs,
+            container_runtime,
+            hosts,
+            redacted_envs,
+            max_output_bytes,
+            successful_log_lines,
+            drained_callback,
+            overdue_factor,
+            host_check_interval,
+            host_check_timeout,
+            notify_callback,
+            kill_grace_period,
+            on_exit,
+            line_buffered,
+            quarantine_group,
+            max_consecutive_failures,
+            worker_timeout,
+            log_key,
+            done,
+        ) = {
             let state = state.lock().unwrap();
             (
                 state.settings.shared.pueue_directory.clone(),
                 state.settings.daemon.callback.clone(),
                 state.settings.daemon.pause_on_failure,
+                state.settings.daemon.max_starts_per_minute.clone(),
+                state.settings.daemon.max_parallel_tasks,
+                state.settings.daemon.preemption,
+                state.settings.daemon.run_windows.clone(),
+                state.settings.daemon.clean_tempdirs,
+                state.settings.daemon.container_runtime.clone(),
+                state.settings.hosts.clone(),
+                state.settings.shared.redacted_envs.clone(),
+                state.settings.daemon.max_output_bytes,
+                state.settings.daemon.successful_log_lines,
+                state.settings.daemon.drained_callback.clone(),
+                state.settings.daemon.overdue_factor,
+                Duration::from_secs(state.settings.daemon.host_check_interval.max(1)),
+                state
+                    .settings
+                    .daemon
+                    .host_check_timeout
+                    .map(Duration::from_secs),
+                state.settings.daemon.notify_callback.clone(),
+                state.settings.daemon.kill_grace_period,
+                state.settings.daemon.on_exit.clone(),
+                state.settings.daemon.line_buffered,
+                state.settings.daemon.quarantine_group.clone(),
+                state.settings.daemon.max_consecutive_failures.clone(),
+                Duration::from_secs(state.settings.daemon.worker_timeout),
+                state.log_key.clone(),
+                // Tasks that finished before the daemon started don't run their hooks again.
+                state
+                    .tasks
+                    .values()
+                    .filter(|task| task.status == TaskStatus::Done)
+                    .map(|task| task.id)
+                    .collect(),
             )
         };
 
@@ -60,9 +196,45 @@ impl TaskHandler {
             children: BTreeMap::new(),
             callbacks: Vec::new(),
             reset: false,
+            graceful_shutdown: false,
+            start_history: HashMap::new(),
+            last_started: HashMap::new(),
+            held_locks: HashMap::new(),
+            containers: HashSet::new(),
+            queue_busy: false,
+            host_checks: HashMap::new(),
+            window_open: HashMap::new(),
+            window_paused: HashSet::new(),
+            preempted: HashSet::new(),
+            done,
+            worker_slots: HashMap::new(),
+            terminating: HashMap::new(),
             pueue_directory,
             callback,
             pause_on_failure,
+            max_starts_per_minute,
+            max_parallel_tasks,
+            preemption,
+            run_windows,
+            clean_tempdirs,
+            container_runtime,
+            hosts,
+            redacted_envs,
+            max_output_bytes,
+            successful_log_lines,
+            drained_callback,
+            overdue_factor,
+            host_check_interval,
+            host_check_timeout,
+            notify_callback,
+            kill_grace_period,
+            on_exit,
+            line_buffered,
+            quarantine_group,
+            max_consecutive_failures,
+            failure_streaks: HashMap::new(),
+            worker_timeout,
+            log_key,
         }
     }
 }
@@ -74,38 +246,63 @@ impl TaskHandler {
     /// 2. Check whether any tasks just finished.
     /// 3. Check if there are any stashed processes ready for being enqueued.
     /// 4. Check whether we can spawn new tasks.
+    /// 5. Check whether the whole queue has just been drained.
+    /// 6. Check whether any task runs abnormally long.
+    /// 7. Pause or kill tasks of groups, whose run window just ended.
+    /// 8. Kill tasks that didn't exit within their grace period after a SIGTERM.
+    /// 9. Fail tasks of remote workers that stopped sending heartbeats.
     pub fn run(&mut self) {
         loop {
             self.receive_commands();
             self.handle_finished_tasks();
+            self.check_done_tasks();
             self.handle_reset();
+            self.handle_graceful_shutdown();
             self.check_callbacks();
             self.check_stashed();
+            self.check_wait_paths();
+            self.check_wait_hosts();
             self.check_failed_dependencies();
-            if !self.reset {
+            if !self.reset && !self.graceful_shutdown {
                 let _res = self.check_new();
             }
+            self.check_queue_drained();
+            self.check_overdue_tasks();
+            self.check_run_windows();
+            self.check_kill_deadlines();
+            self.check_quarantine();
+            self.check_worker_heartbeats();
         }
     }
 
-    /// Search and return the next task that can be started.
+    /// Search and return the next task that can be started or, if it has been preempted, resumed.
+    /// Tasks with the highest priority are picked first, preempted tasks before queued ones.
+    /// Among those, groups take turns: The group that didn't start a task for the longest time
+    /// is preferred, so a huge batch in one group can't starve the others.
     /// Precondition for a task to be started:
-    /// - is in Queued state
+    /// - The task is startable, see `is_startable`
     /// - There are free slots in the task's group
-    /// - The group is running
-    /// - has all its dependencies in `Done` state
+    ///   (either enough parallel slots or enough resource slots of the group's slot pool)
+    /// - Fewer than `max_parallel_tasks` tasks are running across all groups
     pub fn get_next_task_id(&mut self) -> Option<usize> {
+        self.prune_start_history();
         let state = self.state.lock().unwrap();
         // Check how many tasks are running in each group
         let mut running_tasks_per_group: HashMap<String, usize> = HashMap::new();
 
+        // Check how many resource slots are used by the running tasks of each group
+        let mut used_slots_per_group: HashMap<String, usize> = HashMap::new();
+
         // Create a default group for tasks without an explicit group
         running_tasks_per_group.insert("default".into(), 0);
 
         // Walk through all tasks and save the number of running tasks by group
         for (_, task) in state.tasks.iter() {
             // We are only interested in currently running tasks.
-            if ![TaskStatus::Running, TaskStatus::Paused].contains(&task.status) {
+            // Preempted tasks don't occupy a slot.
+            if ![TaskStatus::Running, TaskStatus::Paused].contains(&task.status)
+                || self.preempted.contains(&task.id)
+            {
                 continue;
             }
 
@@ -124,18 +321,43 @@ impl TaskHandler {
                     running_tasks_per_group.insert(group.into(), 1);
                 }
             }
+
+            *used_slots_per_group.entry(group.into()).or_default() += task.slots;
         }
 
+        // Tasks of worker groups don't run on this machine and don't count towards the global limit.
+        if let Some(max_parallel_tasks) = self.max_parallel_tasks {
+            let running: usize = running_tasks_per_group
+                .iter()
+                .filter(|(group, _)| !state.settings.daemon.worker_groups.contains(group))
+                .map(|(_, count)| count)
+                .sum();
+            if running >= max_parallel_tasks {
+                return None;
+            }
+        }
+
+        let free_gpus = self.free_gpus(&state).len();
+
+        // Groups with a resource slot pool pack tasks by their slots instead of their count.
+        // Returns `None`, if the group of the task doesn't have a slot pool.
+        let fits_into_slot_pool = |task: &Task| {
+            let group = task.group.as_deref().unwrap_or("default");
+            state.settings.daemon.slots.get(group).map(|pool| {
+                let used = used_slots_per_group.get(group).unwrap_or(&0);
+                used + task.slots <= *pool
+            })
+        };
+
         state
             .tasks
             .iter()
-            .filter(|(_, task)| task.status == TaskStatus::Queued)
+            .filter(|(_, task)| self.is_startable(&state, task, free_gpus))
             .filter(|(_, task)| {
                 if let Some(group) = &task.group {
                     // The task is assigned to a group.
-                    // First let's check if the group is paused. If it is, simply return false.
-                    if !state.groups.get(group).unwrap() {
-                        return false;
+                    if let Some(fits) = fits_into_slot_pool(task) {
+                        return fits;
                     }
 
                     // If there's no running task for the group yet, we can safely return true
@@ -157,9 +379,8 @@ impl TaskHandler {
                     }
                 } else {
                     // The task is assigned to the default queue.
-                    // Check if the default queue is paused and return false if it's not.
-                    if !state.running {
-                        return false;
+                    if let Some(fits) = fits_into_slot_pool(task) {
+                        return fits;
                     }
 
                     // We can unwrap safely, since default is always created.
@@ -167,16 +388,282 @@ impl TaskHandler {
                     running < &state.settings.daemon.default_parallel_tasks
                 }
             })
-            .find(|(_, task)| {
-                // Check whether all dependencies for this task are fulfilled.
-                task.dependencies
-                    .iter()
-                    .flat_map(|id| state.tasks.get(id))
-                    .all(|task| task.status == TaskStatus::Done)
+            // Tasks with the same priority are picked by their group's turn and then by their id.
+            .max_by_key(|(id, task)| {
+                let group = task.group.as_deref().unwrap_or("default");
+                (
+                    task.priority,
+                    self.preempted.contains(id),
+                    Reverse(self.last_started.get(group)),
+                    Reverse(**id),
+                )
             })
             .map(|(id, _)| *id)
     }
 
+    /// Check whether a task could be started or resumed right now, apart from the free slots of
+    /// its group. This is shared by `get_next_task_id` and `preempt`, so tasks are never preempted
+    /// for a task that can't be started anyway.
+    /// - is in Queued state or has been preempted
+    /// - doesn't wait for a path or host anymore
+    /// - its group is running and isn't a worker group
+    /// - its group didn't exceed its start rate limit and its run window is open
+    /// - its named lock isn't held by another task
+    /// - enough GPUs of the GPU pool are free
+    /// - has all its dependencies in `Done` state
+    fn is_startable(&self, state: &State, task: &Task, free_gpus: usize) -> bool {
+        let preempted = self.preempted.contains(&task.id);
+        if (task.status != TaskStatus::Queued && !preempted) || task.is_waiting() {
+            return false;
+        }
+
+        // Tasks of worker groups are executed by remote workers.
+        let group_running = match &task.group {
+            Some(group) => {
+                !state.settings.daemon.worker_groups.contains(group)
+                    && *state.groups.get(group).unwrap_or(&false)
+            }
+            None => state.running,
+        };
+
+        group_running
+            // Resuming a preempted task doesn't count as a start.
+            && (preempted || !self.is_rate_limited(&task.group))
+            && !self.is_outside_run_window(&task.group)
+            && !self.is_lock_held(task)
+            // Preempted tasks keep their GPUs.
+            && (preempted || task.gpus <= free_gpus)
+            && state.dependencies_fulfilled(task)
+    }
+
+    /// Pause running tasks with a lower priority, if the queued task with the highest priority
+    /// can't be started, since its group is full.
+    /// The next call of `get_next_task_id` then picks the high priority task.
+    fn preempt(&mut self) {
+        for id in self.preemption_victims() {
+            self.pause_task(id, false);
+            let state = self.state.lock().unwrap();
+            let paused = state
+                .tasks
+                .get(&id)
+                .is_some_and(|task| task.status == TaskStatus::Paused);
+            if paused {
+                self.preempted.insert(id);
+            }
+        }
+    }
+
+    /// The running tasks, which have to be preempted for the queued task with the highest priority.
+    /// Only as many tasks as necessary are preempted, the ones with the lowest priority first.
+    fn preemption_victims(&self) -> Vec<usize> {
+        let state = self.state.lock().unwrap();
+        let free_gpus = self.free_gpus(&state).len();
+        // Preempted tasks are already paused, so only queued tasks need to make room.
+        let candidate = state
+            .tasks
+            .values()
+            .filter(|task| task.status == TaskStatus::Queued)
+            .filter(|task| self.is_startable(&state, task, free_gpus))
+            .max_by_key(|task| (task.priority, Reverse(task.id)));
+        let candidate = match candidate {
+            Some(candidate) => candidate,
+            None => return Vec::new(),
+        };
+
+        // All tasks of the group that currently occupy a slot.
+        let mut occupying: Vec<&Task> = state
+            .tasks
+            .values()
+            .filter(|task| task.group == candidate.group && task.is_running())
+            .filter(|task| !self.preempted.contains(&task.id))
+            .collect();
+        let key = candidate.group.as_deref().unwrap_or("default");
+        let pool = state.settings.daemon.slots.get(key);
+
+        // Calculate how many tasks or slots have to be freed.
+        let (mut missing, count_slots) = match pool {
+            Some(pool) => {
+                let used: usize = occupying.iter().map(|task| task.slots).sum();
+                if candidate.slots > *pool {
+                    return Vec::new();
+                }
+                ((used + candidate.slots).saturating_sub(*pool), true)
+            }
+            None => {
+                let allowed = match &candidate.group {
+                    Some(group) => *state.settings.daemon.groups.get(group).unwrap_or(&1),
+                    None => state.settings.daemon.default_parallel_tasks,
+                };
+                ((occupying.len() + 1).saturating_sub(allowed), false)
+            }
+        };
+        if missing == 0 {
+            return Vec::new();
+        }
+
+        // Only running processes of this daemon with a lower priority may be preempted.
+        // The most recently started ones are preempted first, since they lose the least progress.
+        occupying.retain(|task| {
+            task.status == TaskStatus::Running
+                && task.priority < candidate.priority
+                && self.children.contains_key(&task.id)
+        });
+        occupying.sort_by_key(|task| (task.priority, Reverse(task.start)));
+
+        let mut victims = Vec::new();
+        for task in occupying {
+            if missing == 0 {
+                break;
+            }
+            missing = missing.saturating_sub(if count_slots { task.slots } else { 1 });
+            victims.push(task.id);
+        }
+        // Don't preempt anything, if it wouldn't make enough room anyway.
+        if missing > 0 {
+            return Vec::new();
+        }
+        info!(
+            "Preempting tasks {:?} for task {} with priority {}",
+            victims, candidate.id, candidate.priority
+        );
+        victims
+    }
+
+    /// Forget about all task starts that happened more than a minute ago.
+    fn prune_start_history(&mut self) {
+        let minute = Duration::from_secs(60);
+        for starts in self.start_history.values_mut() {
+            starts.retain(|start| start.elapsed() < minute);
+        }
+    }
+
+    /// Check whether a group already started as many tasks during the last minute as it's allowed to.
+    fn is_rate_limited(&self, group: &Option<String>) -> bool {
+        let key = group.as_deref().unwrap_or("default");
+        let max_starts = match self.max_starts_per_minute.get(key) {
+            Some(max_starts) => *max_starts,
+            None => return false,
+        };
+
+        match self.start_history.get(key) {
+            Some(starts) => starts.len() >= max_starts,
+            None => false,
+        }
+    }
+
+    /// Check whether a group has a run window, which is currently closed.
+    fn is_outside_run_window(&self, group: &Option<String>) -> bool {
+        let key = group.as_deref().unwrap_or("default");
+        match self.run_windows.get(key) {
+            Some(window) => !window.contains(Local::now()),
+            None => false,
+        }
+    }
+
+    /// Pause or kill the running tasks of a group, once its run window ends,
+    /// depending on the window's `at_end` action.
+    /// Tasks that have been paused are resumed, once the window opens again.
+    fn check_run_windows(&mut self) {
+        let now = Local::now();
+        let mut changes = Vec::new();
+        for (group, window) in self.run_windows.iter() {
+            let open = window.contains(now);
+            let was_open = self.window_open.insert(group.clone(), open);
+            // Nothing happened or the daemon just started.
+            if was_open.is_none_or(|was_open| was_open == open) {
+                continue;
+            }
+            let action = window.end_action().unwrap_or(WindowEndAction::Continue);
+            changes.push((group.clone(), open, action));
+        }
+
+        for (group, open, action) in changes {
+            let key = if group == "default" {
+                None
+            } else {
+                Some(group.clone())
+            };
+
+            if open {
+                info!("Run window of group {} opened", group);
+                let paused = {
+                    let mut state = self.state.lock().unwrap();
+                    state.task_ids_in_group_with_stati(&key, vec![TaskStatus::Paused])
+                };
+                for id in paused {
+                    if self.window_paused.remove(&id) {
+                        self.continue_task(id, false);
+                    }
+                }
+                continue;
+            }
+
+            info!("Run window of group {} ended", group);
+            match action {
+                WindowEndAction::Continue => (),
+                WindowEndAction::Pause => {
+                    let running = {
+                        let mut state = self.state.lock().unwrap();
+                        state.task_ids_in_group_with_stati(&key, vec![TaskStatus::Running])
+                    };
+                    for id in running {
+                        self.pause_task(id, false);
+                        self.window_paused.insert(id);
+                    }
+                }
+                WindowEndAction::Kill => {
+                    let active = {
+                        let mut state = self.state.lock().unwrap();
+                        state.task_ids_in_group_with_stati(
+                            &key,
+                            vec![TaskStatus::Running, TaskStatus::Paused],
+                        )
+                    };
+                    let grace = self.kill_grace_period;
+                    for id in active {
+                        self.kill_task(id, false, grace);
+                    }
+                }
+            }
+        }
+    }
+
+    /// All GPUs of the GPU pool that aren't assigned to a running or paused task.
+    fn free_gpus(&self, state: &State) -> Vec<usize> {
+        let used: HashSet<usize> = state
+            .tasks
+            .values()
+            .filter(|task| task.is_running())
+            .flat_map(|task| task.assigned_gpus.iter().cloned())
+            .collect();
+
+        state
+            .settings
+            .daemon
+            .gpus
+            .iter()
+            .filter(|gpu| !used.contains(gpu))
+            .cloned()
+            .collect()
+    }
+
+    /// Check whether the named lock of a task is currently held by another task.
+    fn is_lock_held(&self, task: &Task) -> bool {
+        match &task.lock {
+            Some(lock) => match self.held_locks.get(lock) {
+                Some(owner) => *owner != task.id,
+                None => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Release all named locks that are held by a task.
+    /// This has to be called, as soon as a task reaches any final state.
+    fn release_locks(&mut self, task_id: usize) {
+        self.held_locks.retain(|_, owner| *owner != task_id);
+    }
+
     /// Users can issue to reset the daemon.
     /// If that's the case, the `self.reset` flag is set to true, all children are killed
     /// and no new tasks will be spawned.
@@ -192,11 +679,41 @@ impl TaskHandler {
         }
     }
 
-    /// See if we can start a new queued task.
+    /// Exit the daemon, once all running tasks of a graceful shutdown finished.
+    /// Since the socket hasn't been removed on the shutdown request, it's removed now.
+    fn handle_graceful_shutdown(&mut self) {
+        if !self.graceful_shutdown || !self.children.is_empty() {
+            return;
+        }
+
+        {
+            let state = self.state.lock().unwrap();
+            let path = &state.settings.shared.unix_socket_path;
+            if state.settings.listens_on_unix_socket() && std::path::PathBuf::from(&path).exists() {
+                if let Err(err) = std::fs::remove_file(path) {
+                    error!("Failed to remove unix socket on shutdown: {:?}", err);
+                }
+            }
+        }
+        info!("All tasks finished. Shutting down.");
+        cleanup_pid_file(&self.pueue_directory);
+        #[cfg(windows)]
+        crate::platform::service::report_stopped();
+        std::process::exit(0)
+    }
+
+    /// See if we can start a new queued task or resume a preempted one.
+    /// If nothing can be started, try to make room for tasks with a higher priority.
     fn check_new(&mut self) -> Result<()> {
         // Get the next task id that can be started
-        if let Some(id) = self.get_next_task_id() {
-            self.start_process(id);
+        match self.get_next_task_id() {
+            Some(id) if self.preempted.remove(&id) => {
+                info!("Resuming preempted task {}", id);
+                self.continue_task(id, false);
+            }
+            Some(id) => self.start_process(id),
+            None if self.preemption => self.preempt(),
+            None => (),
         }
 
         Ok(())
@@ -204,31 +721,65 @@ impl TaskHandler {
 
     /// Ensure that no `Queued` tasks have any failed dependencies.
     /// Otherwise set their status to `Done` and result to `DependencyFailed`.
+    /// Failure handlers, whose tasks all succeeded, are `Skipped` instead.
+    /// Tasks, whose dependencies aren't fulfilled within their timeout, get a `DependencyTimeout`.
     pub fn check_failed_dependencies(&mut self) {
         let mut state = self.state.lock().unwrap();
         let has_failed_deps: Vec<_> = state
             .tasks
             .iter()
             .filter(|(_, task)| task.status == TaskStatus::Queued)
-            .filter_map(|(id, task)| {
-                let failed = task
-                    .dependencies
-                    .iter()
-                    .flat_map(|id| state.tasks.get(id))
-                    .filter(|task| task.failed())
-                    .map(|task| task.id)
-                    .next();
-
-                failed.map(|f| (*id, f))
-            })
+            .filter_map(|(id, task)| state.failed_dependency(task).map(|f| (*id, f)))
             .collect();
 
+        let mut changed = !has_failed_deps.is_empty();
         for (id, _) in has_failed_deps {
-            if let Some(task) = state.tasks.get_mut(&id) {
+            if let Some(task) = state.task_mut(id) {
                 task.status = TaskStatus::Done;
                 task.result = Some(TaskResult::DependencyFailed);
             }
         }
+
+        let skipped: Vec<usize> = state
+            .tasks
+            .values()
+            .filter(|task| task.status == TaskStatus::Queued)
+            .filter(|task| state.failure_handler_skipped(task))
+            .map(|task| task.id)
+            .collect();
+        changed |= !skipped.is_empty();
+        for id in skipped {
+            let task = state.task_mut(id).unwrap();
+            info!("Skipping task {}, since its dependencies didn't fail", id);
+            task.status = TaskStatus::Done;
+            task.result = Some(TaskResult::Skipped);
+        }
+
+        let now = Local::now();
+        let timed_out: Vec<usize> = state
+            .tasks
+            .values()
+            .filter(|task| task.status == TaskStatus::Queued)
+            .filter(|task| {
+                task.dependency_timeout.is_some_and(|timeout| {
+                    now - task.created_at >= chrono::Duration::seconds(timeout)
+                })
+            })
+            .filter(|task| !state.dependencies_fulfilled(task))
+            .map(|task| task.id)
+            .collect();
+        changed |= !timed_out.is_empty();
+        for id in timed_out {
+            let task = state.task_mut(id).unwrap();
+            warn!("The dependencies of task {} didn't finish in time", id);
+            task.status = TaskStatus::Done;
+            task.result = Some(TaskResult::DependencyTimeout);
+            task.end = Some(now);
+        }
+
+        if changed {
+            state.save();
+        }
     }
 
     /// Actually spawn a new sub process
@@ -238,7 +789,14 @@ impl TaskHandler {
         // while we are looking for a task to start.
         let mut state = self.state.lock().unwrap();
 
-        let task = state.tasks.get_mut(&task_id);
+        // Previous runs might have finished since the task has been added.
+        let expected_runtime = state
+            .tasks
+            .get(&task_id)
+            .and_then(|task| state.expected_runtime(task));
+        let free_gpus = self.free_gpus(&state);
+
+        let task = state.task_mut(task_id);
         let task = match task {
             Some(task) => {
                 if !vec![TaskStatus::Stashed, TaskStatus::Queued, TaskStatus::Paused]
@@ -254,39 +812,174 @@ impl TaskHandler {
                 return;
             }
         };
+        // Never start a task, while another task holds the same named lock.
+        // This also prevents forcefully starting such tasks by hand.
+        if self.is_lock_held(task) {
+            warn!(
+                "Task {} can't be started, since its lock is held by another task",
+                task_id
+            );
+            return;
+        }
+        // The same goes for tasks that need more GPUs than are currently free.
+        if task.gpus > free_gpus.len() {
+            warn!(
+                "Task {} can't be started, since only {} of its {} GPUs are free",
+                task_id,
+                free_gpus.len(),
+                task.gpus
+            );
+            return;
+        }
+        task.assigned_gpus = free_gpus[..task.gpus].to_vec();
+
         // In case a task that has been scheduled for enqueueing, is forcefully
         // started by hand, set `enqueue_at` to `None`.
         task.enqueue_at = None;
 
         // Try to get the log files to which the output of the process
         // will be written to. Error if this doesn't work!
-        let (stdout_log, stderr_log) = match create_log_file_handles(task_id, &self.pueue_directory)
-        {
-            Ok((out, err)) => (out, err),
-            Err(err) => {
-                error!("Failed to create child log files: {:?}", err);
-                return;
+        let key = self.log_key.as_ref();
+        let (stdout_log, stderr_log) =
+            match create_log_file_handles(task_id, &self.pueue_directory, key) {
+                Ok((out, err)) => (out, err),
+                Err(err) => {
+                    error!("Failed to create child log files: {:?}", err);
+                    return;
+                }
+            };
+
+        // Feed the captured stdout of another task into the process, if requested.
+        // Otherwise pipe stdin, so users can send input to the process.
+        let (stdin, stdin_log) = match task.stdin_from {
+            Some(source_id) => {
+                let (source_path, _) = get_log_paths(source_id, &self.pueue_directory);
+                match LogFile::open(&source_path, key) {
+                    // Encrypted logs are decrypted by the daemon, while they're piped into the process.
+                    Ok(log) if log.is_encrypted() => (Ok(Stdio::piped()), Some(log)),
+                    Ok(log) => (Ok(Stdio::from(log.into_file())), None),
+                    Err(err) => (Err(err), None),
+                }
             }
+            None => (Ok(Stdio::piped()), None),
         };
 
+        // Run the task inside a fresh temporary directory, if requested.
+        let working_directory = if task.use_tempdir {
+            Builder::new()
+                .prefix(&format!("pueue_task_{}_", task_id))
+                .tempdir()
+                .map(|tempdir| {
+                    let path = tempdir.into_path();
+                    task.tempdir = Some(path.to_string_lossy().to_string());
+                    path
+                })
+        } else {
+            Ok(PathBuf::from(&task.path))
+        };
+
+        // With an output limit or encrypted logs, the output is piped through the daemon,
+        // which enforces the limit and encrypts the output.
+        // Otherwise, the process writes directly into the log files.
+        let (stdout, stderr, piped_logs) = if self.max_output_bytes.is_some() || key.is_some() {
+            (
+                Stdio::piped(),
+                Stdio::piped(),
+                Some((stdout_log, stderr_log)),
+            )
+        } else {
+            (
+                Stdio::from(stdout_log.into_file()),
+                Stdio::from(stderr_log.into_file()),
+                None,
+            )
+        };
+
+        // Find the lowest free slot index of the task's group.
+        let group = task.group.clone().unwrap_or_else(|| "default".into());
+        let used_slots: HashSet<usize> = self
+            .worker_slots
+            .values()
+            .filter(|(slot_group, _)| slot_group == &group)
+            .map(|(_, slot)| *slot)
+            .collect();
+        let worker_slot = (0..).find(|slot| !used_slots.contains(slot)).unwrap();
+        let (log_path, _) = get_log_paths(task_id, &self.pueue_directory);
+        let command_string = render_command(task, worker_slot);
+        // The setup command runs in the same shell, so it can prepare the environment.
+        // Its syntax depends on the shell, which executes the task.
+        let before = task.before.as_deref();
+        let envs = task_environment(task, &group, worker_slot, &log_path);
+
         // Spawn the actual subprocess
-        let mut command = compile_shell_command(&task.command);
+        let container_runtime = &self.container_runtime;
+        let hosts = &self.hosts;
+        let spawned_command = stdin.and_then(|stdin| {
+            let working_directory = working_directory?;
+            let mut command = if let Some(image) = &task.container {
+                compile_container_command(
+                    container_runtime,
+                    task_id,
+                    image,
+                    &posix_setup_command(before, &command_string),
+                    &working_directory,
+                    task.cpus.as_deref(),
+                    &envs,
+                )
+            } else if let Some(name) = &task.host {
+                let host = hosts.get(name).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, format!("Unknown host '{}'", name))
+                })?;
+                compile_ssh_command(host, &posix_setup_command(before, &command_string), &envs)
+            } else {
+                let line_buffered = task.line_buffered || self.line_buffered;
+                let shell = task.envs.get("SHELL").map(String::as_str).unwrap_or("sh");
+                let login_shell = task.login_shell.then_some(shell);
+                let command_string = prepend_setup_command(before, &command_string, login_shell);
+                let mut command = if task.login_shell || task.direnv || line_buffered {
+                    compile_environment_command(
+                        &command_string,
+                        login_shell,
+                        task.direnv.then_some(working_directory.as_path()),
+                        line_buffered,
+                    )
+                } else {
+                    compile_shell_command(&command_string)
+                };
+                // Tasks with a selective environment don't inherit the daemon's environment.
+                if task.env_mode != EnvironmentMode::All {
+                    command.env_clear();
+                }
+                if let Some(cpus) = &task.cpus {
+                    let cpus = parse_cpu_list(cpus)
+                        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+                    set_cpu_affinity(&mut command, &cpus)?;
+                }
+                command
+            };
 
-        let spawned_command = command
-            .current_dir(&task.path)
-            .stdin(Stdio::piped())
-            .envs(&task.envs)
-            .stdout(Stdio::from(stdout_log))
-            .stderr(Stdio::from(stderr_log))
-            .spawn();
+            command
+                .current_dir(working_directory)
+                .stdin(stdin)
+                .envs(&envs)
+                .stdout(stdout)
+                .stderr(stderr)
+                .spawn()
+        });
 
         // Check if the task managed to spawn
-        let child = match spawned_command {
+        let mut child = match spawned_command {
             Ok(child) => child,
             Err(err) => {
                 let error = format!("Failed to spawn child {} with err: {:?}", task_id, err);
                 error!("{}", error);
                 clean_log_handles(task_id, &self.pueue_directory);
+                // Nothing ran inside the temporary directory, so there's no need to keep it.
+                if let Some(tempdir) = task.tempdir.take() {
+                    if let Err(err) = remove_dir_all(&tempdir) {
+                        warn!("Failed to remove temporary directory {}: {}", tempdir, err);
+                    }
+                }
                 task.status = TaskStatus::Done;
                 task.result = Some(TaskResult::FailedToSpawn(error));
 
@@ -297,9 +990,47 @@ impl TaskHandler {
                 return;
             }
         };
+        if let Some((stdout_log, stderr_log)) = piped_logs {
+            let limit = self.max_output_bytes;
+            if let Some(stdout) = child.stdout.take() {
+                spawn_log_copy(task_id, stdout, stdout_log, limit, self.state.clone());
+            }
+            if let Some(stderr) = child.stderr.take() {
+                spawn_log_copy(task_id, stderr, stderr_log, limit, self.state.clone());
+            }
+        }
+        if let Some(mut stdin_log) = stdin_log {
+            if let Some(mut stdin) = child.stdin.take() {
+                thread::spawn(move || {
+                    if let Err(err) = io::copy(&mut stdin_log, &mut stdin) {
+                        warn!("Failed to feed the input of task {}: {}", task_id, err);
+                    }
+                });
+            }
+        }
+        task.output_truncated = false;
+        task.overdue = false;
+        task.progress = None;
+        task.expected_runtime = expected_runtime;
+
         self.children.insert(task_id, child);
+        if task.container.is_some() {
+            self.containers.insert(task_id);
+        }
         info!("Started task: {}", task.command);
 
+        if let Some(lock) = &task.lock {
+            self.held_locks.insert(lock.clone(), task_id);
+        }
+
+        self.worker_slots
+            .insert(task_id, (group.clone(), worker_slot));
+        self.last_started.insert(group.clone(), Instant::now());
+        self.start_history
+            .entry(group)
+            .or_default()
+            .push(Instant::now());
+
         task.start = Some(Local::now());
         task.status = TaskStatus::Running;
 
@@ -311,7 +1042,7 @@ impl TaskHandler {
     fn check_stashed(&mut self) {
         let mut state = self.state.lock().unwrap();
 
-        let mut changed = false;
+        let mut changed = Vec::new();
         for (_, task) in state.tasks.iter_mut() {
             if task.status != TaskStatus::Stashed {
                 continue;
@@ -323,12 +1054,115 @@ impl TaskHandler {
 
                     task.status = TaskStatus::Queued;
                     task.enqueue_at = None;
-                    changed = true;
+                    changed.push(task.id);
                 }
             }
         }
         // Save the state if a task has been enqueued
-        if changed {
+        if !changed.is_empty() {
+            for task_id in changed {
+                state.mark_changed(task_id);
+            }
+            state.save();
+        }
+    }
+
+    /// Release all queued tasks, whose path they've been waiting for exists by now.
+    fn check_wait_paths(&mut self) {
+        let mut state = self.state.lock().unwrap();
+
+        let mut changed = Vec::new();
+        for (_, task) in state.tasks.iter_mut() {
+            if task.status != TaskStatus::Queued {
+                continue;
+            }
+
+            if let Some(path) = &task.wait_for_path {
+                if Path::new(path).exists() {
+                    info!("Path {} of task {} exists", path, task.id);
+
+                    task.wait_for_path = None;
+                    changed.push(task.id);
+                }
+            }
+        }
+        if !changed.is_empty() {
+            for task_id in changed {
+                state.mark_changed(task_id);
+            }
+            state.save();
+        }
+    }
+
+    /// Periodically try to connect to the hosts of all queued tasks that wait for a host.
+    /// Tasks are released once the connection succeeds and fail, once the timeout is exceeded.
+    fn check_wait_hosts(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let interval = self.host_check_interval;
+
+        let mut changed = Vec::new();
+        for (_, task) in state.tasks.iter_mut() {
+            if task.status != TaskStatus::Queued {
+                continue;
+            }
+            let address = match &task.wait_for_host {
+                Some(address) => address.clone(),
+                None => continue,
+            };
+
+            let check = self
+                .host_checks
+                .entry(task.id)
+                .or_insert_with(|| HostCheck::new(now));
+
+            if let Some(attempt) = check.attempt.take() {
+                if !attempt.is_finished() {
+                    check.attempt = Some(attempt);
+                    continue;
+                }
+                if attempt.join().unwrap_or(false) {
+                    info!("Host {} of task {} is reachable", address, task.id);
+                    task.wait_for_host = None;
+                    changed.push(task.id);
+                    continue;
+                }
+            }
+
+            if let Some(timeout) = self.host_check_timeout {
+                if now - check.since >= timeout {
+                    let error = format!(
+                        "Host {} wasn't reachable within {}s",
+                        address,
+                        timeout.as_secs()
+                    );
+                    error!("Task {}: {}", task.id, error);
+                    task.status = TaskStatus::Done;
+                    task.result = Some(TaskResult::FailedToSpawn(error));
+                    task.end = Some(Local::now());
+                    changed.push(task.id);
+                    continue;
+                }
+            }
+
+            let due = check.last_attempt.is_none_or(|last| now - last >= interval);
+            if due {
+                check.last_attempt = Some(now);
+                check.attempt = Some(spawn_connection_attempt(address, interval));
+            }
+        }
+
+        // Forget about tasks that don't wait anymore, e.g. because they've been removed or stashed.
+        self.host_checks.retain(|id, _| {
+            state.tasks.get(id).is_some_and(|task| {
+                task.status == TaskStatus::Queued && task.wait_for_host.is_some()
+            })
+        });
+
+        if !changed.is_empty() {
+            for task_id in changed {
+                state.mark_changed(task_id);
+            }
             state.save();
         }
     }
@@ -354,15 +1188,20 @@ impl TaskHandler {
                 .children
                 .remove(task_id)
                 .expect("Child went missing while finishing up");
-
-            let exit_code = match child.wait() {
-                Ok(exit_code) => exit_code.code(),
+            self.release_locks(*task_id);
+            self.preempted.remove(task_id);
+            let terminated = self.terminating.remove(task_id).is_some();
+            self.worker_slots.remove(task_id);
+            self.containers.remove(task_id);
+
+            let (exit_code, signal) = match child.wait() {
+                Ok(exit_status) => (exit_status.code(), get_exit_signal(&exit_status)),
                 Err(err) => {
                     error!(
                         "Failed to wait for child {} while finishing up: {:?}",
                         task_id, err
                     );
-                    Some(1)
+                    (Some(1), None)
                 }
             };
 
@@ -371,36 +1210,66 @@ impl TaskHandler {
                 .get_mut(&task_id)
                 .expect("Task was removed before child process has finished!");
 
-            // Processes with exit code 0 exited successfully
-            // Processes with `None` have been killed by a Signal
-            task.result = match exit_code {
-                Some(0) => Some(TaskResult::Success),
-                Some(exit_code) => {
-                    failed_task_exists = true;
-                    Some(TaskResult::Failed(exit_code))
-                }
-                None => Some(TaskResult::Killed),
-            };
+            let result = process_result(exit_code, terminated);
+            if let TaskResult::Failed(_) = result {
+                failed_task_exists = true;
+            }
+            task.result = Some(result);
+            task.signal = signal;
 
             task.status = TaskStatus::Done;
             task.end = Some(Local::now());
+            task.artifacts = collect_artifacts(task);
+
+            // Remove the temporary directory of successful tasks.
+            // It's kept on failure for debugging and if it contains any artifacts.
+            if let Some(tempdir) = &task.tempdir {
+                let has_artifacts = task
+                    .artifacts
+                    .iter()
+                    .any(|artifact| artifact.path.starts_with(tempdir));
+                if self.clean_tempdirs && task.result == Some(TaskResult::Success) && !has_artifacts
+                {
+                    if let Err(err) = remove_dir_all(tempdir) {
+                        warn!("Failed to remove temporary directory {}: {}", tempdir, err);
+                    }
+                }
+            }
 
             // Already remove the output files, if the daemon is being reset anyway
             if self.reset {
                 clean_log_handles(*task_id, &self.pueue_directory);
             }
-            self.spawn_callback(&task);
+            self.spawn_callback(task);
+
+            let task = task.clone();
+            state.record_run(&task);
+            self.trim_successful_logs(&state, *task_id);
         }
 
         // Handle errored tasks
         // TODO: This could be be refactored. Let's try to combine finished and error handling.
         for task_id in errored.iter() {
             let _child = self.children.remove(task_id).expect("Child went missing");
-            let mut task = state.tasks.get_mut(&task_id).unwrap();
+            self.release_locks(*task_id);
+            self.preempted.remove(task_id);
+            self.terminating.remove(task_id);
+            self.worker_slots.remove(task_id);
+            self.containers.remove(task_id);
+            let mut task = state.task_mut(*task_id).unwrap();
             task.status = TaskStatus::Done;
             task.result = Some(TaskResult::Killed);
             failed_task_exists = true;
-            self.spawn_callback(&task);
+            self.spawn_callback(task);
+            self.trim_successful_logs(&state, *task_id);
+        }
+
+        // Pause groups, whose tasks failed too often in a row.
+        let mut tripped = Vec::new();
+        for task_id in finished.iter() {
+            if let Some(message) = self.check_failure_streak(&mut state, *task_id) {
+                tripped.push(message);
+            }
         }
 
         // Pause the daemon, if the settings say so and some process failed
@@ -408,7 +1277,52 @@ impl TaskHandler {
             state.running = false;
         }
 
-        state.save()
+        state.save();
+        drop(state);
+
+        for message in tripped {
+            self.spawn_notify_callback(message);
+        }
+    }
+
+    /// Count the consecutive failures of the task's group and pause the group, once it reached
+    /// its `daemon.max_consecutive_failures`. Killed tasks are neither counted nor reset the count.
+    /// Returns a notification for `daemon.notify_callback`, if the group has been paused.
+    fn check_failure_streak(&mut self, state: &mut State, task_id: usize) -> Option<NotifyMessage> {
+        let task = state.task_mut(task_id)?;
+        let group = task.group.clone();
+        let key = group.clone().unwrap_or_else(|| "default".into());
+        match task.result {
+            Some(TaskResult::Success) => {
+                self.failure_streaks.remove(&key);
+                return None;
+            }
+            Some(TaskResult::Killed) | None => return None,
+            _ => (),
+        }
+
+        let limit = *self.max_consecutive_failures.get(&key)?;
+        let streak = self.failure_streaks.entry(key.clone()).or_insert(0);
+        *streak += 1;
+        if *streak < limit {
+            return None;
+        }
+        self.failure_streaks.remove(&key);
+
+        let text = format!("Paused group {} after {} consecutive failures", key, limit);
+        warn!("{}", text);
+        task.notifications.push(Notification {
+            time: Local::now(),
+            text: text.clone(),
+        });
+        match group {
+            Some(group) => {
+                state.groups.insert(group, false);
+            }
+            None => state.running = false,
+        }
+
+        Some(NotifyMessage { task_id, text })
     }
 
     /// Gather all finished tasks and sort them by finished and errored.
@@ -456,7 +1370,15 @@ impl TaskHandler {
             Message::Kill(message) => self.kill(message),
             Message::Send(message) => self.send(message),
             Message::Reset(children) => self.reset(children),
-            Message::DaemonShutdown => self.shutdown(),
+            Message::Notify(message) => self.spawn_notify_callback(message),
+            Message::DaemonShutdown(graceful) => {
+                if graceful {
+                    info!("Shutting down as soon as all running tasks finished.");
+                    self.graceful_shutdown = true;
+                } else {
+                    self.shutdown();
+                }
+            }
             _ => info!("Received unhandled message {:?}", message),
         }
     }
@@ -467,7 +1389,12 @@ impl TaskHandler {
         match self.children.get(&id) {
             Some(child) => {
                 debug!("Executing action {:?} to {}", action, id);
-                send_signal_to_child(child, &action, children)?;
+                // Processes inside of containers are controlled via the container runtime.
+                if self.containers.contains(&id) {
+                    send_action_to_container(&self.container_runtime, id, &action)?;
+                } else {
+                    send_signal_to_child(child, &action, children)?;
+                }
 
                 Ok(true)
             }
@@ -489,6 +1416,7 @@ impl TaskHandler {
         // This is handled separately, since this can also force-spawn processes
         if !message.task_ids.is_empty() {
             for id in &message.task_ids {
+                self.preempted.remove(id);
                 // Continue all children that are simply paused
                 if self.children.contains_key(id) {
                     self.continue_task(*id, message.children);
@@ -532,9 +1460,12 @@ impl TaskHandler {
             state.task_ids_in_group_with_stati(&None, vec![TaskStatus::Paused])
         };
 
-        // Resume all specified paused tasks
+        // Resume all specified paused tasks.
+        // Preempted tasks are resumed, once there's room in their group.
         for id in keys {
-            self.continue_task(id, message.children);
+            if !self.preempted.contains(&id) {
+                self.continue_task(id, message.children);
+            }
         }
     }
 
@@ -573,6 +1504,10 @@ impl TaskHandler {
         // - The paused tasks of the default queue
         // Only pause specific tasks
         let keys: Vec<usize> = if !message.task_ids.is_empty() {
+            // Tasks that are paused by the user are no longer resumed automatically.
+            for id in message.task_ids.iter() {
+                self.preempted.remove(id);
+            }
             message.task_ids
         } else if message.all {
             // Pause all running tasks
@@ -644,7 +1579,15 @@ impl TaskHandler {
             state.set_status_for_all_groups(false);
 
             info!("Killing all running tasks");
-            self.children.keys().cloned().collect()
+            let mut task_ids: Vec<usize> = self.children.keys().cloned().collect();
+            task_ids.extend(
+                state
+                    .tasks
+                    .values()
+                    .filter(|task| task.status == TaskStatus::Running && task.worker.is_some())
+                    .map(|task| task.id),
+            );
+            task_ids
         } else if let Some(group) = &message.group {
             // Ensure that a given group exists. (Might not happen due to concurrency)
             let mut state = self.state.lock().unwrap();
@@ -668,14 +1611,60 @@ impl TaskHandler {
             state.task_ids_in_group_with_stati(&None, vec![TaskStatus::Running, TaskStatus::Paused])
         };
 
+        let grace = message.grace.unwrap_or(self.kill_grace_period);
         for task_id in task_ids {
-            self.kill_task(task_id, message.children);
+            self.kill_task(task_id, message.children, grace);
         }
     }
 
     /// Kill a specific task and handle it accordingly.
     /// Triggered on `reset` and `kill`.
-    fn kill_task(&mut self, task_id: usize, kill_children: bool) {
+    ///
+    /// With a grace period, the task only gets a SIGTERM or its stop command is executed,
+    /// so it can clean up.
+    /// It's killed with SIGKILL, if it's still running once the grace period is over.
+    fn kill_task(&mut self, task_id: usize, kill_children: bool, grace: u64) {
+        if self.kill_worker_task(task_id) {
+            return;
+        }
+        if grace > 0 && !self.terminating.contains_key(&task_id) {
+            // Processes can't be asked to exit on Windows, so they're killed right away,
+            // unless they have a stop command or run inside of a container.
+            let can_terminate = cfg!(not(windows)) || self.containers.contains(&task_id);
+            let result = if self.run_stop_command(task_id) {
+                Some(Ok(true))
+            } else if can_terminate {
+                Some(self.perform_action(task_id, ProcessAction::Terminate, kill_children))
+            } else {
+                None
+            };
+            match result {
+                Some(Ok(true)) => {
+                    // Stopped processes only handle the signal, once they continue.
+                    let paused = {
+                        let state = self.state.lock().unwrap();
+                        state
+                            .tasks
+                            .get(&task_id)
+                            .is_some_and(|task| task.status == TaskStatus::Paused)
+                    };
+                    if paused {
+                        self.continue_task(task_id, kill_children);
+                    }
+                    let deadline = Instant::now() + Duration::from_secs(grace);
+                    self.terminating.insert(task_id, (deadline, kill_children));
+                    return;
+                }
+                Some(Ok(false)) => return,
+                Some(Err(err)) => warn!(
+                    "Failed to terminate task {}, killing it instead: {:?}",
+                    task_id, err
+                ),
+                None => (),
+            }
+        }
+
+        self.kill_container(task_id);
         if let Some(mut child) = self.children.get_mut(&task_id) {
             kill_child(task_id, &mut child, kill_children);
         } else {
@@ -683,6 +1672,123 @@ impl TaskHandler {
         }
     }
 
+    /// Execute the stop command of a task, if it has one.
+    /// Returns `false`, if there's no stop command or it couldn't be spawned.
+    fn run_stop_command(&mut self, task_id: usize) -> bool {
+        let pid = match self.children.get(&task_id) {
+            Some(child) => child.id(),
+            None => return false,
+        };
+        let mut command = {
+            let state = self.state.lock().unwrap();
+            let task = match state.tasks.get(&task_id) {
+                Some(task) => task,
+                None => return false,
+            };
+            let stop_command = match &task.stop_command {
+                Some(stop_command) => stop_command,
+                None => return false,
+            };
+
+            let mut command = compile_shell_command(stop_command);
+            command
+                .current_dir(&task.path)
+                .envs(&task.envs)
+                .env("PUEUE_TASK_ID", task_id.to_string())
+                .env("PUEUE_PID", pid.to_string());
+            command
+        };
+
+        match command.spawn() {
+            Ok(child) => {
+                debug!("Spawned stop command for task {}", task_id);
+                self.callbacks.push(child);
+                true
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to spawn stop command of task {}, sending SIGTERM instead: {}",
+                    task_id, err
+                );
+                false
+            }
+        }
+    }
+
+    /// Kill all tasks with SIGKILL, which didn't exit within their grace period.
+    fn check_kill_deadlines(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<(usize, bool)> = self
+            .terminating
+            .iter()
+            .filter(|(_, (deadline, _))| *deadline <= now)
+            .map(|(task_id, (_, kill_children))| (*task_id, *kill_children))
+            .collect();
+
+        for (task_id, kill_children) in expired {
+            self.terminating.remove(&task_id);
+            info!(
+                "Task {} didn't exit within its grace period, killing it",
+                task_id
+            );
+            self.kill_task(task_id, kill_children, 0);
+        }
+    }
+
+    /// Move all failed tasks into the quarantine group, see `daemon.quarantine_group`.
+    fn check_quarantine(&mut self) {
+        let quarantine_group = match &self.quarantine_group {
+            Some(group) => group.clone(),
+            None => return,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let failed: Vec<usize> = state
+            .tasks
+            .iter()
+            .filter(|(_, task)| {
+                task.status == TaskStatus::Done
+                    && task.failed()
+                    && !task.quarantined
+                    && task.group.as_ref() != Some(&quarantine_group)
+            })
+            .map(|(task_id, _)| *task_id)
+            .collect();
+        if failed.is_empty() {
+            return;
+        }
+
+        if !state.groups.contains_key(&quarantine_group) {
+            if let Err(err) = state.create_group(&quarantine_group) {
+                error!("Failed to create quarantine group: {}", err);
+                return;
+            }
+        }
+        for task_id in failed {
+            let task = state.task_mut(task_id).unwrap();
+            info!(
+                "Moving failed task {} into group {}",
+                task_id, quarantine_group
+            );
+            task.original_group = task.group.replace(quarantine_group.clone());
+            task.quarantined = true;
+        }
+        state.save();
+    }
+
+    /// Kill the container of a task, if it's executed inside of one.
+    /// Killing the runtime's client process alone might leave the container running.
+    fn kill_container(&self, task_id: usize) {
+        if !self.containers.contains(&task_id) {
+            return;
+        }
+        if let Err(err) =
+            send_action_to_container(&self.container_runtime, task_id, &ProcessAction::Kill)
+        {
+            warn!("{}", err);
+        }
+    }
+
     /// Send some input to a child process.
     fn send(&mut self, message: SendMessage) {
         let task_id = message.task_id;
@@ -698,7 +1804,14 @@ impl TaskHandler {
             }
         };
         {
-            let child_stdin = child.stdin.as_mut().unwrap();
+            // Tasks that read their input from another task's output don't have a stdin pipe.
+            let child_stdin = match child.stdin.as_mut() {
+                Some(child_stdin) => child_stdin,
+                None => {
+                    warn!("Task {} doesn't accept any input: {}", task_id, input);
+                    return;
+                }
+            };
             if let Err(err) = child_stdin.write_all(&input.clone().into_bytes()) {
                 warn!(
                     "Failed to send input to task {} with err {:?}: {}",
@@ -708,6 +1821,79 @@ impl TaskHandler {
         }
     }
 
+    /// Tasks of remote workers don't have a local process, so they're only marked as killed.
+    /// The worker notices with its next heartbeat and kills the process on its machine.
+    /// Returns `true`, if the task is executed by a remote worker.
+    fn kill_worker_task(&mut self, task_id: usize) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let task = match state.task_mut(task_id) {
+            Some(task) if task.status == TaskStatus::Running && task.worker.is_some() => task,
+            _ => return false,
+        };
+
+        info!("Killing task {} of a remote worker", task_id);
+        task.status = TaskStatus::Done;
+        task.result = Some(TaskResult::Killed);
+        task.end = Some(Local::now());
+        state.worker_heartbeats.remove(&task_id);
+        state.save();
+
+        true
+    }
+
+    /// Fail the tasks of remote workers, which didn't send a heartbeat within `daemon.worker_timeout`.
+    /// Their worker most likely went away, so they'd be running forever otherwise.
+    fn check_worker_heartbeats(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let orphaned: Vec<usize> = state
+            .tasks
+            .values()
+            .filter(|task| task.status == TaskStatus::Running && task.worker.is_some())
+            .filter(|task| {
+                state
+                    .worker_heartbeats
+                    .get(&task.id)
+                    .is_none_or(|heartbeat| now - *heartbeat >= self.worker_timeout)
+            })
+            .map(|task| task.id)
+            .collect();
+        if orphaned.is_empty() {
+            return;
+        }
+
+        for task_id in orphaned {
+            state.worker_heartbeats.remove(&task_id);
+            let task = state.task_mut(task_id).unwrap();
+            let worker = task.worker.clone().unwrap_or_default();
+            warn!(
+                "Worker {} of task {} didn't send a heartbeat in time",
+                worker, task_id
+            );
+            task.status = TaskStatus::Done;
+            task.result = Some(TaskResult::Killed);
+            task.end = Some(Local::now());
+
+            // Let the user know, why the task has been killed.
+            // The output, which the worker sent with its heartbeats, is kept.
+            let message = format!(
+                "Worker {} stopped sending heartbeats while executing this task\n",
+                worker
+            );
+            let written = append_to_log_files(
+                task_id,
+                &self.pueue_directory,
+                self.log_key.as_ref(),
+                b"",
+                message.as_bytes(),
+            );
+            if let Err(err) = written {
+                warn!("Failed to write log of task {}: {}", task_id, err);
+            }
+        }
+        state.save();
+    }
+
     /// Kill all children by reusing the `kill` function.
     /// Set the `reset` flag, which will prevent new tasks from being spawned.
     /// If all children finished, the state will be completely reset.
@@ -732,13 +1918,14 @@ impl TaskHandler {
             return;
         };
 
-        // Build the callback command from the given template.
-        let mut handlebars = Handlebars::new();
-        handlebars.set_strict_mode(true);
         // Build templating variables.
         let mut parameters = HashMap::new();
         parameters.insert("id", task.id.to_string());
-        parameters.insert("command", task.command.clone());
+        // Callbacks often end up in notifications or chat messages, which shouldn't contain secrets.
+        parameters.insert(
+            "command",
+            redact_command(&task.command, &task.envs, &self.redacted_envs),
+        );
         parameters.insert("path", task.path.clone());
         parameters.insert("result", task.result.clone().unwrap().to_string());
 
@@ -755,7 +1942,141 @@ impl TaskHandler {
         } else {
             parameters.insert("group", "default".into());
         }
-        let callback_command = match handlebars.render_template(&callback, &parameters) {
+
+        let callback = callback.clone();
+        self.spawn_templated_callback(&callback, &parameters, task.id);
+    }
+
+    /// Only keep the last lines of the logs of successful tasks, see `daemon.successful_log_lines`.
+    /// Tasks that read the output of a task via `--stdin-from` need its whole log,
+    /// so it's only trimmed, once the last of them finished.
+    fn trim_successful_logs(&self, state: &State, task_id: usize) {
+        let lines = match self.successful_log_lines {
+            Some(lines) if !self.reset => lines,
+            _ => return,
+        };
+
+        let source = state.tasks.get(&task_id).and_then(|task| task.stdin_from);
+        for id in std::iter::once(task_id).chain(source) {
+            let successful = state
+                .tasks
+                .get(&id)
+                .is_some_and(|task| task.result == Some(TaskResult::Success));
+            if !successful || state.has_stdin_readers(id) {
+                continue;
+            }
+            if let Err(err) =
+                trim_log_files(id, &self.pueue_directory, self.log_key.as_ref(), lines)
+            {
+                warn!("Failed to trim logs of task {}: {}", id, err);
+            }
+        }
+    }
+
+    /// Run the `on_exit` command of all tasks that reached their final state since the last check.
+    /// This covers every way a task can end, be it by exiting, failing to spawn or due to its
+    /// dependencies, including tasks that have been finished by remote workers or the user.
+    fn check_done_tasks(&mut self) {
+        let finished: Vec<Task> = {
+            let state = self.state.lock().unwrap();
+            // Forget tasks that have been removed or restarted in the meantime.
+            self.done.retain(|id| {
+                state
+                    .tasks
+                    .get(id)
+                    .is_some_and(|task| task.status == TaskStatus::Done)
+            });
+            state
+                .tasks
+                .values()
+                .filter(|task| task.status == TaskStatus::Done && !self.done.contains(&task.id))
+                .cloned()
+                .collect()
+        };
+
+        for task in finished {
+            self.done.insert(task.id);
+            self.spawn_on_exit(&task);
+        }
+    }
+
+    /// Execute the `on_exit` command of a finished task or the default of its group.
+    fn spawn_on_exit(&mut self, task: &Task) {
+        let group = task.group.as_deref().unwrap_or("default");
+        let on_exit = match task.on_exit.as_ref().or_else(|| self.on_exit.get(group)) {
+            Some(on_exit) => on_exit,
+            None => return,
+        };
+
+        let exit_code = match task.result {
+            Some(TaskResult::Success) => "0".to_string(),
+            Some(TaskResult::Failed(code)) => code.to_string(),
+            _ => String::new(),
+        };
+        let result = task
+            .result
+            .as_ref()
+            .map(|result| result.to_string())
+            .unwrap_or_default();
+
+        let mut command = compile_shell_command(on_exit);
+        command
+            .current_dir(&task.path)
+            .envs(&task.envs)
+            .env("PUEUE_TASK_ID", task.id.to_string())
+            .env("PUEUE_GROUP", group)
+            .env("PUEUE_RESULT", result)
+            .env("PUEUE_EXIT_CODE", exit_code);
+        match command.spawn() {
+            Ok(child) => {
+                debug!("Spawned on exit command for task {}", task.id);
+                self.callbacks.push(child);
+            }
+            Err(err) => error!(
+                "Failed to spawn on exit command of task {}: {}",
+                task.id, err
+            ),
+        }
+    }
+
+    /// Users can specify a callback that's fired whenever a task sends a message via `pueue notify`.
+    fn spawn_notify_callback(&mut self, message: NotifyMessage) {
+        let callback = match &self.notify_callback {
+            Some(callback) => callback.clone(),
+            None => return,
+        };
+
+        let state = self.state.lock().unwrap();
+        let task = match state.tasks.get(&message.task_id) {
+            Some(task) => task,
+            None => return,
+        };
+        let mut parameters = HashMap::new();
+        parameters.insert("id", task.id.to_string());
+        parameters.insert("message", message.text);
+        parameters.insert(
+            "command",
+            redact_command(&task.command, &task.envs, &self.redacted_envs),
+        );
+        parameters.insert(
+            "group",
+            task.group.clone().unwrap_or_else(|| "default".into()),
+        );
+        drop(state);
+
+        self.spawn_templated_callback(&callback, &parameters, message.task_id);
+    }
+
+    /// Render a callback template and spawn the resulting command.
+    fn spawn_templated_callback(
+        &mut self,
+        template: &str,
+        parameters: &HashMap<&str, String>,
+        task_id: usize,
+    ) {
+        let mut handlebars = Handlebars::new();
+        handlebars.set_strict_mode(true);
+        let callback_command = match handlebars.render_template(template, parameters) {
             Ok(callback_command) => callback_command,
             Err(err) => {
                 error!(
@@ -778,10 +2099,84 @@ impl TaskHandler {
             Ok(child) => child,
         };
 
-        debug!("Spawned callback for task {}", task.id);
+        debug!("Spawned callback for task {}", task_id);
         self.callbacks.push(child);
     }
 
+    /// Fire the `drained_callback`, once no task of any group is queued or running anymore.
+    fn check_queue_drained(&mut self) {
+        let busy = {
+            let state = self.state.lock().unwrap();
+            state.tasks.values().any(|task| task.is_pending())
+        };
+        let drained = self.queue_busy && !busy;
+        self.queue_busy = busy;
+
+        let callback = match &self.drained_callback {
+            Some(callback) if drained => callback,
+            _ => return,
+        };
+
+        info!("The queue has been drained");
+        match compile_shell_command(callback).spawn() {
+            Ok(child) => self.callbacks.push(child),
+            Err(error) => error!("Failed to spawn drained callback with error: {}", error),
+        }
+    }
+
+    /// Flag running tasks, which run longer than `overdue_factor` times their expected runtime.
+    /// Each task is only flagged once per run and gets a notification, which fires the notify callback.
+    fn check_overdue_tasks(&mut self) {
+        let factor = match self.overdue_factor {
+            Some(factor) => factor,
+            None => return,
+        };
+
+        let mut overdue = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            let now = Local::now();
+            for task in state.tasks.values_mut() {
+                let (expected, start) = match (task.expected_runtime, task.start) {
+                    (Some(expected), Some(start)) => (expected, start),
+                    _ => continue,
+                };
+                if task.status != TaskStatus::Running || task.overdue {
+                    continue;
+                }
+                let elapsed = (now - start).num_seconds();
+                if (elapsed as f64) <= expected as f64 * factor {
+                    continue;
+                }
+
+                let text = format!(
+                    "Task has been running for {}s, which is more than {} times its expected runtime of {}s",
+                    elapsed, factor, expected
+                );
+                warn!("Task {}: {}", task.id, text);
+                task.overdue = true;
+                task.notifications.push(Notification {
+                    time: now,
+                    text: text.clone(),
+                });
+                overdue.push(NotifyMessage {
+                    task_id: task.id,
+                    text,
+                });
+            }
+            if !overdue.is_empty() {
+                for message in overdue.iter() {
+                    state.mark_changed(message.task_id);
+                }
+                state.save();
+            }
+        }
+
+        for message in overdue {
+            self.spawn_notify_callback(message);
+        }
+    }
+
     /// Look at all running callbacks and log any errors.
     /// If everything went smoothly, simply remove them from the list.
     fn check_callbacks(&mut self) {
@@ -817,6 +2212,7 @@ impl TaskHandler {
 
         let task_ids: Vec<usize> = self.children.keys().cloned().collect();
         for task_id in task_ids {
+            self.kill_container(task_id);
             let child = self.children.remove(&task_id);
 
             if let Some(mut child) = child {
@@ -828,6 +2224,488 @@ impl TaskHandler {
         }
 
         // Exit pueued
+        cleanup_pid_file(&self.pueue_directory);
+        #[cfg(windows)]
+        crate::platform::service::report_stopped();
         std::process::exit(0)
     }
 }
+
+/// Determine the result of a finished process.
+/// Processes with exit code 0 exited successfully, processes without one have been killed by a signal.
+/// Tasks that exited after a SIGTERM or their stop command have been stopped deliberately,
+/// so they count as killed, no matter how they exited.
+fn process_result(exit_code: Option<i32>, terminated: bool) -> TaskResult {
+    match exit_code {
+        _ if terminated => TaskResult::Killed,
+        Some(0) => TaskResult::Success,
+        Some(exit_code) => TaskResult::Failed(exit_code),
+        None => TaskResult::Killed,
+    }
+}
+
+/// The environment of a task's process, i.e. the task's own environment and Pueue's variables.
+/// Containers don't inherit it from the runtime, so it has to be passed on explicitly.
+pub fn task_environment(
+    task: &Task,
+    group: &str,
+    worker_slot: usize,
+    log_path: &Path,
+) -> BTreeMap<String, String> {
+    let mut envs: BTreeMap<String, String> = task
+        .envs
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    envs.insert("PUEUE_TASK_ID".into(), task.id.to_string());
+    envs.insert("PUEUE_GROUP".into(), group.to_string());
+    envs.insert("PUEUE_WORKER".into(), worker_slot.to_string());
+    envs.insert(
+        "PUEUE_LOG_PATH".into(),
+        log_path.to_string_lossy().to_string(),
+    );
+    if let Some(index) = task.array_index {
+        envs.insert("PUEUE_ARRAY_INDEX".into(), index.to_string());
+    }
+    // This overwrites any old value of the task's environment.
+    if !task.assigned_gpus.is_empty() {
+        let gpus: Vec<String> = task
+            .assigned_gpus
+            .iter()
+            .map(|gpu| gpu.to_string())
+            .collect();
+        envs.insert("CUDA_VISIBLE_DEVICES".into(), gpus.join(","));
+    }
+
+    envs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+
+    use tempfile::TempDir;
+
+    use pueue::settings::Settings;
+
+    use super::*;
+
+    /// A task handler with the given `daemon` settings, whose pueue directory is temporary.
+    /// The settings are given as indented YAML lines.
+    fn test_handler(daemon_config: &str) -> (TaskHandler, TempDir) {
+        let directory = tempfile::tempdir().unwrap();
+        fs::create_dir(directory.path().join("task_logs")).unwrap();
+        let config_path = directory.path().join("pueue.yml");
+        let config = format!(
+            "shared:\n  pueue_directory: {}\ndaemon:\n  pause_on_failure: false\n{}",
+            directory.path().to_string_lossy(),
+            daemon_config
+        );
+        fs::write(&config_path, config).unwrap();
+
+        let settings = Settings::new(true, &Some(config_path)).unwrap();
+        let state = Arc::new(Mutex::new(State::new(&settings, None)));
+        let (_sender, receiver) = channel();
+
+        (TaskHandler::new(state, receiver), directory)
+    }
+
+    /// Add a task with the given status to the state of the task handler.
+    fn add_task(handler: &TaskHandler, status: TaskStatus, setup: impl FnOnce(&mut Task)) -> usize {
+        let mut task = Task::new(
+            "sleep 60".into(),
+            "/tmp".into(),
+            HashMap::new(),
+            None,
+            status,
+            None,
+            Vec::new(),
+        );
+        setup(&mut task);
+        handler.state.lock().unwrap().add_task(task)
+    }
+
+    /// Modify a task inside the state of the task handler.
+    fn update_task(handler: &TaskHandler, task_id: usize, update: impl FnOnce(&mut Task)) {
+        update(handler.state.lock().unwrap().task_mut(task_id).unwrap());
+    }
+
+    #[test]
+    fn test_trim_logs_after_stdin_readers() {
+        let (handler, directory) = test_handler("  successful_log_lines: 1\n");
+        let pueue_directory = directory.path().to_string_lossy().to_string();
+        let source = add_task(&handler, TaskStatus::Done, |task| {
+            task.result = Some(TaskResult::Success)
+        });
+        let reader = add_task(&handler, TaskStatus::Queued, |task| {
+            task.stdin_from = Some(source)
+        });
+        let (stdout, _) = get_log_paths(source, &pueue_directory);
+        let (reader_stdout, _) = get_log_paths(reader, &pueue_directory);
+        fs::write(&stdout, "1\n2\n3\n").unwrap();
+        fs::write(&reader_stdout, "output\n").unwrap();
+
+        // The reader still needs the whole output of the source.
+        let mut state = handler.state.lock().unwrap();
+        handler.trim_successful_logs(&state, source);
+        assert_eq!(fs::read_to_string(&stdout).unwrap(), "1\n2\n3\n");
+
+        // Once the reader finished, the source is trimmed.
+        let task = state.task_mut(reader).unwrap();
+        task.status = TaskStatus::Done;
+        task.result = Some(TaskResult::Failed(1));
+        handler.trim_successful_logs(&state, reader);
+        assert_eq!(
+            fs::read_to_string(&stdout).unwrap(),
+            "[pueue] 2 lines trimmed\n3\n"
+        );
+        // Only successful tasks are trimmed.
+        assert_eq!(fs::read_to_string(&reader_stdout).unwrap(), "output\n");
+    }
+
+    #[test]
+    fn test_revisions_contain_changed_tasks() {
+        let (mut handler, _directory) = test_handler("");
+        let failed = add_task(&handler, TaskStatus::Done, |task| {
+            task.result = Some(TaskResult::Failed(1))
+        });
+        let dependant = add_task(&handler, TaskStatus::Queued, |task| {
+            task.dependencies = vec![failed]
+        });
+        let revision = {
+            let mut state = handler.state.lock().unwrap();
+            let revision = state.revision;
+            // Saving without any changes doesn't create a new revision.
+            state.save();
+            assert_eq!(state.revision, revision);
+            revision
+        };
+
+        handler.check_failed_dependencies();
+        let mut state = handler.state.lock().unwrap();
+        assert_eq!(state.revision, revision + 1);
+        let delta = state.changes_since(revision).unwrap();
+        assert_eq!(delta.changed.keys().collect::<Vec<_>>(), vec![&dependant]);
+
+        // Changes that aren't saved right away are part of the next revision.
+        state.set_enqueue_at(failed, Some(Local::now()));
+        state.track_changes();
+        let delta = state.changes_since(revision + 1).unwrap();
+        assert_eq!(delta.changed.keys().collect::<Vec<_>>(), vec![&failed]);
+        assert!(delta.removed.is_empty());
+
+        state.remove_task(dependant);
+        state.save();
+        let delta = state.changes_since(revision + 2).unwrap();
+        assert!(delta.changed.is_empty());
+        assert_eq!(delta.removed, vec![dependant]);
+    }
+
+    #[test]
+    fn test_queue_limit() {
+        let (handler, _directory) = test_handler("  max_queued:\n    default: 2\n");
+        add_task(&handler, TaskStatus::Queued, |_| ());
+        add_task(&handler, TaskStatus::Done, |_| ());
+        {
+            let state = handler.state.lock().unwrap();
+            assert_eq!(state.free_queue_slots(&None), Some(1));
+            assert!(!state.queue_is_full(&None));
+        }
+
+        // Stashed tasks count towards the limit as well, finished ones don't.
+        add_task(&handler, TaskStatus::Stashed, |_| ());
+        let state = handler.state.lock().unwrap();
+        assert_eq!(state.free_queue_slots(&None), Some(0));
+        assert!(state.queue_is_full(&None));
+        // Groups without a limit are never full.
+        assert_eq!(state.free_queue_slots(&Some("other".into())), None);
+    }
+
+    #[test]
+    fn test_unique_key_coalescing() {
+        let (handler, _directory) = test_handler("");
+        add_task(&handler, TaskStatus::Done, |task| {
+            task.unique_key = Some("build".into())
+        });
+        let queued = add_task(&handler, TaskStatus::Queued, |task| {
+            task.unique_key = Some("build".into())
+        });
+        add_task(&handler, TaskStatus::Queued, |_| ());
+
+        // Only pending tasks are coalesced, finished ones don't count.
+        let mut state = handler.state.lock().unwrap();
+        assert_eq!(state.queued_task_with_key("build"), Some(queued));
+        assert_eq!(state.queued_task_with_key("test"), None);
+
+        // Replacing a duplicate keeps its id.
+        let mut task = state.tasks[&queued].clone();
+        task.command = "make all".into();
+        state.replace_task(queued, task);
+        assert_eq!(state.tasks[&queued].command, "make all");
+        assert_eq!(state.tasks[&queued].id, queued);
+        assert_eq!(state.tasks.len(), 3);
+    }
+
+    #[test]
+    fn test_start_rate_limit() {
+        let (mut handler, _directory) = test_handler("  max_starts_per_minute:\n    default: 2\n");
+        let first = add_task(&handler, TaskStatus::Queued, |_| ());
+        assert_eq!(handler.get_next_task_id(), Some(first));
+
+        // Two tasks have been started during the last minute.
+        let now = Instant::now();
+        handler
+            .start_history
+            .insert("default".into(), vec![now, now]);
+        assert_eq!(handler.get_next_task_id(), None);
+
+        // Starts that happened more than a minute ago don't count anymore.
+        let earlier = now - Duration::from_secs(61);
+        handler
+            .start_history
+            .insert("default".into(), vec![earlier, now]);
+        assert_eq!(handler.get_next_task_id(), Some(first));
+    }
+
+    #[test]
+    fn test_resource_slots() {
+        let (mut handler, _directory) =
+            test_handler("  default_parallel_tasks: 1\n  slots:\n    default: 4\n");
+        let running = add_task(&handler, TaskStatus::Running, |task| task.slots = 3);
+        let large = add_task(&handler, TaskStatus::Queued, |task| task.slots = 2);
+        let small = add_task(&handler, TaskStatus::Queued, |task| task.slots = 1);
+
+        // The slot pool replaces the parallel limit of the group.
+        assert_eq!(handler.get_next_task_id(), Some(small));
+
+        handler.state.lock().unwrap().tasks.remove(&small);
+        assert_eq!(handler.get_next_task_id(), None);
+
+        handler.state.lock().unwrap().tasks.remove(&running);
+        assert_eq!(handler.get_next_task_id(), Some(large));
+    }
+
+    #[test]
+    fn test_named_locks() {
+        let (mut handler, _directory) = test_handler("  default_parallel_tasks: 3\n");
+        let holder = add_task(&handler, TaskStatus::Running, |task| {
+            task.lock = Some("db".into())
+        });
+        let waiting = add_task(&handler, TaskStatus::Queued, |task| {
+            task.lock = Some("db".into())
+        });
+        let other = add_task(&handler, TaskStatus::Queued, |_| ());
+        handler.held_locks.insert("db".into(), holder);
+
+        // Tasks without the lock overtake the one that waits for it.
+        assert_eq!(handler.get_next_task_id(), Some(other));
+        assert!(!handler.is_lock_held(&handler.state.lock().unwrap().tasks[&holder]));
+
+        handler.release_locks(holder);
+        assert_eq!(handler.get_next_task_id(), Some(waiting));
+    }
+
+    #[test]
+    fn test_priority_preemption() {
+        let (mut handler, _directory) =
+            test_handler("  default_parallel_tasks: 2\n  preemption: true\n");
+        let now = Local::now();
+        let low = add_task(&handler, TaskStatus::Running, |task| {
+            task.start = Some(now - chrono::Duration::minutes(1))
+        });
+        let recent = add_task(&handler, TaskStatus::Running, |task| task.start = Some(now));
+        let high = add_task(&handler, TaskStatus::Queued, |task| task.priority = 1);
+        // Only processes of this daemon can be preempted.
+        for id in [low, recent] {
+            let child = compile_shell_command("sleep 60").spawn().unwrap();
+            handler.children.insert(id, child);
+        }
+
+        // The most recently started task is preempted first.
+        assert_eq!(handler.get_next_task_id(), None);
+        assert_eq!(handler.preemption_victims(), vec![recent]);
+
+        // Tasks with a lower priority are preempted first, tasks with the same one never.
+        update_task(&handler, recent, |task| task.priority = 1);
+        assert_eq!(handler.preemption_victims(), vec![low]);
+        update_task(&handler, low, |task| task.priority = 1);
+        assert!(handler.preemption_victims().is_empty());
+
+        // Once there's room, the task with the highest priority is picked.
+        let lower = add_task(&handler, TaskStatus::Queued, |_| ());
+        update_task(&handler, recent, |task| task.status = TaskStatus::Done);
+        assert_eq!(handler.get_next_task_id(), Some(high));
+        update_task(&handler, high, |task| task.status = TaskStatus::Done);
+        assert_eq!(handler.get_next_task_id(), Some(lower));
+
+        for child in handler.children.values_mut() {
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_fair_share() {
+        let (mut handler, _directory) =
+            test_handler("  max_parallel_tasks: 2\n  groups:\n    a: 2\n    b: 2\n");
+        let in_group = |group: &str| {
+            let group = Some(group.to_string());
+            move |task: &mut Task| task.group = group
+        };
+        add_task(&handler, TaskStatus::Running, in_group("a"));
+        let queued_a = add_task(&handler, TaskStatus::Queued, in_group("a"));
+        let queued_b = add_task(&handler, TaskStatus::Queued, in_group("b"));
+
+        // Group `a` started a task more recently, so it's `b`'s turn.
+        handler.last_started.insert("a".into(), Instant::now());
+        assert_eq!(handler.get_next_task_id(), Some(queued_b));
+        handler.last_started.insert("b".into(), Instant::now());
+        assert_eq!(handler.get_next_task_id(), Some(queued_a));
+
+        // The global cap applies across all groups.
+        update_task(&handler, queued_b, |task| task.status = TaskStatus::Running);
+        assert_eq!(handler.get_next_task_id(), None);
+    }
+
+    #[test]
+    fn test_gpu_slots() {
+        let (mut handler, _directory) =
+            test_handler("  default_parallel_tasks: 3\n  gpus: [0, 1, 2]\n");
+        let running = add_task(&handler, TaskStatus::Running, |task| {
+            task.gpus = 1;
+            task.assigned_gpus = vec![1];
+        });
+        let large = add_task(&handler, TaskStatus::Queued, |task| task.gpus = 3);
+        let small = add_task(&handler, TaskStatus::Queued, |task| task.gpus = 2);
+
+        // Tasks only start, if enough GPUs are free.
+        assert_eq!(
+            handler.free_gpus(&handler.state.lock().unwrap()),
+            vec![0, 2]
+        );
+        assert_eq!(handler.get_next_task_id(), Some(small));
+
+        handler.state.lock().unwrap().tasks.remove(&small);
+        assert_eq!(handler.get_next_task_id(), None);
+        handler.state.lock().unwrap().tasks.remove(&running);
+        assert_eq!(handler.get_next_task_id(), Some(large));
+    }
+
+    #[test]
+    fn test_any_dependencies() {
+        let (mut handler, _directory) = test_handler("  default_parallel_tasks: 3\n");
+        let failed = add_task(&handler, TaskStatus::Done, |task| {
+            task.result = Some(TaskResult::Failed(1))
+        });
+        let succeeded = add_task(&handler, TaskStatus::Done, |task| {
+            task.result = Some(TaskResult::Success)
+        });
+        let running = add_task(&handler, TaskStatus::Running, |_| ());
+        let waiting = add_task(&handler, TaskStatus::Queued, |task| {
+            task.any_dependencies = vec![failed, running]
+        });
+        let ready = add_task(&handler, TaskStatus::Queued, |task| {
+            task.any_dependencies = vec![failed, succeeded]
+        });
+
+        // A single successful dependency is enough.
+        handler.check_failed_dependencies();
+        assert_eq!(handler.get_next_task_id(), Some(ready));
+        update_task(&handler, ready, |task| task.status = TaskStatus::Running);
+        assert_eq!(handler.get_next_task_id(), None);
+
+        // Once all of them failed, the task fails as well.
+        update_task(&handler, running, |task| {
+            task.status = TaskStatus::Done;
+            task.result = Some(TaskResult::Failed(1));
+        });
+        handler.check_failed_dependencies();
+        let state = handler.state.lock().unwrap();
+        assert_eq!(state.tasks[&waiting].status, TaskStatus::Done);
+        assert_eq!(
+            state.tasks[&waiting].result,
+            Some(TaskResult::DependencyFailed)
+        );
+    }
+
+    #[test]
+    fn test_failure_handlers() {
+        let (mut handler, _directory) = test_handler("  default_parallel_tasks: 2\n");
+        let failed = add_task(&handler, TaskStatus::Done, |task| {
+            task.result = Some(TaskResult::Failed(1))
+        });
+        let succeeded = add_task(&handler, TaskStatus::Done, |task| {
+            task.result = Some(TaskResult::Success)
+        });
+        let running = add_task(&handler, TaskStatus::Running, |_| ());
+        let skipped = add_task(&handler, TaskStatus::Queued, |task| {
+            task.failure_dependencies = vec![succeeded, running]
+        });
+        let handling = add_task(&handler, TaskStatus::Queued, |task| {
+            task.failure_dependencies = vec![succeeded, failed]
+        });
+
+        // Failure handlers run, as soon as any of their tasks failed.
+        handler.check_failed_dependencies();
+        assert_eq!(handler.get_next_task_id(), Some(handling));
+        assert_eq!(
+            handler.state.lock().unwrap().tasks[&skipped].status,
+            TaskStatus::Queued
+        );
+
+        // They're skipped, once all of their tasks succeeded.
+        update_task(&handler, running, |task| {
+            task.status = TaskStatus::Done;
+            task.result = Some(TaskResult::Success);
+        });
+        handler.check_failed_dependencies();
+        let state = handler.state.lock().unwrap();
+        assert_eq!(state.tasks[&skipped].result, Some(TaskResult::Skipped));
+        assert_eq!(state.tasks[&handling].status, TaskStatus::Queued);
+    }
+
+    #[test]
+    fn test_dependency_timeout() {
+        let (mut handler, _directory) = test_handler("");
+        let running = add_task(&handler, TaskStatus::Running, |_| ());
+        let created_at = Local::now() - chrono::Duration::seconds(120);
+        let timed_out = add_task(&handler, TaskStatus::Queued, |task| {
+            task.dependencies = vec![running];
+            task.dependency_timeout = Some(60);
+            task.created_at = created_at;
+        });
+        let patient = add_task(&handler, TaskStatus::Queued, |task| {
+            task.dependencies = vec![running];
+            task.dependency_timeout = Some(300);
+            task.created_at = created_at;
+        });
+        let fulfilled = add_task(&handler, TaskStatus::Queued, |task| {
+            task.dependency_timeout = Some(60);
+            task.created_at = created_at;
+        });
+
+        // Only tasks, whose dependencies are still unfinished, time out.
+        handler.check_failed_dependencies();
+        let state = handler.state.lock().unwrap();
+        assert_eq!(
+            state.tasks[&timed_out].result,
+            Some(TaskResult::DependencyTimeout)
+        );
+        assert_eq!(state.tasks[&patient].status, TaskStatus::Queued);
+        assert_eq!(state.tasks[&fulfilled].status, TaskStatus::Queued);
+    }
+
+    #[test]
+    fn test_process_result() {
+        assert_eq!(process_result(Some(0), false), TaskResult::Success);
+        assert_eq!(process_result(Some(3), false), TaskResult::Failed(3));
+        assert_eq!(process_result(None, false), TaskResult::Killed);
+
+        // Tasks that handled the SIGTERM of `kill` have been killed, even if they exited cleanly.
+        assert_eq!(process_result(Some(0), true), TaskResult::Killed);
+        assert_eq!(process_result(Some(143), true), TaskResult::Killed);
+        assert_eq!(process_result(None, true), TaskResult::Killed);
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use chrono::prelude::*;
+use handlebars::{no_escape, Handlebars};
+use log::warn;
+
+use pueue::task::Task;
+
+/// Expand the template variables inside of a task's command right before it's started,
+/// e.g. `ffmpeg -i in.mp4 out_{{task_id}}_{{date}}.mkv`.
+///
+/// Supported variables are `task_id`, `group`, `label`, `worker`, `date`, `time` and `timestamp`.
+/// Commands that use unknown variables or aren't valid templates are executed as they are,
+/// since they might simply contain curly braces for other reasons.
+pub fn render_command(task: &Task, worker_slot: usize) -> String {
+    if !task.command.contains("{{") {
+        return task.command.clone();
+    }
+
+    let now = Local::now();
+    let mut parameters = HashMap::new();
+    parameters.insert("task_id", task.id.to_string());
+    parameters.insert(
+        "group",
+        task.group.clone().unwrap_or_else(|| "default".into()),
+    );
+    parameters.insert("label", task.label.clone().unwrap_or_default());
+    parameters.insert("worker", worker_slot.to_string());
+    parameters.insert("date", now.format("%Y-%m-%d").to_string());
+    parameters.insert("time", now.format("%H-%M-%S").to_string());
+    parameters.insert("timestamp", now.timestamp().to_string());
+
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    // The command is executed by a shell, not displayed in a browser.
+    handlebars.register_escape_fn(no_escape);
+    match handlebars.render_template(&task.command, &parameters) {
+        Ok(command) => command,
+        Err(error) => {
+            warn!(
+                "Executing command of task {} without expanding variables: {}",
+                task.id, error
+            );
+            task.command.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pueue::task::TaskStatus;
+
+    use super::*;
+
+    fn task(command: &str) -> Task {
+        let mut task = Task::new(
+            command.into(),
+            "/tmp".into(),
+            HashMap::new(),
+            Some("gpu".into()),
+            TaskStatus::Queued,
+            None,
+            Vec::new(),
+        );
+        task.id = 4;
+        task
+    }
+
+    #[test]
+    fn test_render_command() {
+        assert_eq!(
+            render_command(&task("echo {{task_id}}_{{group}}_{{worker}} > a&b"), 2),
+            "echo 4_gpu_2 > a&b"
+        );
+        let date = Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(
+            render_command(&task("touch out_{{date}}"), 0),
+            format!("touch out_{}", date)
+        );
+        // Unknown variables leave the command untouched.
+        assert_eq!(
+            render_command(&task("echo {{unknown}} {{task_id}}"), 0),
+            "echo {{unknown}} {{task_id}}"
+        );
+    }
+}
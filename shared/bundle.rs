@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{bail, Context, Result};
+use serde_derive::{Deserialize, Serialize};
+
+/// Multiple named tasks that are added at once via `pueue add --from-yaml`, e.g.:
+///
+/// ```yaml
+/// tasks:
+///   build:
+///     command: cargo build --release
+///     group: compile
+///   test:
+///     command: cargo test
+///     after: [build]
+///     env:
+///       RUST_BACKTRACE: "1"
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bundle {
+    pub tasks: BTreeMap<String, BundleTask>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BundleTask {
+    pub command: String,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// The working directory. Relative paths are resolved from the client's current directory.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Additional environment variables on top of the client's environment.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// The names of other tasks in the bundle, which have to succeed before this task starts.
+    #[serde(default)]
+    pub after: Vec<String>,
+    #[serde(default)]
+    pub stashed: bool,
+}
+
+impl Bundle {
+    pub fn from_yaml(content: &str) -> Result<Bundle> {
+        let bundle: Bundle = serde_yaml::from_str(content).context("Failed to parse bundle")?;
+        if bundle.tasks.is_empty() {
+            bail!("The bundle doesn't contain any tasks");
+        }
+        bundle.ordered_names()?;
+
+        Ok(bundle)
+    }
+
+    /// Order the task names, so that every task comes after all of its dependencies.
+    /// Tasks are added in this order, which ensures that dependencies always have lower ids.
+    /// Fails on unknown dependencies and dependency cycles.
+    pub fn ordered_names(&self) -> Result<Vec<String>> {
+        for (name, task) in self.tasks.iter() {
+            for dependency in task.after.iter() {
+                if !self.tasks.contains_key(dependency) {
+                    bail!("Task {} depends on unknown task {}", name, dependency);
+                }
+            }
+        }
+
+        let mut ordered: Vec<String> = Vec::new();
+        while ordered.len() < self.tasks.len() {
+            let next = self.tasks.iter().find(|(name, task)| {
+                !ordered.contains(name)
+                    && task
+                        .after
+                        .iter()
+                        .all(|dependency| ordered.contains(dependency))
+            });
+            match next {
+                Some((name, _)) => ordered.push(name.clone()),
+                None => bail!("The dependencies of the bundle's tasks contain a cycle"),
+            }
+        }
+
+        Ok(ordered)
+    }
+}
+
+/// A pipeline of stages, which is added via `pueue apply`, e.g.:
+///
+/// ```yaml
+/// name: release
+/// stages:
+///   - name: build
+///     tasks:
+///       linux:
+///         command: ./build.sh linux
+///       windows:
+///         command: ./build.sh windows
+///   - name: deploy
+///     tasks:
+///       upload:
+///         command: ./upload.sh
+/// ```
+///
+/// Each task of a stage depends on all tasks of the previous stage.
+/// All tasks are tagged with the pipeline's name, so they can be shown and canceled together.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    pub name: String,
+    pub stages: Vec<Stage>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stage {
+    pub name: String,
+    /// The tasks of the stage. Their `after` may only refer to tasks of the same stage.
+    pub tasks: BTreeMap<String, BundleTask>,
+}
+
+impl Pipeline {
+    pub fn from_yaml(content: &str) -> Result<Pipeline> {
+        let pipeline: Pipeline =
+            serde_yaml::from_str(content).context("Failed to parse pipeline")?;
+        pipeline.to_bundle()?;
+
+        Ok(pipeline)
+    }
+
+    /// Wire up the stages into a bundle of tasks named `stage.task`.
+    pub fn to_bundle(&self) -> Result<Bundle> {
+        let mut tasks = BTreeMap::new();
+        let mut previous_stage: Vec<String> = Vec::new();
+        for stage in self.stages.iter() {
+            if stage.tasks.is_empty() {
+                bail!("Stage {} doesn't contain any tasks", stage.name);
+            }
+
+            let mut current_stage = Vec::new();
+            for (name, task) in stage.tasks.iter() {
+                let mut task = task.clone();
+                let mut after = previous_stage.clone();
+                after.extend(
+                    task.after
+                        .iter()
+                        .map(|dependency| format!("{}.{}", stage.name, dependency)),
+                );
+                task.after = after;
+
+                let name = format!("{}.{}", stage.name, name);
+                if tasks.insert(name.clone(), task).is_some() {
+                    bail!("Task {} is defined twice", name);
+                }
+                current_stage.push(name);
+            }
+            previous_stage = current_stage;
+        }
+
+        let bundle = Bundle { tasks };
+        if bundle.tasks.is_empty() {
+            bail!("The pipeline doesn't contain any stages");
+        }
+        bundle.ordered_names()?;
+
+        Ok(bundle)
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+const DURATION_HINT: &str = "Use a number followed by s, m, h or d, e.g. 30s, 15m, 2h, 1d or 1h30m";
+
+/// Parse a relative duration like `30s`, `15m`, `2h`, `1d` or `1h30m` into seconds.
+/// Plain numbers are interpreted as seconds.
+pub fn parse_duration(src: &str) -> Result<i64, String> {
+    let text = src.trim();
+    if text.is_empty() {
+        return Err(format!("The duration is empty. {}", DURATION_HINT));
+    }
+    if let Ok(seconds) = text.parse::<i64>() {
+        if seconds < 0 {
+            return Err(format!("The duration {} is negative", src));
+        }
+        return Ok(seconds);
+    }
+
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    for character in text.chars() {
+        if character.is_ascii_digit() {
+            number.push(character);
+            continue;
+        }
+
+        let factor = match character {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => {
+                return Err(format!(
+                    "Invalid duration {}: unknown unit '{}'. {}",
+                    src, character, DURATION_HINT
+                ))
+            }
+        };
+        if number.is_empty() {
+            return Err(format!(
+                "Invalid duration {}: '{}' needs a number in front of it. {}",
+                src, character, DURATION_HINT
+            ));
+        }
+        let value: i64 = number
+            .parse()
+            .map_err(|_| format!("Invalid duration {}: {} is too large", src, number))?;
+        seconds = value
+            .checked_mul(factor)
+            .and_then(|value| seconds.checked_add(value))
+            .ok_or_else(|| format!("Invalid duration {}: it is too large", src))?;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        return Err(format!(
+            "Invalid duration {}: {} has no unit. {}",
+            src, number, DURATION_HINT
+        ));
+    }
+
+    Ok(seconds)
+}
+
+/// Deserialize a duration setting, which is either a number of seconds or a string like `7d`.
+/// Binary formats like our protocol always contain plain seconds.
+pub fn deserialize_duration<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    if !deserializer.is_human_readable() {
+        return u64::deserialize(deserializer);
+    }
+
+    deserializer.deserialize_any(DurationVisitor)
+}
+
+/// Like [deserialize_duration], but for optional settings.
+pub fn deserialize_optional_duration<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(serde_derive::Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "deserialize_duration")] u64);
+
+    Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(seconds)| seconds))
+}
+
+struct DurationVisitor;
+
+impl<'de> Visitor<'de> for DurationVisitor {
+    type Value = u64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number of seconds or a duration like 30s, 15m, 2h or 1d")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<u64, E> {
+        Ok(value)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<u64, E> {
+        if value < 0 {
+            return Err(E::custom(format!("The duration {} is negative", value)));
+        }
+        Ok(value as u64)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<u64, E> {
+        parse_duration(value)
+            .map(|seconds| seconds as u64)
+            .map_err(E::custom)
+    }
+}
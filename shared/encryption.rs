@@ -0,0 +1,227 @@
+//! Encryption of task logs on disk.
+//!
+//! Encrypted logs start with a header, which consists of a magic value and a random nonce.
+//! The output is encrypted with the ChaCha20 stream cipher, so logs can still be appended to
+//! and read from any position, e.g. while following the output of a running task.
+//! The logs aren't authenticated, i.e. modifications by users with write access aren't detected.
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use chacha20::{ChaCha20, Key, Nonce};
+
+use crate::settings::Daemon;
+use crate::shell::compile_shell_command;
+
+const MAGIC: &[u8] = b"PUEUEENC";
+const NONCE_SIZE: usize = 12;
+const HEADER_SIZE: u64 = (MAGIC.len() + NONCE_SIZE) as u64;
+
+/// The key, with which the task logs are encrypted.
+#[derive(Clone)]
+pub struct LogKey([u8; 32]);
+
+impl LogKey {
+    /// Parse a key of 64 hexadecimal characters.
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.chars().all(|character| character.is_ascii_hexdigit()) {
+            bail!("The log key has to consist of 64 hexadecimal characters.");
+        }
+
+        let mut key = [0; 32];
+        for (index, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)?;
+        }
+
+        Ok(LogKey(key))
+    }
+
+    /// Read the key from `daemon.log_key` or the output of `daemon.log_key_command`.
+    /// Without either of them, the logs aren't encrypted.
+    pub fn from_settings(settings: &Daemon) -> Result<Option<Self>> {
+        if let Some(key) = &settings.log_key {
+            return Ok(Some(LogKey::from_hex(key)?));
+        }
+        let command = match &settings.log_key_command {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+
+        let output = compile_shell_command(command)
+            .output()
+            .context("Failed to execute the log key command")?;
+        if !output.status.success() {
+            bail!("The log key command failed with {}", output.status);
+        }
+
+        Ok(Some(LogKey::from_hex(&String::from_utf8_lossy(
+            &output.stdout,
+        ))?))
+    }
+
+    fn cipher(&self, nonce: &[u8]) -> ChaCha20 {
+        ChaCha20::new(Key::from_slice(&self.0), Nonce::from_slice(nonce))
+    }
+}
+
+/// Never print the key, e.g. in debug logs of the state.
+impl fmt::Debug for LogKey {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("LogKey(********)")
+    }
+}
+
+/// A task log, which is transparently encrypted and decrypted.
+/// Encrypted logs are recognized by their header, so logs without one are read as they are.
+pub struct LogFile {
+    file: File,
+    cipher: Option<ChaCha20>,
+}
+
+impl LogFile {
+    /// Create a new log, which is encrypted, if there's a key.
+    pub fn create(path: &Path, key: Option<&LogKey>) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        let cipher = match key {
+            Some(key) => Some(write_header(&mut file, key)?),
+            None => None,
+        };
+
+        Ok(LogFile { file, cipher })
+    }
+
+    /// Open an existing log for reading.
+    pub fn open(path: &Path, key: Option<&LogKey>) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let cipher = read_header(&mut file, key)?;
+
+        Ok(LogFile { file, cipher })
+    }
+
+    /// Open a log for appending. Logs that don't exist yet are created.
+    pub fn append(path: &Path, key: Option<&LogKey>) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        if file.metadata()?.len() == 0 {
+            let cipher = match key {
+                Some(key) => Some(write_header(&mut file, key)?),
+                None => None,
+            };
+            return Ok(LogFile { file, cipher });
+        }
+
+        let mut log = LogFile {
+            cipher: read_header(&mut file, key)?,
+            file,
+        };
+        log.seek(SeekFrom::End(0))?;
+
+        Ok(log)
+    }
+
+    /// The length of the output in the log.
+    pub fn size(&self) -> io::Result<u64> {
+        let length = self.file.metadata()?.len();
+        Ok(length.saturating_sub(self.header_size()))
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// The raw file, e.g. to let a process write into an unencrypted log directly.
+    pub fn into_file(self) -> File {
+        self.file
+    }
+
+    fn header_size(&self) -> u64 {
+        if self.cipher.is_some() {
+            HEADER_SIZE
+        } else {
+            0
+        }
+    }
+}
+
+impl Read for LogFile {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buffer)?;
+        if let Some(cipher) = &mut self.cipher {
+            cipher.apply_keystream(&mut buffer[..read]);
+        }
+
+        Ok(read)
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let cipher = match &mut self.cipher {
+            Some(cipher) => cipher,
+            None => return self.file.write(buffer),
+        };
+
+        // The keystream has already advanced, so the whole buffer has to be written.
+        let mut encrypted = buffer.to_vec();
+        cipher.apply_keystream(&mut encrypted);
+        self.file.write_all(&encrypted)?;
+
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Positions are relative to the start of the output, i.e. the header is skipped.
+impl Seek for LogFile {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let header_size = self.header_size();
+        let position = match position {
+            SeekFrom::Start(offset) => SeekFrom::Start(offset + header_size),
+            position => position,
+        };
+        let mut absolute = self.file.seek(position)?;
+        if absolute < header_size {
+            absolute = self.file.seek(SeekFrom::Start(header_size))?;
+        }
+        if let Some(cipher) = &mut self.cipher {
+            cipher.seek(absolute - header_size);
+        }
+
+        Ok(absolute - header_size)
+    }
+}
+
+fn write_header(file: &mut File, key: &LogKey) -> io::Result<ChaCha20> {
+    let nonce: [u8; NONCE_SIZE] = rand::random();
+    file.write_all(MAGIC)?;
+    file.write_all(&nonce)?;
+
+    Ok(key.cipher(&nonce))
+}
+
+fn read_header(file: &mut File, key: Option<&LogKey>) -> io::Result<Option<ChaCha20>> {
+    let mut header = Vec::new();
+    (&mut *file).take(HEADER_SIZE).read_to_end(&mut header)?;
+    if header.len() < HEADER_SIZE as usize || !header.starts_with(MAGIC) {
+        file.seek(SeekFrom::Start(0))?;
+        return Ok(None);
+    }
+
+    match key {
+        Some(key) => Ok(Some(key.cipher(&header[MAGIC.len()..]))),
+        None => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "The log is encrypted, but there's no key",
+        )),
+    }
+}
@@ -1,7 +1,18 @@
+pub mod bundle;
+pub mod duration;
+pub mod encryption;
 pub mod log;
+pub mod man;
 pub mod message;
+pub mod pattern;
 pub mod platform;
 pub mod protocol;
+pub mod redact;
 pub mod settings;
+pub mod shell;
 pub mod state;
+pub mod stats;
 pub mod task;
+pub mod tls;
+pub mod trash;
+pub mod window;
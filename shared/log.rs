@@ -1,12 +1,16 @@
-use std::fs::{read_dir, remove_file, File};
-use std::io;
+use std::collections::VecDeque;
+use std::fs::{read_dir, remove_file};
 use std::io::prelude::*;
+use std::io::{self, SeekFrom};
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Result};
 use log::error;
 use snap::write::FrameEncoder;
 
+use crate::encryption::{LogFile, LogKey};
+use crate::message::LogMatch;
+
 /// Return the paths to temporary stdout and stderr files for a task.
 pub fn get_log_paths(task_id: usize, path: &str) -> (PathBuf, PathBuf) {
     let task_log_dir = Path::new(path).join("task_logs");
@@ -16,26 +20,55 @@ pub fn get_log_paths(task_id: usize, path: &str) -> (PathBuf, PathBuf) {
 }
 
 /// Create and return the file handle for temporary stdout and stderr files for a task.
-pub fn create_log_file_handles(task_id: usize, path: &str) -> Result<(File, File)> {
+/// With a key, the logs are encrypted.
+pub fn create_log_file_handles(
+    task_id: usize,
+    path: &str,
+    key: Option<&LogKey>,
+) -> Result<(LogFile, LogFile)> {
     let (out_path, err_path) = get_log_paths(task_id, path);
-    let stdout = File::create(out_path)?;
-    let stderr = File::create(err_path)?;
+    let stdout = LogFile::create(&out_path, key)?;
+    let stderr = LogFile::create(&err_path, key)?;
 
     Ok((stdout, stderr))
 }
 
+/// Append output to the stdout and stderr files of a task, e.g. the output of remote workers.
+pub fn append_to_log_files(
+    task_id: usize,
+    path: &str,
+    key: Option<&LogKey>,
+    stdout: &[u8],
+    stderr: &[u8],
+) -> Result<()> {
+    let (out_path, err_path) = get_log_paths(task_id, path);
+    LogFile::append(&out_path, key)?.write_all(stdout)?;
+    LogFile::append(&err_path, key)?.write_all(stderr)?;
+
+    Ok(())
+}
+
 /// Return the file handle for temporary stdout and stderr files for a task.
-pub fn get_log_file_handles(task_id: usize, path: &str) -> Result<(File, File)> {
+/// Encrypted logs can only be opened with the key.
+pub fn get_log_file_handles(
+    task_id: usize,
+    path: &str,
+    key: Option<&LogKey>,
+) -> Result<(LogFile, LogFile)> {
     let (out_path, err_path) = get_log_paths(task_id, path);
-    let stdout = File::open(out_path)?;
-    let stderr = File::open(err_path)?;
+    let stdout = LogFile::open(&out_path, key)?;
+    let stderr = LogFile::open(&err_path, key)?;
 
     Ok((stdout, stderr))
 }
 
 /// Return the content of temporary stdout and stderr files for a task.
-pub fn read_log_files(task_id: usize, path: &str) -> Result<(String, String)> {
-    let (mut stdout_handle, mut stderr_handle) = get_log_file_handles(task_id, path)?;
+pub fn read_log_files(
+    task_id: usize,
+    path: &str,
+    key: Option<&LogKey>,
+) -> Result<(String, String)> {
+    let (mut stdout_handle, mut stderr_handle) = get_log_file_handles(task_id, path, key)?;
     let mut stdout_buffer = Vec::new();
     let mut stderr_buffer = Vec::new();
 
@@ -65,10 +98,149 @@ pub fn clean_log_handles(task_id: usize, path: &str) {
     };
 }
 
+/// Read the last line of the stdout of a task, e.g. to show the progress of a running task.
+/// Progress bars usually redraw their line with a carriage return,
+/// so carriage returns are treated as line breaks as well.
+pub fn read_last_line(task_id: usize, path: &str, key: Option<&LogKey>) -> Option<String> {
+    let (out_path, _) = get_log_paths(task_id, path);
+    let mut file = LogFile::open(&out_path, key).ok()?;
+
+    // The last line is somewhere at the end, there's no need to read the whole file.
+    let length = file.size().ok()?;
+    file.seek(SeekFrom::Start(length.saturating_sub(4096)))
+        .ok()?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).ok()?;
+
+    let text = String::from_utf8_lossy(&bytes);
+    let line = text
+        .rsplit(['\n', '\r'])
+        .map(strip_escape_sequences)
+        .find(|line| !line.trim().is_empty())?;
+
+    Some(line.trim().to_string())
+}
+
+/// Remove terminal escape sequences and progress bar redraws from a task's output,
+/// so only the text that's finally visible in a terminal remains.
+/// Text that's overwritten via a carriage return is dropped.
+pub fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let (content, newline) = match line.strip_suffix('\n') {
+            Some(content) => (content, "\n"),
+            None => (line, ""),
+        };
+        let content = content.trim_end_matches('\r');
+        let visible = content.rsplit('\r').next().unwrap_or_default();
+        result.push_str(&strip_escape_sequences(visible));
+        result.push_str(newline);
+    }
+
+    result
+}
+
+/// Remove terminal escape sequences, e.g. colors, and other control characters except tabs.
+fn strip_escape_sequences(line: &str) -> String {
+    let mut result = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(character) = chars.next() {
+        if character == '\u{1b}' {
+            // Skip `ESC [ ... <letter>` sequences.
+            if chars.peek() == Some(&'[') {
+                for character in chars.by_ref().skip(1) {
+                    if character.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        if !character.is_control() || character == '\t' {
+            result.push(character);
+        }
+    }
+
+    result
+}
+
+/// Only keep the last lines of the stdout and stderr files of a task.
+/// A marker at the start of the trimmed logs shows that older lines have been removed.
+pub fn trim_log_files(
+    task_id: usize,
+    path: &str,
+    key: Option<&LogKey>,
+    lines: usize,
+) -> Result<()> {
+    let (out_path, err_path) = get_log_paths(task_id, path);
+    trim_file(&out_path, key, lines)?;
+    trim_file(&err_path, key, lines)?;
+
+    Ok(())
+}
+
+fn trim_file(path: &Path, key: Option<&LogKey>, lines: usize) -> Result<()> {
+    // Only keep the last lines in memory, since logs of chatty tasks can get huge.
+    let mut reader = io::BufReader::new(LogFile::open(path, key)?);
+    let mut tail = VecDeque::with_capacity(lines + 1);
+    let mut removed = 0;
+    let mut previously_removed = 0;
+    let mut first_line = true;
+    loop {
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        // Logs that have already been trimmed start with a marker, which isn't part of the output.
+        if std::mem::take(&mut first_line) {
+            if let Some(count) = trimmed_lines(&line) {
+                previously_removed = count;
+                continue;
+            }
+        }
+        tail.push_back(line);
+        if tail.len() > lines {
+            tail.pop_front();
+            removed += 1;
+        }
+    }
+
+    if removed == 0 {
+        return Ok(());
+    }
+
+    let mut file = LogFile::create(path, key)?;
+    writeln!(
+        file,
+        "[pueue] {} lines trimmed",
+        previously_removed + removed
+    )?;
+    for line in tail {
+        file.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+/// The amount of lines that have been removed, if this is the marker of a trimmed log.
+fn trimmed_lines(line: &[u8]) -> Option<usize> {
+    String::from_utf8_lossy(line)
+        .trim_end()
+        .strip_prefix("[pueue] ")?
+        .strip_suffix(" lines trimmed")?
+        .parse()
+        .ok()
+}
+
 /// Return stdout and stderr of a finished process.
 /// Task output is compressed using snap to save some memory and bandwidth.
-pub fn read_and_compress_log_files(task_id: usize, path: &str) -> Result<(Vec<u8>, Vec<u8>)> {
-    let (mut stdout_handle, mut stderr_handle) = match get_log_file_handles(task_id, path) {
+/// Encrypted logs are decrypted beforehand.
+pub fn read_and_compress_log_files(
+    task_id: usize,
+    path: &str,
+    key: Option<&LogKey>,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let (mut stdout_handle, mut stderr_handle) = match get_log_file_handles(task_id, path, key) {
         Ok((stdout, stderr)) => (stdout, stderr),
         Err(err) => {
             bail!("Error while opening the output files: {}", err);
@@ -88,6 +260,54 @@ pub fn read_and_compress_log_files(task_id: usize, path: &str) -> Result<(Vec<u8
     Ok((stdout, stderr))
 }
 
+/// Search the stdout and stderr of a task for lines containing the given pattern.
+pub fn grep_log_files(
+    task_id: usize,
+    path: &str,
+    key: Option<&LogKey>,
+    pattern: &str,
+    context: usize,
+) -> Result<Vec<LogMatch>> {
+    let (stdout, stderr) = read_log_files(task_id, path, key)?;
+
+    let mut matches = find_matches(&stdout, pattern, context, false);
+    matches.append(&mut find_matches(&stderr, pattern, context, true));
+
+    Ok(matches)
+}
+
+/// Find all lines containing the pattern and add `context` lines before and after each of them.
+/// Overlapping blocks of lines are merged into a single match.
+pub fn find_matches(content: &str, pattern: &str, context: usize, stderr: bool) -> Vec<LogMatch> {
+    let lines: Vec<&str> = content.lines().collect();
+
+    // Collect the ranges of lines that should be shown.
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if !line.contains(pattern) {
+            continue;
+        }
+        let start = index.saturating_sub(context);
+        let end = (index + context).min(lines.len() - 1);
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| LogMatch {
+            stderr,
+            start: start + 1,
+            lines: lines[start..=end]
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+        })
+        .collect()
+}
+
 /// Remove temporary stdout and stderr files for a task.
 pub fn reset_task_log_directory(path: &str) {
     let task_log_dir = Path::new(path).join("task_logs");
@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::prelude::*;
+use structopt::clap::{App, AppSettings};
+
+/// Render a man page for a binary from its clap definition and write it to `{directory}/{name}.1`.
+/// The help texts of the binary and of all its visible subcommands are included verbatim,
+/// so the man page never diverges from `--help`.
+pub fn write_man_page(mut app: App, name: &str, directory: &Path) -> Result<()> {
+    let mut page = String::new();
+    page.push_str(&format!(
+        ".TH {} 1 \"{}\" \"{} {}\"\n",
+        name.to_uppercase(),
+        Local::today().format("%Y-%m-%d"),
+        name,
+        env!("CARGO_PKG_VERSION")
+    ));
+    page.push_str(".SH NAME\n");
+    page.push_str(&format!(
+        "{} \\- {}\n",
+        name,
+        escape(app.p.meta.about.unwrap_or(""))
+    ));
+
+    // Clap only exposes the list of subcommands and its meta data via the (hidden) parser.
+    // This is the same approach clap uses to generate shell completions.
+    app.p.meta.bin_name = Some(name.to_string());
+    page.push_str(".SH DESCRIPTION\n");
+    page.push_str(&preformatted(&long_help(&mut app)?));
+
+    let mut subcommands: Vec<&mut App> = app
+        .p
+        .subcommands
+        .iter_mut()
+        .filter(|subcommand| !subcommand.p.is_set(AppSettings::Hidden))
+        .collect();
+    if !subcommands.is_empty() {
+        page.push_str(".SH COMMANDS\n");
+    }
+    for subcommand in subcommands.iter_mut() {
+        let subcommand_name = subcommand.p.meta.name.clone();
+        subcommand.p.meta.bin_name = Some(format!("{} {}", name, subcommand_name));
+        page.push_str(&format!(".SS {}\n", escape(&subcommand_name)));
+        page.push_str(&preformatted(&long_help(subcommand)?));
+    }
+
+    let mut file = File::create(directory.join(format!("{}.1", name)))?;
+    file.write_all(page.as_bytes())?;
+
+    Ok(())
+}
+
+fn long_help(app: &mut App) -> Result<String> {
+    let mut help = Vec::new();
+    app.write_long_help(&mut help)?;
+
+    Ok(String::from_utf8_lossy(&help).to_string())
+}
+
+/// Wrap text in a block that's displayed without reformatting.
+fn preformatted(text: &str) -> String {
+    let mut block = String::from(".nf\n");
+    for line in text.trim_end().lines() {
+        block.push_str(&escape(line));
+        block.push('\n');
+    }
+    block.push_str(".fi\n");
+
+    block
+}
+
+/// Escape text, so it isn't interpreted as roff requests or escape sequences.
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e");
+    if text.starts_with('.') || text.starts_with('\'') {
+        return format!("\\&{}", text);
+    }
+
+    text
+}
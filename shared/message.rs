@@ -1,19 +1,43 @@
 use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
 
 use chrono::prelude::*;
 use serde_derive::{Deserialize, Serialize};
 
+use crate::bundle::Bundle;
+use crate::pattern::wildcard_match;
 use crate::state::State;
-use crate::task::Task;
+use crate::stats::Statistics;
+use crate::task::{EnvironmentMode, Progress, Task, TaskResult};
+use crate::trash::TrashedTask;
 
 /// The Message used to add a new command to the daemon.
+///
+/// Large payloads are boxed, so small messages don't take up the size of the largest one.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Message {
-    Add(AddMessage),
-    Remove(Vec<usize>),
+    // Bincode encodes variants by their index. The version handshake and the error responses to
+    // incompatible clients have to be understood by all versions of Pueue. That's why these three
+    // variants always stay the first ones. Never reorder them or add any variant in front of them.
+    /// Exchanged right after the handshake. Contains the version of the sender.
+    Version(String),
+    Success(String),
+    Failure(String),
+
+    Add(Box<AddMessage>),
+    AddBundle(BundleMessage),
+    /// Kill the running tasks of a pipeline and cancel all of its pending tasks.
+    CancelPipeline(String),
+    /// The ids of the tasks of a bundle by their names.
+    BundleResponse(BTreeMap<String, usize>),
+    Remove(TaskSelection),
     Switch(SwitchMessage),
-    Stash(Vec<usize>),
+    Stash(TaskSelection),
     Enqueue(EnqueueMessage),
+    /// Move queued or stashed tasks to another group.
+    Move(MoveMessage),
+    /// Restart finished tasks in place, i.e. they keep their ids.
+    Restart(RestartMessage),
 
     Start(StartMessage),
     Pause(PauseMessage),
@@ -26,20 +50,63 @@ pub enum Message {
     Group(GroupMessage),
 
     Status,
-    StatusResponse(State),
+    StatusPage(StatusPageMessage),
+    StatusResponse(Box<State>),
     Log(LogRequestMessage),
     LogResponse(BTreeMap<usize, TaskLogMessage>),
+    LogGrep(LogGrepMessage),
+    LogGrepResponse(BTreeMap<usize, Vec<LogMatch>>),
     Stream(String),
     StreamRequest(StreamRequestMessage),
     /// The boolean decides, whether the children should be get a SIGTERM as well.
     Reset(bool),
-    Clean,
-    DaemonShutdown,
-
-    Success(String),
-    Failure(String),
+    /// If `true`, only successful tasks are removed.
+    Clean(bool),
+    TrashList,
+    TrashResponse(Vec<TrashedTask>),
+    TrashRestore(Vec<usize>),
+    TrashEmpty,
+    /// Revert the most recent remove, clean or pipeline cancel.
+    Undo,
+    /// Shut down the daemon. If `true`, wait for all running tasks to finish first.
+    DaemonShutdown(bool),
 
     Parallel(ParallelMessage),
+
+    DaemonInfo,
+    DaemonInfoResponse(DaemonInfoMessage),
+
+    /// Request all changes since the given state revision.
+    StatusSince(u64),
+    StatusDelta(Box<StatusDeltaMessage>),
+
+    /// Sent by remote workers to request the next task of a worker group.
+    WorkerPull(WorkerPullMessage),
+    /// The response to a `WorkerPull`, containing the task that should be executed.
+    WorkerTask(Box<WorkerTaskMessage>),
+    /// Sent by remote workers, once a pulled task has finished.
+    WorkerReport(WorkerReportMessage),
+    /// Sent by remote workers while executing a task, so the daemon knows they're still alive.
+    /// It also contains the output of the task since the last heartbeat.
+    /// The response is a failure, if the task has been killed in the meantime.
+    WorkerHeartbeat(WorkerHeartbeatMessage),
+
+    Notify(NotifyMessage),
+    Progress(ProgressMessage),
+
+    /// Request the run statistics of all labels and commands containing the given text.
+    Stats(Option<String>),
+    StatsResponse(Statistics),
+}
+
+/// Add all tasks of a bundle at once. Either all tasks are added or none.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BundleMessage {
+    pub bundle: Bundle,
+    pub path: String,
+    pub envs: HashMap<String, String>,
+    /// Tag all tasks with the name of this pipeline.
+    pub pipeline: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -50,9 +117,44 @@ pub struct AddMessage {
     pub start_immediately: bool,
     pub stashed: bool,
     pub group: Option<String>,
-    pub enqueue_at: Option<DateTime<Local>>,
+    /// The instant with the client's UTC offset, so the daemon can answer in the client's local time.
+    pub enqueue_at: Option<DateTime<FixedOffset>>,
     pub dependencies: Vec<usize>,
+    pub any_dependencies: Vec<usize>,
+    pub failure_dependencies: Vec<usize>,
+    pub dependency_timeout: Option<i64>,
     pub ignore_aliases: bool,
+    pub unique_key: Option<String>,
+    /// If a queued task with the same `unique_key` exists, replace it instead of dropping this one.
+    pub replace_duplicate: bool,
+    pub slots: usize,
+    pub lock: Option<String>,
+    pub stdin_from: Option<usize>,
+    pub artifacts: Vec<String>,
+    pub tempdir: bool,
+    pub container: Option<String>,
+    pub host: Option<String>,
+    pub stop_command: Option<String>,
+    pub on_exit: Option<String>,
+    pub before: Option<String>,
+    pub env_mode: EnvironmentMode,
+    pub line_buffered: bool,
+    pub login_shell: bool,
+    pub direnv: bool,
+    pub label: Option<String>,
+    pub priority: i32,
+    pub cpus: Option<String>,
+    pub gpus: usize,
+    pub wait_for_path: Option<String>,
+    pub wait_for_host: Option<String>,
+    /// Add an array job with one task for each index of this inclusive range.
+    pub array: Option<(usize, usize)>,
+    /// Add the tasks to this existing array job instead, e.g. when restarting a single index.
+    pub array_id: Option<usize>,
+    /// Don't execute the task, if the same task succeeded within this many seconds.
+    pub cache: Option<i64>,
+    /// The task this one is a restart of.
+    pub restarted_from: Option<usize>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -64,7 +166,30 @@ pub struct SwitchMessage {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct EnqueueMessage {
     pub task_ids: Vec<usize>,
-    pub enqueue_at: Option<DateTime<Local>>,
+    /// The instant with the client's UTC offset, see [AddMessage::enqueue_at].
+    pub enqueue_at: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MoveMessage {
+    pub task_ids: Vec<usize>,
+    /// The target group. `None` is the default queue.
+    pub group: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RestartMessage {
+    pub tasks: Vec<TaskToRestart>,
+    pub start_immediately: bool,
+    pub stashed: bool,
+}
+
+/// A task that's restarted in place. Command and path may have been edited by the client.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TaskToRestart {
+    pub task_id: usize,
+    pub command: String,
+    pub path: String,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -91,6 +216,10 @@ pub struct KillMessage {
     pub default: bool,
     pub all: bool,
     pub children: bool,
+    /// Overwrite `daemon.kill_grace_period` for these tasks.
+    pub grace: Option<u64>,
+    /// Kill the tasks matching this selection. It's resolved by the daemon into `task_ids`.
+    pub selection: Option<TaskSelection>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -104,6 +233,8 @@ pub struct EditMessage {
     pub task_id: usize,
     pub command: String,
     pub path: String,
+    pub label: Option<String>,
+    pub group: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -111,6 +242,8 @@ pub struct EditResponseMessage {
     pub task_id: usize,
     pub command: String,
     pub path: String,
+    pub label: Option<String>,
+    pub group: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -144,12 +277,277 @@ pub struct TaskLogMessage {
     pub stderr: Option<Vec<u8>>,
 }
 
+/// Search the logs of specific tasks for a pattern.
+/// An empty task_id vector will search the logs of all tasks.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LogGrepMessage {
+    pub task_ids: Vec<usize>,
+    pub pattern: String,
+    /// The amount of lines shown before and after each matching line.
+    pub context: usize,
+}
+
+/// A block of log lines, which contains at least one match.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LogMatch {
+    /// Whether the lines are from stderr or stdout.
+    pub stderr: bool,
+    /// The line number of the first line in this block, starting at 1.
+    pub start: usize,
+    pub lines: Vec<String>,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ParallelMessage {
     pub parallel_tasks: usize,
     pub group: Option<String>,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WorkerPullMessage {
+    pub worker: String,
+    pub group: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WorkerTaskMessage {
+    pub task: Task,
+    /// The command with all template variables expanded, as the daemon would execute it.
+    pub command: String,
+    /// The full environment of the task, including the `PUEUE_*` variables.
+    pub envs: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WorkerReportMessage {
+    pub task_id: usize,
+    pub worker: String,
+    /// `None`, if the process has been killed by a signal.
+    pub exit_code: Option<i32>,
+    /// The remaining output, which hasn't been sent with a heartbeat yet.
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// The reason why the process couldn't be spawned on the worker.
+    pub spawn_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WorkerHeartbeatMessage {
+    pub task_id: usize,
+    pub worker: String,
+    /// The output of the task since the last heartbeat.
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DaemonInfoMessage {
+    pub version: String,
+    pub start: DateTime<Local>,
+    pub pid: u32,
+    /// `None`, if the config is loaded from the default locations.
+    pub config_path: Option<String>,
+    pub pueue_directory: String,
+    pub socket: String,
+    pub clients: usize,
+    /// The size of the state file in bytes.
+    pub state_file_size: Option<u64>,
+}
+
+/// Filter tasks by their exit code, e.g. `!=0` or `>1`. A plain number checks for equality.
+/// Tasks without an exit code, e.g. unfinished tasks or tasks killed by a signal, never match.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ExitCodeFilter {
+    pub operator: String,
+    pub value: i32,
+}
+
+impl ExitCodeFilter {
+    pub fn matches(&self, task: &Task) -> bool {
+        let exit_code = match task.result {
+            Some(TaskResult::Success) => 0,
+            Some(TaskResult::Failed(exit_code)) => exit_code,
+            _ => return false,
+        };
+        match self.operator.as_str() {
+            "!=" => exit_code != self.value,
+            ">" => exit_code > self.value,
+            ">=" => exit_code >= self.value,
+            "<" => exit_code < self.value,
+            "<=" => exit_code <= self.value,
+            _ => exit_code == self.value,
+        }
+    }
+}
+
+impl FromStr for ExitCodeFilter {
+    type Err = String;
+
+    fn from_str(filter: &str) -> Result<Self, Self::Err> {
+        let filter = filter.trim();
+        let operator = ["!=", ">=", "<=", "==", ">", "<", "="]
+            .iter()
+            .find(|operator| filter.starts_with(*operator))
+            .copied()
+            .unwrap_or("==");
+        let value = filter.strip_prefix(operator).unwrap_or(filter).trim();
+        let value = value.parse().map_err(|_| {
+            format!(
+                "Invalid exit code filter {}. Use e.g. 1, !=0 or >=2.",
+                filter
+            )
+        })?;
+        let operator = if operator == "=" { "==" } else { operator };
+
+        Ok(ExitCodeFilter {
+            operator: operator.to_string(),
+            value,
+        })
+    }
+}
+
+/// Attach the UTC offset of the client's timezone at that instant to a point in time.
+/// It's only used to show the time in the client's local time, daylight saving time included.
+pub fn client_instant(time: DateTime<Local>) -> DateTime<FixedOffset> {
+    time.with_timezone(time.offset())
+}
+
+/// Select tasks by ids, id ranges, their group and a glob pattern on their command.
+/// Without any ids or ranges, all tasks of the group or matching the pattern are selected.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct TaskSelection {
+    pub task_ids: Vec<usize>,
+    /// Inclusive ranges of task ids. Ids that don't exist are simply ignored.
+    pub ranges: Vec<(usize, usize)>,
+    /// Only select tasks of this group. `default` is the default queue.
+    pub group: Option<String>,
+    /// Only select tasks whose command matches this pattern, e.g. `ffmpeg*`.
+    pub pattern: Option<String>,
+}
+
+impl TaskSelection {
+    /// Build a selection from ranges, where single ids have the same start and end.
+    pub fn from_ranges(
+        ranges: &[(usize, usize)],
+        group: Option<String>,
+        pattern: Option<String>,
+    ) -> Self {
+        let (task_ids, ranges): (Vec<_>, Vec<_>) =
+            ranges.iter().partition(|(start, end)| start == end);
+
+        TaskSelection {
+            task_ids: task_ids.iter().map(|(id, _)| *id).collect(),
+            ranges,
+            group,
+            pattern,
+        }
+    }
+
+    /// Whether this selection only consists of plain task ids.
+    pub fn is_plain(&self) -> bool {
+        self.ranges.is_empty() && self.group.is_none() && self.pattern.is_none()
+    }
+
+    /// Whether the task passes the group and pattern filters.
+    pub fn matches(&self, task: &Task) -> bool {
+        if let Some(group) = &self.group {
+            if task.group.as_deref().unwrap_or("default") != group {
+                return false;
+            }
+        }
+
+        match &self.pattern {
+            Some(pattern) => wildcard_match(pattern, &task.command),
+            None => true,
+        }
+    }
+}
+
+impl From<Vec<usize>> for TaskSelection {
+    fn from(task_ids: Vec<usize>) -> Self {
+        TaskSelection {
+            task_ids,
+            ..Default::default()
+        }
+    }
+}
+
+/// Request a single page of tasks.
+/// The pagination is applied after filtering by group and query.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StatusPageMessage {
+    pub group: Option<String>,
+    /// Only include tasks whose command, label or path contain this text.
+    pub query: Option<String>,
+    /// Only include tasks of this pipeline.
+    pub pipeline: Option<String>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub added_after: Option<DateTime<Local>>,
+    pub added_before: Option<DateTime<Local>>,
+    /// Tasks that haven't finished yet never match a `finished_*` filter.
+    pub finished_after: Option<DateTime<Local>>,
+    pub finished_before: Option<DateTime<Local>>,
+    pub exit_code: Option<ExitCodeFilter>,
+}
+
+impl StatusPageMessage {
+    /// Check whether the task has been added and finished in the requested time ranges.
+    pub fn matches_time_range(&self, task: &Task) -> bool {
+        let in_range = |time: Option<DateTime<Local>>,
+                        after: &Option<DateTime<Local>>,
+                        before: &Option<DateTime<Local>>| {
+            if after.is_none() && before.is_none() {
+                return true;
+            }
+            let time = match time {
+                Some(time) => time,
+                None => return false,
+            };
+            if let Some(after) = after {
+                if time < *after {
+                    return false;
+                }
+            }
+            if let Some(before) = before {
+                if time > *before {
+                    return false;
+                }
+            }
+            true
+        };
+
+        in_range(Some(task.created_at), &self.added_after, &self.added_before)
+            && in_range(task.end, &self.finished_after, &self.finished_before)
+    }
+}
+
+/// A custom message of a task, e.g. to report its progress via `pueue notify`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NotifyMessage {
+    pub task_id: usize,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProgressMessage {
+    pub task_id: usize,
+    pub progress: Progress,
+}
+
+/// An incremental status update.
+/// Contains all tasks that have been added or changed and the ids of all removed tasks.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StatusDeltaMessage {
+    pub revision: u64,
+    /// The full state, in case the daemon doesn't know the changes since the requested revision.
+    pub full: Option<State>,
+    pub changed: BTreeMap<usize, Task>,
+    pub removed: Vec<usize>,
+    pub running: bool,
+    pub groups: HashMap<String, bool>,
+}
+
 pub fn create_success_message<T: ToString>(text: T) -> Message {
     Message::Success(text.to_string())
 }
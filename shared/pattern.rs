@@ -0,0 +1,28 @@
+/// Match a text against a pattern with `*` and `?` wildcards.
+/// `*` matches any amount of characters, `?` matches exactly one character.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Position of the last `*` in the pattern and the text position it matched up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last `*` consume another character.
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, t));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -19,6 +19,14 @@ pub use self::unix::socket;
 #[cfg(target_os = "windows")]
 pub use self::windows::socket;
 
+/// Shared unix stuff for the shell, which executes tasks
+#[cfg(not(target_os = "windows"))]
+pub use self::unix::shell;
+
+/// Windows specific shell stuff
+#[cfg(target_os = "windows")]
+pub use self::windows::shell;
+
 // The next block is platform specific directory functions
 #[cfg(any(target_os = "linux", target_os = "freebsd"))]
 pub use self::linux::directories;
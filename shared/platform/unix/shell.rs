@@ -0,0 +1,32 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::shell::posix_setup_command;
+
+pub fn compile_shell_command(command_string: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_string);
+
+    command
+}
+
+/// Prepend the setup command of a task in the syntax of the shell, which executes it.
+/// That's the login shell, if one is used, `sh` otherwise.
+pub fn prepend_setup_command(
+    before: Option<&str>,
+    command_string: &str,
+    login_shell: Option<&str>,
+) -> String {
+    let is_fish = login_shell
+        .and_then(|shell| Path::new(shell).file_name())
+        .is_some_and(|name| name == "fish");
+    match before {
+        Some(before) if is_fish => {
+            format!(
+                "begin\n{}\nend\nor exit $status\n{}",
+                before, command_string
+            )
+        }
+        _ => posix_setup_command(before, command_string),
+    }
+}
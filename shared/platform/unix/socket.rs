@@ -1,3 +1,10 @@
+use std::fs::{set_permissions, Permissions};
+use std::os::unix::fs::PermissionsExt;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{
+    SocketAddr, UnixListener as StdUnixListener, UnixStream as StdUnixStream,
+};
 use std::path::PathBuf;
 
 use anyhow::{bail, Context, Result};
@@ -5,6 +12,12 @@ use async_std::io::{Read, Write};
 use async_std::net::{TcpListener, TcpStream};
 use async_std::os::unix::net::{UnixListener, UnixStream};
 use async_trait::async_trait;
+#[cfg(target_os = "linux")]
+use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+use nix::unistd::{chown, Gid};
+use users::get_group_by_name;
+#[cfg(target_os = "linux")]
+use users::get_user_by_uid;
 
 /// A new trait, which can be used to represent Unix- and TcpListeners.
 /// This is necessary to easily write generic functions where both types can be used.
@@ -31,9 +44,39 @@ impl GenericListener for UnixListener {
 
 /// A new trait, which can be used to represent Unix- and TcpStream.
 /// This is necessary to easily write generic functions where both types can be used.
-pub trait GenericSocket: Read + Write + Unpin + Send + Sync {}
-impl GenericSocket for TcpStream {}
-impl GenericSocket for UnixStream {}
+pub trait GenericSocket: Read + Write + Unpin + Send + Sync {
+    /// A short description of the connected peer, e.g. for the daemon's audit log.
+    fn peer(&self) -> String;
+}
+
+impl GenericSocket for TcpStream {
+    fn peer(&self) -> String {
+        match self.peer_addr() {
+            Ok(address) => format!("tcp {}", address),
+            Err(_) => "tcp unknown".into(),
+        }
+    }
+}
+
+impl GenericSocket for UnixStream {
+    /// Unix socket peers are identified by the user of the connecting process.
+    #[cfg(target_os = "linux")]
+    fn peer(&self) -> String {
+        let uid = match getsockopt(self.as_raw_fd(), PeerCredentials) {
+            Ok(credentials) => credentials.uid(),
+            Err(_) => return "unix unknown".into(),
+        };
+        match get_user_by_uid(uid) {
+            Some(user) => format!("unix {}", user.name().to_string_lossy()),
+            None => format!("unix uid {}", uid),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn peer(&self) -> String {
+        "unix".into()
+    }
+}
 
 /// Two convenient types, so we don't have type write Box<dyn ...> all the time.
 pub type Listener = Box<dyn GenericListener>;
@@ -42,8 +85,18 @@ pub type Socket = Box<dyn GenericSocket>;
 /// Get a new stream for the client.
 /// This can either be a UnixStream or a TCPStream,
 /// which depends on the parameters.
-pub async fn get_client(unix_socket_path: Option<String>, port: Option<String>) -> Result<Socket> {
+pub async fn get_client(
+    unix_socket_path: Option<String>,
+    address: Option<String>,
+) -> Result<Socket> {
     if let Some(socket_path) = unix_socket_path {
+        if is_abstract_socket(&socket_path) {
+            let stream = StdUnixStream::connect_addr(&abstract_address(&socket_path)?).context(
+                "Failed to connect to the abstract unix socket. Is the daemon running yet?",
+            )?;
+            return Ok(Box::new(UnixStream::from(stream)));
+        }
+
         if !PathBuf::from(&socket_path).exists() {
             bail!(
                 "Couldn't find unix socket at path {:?}. Is the daemon running yet?",
@@ -54,28 +107,86 @@ pub async fn get_client(unix_socket_path: Option<String>, port: Option<String>)
         return Ok(Box::new(stream));
     }
 
-    let port = port.unwrap();
-    // Don't allow anything else than loopback until we have proper crypto
-    // let address = format!("{}:{}", address, port);
-    let address = format!("127.0.0.1:{}", &port);
+    let address = address.unwrap();
 
     // Connect to socket
     let socket = TcpStream::connect(&address).await.context(format!(
-        "Failed to connect to the daemon on port {}. Did you start it?",
-        &port
+        "Failed to connect to the daemon at {}. Did you start it?",
+        &address
     ))?;
 
     Ok(Box::new(socket))
 }
 
+/// Abstract sockets live in the abstract namespace of Linux instead of the filesystem.
+/// They're configured via a leading null byte in the socket path, e.g. `"\0pueue"`.
+/// There's no file, which needs to be cleaned up after a crash or shared with containers.
+pub fn is_abstract_socket(socket_path: &str) -> bool {
+    socket_path.starts_with('\0')
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_address(socket_path: &str) -> Result<SocketAddr> {
+    use std::os::linux::net::SocketAddrExt;
+
+    Ok(SocketAddr::from_abstract_name(
+        &socket_path.as_bytes()[1..],
+    )?)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn abstract_address(_socket_path: &str) -> Result<SocketAddr> {
+    bail!("Abstract unix sockets are only supported on Linux.")
+}
+
+/// Set the permissions and group of the unix socket right after it has been bound.
+/// The mode is given in octal notation, e.g. `0660`.
+pub fn set_socket_permissions(
+    socket_path: &str,
+    mode: &Option<String>,
+    group: &Option<String>,
+) -> Result<()> {
+    // Abstract sockets don't have any permissions.
+    if is_abstract_socket(socket_path) {
+        return Ok(());
+    }
+
+    if let Some(mode) = mode {
+        let mode = u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+            .context(format!("Invalid unix socket mode {}", mode))?;
+        set_permissions(socket_path, Permissions::from_mode(mode))
+            .context("Failed to set the permissions of the unix socket")?;
+    }
+
+    if let Some(group) = group {
+        let gid = match get_group_by_name(group) {
+            Some(group) => group.gid(),
+            None => bail!("Unknown group {} for the unix socket", group),
+        };
+        chown(socket_path, None, Some(Gid::from_raw(gid)))
+            .context("Failed to change the group of the unix socket")?;
+    }
+
+    Ok(())
+}
+
 /// Get a new listener for the daemon.
 /// This can either be a UnixListener or a TCPlistener,
 /// which depends on the parameters.
 pub async fn get_listener(
     unix_socket_path: Option<String>,
-    port: Option<String>,
+    address: Option<String>,
 ) -> Result<Listener> {
     if let Some(socket_path) = unix_socket_path {
+        // Abstract sockets are removed by the kernel, as soon as the daemon exits.
+        // Binding only fails, if another daemon is still running.
+        if is_abstract_socket(&socket_path) {
+            let listener = StdUnixListener::bind_addr(&abstract_address(&socket_path)?).context(
+                "Failed to bind the abstract unix socket. Is another pueue daemon running?",
+            )?;
+            return Ok(Box::new(UnixListener::from(listener)));
+        }
+
         // Check, if the socket already exists
         // In case it does, we have to check, if it's an active socket.
         // If it is, we have to throw an error, because another daemon is already running.
@@ -95,7 +206,9 @@ pub async fn get_listener(
         return Ok(Box::new(UnixListener::bind(socket_path).await?));
     }
 
-    let port = port.unwrap();
-    let address = format!("127.0.0.1:{}", port);
-    Ok(Box::new(TcpListener::bind(address).await?))
+    let address = address.unwrap();
+    let listener = TcpListener::bind(&address)
+        .await
+        .context(format!("Failed to bind the tcp listener to {}", address))?;
+    Ok(Box::new(listener))
 }
@@ -1,2 +1,3 @@
 pub mod directories;
+pub mod shell;
 pub mod socket;
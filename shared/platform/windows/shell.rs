@@ -0,0 +1,28 @@
+use std::process::Command;
+
+pub fn compile_shell_command(command_string: &str) -> Command {
+    // Chain two `powershell` commands, one that sets the output encoding to utf8 and then the user provided one.
+    let mut command = Command::new("powershell");
+    command.arg("-c").arg(format!(
+        "[Console]::OutputEncoding = [Text.UTF8Encoding]::UTF8; {}",
+        command_string
+    ));
+
+    command
+}
+
+/// Prepend the setup command of a task in `powershell` syntax, since there are no login shells.
+/// If the setup command fails, its exit code becomes the task's exit code.
+pub fn prepend_setup_command(
+    before: Option<&str>,
+    command_string: &str,
+    _login_shell: Option<&str>,
+) -> String {
+    match before {
+        Some(before) => format!(
+            "{}\nif (-not $?) {{ if ($LASTEXITCODE) {{ exit $LASTEXITCODE }}; exit 1 }}\n{}",
+            before, command_string
+        ),
+        None => command_string.to_string(),
+    }
+}
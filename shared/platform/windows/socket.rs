@@ -16,29 +16,50 @@ impl GenericListener for TcpListener {
     }
 }
 
-pub trait GenericSocket: Read + Write + Unpin + Send + Sync {}
-impl GenericSocket for TcpStream {}
+pub trait GenericSocket: Read + Write + Unpin + Send + Sync {
+    /// A short description of the connected peer, e.g. for the daemon's audit log.
+    fn peer(&self) -> String;
+}
+
+impl GenericSocket for TcpStream {
+    fn peer(&self) -> String {
+        match self.peer_addr() {
+            Ok(address) => format!("tcp {}", address),
+            Err(_) => "tcp unknown".into(),
+        }
+    }
+}
 
 pub type Listener = Box<dyn GenericListener>;
 pub type Socket = Box<dyn GenericSocket>;
 
-pub async fn get_client(_unix_socket_path: Option<String>, port: Option<String>) -> Result<Socket> {
-    // Don't allow anything else than loopback until we have proper crypto
-    let address = format!("127.0.0.1:{}", port.unwrap());
+/// Unix sockets aren't supported on Windows, hence there are no abstract sockets either.
+pub fn is_abstract_socket(_socket_path: &str) -> bool {
+    false
+}
+
+pub async fn get_client(
+    _unix_socket_path: Option<String>,
+    address: Option<String>,
+) -> Result<Socket> {
+    let address = address.unwrap();
 
     // Connect to socket
-    let socket = TcpStream::connect(&address)
-        .await
-        .context("Failed to connect to the daemon. Did you start it?")?;
+    let socket = TcpStream::connect(&address).await.context(format!(
+        "Failed to connect to the daemon at {}. Did you start it?",
+        &address
+    ))?;
 
     Ok(Box::new(socket))
 }
 
 pub async fn get_listener(
     _unix_socket_path: Option<String>,
-    port: Option<String>,
+    address: Option<String>,
 ) -> Result<Listener> {
-    let port = port.unwrap();
-    let address = format!("127.0.0.1:{}", port);
-    Ok(Box::new(TcpListener::bind(address).await?))
+    let address = address.unwrap();
+    let listener = TcpListener::bind(&address)
+        .await
+        .context(format!("Failed to bind the tcp listener to {}", address))?;
+    Ok(Box::new(listener))
 }
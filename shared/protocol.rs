@@ -11,6 +11,9 @@ pub use crate::platform::socket::*;
 
 /// Convenience wrapper around send_bytes.
 /// Deserialize a message and feed the bytes into send_bytes.
+///
+/// Messages are always encoded with bincode, which already is a compact binary format.
+/// There's no JSON on the wire, JSON is only used for the state file and `--json` output.
 pub async fn send_message(message: Message, socket: &mut Socket) -> Result<()> {
     debug!("Sending message: {:?}", message);
     // Prepare command for transfer and determine message byte size
@@ -76,6 +79,28 @@ pub async fn receive_bytes(socket: &mut Socket) -> Result<Vec<u8>> {
     Ok(payload_bytes)
 }
 
+/// Check whether two versions of Pueue are compatible with each other.
+/// Versions are compatible, if their major versions match.
+/// For `0.x` versions, the minor versions have to match as well.
+pub fn versions_are_compatible(first: &str, second: &str) -> bool {
+    let parse = |version: &str| -> (u64, u64) {
+        let mut parts = version
+            .split(['.', '-'].as_ref())
+            .map(|part| part.parse().unwrap_or(0));
+        (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+    };
+    let ((first_major, first_minor), (second_major, second_minor)) = (parse(first), parse(second));
+
+    if first_major != second_major {
+        return false;
+    }
+    if first_major == 0 {
+        return first_minor == second_minor;
+    }
+
+    true
+}
+
 /// Convenience wrapper that receives a message and converts it into a Message.
 pub async fn receive_message(socket: &mut Socket) -> Result<Message> {
     let payload_bytes = receive_bytes(socket).await?;
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+use crate::pattern::wildcard_match;
+use crate::task::Task;
+
+/// The text that replaces redacted values.
+pub const MASK: &str = "********";
+
+/// Check whether an environment variable contains a secret,
+/// i.e. whether its name matches one of the patterns, e.g. `*_TOKEN`.
+pub fn is_secret(name: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| wildcard_match(pattern, name))
+}
+
+/// Mask the values of all secret environment variables of a task.
+/// Secrets that appear in the command are masked as well.
+pub fn redact_task(task: &mut Task, patterns: &[String]) {
+    task.command = redact_command(&task.command, &task.envs, patterns);
+    for (name, value) in task.envs.iter_mut() {
+        if is_secret(name, patterns) {
+            *value = MASK.to_string();
+        }
+    }
+}
+
+/// Mask secrets in a command.
+/// This covers inline assignments like `API_TOKEN=1234 curl ...`
+/// as well as the values of the secret environment variables of the task.
+pub fn redact_command(
+    command: &str,
+    envs: &HashMap<String, String>,
+    patterns: &[String],
+) -> String {
+    let mut command = command
+        .split(' ')
+        .map(|word| match word.split_once('=') {
+            Some((name, _)) if is_secret(name, patterns) => format!("{}={}", name, MASK),
+            _ => word.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    for (name, value) in envs.iter() {
+        if !value.is_empty() && is_secret(name, patterns) {
+            command = command.replace(value.as_str(), MASK);
+        }
+    }
+
+    command
+}
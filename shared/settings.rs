@@ -9,16 +9,34 @@ use log::info;
 use rand::Rng;
 use serde_derive::{Deserialize, Serialize};
 
+use crate::duration::{deserialize_duration, deserialize_optional_duration};
 use crate::platform::directories::*;
+use crate::window::RunWindow;
 
 /// All settings which are used by both, the client and the daemon
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Shared {
+    /// The address the daemon's tcp listener binds to, e.g. `127.0.0.1`, `::1` or `0.0.0.0`.
+    /// Binding to `::` accepts IPv4 and IPv6 connections on most systems.
+    pub host: String,
     pub port: String,
     pub secret: String,
     pub pueue_directory: String,
     pub use_unix_socket: bool,
+    /// A leading null byte, e.g. `"\0pueue"`, uses an abstract socket on Linux.
     pub unix_socket_path: String,
+    /// The permissions of the unix socket as octal mode, e.g. `"0660"`.
+    pub unix_socket_mode: Option<String>,
+    /// The group that owns the unix socket.
+    /// Together with the mode, this allows all members of the group to use the daemon.
+    pub unix_socket_group: Option<String>,
+    /// Values of environment variables whose names match any of these patterns, e.g. `*_TOKEN`,
+    /// are masked in the output of `status` and `log` and in callbacks.
+    pub redacted_envs: Vec<String>,
+    /// The path of a CA certificate in PEM format.
+    /// Once it's set, tcp connections are encrypted via TLS and the daemon only accepts
+    /// clients with a certificate that's signed by this CA.
+    pub tls_ca: Option<String>,
 }
 
 /// All settings which are used by the client
@@ -26,16 +44,266 @@ pub struct Shared {
 pub struct Client {
     pub read_local_logs: bool,
     pub show_confirmation_questions: bool,
+    /// Ask for confirmation before `reset`, `kill --all` and removing many tasks at once.
+    /// The prompt shows how many tasks of which groups are affected. Skip it with `--force`.
+    pub confirm_destructive: bool,
+    /// Echo the point in time of date expressions like `--delay 'tomorrow 9am'` and ask for confirmation.
+    pub confirm_date_expressions: bool,
     pub max_status_lines: Option<usize>,
+    /// The maximum amount of tasks that's requested by `pueue status`.
+    /// Keeps the status of daemons with lots of tasks fast.
+    pub status_limit: Option<usize>,
+    /// The time columns that are shown by `pueue status` in this order.
+    /// Possible values are `added`, `start`, `end` and `duration`.
+    pub status_time_columns: Vec<String>,
+    /// The maximum width of the command column of `pueue status` on terminals.
+    pub max_command_width: Option<usize>,
+    /// Cut long commands in `pueue status` instead of wrapping them onto multiple lines.
+    pub truncate_commands: bool,
+    /// Translate paths of this machine into paths of the daemon's machine, e.g. if the
+    /// daemon runs on another host with a shared file system. Once any mapping is configured,
+    /// every path that's sent to the daemon has to match one of them.
+    pub path_mappings: Vec<PathMapping>,
+    /// The certificate and private key in PEM format, with which the client authenticates
+    /// itself to the daemon. Required for tcp connections, once `shared.tls_ca` is set.
+    pub tls_certificate: Option<String>,
+    pub tls_key: Option<String>,
+}
+
+/// Replace the `client` prefix of a path with the `daemon` prefix.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PathMapping {
+    pub client: String,
+    pub daemon: String,
+}
+
+impl Client {
+    /// Translate a path of this machine into the corresponding path on the daemon's machine.
+    /// The mapping with the longest matching prefix wins.
+    /// Without any mappings, the path is returned as it is.
+    pub fn map_path(&self, path: &str) -> Result<String> {
+        match translate_path(path, &self.path_mappings, |mapping| {
+            (&mapping.client, &mapping.daemon)
+        }) {
+            Some(mapped) => Ok(mapped),
+            None => bail!(
+                "No path mapping matches {}. Add one to `client.path_mappings`, \
+                so the daemon knows where this directory is on its machine.",
+                path
+            ),
+        }
+    }
+
+    /// Translate a path of the daemon's machine into the corresponding path on this machine.
+    /// This is the reverse of [Client::map_path], e.g. for tasks that are executed by `pueue worker`.
+    /// Returns `None`, if no mapping matches.
+    pub fn unmap_path(&self, path: &str) -> Option<String> {
+        translate_path(path, &self.path_mappings, |mapping| {
+            (&mapping.daemon, &mapping.client)
+        })
+    }
+}
+
+/// Replace the longest matching `from` prefix of the given path with its `to` prefix.
+/// Without any mappings, the path is returned as it is.
+fn translate_path(
+    path: &str,
+    mappings: &[PathMapping],
+    prefixes: fn(&PathMapping) -> (&String, &String),
+) -> Option<String> {
+    if mappings.is_empty() {
+        return Some(path.to_string());
+    }
+
+    let (from, to) = mappings
+        .iter()
+        .map(prefixes)
+        .filter(|(from, _)| {
+            let prefix = from.trim_end_matches(is_separator);
+            match path.strip_prefix(prefix) {
+                // Only match whole path components, i.e. `/data` doesn't match `/database`.
+                Some(rest) => rest.is_empty() || rest.starts_with(is_separator),
+                None => false,
+            }
+        })
+        .max_by_key(|(from, _)| from.trim_end_matches(is_separator).len())?;
+
+    let rest = &path[from.trim_end_matches(is_separator).len()..];
+    let to_prefix = to.trim_end_matches(is_separator);
+    // Both machines may use different path separators, e.g. a linux client and a windows daemon.
+    let separator = if to_prefix.contains('\\') && !to_prefix.contains('/') {
+        "\\"
+    } else {
+        "/"
+    };
+    let components: Vec<&str> = rest.split(is_separator).filter(|c| !c.is_empty()).collect();
+    if components.is_empty() && to_prefix.is_empty() {
+        return Some(separator.to_string());
+    }
+
+    let mut mapped = to_prefix.to_string();
+    for component in components {
+        mapped.push_str(separator);
+        mapped.push_str(component);
+    }
+
+    Some(mapped)
+}
+
+fn is_separator(character: char) -> bool {
+    character == '/' || character == '\\'
 }
 
 /// All settings which are used by the daemon
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Daemon {
     pub default_parallel_tasks: usize,
+    /// The maximum amount of tasks that run at the same time across all groups.
+    /// Once it's reached, groups take turns in starting their tasks.
+    pub max_parallel_tasks: Option<usize>,
     pub pause_on_failure: bool,
     pub callback: Option<String>,
+    /// The default `add --on-exit` command of each group's tasks.
+    /// Use `default` as key for the default queue.
+    pub on_exit: HashMap<String, String>,
+    /// Force line buffering of all tasks' output via `stdbuf`, see `add --line-buffered`.
+    pub line_buffered: bool,
+    /// Failed tasks are moved into this group, so all failures are collected in one place.
+    /// Their original group is remembered and used, once they're restarted.
+    pub quarantine_group: Option<String>,
     pub groups: HashMap<String, usize>,
+    /// The maximum amount of queued and stashed tasks per group.
+    /// Use `default` as key for the default queue.
+    pub max_queued: HashMap<String, usize>,
+    /// The maximum amount of tasks that may be started per minute for each group.
+    /// Use `default` as key for the default queue.
+    pub max_starts_per_minute: HashMap<String, usize>,
+    /// Pause a group, once this many of its tasks failed in a row.
+    /// Use `default` as key for the default queue.
+    pub max_consecutive_failures: HashMap<String, usize>,
+    /// The size of the resource slot pool of each group.
+    /// Groups with a slot pool start tasks depending on their slots instead of the parallel limit.
+    /// Use `default` as key for the default queue.
+    pub slots: HashMap<String, usize>,
+    /// The indices of all GPUs the daemon may hand out to tasks of `add --gpus`.
+    pub gpus: Vec<usize>,
+    /// Pause running tasks with a lower priority, if a queued task with a higher priority
+    /// can't be started, because its group is full.
+    /// Preempted tasks are resumed, once there's room again.
+    pub preemption: bool,
+    /// The daily time windows in which groups are allowed to run tasks.
+    /// Use `default` as key for the default queue.
+    pub run_windows: HashMap<String, RunWindow>,
+    /// Remove the temporary directory of `add --tempdir` tasks, once they finished successfully.
+    /// Directories of failed tasks are always kept for debugging.
+    pub clean_tempdirs: bool,
+    /// The container runtime that's used for `add --container`, e.g. `docker` or `podman`.
+    pub container_runtime: String,
+    /// Tasks of these groups are never started by the daemon itself.
+    /// Instead, they're pulled and executed by remote workers via `pueue worker`.
+    pub worker_groups: Vec<String>,
+    /// The transports the daemon listens on at the same time, `unix` and/or `tcp`.
+    /// If empty, only the one chosen by `shared.use_unix_socket` is used.
+    pub listeners: Vec<String>,
+    /// Clients that authenticate with this secret instead of `shared.secret`
+    /// may only read the state and logs, e.g. for monitoring dashboards.
+    pub read_only_secret: Option<String>,
+    /// Append every received message that changes the state to `audit.log` in the pueue directory.
+    pub audit_log: bool,
+    /// Restrict access to the task logs to the user running the daemon.
+    /// Other users can still see logs via the daemon, e.g. through a shared socket group.
+    /// This only sets the permissions of the log directory, use `daemon.log_key` to encrypt them.
+    /// Not supported on Windows, where the daemon refuses to start with this setting.
+    pub private_logs: bool,
+    /// Encrypt the task logs on disk with this key of 64 hexadecimal characters,
+    /// e.g. from `openssl rand -hex 32`. Clients then receive all logs from the daemon.
+    pub log_key: Option<String>,
+    /// A command that prints the key for the task logs, e.g. to fetch it from a password manager.
+    /// It's executed once on startup, if `daemon.log_key` isn't set.
+    pub log_key_command: Option<String>,
+    /// The maximum amount of bytes that's written to the stdout and stderr log of a task each.
+    /// Any further output is dropped and the task is flagged as truncated.
+    pub max_output_bytes: Option<u64>,
+    /// Trim the logs of successful tasks down to their last lines, once they finished.
+    /// Logs of failed tasks are always kept in full.
+    pub successful_log_lines: Option<usize>,
+    /// A command that's executed, once no task of any group is queued or running anymore.
+    pub drained_callback: Option<String>,
+    /// Flag running tasks as overdue, once they run longer than this multiple of their
+    /// expected runtime. This also sends a notification for the task.
+    pub overdue_factor: Option<f64>,
+    /// The interval in seconds in which hosts of `add --wait-for-host` are checked.
+    /// Like all durations, this can also be given as e.g. `30s`, `15m`, `2h` or `1d`.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub host_check_interval: u64,
+    /// Tasks fail, if their host of `add --wait-for-host` isn't reachable within this many seconds.
+    /// By default, tasks wait forever.
+    #[serde(deserialize_with = "deserialize_optional_duration")]
+    pub host_check_timeout: Option<u64>,
+    /// A command that's executed, whenever a task sends a message via `pueue notify`.
+    /// Supports the `{{id}}`, `{{message}}`, `{{command}}` and `{{group}}` template variables.
+    pub notify_callback: Option<String>,
+    /// The seconds `pueue kill` waits after sending SIGTERM, before it escalates to SIGKILL.
+    /// With 0, tasks are killed immediately.
+    /// There's no SIGTERM on Windows, so only tasks with a stop command or a container get one.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub kill_grace_period: u64,
+    /// The seconds removed tasks are kept in the trash, so they can be restored.
+    /// With 0, tasks are deleted right away.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub trash_retention: u64,
+    /// Tasks of worker groups fail, if their worker didn't send a heartbeat for this many seconds,
+    /// e.g. because the worker's machine went down.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub worker_timeout: u64,
+    /// The certificate and private key in PEM format, with which the daemon authenticates
+    /// itself to clients. Required for the tcp listener, once `shared.tls_ca` is set.
+    pub tls_certificate: Option<String>,
+    pub tls_key: Option<String>,
+}
+
+/// Connection settings of a remote host, which can be used via `add --host`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Host {
+    /// The address of the host. This can also be a host alias from your ssh config.
+    pub address: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// The directory in which tasks are executed on the remote host.
+    /// Defaults to the home directory of the user.
+    pub directory: Option<String>,
+}
+
+impl Shared {
+    /// The address the daemon binds its tcp listener to.
+    pub fn tcp_address(&self, port: &str) -> String {
+        format_address(&self.host, port)
+    }
+
+    /// The address a client connects to.
+    /// Unspecified addresses, which bind to all interfaces, are reached via loopback.
+    pub fn client_tcp_address(&self, port: &str) -> String {
+        format_address(self.client_host(), port)
+    }
+
+    /// The host a client connects to.
+    /// Unspecified addresses, which bind to all interfaces, are reached via loopback.
+    pub fn client_host(&self) -> &str {
+        match self.host.as_str() {
+            "0.0.0.0" => "127.0.0.1",
+            "::" | "[::]" => "::1",
+            host => host,
+        }
+    }
+}
+
+/// Combine host and port. IPv6 addresses have to be put into brackets.
+fn format_address(host: &str, port: &str) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -43,9 +311,45 @@ pub struct Settings {
     pub shared: Shared,
     pub client: Client,
     pub daemon: Daemon,
+    /// All known remote hosts by name.
+    pub hosts: HashMap<String, Host>,
 }
 
 impl Settings {
+    /// Check on which transports the daemon listens, as `(unix socket, tcp)`.
+    pub fn daemon_listeners(&self) -> Result<(bool, bool)> {
+        if self.daemon.listeners.is_empty() {
+            let unix = self.shared.use_unix_socket;
+            return Ok((unix, !unix));
+        }
+
+        let (mut unix, mut tcp) = (false, false);
+        for listener in self.daemon.listeners.iter() {
+            match listener.as_str() {
+                "unix" => unix = true,
+                "tcp" => tcp = true,
+                _ => bail!("Unknown listener {}. Use `unix` or `tcp`.", listener),
+            }
+        }
+        Ok((unix, tcp))
+    }
+
+    /// Whether the client reads the logs directly from the pueue directory.
+    /// Encrypted logs can only be read via the daemon, which knows the key.
+    pub fn read_local_logs(&self) -> bool {
+        self.client.read_local_logs
+            && self.daemon.log_key.is_none()
+            && self.daemon.log_key_command.is_none()
+    }
+
+    /// Check whether the daemon listens on the unix socket,
+    /// i.e. whether the socket has to be removed on shutdown.
+    pub fn listens_on_unix_socket(&self) -> bool {
+        self.daemon_listeners()
+            .map(|(unix, _)| unix)
+            .unwrap_or(false)
+    }
+
     /// This function creates a new configuration instance and
     /// populates it with default values for every option.
     /// If a local config file already exists it is parsed and
@@ -56,22 +360,80 @@ impl Settings {
     pub fn new(require_config: bool, from_file: &Option<PathBuf>) -> Result<Settings> {
         let mut config = Config::new();
 
+        config.set_default("shared.host", "127.0.0.1")?;
         config.set_default("shared.port", "6924")?;
         config.set_default("shared.secret", gen_random_secret())?;
         config.set_default("shared.pueue_directory", default_pueue_path()?)?;
         config.set_default("shared.use_unix_socket", false)?;
         config.set_default("shared.unix_socket_path", get_unix_socket_path()?)?;
+        config.set_default("shared.unix_socket_mode", None::<String>)?;
+        config.set_default("shared.unix_socket_group", None::<String>)?;
+        config.set_default(
+            "shared.redacted_envs",
+            vec!["*_TOKEN", "*_PASSWORD", "*_SECRET"],
+        )?;
+        config.set_default("shared.tls_ca", None::<String>)?;
 
         // Client specific config
         config.set_default("client.read_local_logs", true)?;
         config.set_default("client.show_confirmation_questions", false)?;
+        config.set_default("client.confirm_destructive", true)?;
+        config.set_default("client.confirm_date_expressions", true)?;
         config.set_default("client.max_status_lines", None::<i64>)?;
+        config.set_default("client.status_limit", None::<i64>)?;
+        config.set_default("client.status_time_columns", vec!["start", "end"])?;
+        config.set_default("client.max_command_width", None::<i64>)?;
+        config.set_default("client.truncate_commands", false)?;
+        config.set_default("client.path_mappings", Vec::<String>::new())?;
+        config.set_default("client.tls_certificate", None::<String>)?;
+        config.set_default("client.tls_key", None::<String>)?;
 
         // Daemon specific config
         config.set_default("daemon.default_parallel_tasks", 1)?;
+        config.set_default("daemon.max_parallel_tasks", None::<i64>)?;
         config.set_default("daemon.pause_on_failure", false)?;
         config.set_default("daemon.callback", None::<String>)?;
+        config.set_default("daemon.on_exit", HashMap::<String, String>::new())?;
+        config.set_default("daemon.line_buffered", false)?;
+        config.set_default("daemon.quarantine_group", None::<String>)?;
         config.set_default("daemon.groups", HashMap::<String, i64>::new())?;
+        config.set_default("daemon.max_queued", HashMap::<String, i64>::new())?;
+        config.set_default(
+            "daemon.max_starts_per_minute",
+            HashMap::<String, i64>::new(),
+        )?;
+        config.set_default(
+            "daemon.max_consecutive_failures",
+            HashMap::<String, i64>::new(),
+        )?;
+        config.set_default("daemon.slots", HashMap::<String, i64>::new())?;
+        config.set_default("daemon.gpus", Vec::<i64>::new())?;
+        config.set_default("daemon.preemption", false)?;
+        config.set_default("daemon.run_windows", HashMap::<String, String>::new())?;
+        config.set_default("daemon.clean_tempdirs", true)?;
+        config.set_default("daemon.container_runtime", "docker")?;
+        config.set_default("daemon.worker_groups", Vec::<String>::new())?;
+        config.set_default("daemon.listeners", Vec::<String>::new())?;
+        config.set_default("daemon.read_only_secret", None::<String>)?;
+        config.set_default("daemon.audit_log", false)?;
+        config.set_default("daemon.private_logs", false)?;
+        config.set_default("daemon.log_key", None::<String>)?;
+        config.set_default("daemon.log_key_command", None::<String>)?;
+        config.set_default("daemon.max_output_bytes", None::<i64>)?;
+        config.set_default("daemon.successful_log_lines", None::<i64>)?;
+        config.set_default("daemon.drained_callback", None::<String>)?;
+        config.set_default("daemon.overdue_factor", None::<f64>)?;
+        config.set_default("daemon.host_check_interval", 5)?;
+        config.set_default("daemon.host_check_timeout", None::<i64>)?;
+        config.set_default("daemon.kill_grace_period", 10)?;
+        config.set_default("daemon.notify_callback", None::<String>)?;
+        config.set_default("daemon.trash_retention", 7 * 24 * 60 * 60)?;
+        config.set_default("daemon.worker_timeout", 60)?;
+        config.set_default("daemon.tls_certificate", None::<String>)?;
+        config.set_default("daemon.tls_key", None::<String>)?;
+
+        // Remote hosts
+        config.set_default("hosts", HashMap::<String, String>::new())?;
 
         // Load the config from a very specific file path
         if let Some(path) = from_file {
@@ -0,0 +1,12 @@
+//! The shell commands, which execute tasks and callbacks.
+//! They're shared by the daemon and remote workers, so tasks are executed the same way.
+pub use crate::platform::shell::{compile_shell_command, prepend_setup_command};
+
+/// Prepend the setup command of a task in POSIX shell syntax.
+/// If the setup command fails, its exit code becomes the task's exit code.
+pub fn posix_setup_command(before: Option<&str>, command_string: &str) -> String {
+    match before {
+        Some(before) => format!("{{ {}\n}} || exit $?\n{}", before, command_string),
+        None => command_string.to_string(),
+    }
+}
@@ -1,20 +1,37 @@
-use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 
 use anyhow::Result;
 use chrono::prelude::*;
 use log::{debug, error, info};
 use serde_derive::{Deserialize, Serialize};
 
+use crate::encryption::LogKey;
+use crate::message::{StatusDeltaMessage, StatusPageMessage, TaskSelection};
 use crate::settings::Settings;
+use crate::stats::{load_statistics, save_statistics, Statistics};
 use crate::task::{Task, TaskResult, TaskStatus};
+use crate::trash::{expire_trash, load_trash, save_trash, Trash, TrashedTask};
 
 pub type SharedState = Arc<Mutex<State>>;
 
+/// The amount of destructive operations that can be reverted via `pueue undo`.
+const JOURNAL_SIZE: usize = 10;
+
+/// A destructive operation, which can be reverted via `pueue undo`.
+#[derive(Clone, Debug)]
+pub struct JournalEntry {
+    /// The name of the operation, e.g. `remove`.
+    pub operation: String,
+    /// The affected tasks as they were before the operation.
+    pub tasks: Vec<Task>,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct State {
     max_id: usize,
@@ -24,6 +41,36 @@ pub struct State {
     /// Represents whether the group is currently paused or running
     pub groups: HashMap<String, bool>,
     config_path: Option<PathBuf>,
+    /// Increases with every saved change. Used for incremental status updates.
+    pub revision: u64,
+    /// Run statistics of each label or command, which are used to estimate runtimes.
+    /// They're kept across `clean` and `reset` and are saved in their own file.
+    #[serde(skip)]
+    pub statistics: Statistics,
+    /// Removed tasks, which can still be restored. They're saved in their own file.
+    #[serde(skip)]
+    trash: Trash,
+    /// The first revision of this daemon session. Earlier changes aren't known.
+    #[serde(skip)]
+    first_revision: u64,
+    /// The revision at which each task has been changed the last time.
+    #[serde(skip)]
+    task_revisions: BTreeMap<usize, u64>,
+    /// The revision at which tasks have been removed.
+    #[serde(skip)]
+    removed_tasks: BTreeMap<usize, u64>,
+    /// Tasks that have been added, changed or removed since the last revision.
+    #[serde(skip)]
+    changed_tasks: BTreeSet<usize>,
+    /// The most recent destructive operations of this daemon session.
+    #[serde(skip)]
+    journal: Vec<JournalEntry>,
+    /// The last heartbeat of the worker of each task that's executed by a remote worker.
+    #[serde(skip)]
+    pub worker_heartbeats: HashMap<usize, Instant>,
+    /// The key, with which the task logs are encrypted, if `daemon.log_key` is configured.
+    #[serde(skip)]
+    pub log_key: Option<LogKey>,
 }
 
 /// This is the full representation of the current state of the Pueue daemon.
@@ -52,33 +99,184 @@ impl State {
             tasks: BTreeMap::new(),
             groups,
             config_path,
+            revision: 0,
+            statistics: Statistics::new(),
+            trash: Trash::new(),
+            first_revision: 0,
+            task_revisions: BTreeMap::new(),
+            removed_tasks: BTreeMap::new(),
+            changed_tasks: BTreeSet::new(),
+            journal: Vec::new(),
+            worker_heartbeats: HashMap::new(),
+            log_key: None,
         };
         state.restore();
         state.save();
         state
     }
 
-    pub fn add_task(&mut self, mut task: Task) -> usize {
-        task.id = self.max_id;
-        self.tasks.insert(self.max_id, task);
+    pub fn add_task(&mut self, task: Task) -> usize {
+        let id = self.max_id;
         self.max_id += 1;
+        self.replace_task(id, task);
+        id
+    }
+
+    /// The id of the queued or stashed task with the given unique key, if there's one.
+    pub fn queued_task_with_key(&self, key: &str) -> Option<usize> {
+        self.tasks
+            .iter()
+            .find(|(_, task)| task.is_queued() && task.unique_key.as_deref() == Some(key))
+            .map(|(id, _)| *id)
+    }
+
+    /// Insert a task under the given id, e.g. to replace a queued task with the same unique key.
+    pub fn replace_task(&mut self, id: usize, mut task: Task) {
+        task.id = id;
+        task.expected_runtime = self.expected_runtime(&task);
+        self.tasks.insert(id, task);
+        self.changed_tasks.insert(id);
         self.save();
-        self.max_id - 1
+    }
+
+    /// Get a task for modification. The task is part of the next revision.
+    pub fn task_mut(&mut self, id: usize) -> Option<&mut Task> {
+        let task = self.tasks.get_mut(&id)?;
+        self.changed_tasks.insert(id);
+        Some(task)
+    }
+
+    /// Remove a task. The removal is part of the next revision.
+    pub fn remove_task(&mut self, id: usize) -> Option<Task> {
+        let task = self.tasks.remove(&id)?;
+        self.changed_tasks.insert(id);
+        Some(task)
+    }
+
+    /// Remember that a task has been modified directly, e.g. while iterating over all tasks.
+    pub fn mark_changed(&mut self, id: usize) {
+        self.changed_tasks.insert(id);
     }
 
     pub fn change_status(&mut self, id: usize, new_status: TaskStatus) {
-        if let Some(ref mut task) = self.tasks.get_mut(&id) {
+        if let Some(task) = self.task_mut(id) {
             task.status = new_status;
             self.save();
         };
     }
 
     pub fn set_enqueue_at(&mut self, id: usize, enqueue_at: Option<DateTime<Local>>) {
-        if let Some(ref mut task) = self.tasks.get_mut(&id) {
+        if let Some(task) = self.task_mut(id) {
             task.enqueue_at = enqueue_at;
         }
     }
 
+    /// Add a finished task to the run statistics of its label or command.
+    /// Killed tasks and tasks that never ran are ignored.
+    pub fn record_run(&mut self, task: &Task) {
+        let success = match task.result {
+            Some(TaskResult::Success) => true,
+            Some(TaskResult::Failed(_)) => false,
+            _ => return,
+        };
+        let runtime = match (task.start, task.end) {
+            (Some(start), Some(end)) => (end - start).num_seconds(),
+            _ => return,
+        };
+
+        self.statistics
+            .entry(task.history_key())
+            .or_default()
+            .record(runtime, success);
+        if let Err(error) = save_statistics(&self.settings.shared.pueue_directory, &self.statistics)
+        {
+            error!("Failed to save run statistics: {:?}", error);
+        }
+    }
+
+    /// Move removed tasks into the trash, so they can be restored for `daemon.trash_retention`.
+    pub fn trash_tasks(&mut self, tasks: Vec<Task>) {
+        if tasks.is_empty() || self.settings.daemon.trash_retention == 0 {
+            return;
+        }
+        let now = Local::now();
+        for task in tasks {
+            let trashed = TrashedTask {
+                task,
+                removed_at: now,
+            };
+            self.trash.insert(trashed.task.id, trashed);
+        }
+        expire_trash(&mut self.trash, self.settings.daemon.trash_retention, now);
+        self.save_trash();
+    }
+
+    /// All tasks in the trash, which haven't expired yet.
+    pub fn trashed_tasks(&mut self) -> Vec<TrashedTask> {
+        if expire_trash(
+            &mut self.trash,
+            self.settings.daemon.trash_retention,
+            Local::now(),
+        ) {
+            self.save_trash();
+        }
+        self.trash.values().cloned().collect()
+    }
+
+    /// Put tasks from the trash back into the task list.
+    /// Tasks keep their id, unless it has been taken by another task in the meantime.
+    /// Returns the new ids of all restored tasks and the ids that aren't in the trash.
+    pub fn restore_from_trash(&mut self, task_ids: Vec<usize>) -> (Vec<usize>, Vec<usize>) {
+        let trashed_ids: Vec<usize> = self
+            .trashed_tasks()
+            .iter()
+            .map(|trashed| trashed.task.id)
+            .collect();
+        let mut restored = Vec::new();
+        let mut missing = Vec::new();
+        for task_id in task_ids {
+            if !trashed_ids.contains(&task_id) {
+                missing.push(task_id);
+                continue;
+            }
+            let task = self.trash.remove(&task_id).unwrap().task;
+            let id_taken = self.tasks.contains_key(&task_id);
+            if id_taken {
+                restored.push(self.add_task(task));
+            } else {
+                self.tasks.insert(task_id, task);
+                self.changed_tasks.insert(task_id);
+                restored.push(task_id);
+            }
+        }
+
+        if !restored.is_empty() {
+            self.save_trash();
+            self.save();
+        }
+        (restored, missing)
+    }
+
+    /// Delete all tasks in the trash for good. Returns the amount of deleted tasks.
+    pub fn empty_trash(&mut self) -> usize {
+        let count = self.trash.len();
+        self.trash.clear();
+        self.save_trash();
+
+        count
+    }
+
+    fn save_trash(&self) {
+        if let Err(error) = save_trash(&self.settings.shared.pueue_directory, &self.trash) {
+            error!("Failed to save the trash: {:?}", error);
+        }
+    }
+
+    /// The expected runtime of a task in seconds, based on previous runs of the same label or command.
+    pub fn expected_runtime(&self, task: &Task) -> Option<i64> {
+        self.statistics.get(&task.history_key())?.expected_runtime()
+    }
+
     /// Check if the given group already exists.
     /// If it doesn't exist yet, create a state entry and a new settings entry.
     pub fn create_group(&mut self, group: &str) -> Result<()> {
@@ -100,10 +298,11 @@ impl State {
         self.groups.remove(group);
 
         // Reset all tasks with removed group to the default
-        for (_, task) in self.tasks.iter_mut() {
+        for (id, task) in self.tasks.iter_mut() {
             if let Some(group_name) = &task.group {
                 if group_name == group {
                     task.group = None;
+                    self.changed_tasks.insert(*id);
                 }
             }
         }
@@ -136,6 +335,102 @@ impl State {
             .collect()
     }
 
+    /// Check whether a task may start regarding its dependencies.
+    /// All of its `dependencies` have to be finished, one of its `any_dependencies` has to be
+    /// successful and one of its `failure_dependencies` has to be failed, if there are any.
+    /// Removed tasks are ignored.
+    pub fn dependencies_fulfilled(&self, task: &Task) -> bool {
+        let all_done = task
+            .dependencies
+            .iter()
+            .flat_map(|id| self.tasks.get(id))
+            .all(|task| task.status == TaskStatus::Done);
+
+        let mut any = task
+            .any_dependencies
+            .iter()
+            .flat_map(|id| self.tasks.get(id))
+            .peekable();
+        let any_succeeded =
+            any.peek().is_none() || any.any(|task| task.result == Some(TaskResult::Success));
+
+        let any_failed = task.failure_dependencies.is_empty()
+            || task
+                .failure_dependencies
+                .iter()
+                .flat_map(|id| self.tasks.get(id))
+                .any(|task| task.failed());
+
+        all_done && any_succeeded && any_failed
+    }
+
+    /// Check whether a task of `add --after-failure` won't ever run,
+    /// since all tasks it handles the failure of finished without failing.
+    pub fn failure_handler_skipped(&self, task: &Task) -> bool {
+        if task.failure_dependencies.is_empty() {
+            return false;
+        }
+
+        task.failure_dependencies
+            .iter()
+            .flat_map(|id| self.tasks.get(id))
+            .all(|task| task.status == TaskStatus::Done && !task.failed())
+    }
+
+    /// Return a failed dependency of the task, due to which it can never start.
+    /// That's either a failed task of its `dependencies` or, if all of its `any_dependencies`
+    /// failed, the last of them.
+    pub fn failed_dependency(&self, task: &Task) -> Option<usize> {
+        let failed = task
+            .dependencies
+            .iter()
+            .flat_map(|id| self.tasks.get(id))
+            .find(|task| task.failed());
+        if let Some(failed) = failed {
+            return Some(failed.id);
+        }
+
+        let any: Vec<&Task> = task
+            .any_dependencies
+            .iter()
+            .flat_map(|id| self.tasks.get(id))
+            .collect();
+        if !any.is_empty() && any.iter().all(|task| task.failed()) {
+            return any.last().map(|task| task.id);
+        }
+
+        None
+    }
+
+    /// Whether any unfinished task still reads the output of the given task via `--stdin-from`.
+    pub fn has_stdin_readers(&self, task_id: usize) -> bool {
+        self.tasks
+            .values()
+            .any(|task| task.stdin_from == Some(task_id) && !task.is_done())
+    }
+
+    /// Check whether a group already reached its `max_queued` limit.
+    /// Queued and stashed tasks both count towards this limit.
+    pub fn queue_is_full(&self, group: &Option<String>) -> bool {
+        self.free_queue_slots(group) == Some(0)
+    }
+
+    /// The amount of tasks that can still be added to a group, until it reaches `max_queued`.
+    /// `None`, if the group doesn't have a limit.
+    pub fn free_queue_slots(&self, group: &Option<String>) -> Option<usize> {
+        let key = group.as_deref().unwrap_or("default");
+        let max_queued = *self.settings.daemon.max_queued.get(key)?;
+
+        let queued = self
+            .tasks
+            .iter()
+            .filter(|(_, task)| task.is_queued())
+            .filter(|(_, task)| group == &task.group)
+            .count();
+
+        Some(max_queued.saturating_sub(queued))
+    }
+
     /// This checks, whether the given task_ids are in the specified statuses.
     /// The first result is the list of task_ids that match these statuses.
     /// The second result is the list of task_ids that don't match these statuses.
@@ -180,19 +475,222 @@ impl State {
     pub fn reset(&mut self) {
         self.backup();
         self.max_id = 0;
+        self.changed_tasks.extend(self.tasks.keys());
         self.tasks = BTreeMap::new();
+        self.journal.clear();
         self.set_status_for_all_groups(true);
     }
 
+    /// Remember the tasks affected by a destructive operation, as they were before it.
+    pub fn journal(&mut self, operation: &str, tasks: Vec<Task>) {
+        if tasks.is_empty() {
+            return;
+        }
+        self.journal.push(JournalEntry {
+            operation: operation.to_string(),
+            tasks,
+        });
+        if self.journal.len() > JOURNAL_SIZE {
+            self.journal.remove(0);
+        }
+    }
+
+    /// Revert the most recent destructive operation by restoring the affected tasks.
+    /// Tasks that have been started or restarted in the meantime are left alone.
+    /// Returns the name of the operation and the ids of all restored tasks.
+    pub fn undo(&mut self) -> Option<(String, Vec<usize>)> {
+        let entry = self.journal.pop()?;
+        let mut restored = Vec::new();
+        for task in entry.tasks {
+            let restorable = match self.tasks.get(&task.id) {
+                Some(current) => current.status == TaskStatus::Done,
+                None => true,
+            };
+            if restorable {
+                restored.push(task.id);
+                self.trash.remove(&task.id);
+                self.changed_tasks.insert(task.id);
+                self.tasks.insert(task.id, task);
+            }
+        }
+        restored.sort_unstable();
+        if entry.operation == "remove" {
+            self.save_trash();
+        }
+        self.save();
+
+        Some((entry.operation, restored))
+    }
+
+    /// The path of the config file that has been explicitly passed to the daemon, if any.
+    pub fn config_path(&self) -> &Option<PathBuf> {
+        &self.config_path
+    }
+
     pub fn save_settings(&mut self) -> Result<()> {
         self.settings.save(&self.config_path)
     }
 
     /// Convenience wrapper around save_to_file.
+    /// Every save with changed tasks creates a new revision of the state.
     pub fn save(&mut self) {
+        self.track_changes();
         self.save_to_file(false);
     }
 
+    /// Bump the revision, if any tasks have been changed or removed since the last one.
+    /// Remember the revision at which each of those tasks changed.
+    pub fn track_changes(&mut self) {
+        if self.changed_tasks.is_empty() {
+            return;
+        }
+
+        self.revision += 1;
+        for id in std::mem::take(&mut self.changed_tasks) {
+            if self.tasks.contains_key(&id) {
+                self.task_revisions.insert(id, self.revision);
+                self.removed_tasks.remove(&id);
+            } else {
+                self.task_revisions.remove(&id);
+                self.removed_tasks.insert(id, self.revision);
+            }
+        }
+    }
+
+    /// Collect all changes since the given revision.
+    /// Returns `None`, if the changes aren't known, e.g. since the daemon has been restarted.
+    /// A client has to fetch the full state in that case.
+    pub fn changes_since(&self, revision: u64) -> Option<StatusDeltaMessage> {
+        if revision < self.first_revision || revision > self.revision {
+            return None;
+        }
+
+        let changed = self
+            .task_revisions
+            .iter()
+            .filter(|(_, changed_at)| **changed_at > revision)
+            .filter_map(|(id, _)| self.tasks.get(id).map(|task| (*id, task.clone())))
+            .collect();
+        let removed = self
+            .removed_tasks
+            .iter()
+            .filter(|(_, removed_at)| **removed_at > revision)
+            .map(|(id, _)| *id)
+            .collect();
+
+        Some(StatusDeltaMessage {
+            revision: self.revision,
+            full: None,
+            changed,
+            removed,
+            running: self.running,
+            groups: self.groups.clone(),
+        })
+    }
+
+    /// Resolve a selection into a sorted list of task ids.
+    /// Explicitly given ids of non-existing tasks are kept, so they can be reported as such.
+    /// An empty selection selects nothing.
+    pub fn select_tasks(&self, selection: &TaskSelection) -> Vec<usize> {
+        if selection == &TaskSelection::default() {
+            return Vec::new();
+        }
+
+        let mut task_ids: Vec<usize> =
+            if selection.task_ids.is_empty() && selection.ranges.is_empty() {
+                self.tasks.keys().cloned().collect()
+            } else {
+                let in_ranges = self.tasks.keys().filter(|task_id| {
+                    selection
+                        .ranges
+                        .iter()
+                        .any(|(start, end)| (*start..=*end).contains(*task_id))
+                });
+                selection
+                    .task_ids
+                    .iter()
+                    .chain(in_ranges)
+                    .cloned()
+                    .collect()
+            };
+
+        task_ids.retain(|task_id| match self.tasks.get(task_id) {
+            Some(task) => selection.matches(task),
+            None => true,
+        });
+        task_ids.sort_unstable();
+        task_ids.dedup();
+
+        task_ids
+    }
+
+    /// Return a copy of the state, which only contains a single page of tasks.
+    /// If a group or query is given, only matching tasks are considered.
+    pub fn page(&self, message: &StatusPageMessage) -> State {
+        let StatusPageMessage {
+            group,
+            query,
+            offset,
+            limit,
+            ..
+        } = message;
+        let tasks = self
+            .tasks
+            .iter()
+            .filter(|(_, task)| group.is_none() || group == &task.group)
+            .filter(|(_, task)| match query {
+                Some(query) => task.matches_query(query),
+                None => true,
+            })
+            .filter(|(_, task)| message.pipeline.is_none() || message.pipeline == task.pipeline)
+            .filter(|(_, task)| message.matches_time_range(task))
+            .filter(|(_, task)| {
+                message
+                    .exit_code
+                    .as_ref()
+                    .is_none_or(|filter| filter.matches(task))
+            })
+            .skip(*offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|(id, task)| (*id, task.clone()))
+            .collect();
+
+        State {
+            max_id: self.max_id,
+            settings: self.settings.clone(),
+            running: self.running,
+            tasks,
+            groups: self.groups.clone(),
+            config_path: self.config_path.clone(),
+            revision: self.revision,
+            statistics: Statistics::new(),
+            trash: Trash::new(),
+            first_revision: self.first_revision,
+            task_revisions: BTreeMap::new(),
+            removed_tasks: BTreeMap::new(),
+            changed_tasks: BTreeSet::new(),
+            journal: Vec::new(),
+            worker_heartbeats: HashMap::new(),
+            log_key: None,
+        }
+    }
+
+    /// Apply an incremental status update, which has been received from the daemon.
+    pub fn apply_delta(&mut self, delta: StatusDeltaMessage) {
+        if let Some(state) = delta.full {
+            *self = state;
+            return;
+        }
+
+        self.tasks.extend(delta.changed);
+        for id in delta.removed.iter() {
+            self.tasks.remove(id);
+        }
+        self.running = delta.running;
+        self.groups = delta.groups;
+        self.revision = delta.revision;
+    }
+
     /// Save the current current state in a file with a timestamp.
     /// At the same time remove old state logs from the log directory.
     /// This function is called, when large changes to the state are applied, e.g. clean/reset.
@@ -232,7 +730,7 @@ impl State {
         };
 
         // Write to temporary log file first, to prevent loss due to crashes.
-        if let Err(error) = fs::write(&temp, serialized) {
+        if let Err(error) = write_private_file(&temp, &serialized) {
             error!(
                 "Failed to write log to directory. File permissions? Error: {:?}",
                 error
@@ -259,6 +757,15 @@ impl State {
     /// Restore the last state from a previous session.
     /// The state is stored as json in the log directory.
     fn restore(&mut self) {
+        match load_statistics(&self.settings.shared.pueue_directory) {
+            Ok(statistics) => self.statistics = statistics,
+            Err(error) => error!("Failed to restore run statistics: {:?}", error),
+        }
+        match load_trash(&self.settings.shared.pueue_directory) {
+            Ok(trash) => self.trash = trash,
+            Err(error) => error!("Failed to restore the trash: {:?}", error),
+        }
+
         let path = Path::new(&self.settings.shared.pueue_directory).join("state.json");
 
         // Ignore if the file doesn't exist. It doesn't have to.
@@ -341,6 +848,8 @@ impl State {
 
         self.running = state.running;
         self.max_id = state.max_id;
+        self.revision = state.revision;
+        self.first_revision = state.revision;
     }
 
     /// Remove old logs that aren't needed any longer.
@@ -373,3 +882,16 @@ impl State {
         Ok(())
     }
 }
+
+/// The state contains the secrets of the daemon, so only the daemon's user may read it.
+fn write_private_file(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(not(windows))]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)?.write_all(content.as_bytes())
+}
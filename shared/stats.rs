@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use serde_derive::{Deserialize, Serialize};
+
+/// The amount of recent runtimes that are kept to calculate percentiles.
+const RECENT_RUNS: usize = 100;
+
+/// The amount of recent runtimes that are used to estimate the runtime of new tasks.
+const ESTIMATE_RUNS: usize = 10;
+
+/// Statistics of all runs of each label or command, see `Task::history_key`.
+pub type Statistics = BTreeMap<String, RunStatistics>;
+
+/// Aggregated statistics about all finished runs of a label or command.
+/// Killed tasks aren't counted, since they didn't finish on their own.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct RunStatistics {
+    pub runs: u64,
+    pub failures: u64,
+    /// The summed up runtime of all successful runs in seconds.
+    pub total_runtime: i64,
+    /// The runtimes of the most recent successful runs in seconds, the newest last.
+    pub recent_runtimes: Vec<i64>,
+}
+
+impl RunStatistics {
+    pub fn record(&mut self, runtime: i64, success: bool) {
+        self.runs += 1;
+        if !success {
+            self.failures += 1;
+            return;
+        }
+
+        self.total_runtime += runtime;
+        self.recent_runtimes.push(runtime);
+        if self.recent_runtimes.len() > RECENT_RUNS {
+            self.recent_runtimes.remove(0);
+        }
+    }
+
+    /// The average runtime of all successful runs in seconds.
+    pub fn mean(&self) -> Option<i64> {
+        let successes = self.runs - self.failures;
+        if successes == 0 {
+            return None;
+        }
+        Some(self.total_runtime / successes as i64)
+    }
+
+    /// The 95th percentile of the recent successful runtimes in seconds.
+    pub fn p95(&self) -> Option<i64> {
+        if self.recent_runtimes.is_empty() {
+            return None;
+        }
+        let mut runtimes = self.recent_runtimes.clone();
+        runtimes.sort_unstable();
+        let index = (runtimes.len() * 95).div_ceil(100) - 1;
+        Some(runtimes[index])
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        self.failures as f64 / self.runs as f64
+    }
+
+    /// The average of the last few successful runtimes.
+    /// Recent runs are a better estimate than the overall mean, if a command got slower over time.
+    pub fn expected_runtime(&self) -> Option<i64> {
+        let start = self.recent_runtimes.len().saturating_sub(ESTIMATE_RUNS);
+        let runtimes = &self.recent_runtimes[start..];
+        if runtimes.is_empty() {
+            return None;
+        }
+        Some(runtimes.iter().sum::<i64>() / runtimes.len() as i64)
+    }
+}
+
+/// Load the statistics from the pueue directory.
+/// A missing file simply means that no task has finished yet.
+pub fn load_statistics(pueue_directory: &str) -> Result<Statistics> {
+    let path = Path::new(pueue_directory).join("stats.json");
+    if !path.exists() {
+        return Ok(Statistics::new());
+    }
+
+    let data = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+/// Save the statistics in the pueue directory.
+/// Like the state, they're written to a temporary file first to prevent loss due to crashes.
+pub fn save_statistics(pueue_directory: &str, statistics: &Statistics) -> Result<()> {
+    let path = Path::new(pueue_directory);
+    let temp = path.join("stats.json.partial");
+    fs::write(&temp, serde_json::to_string(statistics)?)?;
+    fs::rename(&temp, path.join("stats.json"))?;
+
+    Ok(())
+}
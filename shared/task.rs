@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 use chrono::prelude::*;
 use serde_derive::{Deserialize, Serialize};
@@ -36,6 +38,114 @@ pub enum TaskResult {
     Killed,
     /// A dependency of the task failed.
     DependencyFailed,
+    /// The task only runs on failure, see `add --after-failure`, but its dependencies succeeded.
+    Skipped,
+    /// The dependencies of the task didn't finish within its `add --dependency-timeout`.
+    DependencyTimeout,
+}
+
+/// A file that has been produced by a task.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Artifact {
+    pub path: String,
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+/// A custom message a task sent via `pueue notify`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Notification {
+    pub time: DateTime<Local>,
+    pub text: String,
+}
+
+/// Which environment variables a task inherits from the client that added it.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum EnvironmentMode {
+    /// The whole environment of the client.
+    All,
+    /// Only the given variables of the client's environment.
+    Only(Vec<String>),
+    /// No environment variables at all, except for Pueue's own `PUEUE_*` variables.
+    Empty,
+}
+
+impl EnvironmentMode {
+    /// Select the variables of the client's environment that are passed to the task.
+    pub fn filter(&self, envs: HashMap<String, String>) -> HashMap<String, String> {
+        match self {
+            EnvironmentMode::All => envs,
+            EnvironmentMode::Only(names) => envs
+                .into_iter()
+                .filter(|(key, _)| names.contains(key))
+                .collect(),
+            EnvironmentMode::Empty => HashMap::new(),
+        }
+    }
+}
+
+/// The progress a task reported via `pueue progress`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Progress {
+    /// A percentage between 0 and 100.
+    Percentage(f64),
+    /// The amount of processed items out of a total, e.g. `5/120` files.
+    Count(u64, u64),
+}
+
+impl FromStr for Progress {
+    type Err = String;
+
+    /// Parse a progress like `42%`, `42.5%` or `5/120`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if let Some(percentage) = text.strip_suffix('%') {
+            return match percentage.trim().parse::<f64>() {
+                Ok(percentage) if (0.0..=100.0).contains(&percentage) => {
+                    Ok(Progress::Percentage(percentage))
+                }
+                _ => Err(format!("Invalid percentage: {}", text)),
+            };
+        }
+
+        let invalid = || format!("Invalid progress {}. Use e.g. `42%` or `5/120`", text);
+        let (done, total) = text.split_once('/').ok_or_else(invalid)?;
+        let done = done.trim().parse().map_err(|_| invalid())?;
+        let total = total.trim().parse().map_err(|_| invalid())?;
+        Ok(Progress::Count(done, total))
+    }
+}
+
+/// Parse a list of CPUs like `0-3,6`, as it's used by `add --cpus`.
+pub fn parse_cpu_list(text: &str) -> Result<Vec<usize>, String> {
+    let invalid = || format!("Invalid CPU list {}. Use e.g. `0-3,6`", text);
+    let mut cpus = Vec::new();
+    for part in text.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.trim().parse().map_err(|_| invalid())?;
+                let last: usize = last.trim().parse().map_err(|_| invalid())?;
+                if first > last {
+                    return Err(invalid());
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(part.trim().parse().map_err(|_| invalid())?),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+
+    Ok(cpus)
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Progress::Percentage(percentage) => write!(f, "{:.0}%", percentage),
+            Progress::Count(done, total) => write!(f, "{}/{}", done, total),
+        }
+    }
 }
 
 /// Representation of a task.
@@ -43,7 +153,7 @@ pub enum TaskResult {
 /// exit_code, output and end won't be initialized, until the task has finished.
 /// The output of the task is written into seperate files.
 /// Upon task completion, the output is read from the files and put into the struct.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Task {
     pub id: usize,
     pub command: String,
@@ -52,9 +162,100 @@ pub struct Task {
     pub group: Option<String>,
     pub enqueue_at: Option<DateTime<Local>>,
     pub dependencies: Vec<usize>,
+    /// The task may start, as soon as any one of these tasks succeeded.
+    pub any_dependencies: Vec<usize>,
+    /// The task only starts, once one of these tasks failed.
+    /// It's skipped, if all of them succeeded.
+    pub failure_dependencies: Vec<usize>,
+    /// The seconds since the task has been added, after which it fails,
+    /// if its dependencies still aren't fulfilled.
+    pub dependency_timeout: Option<i64>,
+    /// Only a single queued task with the same key may exist at any time.
+    pub unique_key: Option<String>,
+    /// The amount of resource slots this task occupies in its group's slot pool.
+    pub slots: usize,
+    /// Tasks with the same named lock never run at the same time, even across groups.
+    pub lock: Option<String>,
+    /// A queued task isn't started, until this path exists. It's removed, once the path exists.
+    pub wait_for_path: Option<String>,
+    /// A queued task isn't started, until a TCP connection to this `host:port` succeeds.
+    /// It's removed, once the host is reachable.
+    pub wait_for_host: Option<String>,
+    /// The id of the task, whose stdout is fed into this task's stdin.
+    pub stdin_from: Option<usize>,
+    /// Glob patterns of the files this task produces.
+    pub artifact_patterns: Vec<String>,
+    /// The files that matched the artifact patterns, once the task finished.
+    pub artifacts: Vec<Artifact>,
+    /// Run the task inside a fresh temporary directory instead of `path`.
+    pub use_tempdir: bool,
+    /// The temporary directory that has been created for this task.
+    pub tempdir: Option<String>,
+    /// The container image this task is executed in.
+    pub container: Option<String>,
+    /// The remote host this task is executed on via SSH.
+    pub host: Option<String>,
+    /// A command that's executed to stop the task on `pueue kill`, instead of sending SIGTERM.
+    pub stop_command: Option<String>,
+    /// A command that's executed, once the task's process exited, e.g. to clean up.
+    pub on_exit: Option<String>,
+    /// A setup command that's executed right before the actual command in the same shell.
+    /// The actual command isn't executed, if the setup command fails.
+    pub before: Option<String>,
+    /// Which environment variables of the client the task inherited.
+    /// Unless it's `All`, the daemon's own environment isn't passed to the task either.
+    pub env_mode: EnvironmentMode,
+    /// Force line buffering of the task's output, even though it's written to a file.
+    pub line_buffered: bool,
+    /// Run the command via a login shell, so the user's shell profile is loaded.
+    pub login_shell: bool,
+    /// Load the `.envrc` of the working directory via `direnv` before running the command.
+    pub direnv: bool,
+    /// The remote worker that pulled this task.
+    pub worker: Option<String>,
+    /// A short, human readable description of the task.
+    pub label: Option<String>,
+    /// Queued tasks with a higher priority are started first.
+    pub priority: i32,
+    /// The CPUs the task is restricted to, e.g. `0-3,6`.
+    pub cpus: Option<String>,
+    /// The amount of GPUs of the daemon's GPU pool this task needs.
+    pub gpus: usize,
+    /// The indices of the GPUs that have been assigned to the task, once it started.
+    pub assigned_gpus: Vec<usize>,
+    /// The array job this task belongs to, see `add --array`.
+    /// Array jobs are identified by the id of their first task.
+    pub array_id: Option<usize>,
+    /// The index of this task inside of its array job. Passed via `PUEUE_ARRAY_INDEX`.
+    pub array_index: Option<usize>,
+    /// The name of the pipeline this task belongs to, see `pueue apply`.
+    pub pipeline: Option<String>,
+    /// The task failed and has been moved into `daemon.quarantine_group`.
+    pub quarantined: bool,
+    /// The group the task has been in before it was quarantined.
+    pub original_group: Option<String>,
+    /// The task hasn't been executed, since this task recently succeeded with the same
+    /// command, path and environment. See `add --cache`.
+    pub cached_from: Option<usize>,
+    /// The task this one has been restarted from. Used to show previous attempts.
+    pub restarted_from: Option<usize>,
+    /// The signal that killed the task's process, if it didn't exit by itself.
+    pub signal: Option<i32>,
+    /// The output exceeded `daemon.max_output_bytes` and has been truncated.
+    pub output_truncated: bool,
+    /// The task runs much longer than usual, see `daemon.overdue_factor`.
+    pub overdue: bool,
+    /// All messages the task sent via `pueue notify`.
+    pub notifications: Vec<Notification>,
+    /// The last progress the task reported via `pueue progress`.
+    pub progress: Option<Progress>,
+    /// The estimated runtime in seconds, based on previous runs of the same label or command.
+    pub expected_runtime: Option<i64>,
     pub status: TaskStatus,
     pub prev_status: TaskStatus,
     pub result: Option<TaskResult>,
+    /// The time the task has been added to the queue.
+    pub created_at: DateTime<Local>,
     pub start: Option<DateTime<Local>>,
     pub end: Option<DateTime<Local>>,
 }
@@ -77,9 +278,51 @@ impl Task {
             group,
             enqueue_at,
             dependencies,
+            any_dependencies: Vec::new(),
+            failure_dependencies: Vec::new(),
+            dependency_timeout: None,
+            unique_key: None,
+            slots: 1,
+            lock: None,
+            wait_for_path: None,
+            wait_for_host: None,
+            stdin_from: None,
+            artifact_patterns: Vec::new(),
+            artifacts: Vec::new(),
+            use_tempdir: false,
+            tempdir: None,
+            container: None,
+            host: None,
+            stop_command: None,
+            on_exit: None,
+            before: None,
+            env_mode: EnvironmentMode::All,
+            line_buffered: false,
+            login_shell: false,
+            direnv: false,
+            worker: None,
+            label: None,
+            priority: 0,
+            cpus: None,
+            gpus: 0,
+            assigned_gpus: Vec::new(),
+            array_id: None,
+            array_index: None,
+            pipeline: None,
+            quarantined: false,
+            original_group: None,
+            cached_from: None,
+            restarted_from: None,
+            signal: None,
+            output_truncated: false,
+            overdue: false,
+            notifications: Vec::new(),
+            progress: None,
+            expected_runtime: None,
             status: starting_status.clone(),
             prev_status: starting_status,
             result: None,
+            created_at: Local::now(),
             start: None,
             end: None,
         }
@@ -95,14 +338,89 @@ impl Task {
             group: None,
             enqueue_at: None,
             dependencies: Vec::new(),
+            any_dependencies: Vec::new(),
+            failure_dependencies: Vec::new(),
+            dependency_timeout: None,
+            unique_key: task.unique_key.clone(),
+            slots: task.slots,
+            lock: task.lock.clone(),
+            wait_for_path: None,
+            wait_for_host: None,
+            stdin_from: task.stdin_from,
+            artifact_patterns: task.artifact_patterns.clone(),
+            artifacts: Vec::new(),
+            use_tempdir: task.use_tempdir,
+            tempdir: None,
+            container: task.container.clone(),
+            host: task.host.clone(),
+            stop_command: task.stop_command.clone(),
+            on_exit: task.on_exit.clone(),
+            before: task.before.clone(),
+            env_mode: task.env_mode.clone(),
+            line_buffered: task.line_buffered,
+            login_shell: task.login_shell,
+            direnv: task.direnv,
+            worker: None,
+            label: task.label.clone(),
+            priority: task.priority,
+            cpus: task.cpus.clone(),
+            gpus: task.gpus,
+            assigned_gpus: Vec::new(),
+            array_id: None,
+            array_index: None,
+            pipeline: None,
+            quarantined: false,
+            original_group: None,
+            cached_from: None,
+            restarted_from: None,
+            signal: None,
+            output_truncated: false,
+            overdue: false,
+            notifications: Vec::new(),
+            progress: None,
+            expected_runtime: None,
             status: TaskStatus::Queued,
             prev_status: TaskStatus::Queued,
             result: None,
+            created_at: Local::now(),
             start: None,
             end: None,
         }
     }
 
+    /// The directory the task is actually executed in.
+    pub fn working_directory(&self) -> &str {
+        match &self.tempdir {
+            Some(tempdir) => tempdir,
+            None => &self.path,
+        }
+    }
+
+    /// Check whether the command, label or path of the task contains the query.
+    pub fn matches_query(&self, query: &str) -> bool {
+        self.command.contains(query)
+            || self.path.contains(query)
+            || self
+                .label
+                .as_ref()
+                .map(|label| label.contains(query))
+                .unwrap_or(false)
+    }
+
+    /// Runtimes of previous tasks with the same label or, if there's none, the same command
+    /// are used to estimate the runtime of this task.
+    pub fn history_key(&self) -> String {
+        match &self.label {
+            Some(label) => format!("label:{}", label),
+            None => format!("command:{}", self.command),
+        }
+    }
+
+    /// Whether the task still waits for a path or host, before it may be started.
+    pub fn is_waiting(&self) -> bool {
+        self.wait_for_path.is_some() || self.wait_for_host.is_some()
+    }
+
     pub fn is_running(&self) -> bool {
         self.status == TaskStatus::Running || self.status == TaskStatus::Paused
     }
@@ -111,12 +429,25 @@ impl Task {
         self.status == TaskStatus::Done
     }
 
+    /// Check whether the task still has to run or is running right now.
+    /// Stashed tasks only count, if they're scheduled to be enqueued.
+    pub fn is_pending(&self) -> bool {
+        match self.status {
+            TaskStatus::Queued | TaskStatus::Running | TaskStatus::Paused | TaskStatus::Locked => {
+                true
+            }
+            TaskStatus::Stashed => self.enqueue_at.is_some(),
+            TaskStatus::Done => false,
+        }
+    }
+
     /// Check if the task errored.
     /// The only case when it didn't error is if it didn't run yet or if the task exited successfully.
     pub fn failed(&self) -> bool {
         match self.result {
             None => false,
             Some(TaskResult::Success) => false,
+            Some(TaskResult::Skipped) => false,
             _ => true,
         }
     }
@@ -0,0 +1,183 @@
+//! TLS for tcp connections between clients and the daemon.
+//! Both sides authenticate each other via certificates, which are signed by the same CA.
+//! Clients still have to send the secret afterwards.
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_std::future::timeout;
+use async_trait::async_trait;
+use futures_rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use futures_rustls::rustls::crypto::{ring, CryptoProvider};
+use futures_rustls::rustls::server::WebPkiClientVerifier;
+use futures_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use futures_rustls::{client, server, TlsAcceptor, TlsConnector};
+use log::warn;
+
+use crate::platform::socket::{GenericListener, GenericSocket, Listener, Socket};
+use crate::settings::Settings;
+
+/// Clients that don't finish the handshake within this time are dropped,
+/// so they can't block other clients from connecting.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Read all certificates of a PEM file.
+pub fn load_certificates(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).context(format!("Failed to open certificate {}", path))?;
+    let certificates = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .context(format!("Failed to read certificate {}", path))?;
+    if certificates.is_empty() {
+        bail!("Couldn't find any certificate in {}", path);
+    }
+
+    Ok(certificates)
+}
+
+/// Read the private key of a PEM file.
+pub fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).context(format!("Failed to open private key {}", path))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .context(format!("Failed to read private key {}", path))?
+        .ok_or_else(|| anyhow!("Couldn't find any private key in {}", path))
+}
+
+fn load_root_store(ca_path: &str) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for certificate in load_certificates(ca_path)? {
+        roots
+            .add(certificate)
+            .context(format!("Invalid CA certificate {}", ca_path))?;
+    }
+
+    Ok(roots)
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// The TLS config of the daemon.
+/// Only clients with a certificate, which has been signed by the CA, may connect.
+pub fn build_server_config(
+    ca_path: &str,
+    certificate_path: &str,
+    key_path: &str,
+) -> Result<ServerConfig> {
+    let verifier = WebPkiClientVerifier::builder_with_provider(
+        Arc::new(load_root_store(ca_path)?),
+        provider(),
+    )
+    .build()
+    .context("Failed to create the client certificate verifier")?;
+
+    ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(
+            load_certificates(certificate_path)?,
+            load_private_key(key_path)?,
+        )
+        .context("Invalid certificate or private key of the daemon")
+}
+
+/// The TLS config of clients.
+/// The daemon's certificate has to be signed by the CA as well.
+pub fn build_client_config(
+    ca_path: &str,
+    certificate_path: &str,
+    key_path: &str,
+) -> Result<ClientConfig> {
+    ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(load_root_store(ca_path)?)
+        .with_client_auth_cert(
+            load_certificates(certificate_path)?,
+            load_private_key(key_path)?,
+        )
+        .context("Invalid certificate or private key of the client")
+}
+
+/// The TLS config of the daemon's tcp listener, if `shared.tls_ca` is set.
+pub fn daemon_config(settings: &Settings) -> Result<Option<ServerConfig>> {
+    let ca_path = match &settings.shared.tls_ca {
+        Some(ca_path) => ca_path,
+        None => return Ok(None),
+    };
+    match (&settings.daemon.tls_certificate, &settings.daemon.tls_key) {
+        (Some(certificate), Some(key)) => Ok(Some(build_server_config(ca_path, certificate, key)?)),
+        _ => bail!("`shared.tls_ca` requires `daemon.tls_certificate` and `daemon.tls_key`."),
+    }
+}
+
+/// The TLS config of clients for tcp connections, if `shared.tls_ca` is set.
+pub fn client_config(settings: &Settings) -> Result<Option<ClientConfig>> {
+    let ca_path = match &settings.shared.tls_ca {
+        Some(ca_path) => ca_path,
+        None => return Ok(None),
+    };
+    match (&settings.client.tls_certificate, &settings.client.tls_key) {
+        (Some(certificate), Some(key)) => Ok(Some(build_client_config(ca_path, certificate, key)?)),
+        _ => bail!("`shared.tls_ca` requires `client.tls_certificate` and `client.tls_key`."),
+    }
+}
+
+/// A listener, which performs the TLS handshake with each client, before handing it out.
+pub struct TlsListener {
+    listener: Listener,
+    acceptor: TlsAcceptor,
+}
+
+impl TlsListener {
+    pub fn new(listener: Listener, config: ServerConfig) -> Self {
+        TlsListener {
+            listener,
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+        }
+    }
+}
+
+#[async_trait]
+impl GenericListener for TlsListener {
+    async fn accept<'a>(&'a self) -> Result<Socket> {
+        // Failed handshakes, e.g. of clients without a valid certificate, only drop that client.
+        loop {
+            let socket = self.listener.accept().await?;
+            let peer = socket.peer();
+            match timeout(HANDSHAKE_TIMEOUT, self.acceptor.accept(socket)).await {
+                Ok(Ok(stream)) => return Ok(Box::new(stream)),
+                Ok(Err(err)) => warn!("TLS handshake with {} failed: {}", peer, err),
+                Err(_) => warn!("TLS handshake with {} timed out", peer),
+            }
+        }
+    }
+}
+
+impl GenericSocket for server::TlsStream<Socket> {
+    fn peer(&self) -> String {
+        format!("{} via tls", self.get_ref().0.peer())
+    }
+}
+
+impl GenericSocket for client::TlsStream<Socket> {
+    fn peer(&self) -> String {
+        format!("{} via tls", self.get_ref().0.peer())
+    }
+}
+
+/// Perform the TLS handshake with the daemon on an established connection.
+/// The daemon's certificate has to be valid for the given host.
+pub async fn connect(socket: Socket, config: ClientConfig, host: &str) -> Result<Socket> {
+    let server_name = ServerName::try_from(host.to_string())
+        .context(format!("Invalid host name {} for TLS", host))?;
+    let connector = TlsConnector::from(Arc::new(config));
+    let stream = connector
+        .connect(server_name, socket)
+        .await
+        .context("TLS handshake with the daemon failed")?;
+
+    Ok(Box::new(stream))
+}
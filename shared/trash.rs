@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::prelude::*;
+use chrono::Duration;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::task::Task;
+
+/// All removed tasks by their id, which can still be restored via `pueue trash restore`.
+pub type Trash = BTreeMap<usize, TrashedTask>;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TrashedTask {
+    pub task: Task,
+    pub removed_at: DateTime<Local>,
+}
+
+/// Delete all tasks from the trash, which have been removed more than `retention` seconds ago.
+/// Returns whether any task has been deleted.
+pub fn expire_trash(trash: &mut Trash, retention: u64, now: DateTime<Local>) -> bool {
+    let before = trash.len();
+    let retention = Duration::seconds(retention as i64);
+    trash.retain(|_, trashed| trashed.removed_at + retention > now);
+
+    trash.len() != before
+}
+
+/// Load the trash from the pueue directory.
+/// A missing file simply means that no task has been removed yet.
+pub fn load_trash(pueue_directory: &str) -> Result<Trash> {
+    let path = Path::new(pueue_directory).join("trash.json");
+    if !path.exists() {
+        return Ok(Trash::new());
+    }
+
+    let data = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+/// Save the trash in the pueue directory.
+/// Like the state, it's written to a temporary file first to prevent loss due to crashes.
+pub fn save_trash(pueue_directory: &str, trash: &Trash) -> Result<()> {
+    let path = Path::new(pueue_directory);
+    let temp = path.join("trash.json.partial");
+    fs::write(&temp, serde_json::to_string(trash)?)?;
+    fs::rename(&temp, path.join("trash.json"))?;
+
+    Ok(())
+}
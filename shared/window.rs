@@ -0,0 +1,93 @@
+use anyhow::{bail, Context, Result};
+use chrono::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+
+/// A daily time window in which a group is allowed to run tasks, e.g. `01:00` to `06:00`.
+/// Outside of the window, the group behaves as if it was paused.
+///
+/// If `end` is before `start`, the window spans midnight.
+/// The `days` refer to the day the window starts on.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RunWindow {
+    pub start: String,
+    pub end: String,
+    /// The weekdays on which the window is open, e.g. `["Mon", "Tue"]`. Defaults to every day.
+    pub days: Option<Vec<String>>,
+    /// What happens to running tasks at the end of the window. Either `pause` or `kill`.
+    /// Paused tasks are resumed, once the window opens again.
+    /// By default, running tasks simply continue.
+    pub at_end: Option<String>,
+}
+
+/// What happens to running tasks of a group, once its run window ends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowEndAction {
+    Continue,
+    Pause,
+    Kill,
+}
+
+impl RunWindow {
+    /// Ensure that all times, days and the end action can be parsed.
+    pub fn check(&self) -> Result<()> {
+        self.times()?;
+        self.weekdays()?;
+        self.end_action()?;
+        Ok(())
+    }
+
+    /// Check whether the window is open at the given point in time.
+    /// Windows that can't be parsed are always open, see `check` for proper error handling.
+    pub fn contains(&self, time: DateTime<Local>) -> bool {
+        let (start, end) = match self.times() {
+            Ok(times) => times,
+            Err(_) => return true,
+        };
+        let days = self.weekdays().unwrap_or(None);
+        let allowed = |day: Weekday| days.as_ref().is_none_or(|days| days.contains(&day));
+
+        let now = time.time();
+        let weekday = time.weekday();
+        if start < end {
+            allowed(weekday) && start <= now && now < end
+        } else {
+            (allowed(weekday) && now >= start) || (allowed(weekday.pred()) && now < end)
+        }
+    }
+
+    pub fn end_action(&self) -> Result<WindowEndAction> {
+        match self.at_end.as_deref() {
+            None => Ok(WindowEndAction::Continue),
+            Some("pause") => Ok(WindowEndAction::Pause),
+            Some("kill") => Ok(WindowEndAction::Kill),
+            Some(action) => bail!(
+                "Unknown run window end action {}. Use `pause` or `kill`.",
+                action
+            ),
+        }
+    }
+
+    fn times(&self) -> Result<(NaiveTime, NaiveTime)> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .with_context(|| format!("Failed to parse run window time {}. Use HH:MM.", time))
+        };
+        Ok((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    fn weekdays(&self) -> Result<Option<Vec<Weekday>>> {
+        let days = match &self.days {
+            Some(days) => days,
+            None => return Ok(None),
+        };
+
+        let mut weekdays = Vec::new();
+        for day in days {
+            match day.parse::<Weekday>() {
+                Ok(weekday) => weekdays.push(weekday),
+                Err(_) => bail!("Failed to parse run window day {}. Use e.g. Mon.", day),
+            }
+        }
+        Ok(Some(weekdays))
+    }
+}
@@ -0,0 +1,39 @@
+//! Fixtures, which are shared by the integration tests.
+//! Not every test file uses all of them.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use pueue::settings::Client;
+use pueue::task::{Task, TaskStatus};
+
+/// A task of the default group without any environment variables or dependencies.
+pub fn task(command: &str, status: TaskStatus) -> Task {
+    Task::new(
+        command.into(),
+        "/".into(),
+        HashMap::new(),
+        None,
+        status,
+        None,
+        Vec::new(),
+    )
+}
+
+/// Client settings without any path mappings.
+pub fn client() -> Client {
+    Client {
+        read_local_logs: true,
+        show_confirmation_questions: false,
+        confirm_destructive: true,
+        confirm_date_expressions: true,
+        max_status_lines: None,
+        status_limit: None,
+        status_time_columns: Vec::new(),
+        max_command_width: None,
+        truncate_commands: false,
+        path_mappings: Vec::new(),
+        tls_certificate: None,
+        tls_key: None,
+    }
+}
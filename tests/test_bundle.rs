@@ -0,0 +1,89 @@
+use pueue::bundle::{Bundle, Pipeline};
+
+#[test]
+fn test_bundle_order() {
+    let bundle = Bundle::from_yaml(
+        "
+tasks:
+  deploy:
+    command: ./deploy.sh
+    after: [test, build]
+  test:
+    command: cargo test
+    after: [build]
+  build:
+    command: cargo build
+    group: compile
+    env:
+      RUSTFLAGS: -Dwarnings
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        bundle.ordered_names().unwrap(),
+        vec!["build", "test", "deploy"]
+    );
+    assert_eq!(bundle.tasks["build"].group.as_deref(), Some("compile"));
+    assert_eq!(bundle.tasks["build"].env["RUSTFLAGS"], "-Dwarnings");
+}
+
+#[test]
+fn test_invalid_bundles() {
+    // Unknown dependency
+    assert!(Bundle::from_yaml("tasks:\n  a:\n    command: ls\n    after: [b]\n").is_err());
+    // Dependency cycle
+    assert!(Bundle::from_yaml(
+        "tasks:\n  a:\n    command: ls\n    after: [b]\n  b:\n    command: ls\n    after: [a]\n"
+    )
+    .is_err());
+    // Unknown fields
+    assert!(Bundle::from_yaml("tasks:\n  a:\n    command: ls\n    grup: test\n").is_err());
+    assert!(Bundle::from_yaml("tasks: {}\n").is_err());
+}
+
+#[test]
+fn test_pipeline_stages() {
+    let pipeline = Pipeline::from_yaml(
+        "
+name: release
+stages:
+  - name: build
+    tasks:
+      linux:
+        command: ./build.sh linux
+      windows:
+        command: ./build.sh windows
+  - name: deploy
+    tasks:
+      upload:
+        command: ./upload.sh
+      announce:
+        command: ./announce.sh
+        after: [upload]
+",
+    )
+    .unwrap();
+    let bundle = pipeline.to_bundle().unwrap();
+
+    assert_eq!(
+        bundle.ordered_names().unwrap(),
+        vec![
+            "build.linux",
+            "build.windows",
+            "deploy.upload",
+            "deploy.announce"
+        ]
+    );
+    assert_eq!(
+        bundle.tasks["deploy.announce"].after,
+        vec!["build.linux", "build.windows", "deploy.upload"]
+    );
+    assert!(bundle.tasks["build.linux"].after.is_empty());
+
+    // Dependencies may only refer to tasks of the same stage.
+    assert!(Pipeline::from_yaml(
+        "name: p\nstages:\n  - name: a\n    tasks:\n      x:\n        command: ls\n  - name: b\n    tasks:\n      y:\n        command: ls\n        after: [x]\n"
+    )
+    .is_err());
+}
@@ -0,0 +1,42 @@
+use std::io::Write;
+
+use pueue::duration::parse_duration;
+use pueue::settings::Settings;
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("90"), Ok(90));
+    assert_eq!(parse_duration("30s"), Ok(30));
+    assert_eq!(parse_duration("15m"), Ok(15 * 60));
+    assert_eq!(parse_duration("2h"), Ok(2 * 60 * 60));
+    assert_eq!(parse_duration("1d"), Ok(24 * 60 * 60));
+    assert_eq!(parse_duration("1h30m"), Ok(90 * 60));
+
+    assert!(parse_duration("").is_err());
+    assert!(parse_duration("-5").is_err());
+    assert!(parse_duration("h").is_err());
+    assert!(parse_duration("1h30").unwrap_err().contains("has no unit"));
+    assert!(parse_duration("5x")
+        .unwrap_err()
+        .contains("unknown unit 'x'"));
+}
+
+#[test]
+fn test_duration_settings() {
+    let mut file = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+    writeln!(
+        file,
+        "daemon:\n  trash_retention: 2d\n  host_check_timeout: 1m\n  kill_grace_period: 5"
+    )
+    .unwrap();
+
+    let settings = Settings::new(true, &Some(file.path().to_path_buf())).unwrap();
+    assert_eq!(settings.daemon.kill_grace_period, 5);
+    assert_eq!(settings.daemon.trash_retention, 2 * 24 * 60 * 60);
+    assert_eq!(settings.daemon.host_check_timeout, Some(60));
+
+    // Our protocol is a binary format, which always contains plain seconds.
+    let encoded = bincode::serialize(&settings).unwrap();
+    let decoded: Settings = bincode::deserialize(&encoded).unwrap();
+    assert_eq!(decoded.daemon.trash_retention, 2 * 24 * 60 * 60);
+}
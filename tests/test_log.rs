@@ -0,0 +1,104 @@
+use std::fs;
+
+use std::io::Write;
+
+use pueue::encryption::LogKey;
+use pueue::log::*;
+
+#[test]
+fn test_find_matches_merges_context() {
+    let content = "a\nb\nmatch 1\nc\nmatch 2\nd\ne\nf\ng\nmatch 3";
+    let matches = find_matches(content, "match", 1, false);
+
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].start, 2);
+    assert_eq!(matches[0].lines, vec!["b", "match 1", "c", "match 2", "d"]);
+    assert_eq!(matches[1].start, 9);
+    assert_eq!(matches[1].lines, vec!["g", "match 3"]);
+}
+
+#[test]
+fn test_find_matches_without_match() {
+    assert!(find_matches("a\nb", "match", 2, true).is_empty());
+    assert!(find_matches("", "match", 2, true).is_empty());
+}
+
+#[test]
+fn test_trim_log_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().to_string_lossy().to_string();
+    fs::create_dir(dir.path().join("task_logs")).unwrap();
+    let (stdout, stderr) = get_log_paths(0, &path);
+    fs::write(&stdout, "1\n2\n3\n4\n").unwrap();
+    fs::write(&stderr, "error\n").unwrap();
+
+    trim_log_files(0, &path, None, 2).unwrap();
+    assert_eq!(
+        fs::read_to_string(&stdout).unwrap(),
+        "[pueue] 2 lines trimmed\n3\n4\n"
+    );
+    assert_eq!(fs::read_to_string(&stderr).unwrap(), "error\n");
+
+    // Trimming again keeps counting the removed lines and doesn't count the marker as output.
+    fs::write(&stdout, "[pueue] 2 lines trimmed\n3\n4\n5\n").unwrap();
+    trim_log_files(0, &path, None, 2).unwrap();
+    assert_eq!(
+        fs::read_to_string(&stdout).unwrap(),
+        "[pueue] 3 lines trimmed\n4\n5\n"
+    );
+}
+
+#[test]
+fn test_read_last_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().to_string_lossy().to_string();
+    fs::create_dir(dir.path().join("task_logs")).unwrap();
+    let (stdout, _) = get_log_paths(0, &path);
+
+    assert_eq!(read_last_line(0, &path, None), None);
+    fs::write(&stdout, "start\n\x1b[32m 10%\r\x1b[32m 20%\r\n\n").unwrap();
+    assert_eq!(read_last_line(0, &path, None), Some("20%".to_string()));
+}
+
+#[test]
+fn test_encrypted_logs() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().to_string_lossy().to_string();
+    fs::create_dir(dir.path().join("task_logs")).unwrap();
+    let key = LogKey::from_hex(&"ab".repeat(32)).unwrap();
+    let key = Some(&key);
+
+    let (mut stdout, _) = create_log_file_handles(0, &path, key).unwrap();
+    stdout.write_all(b"secret 1\n").unwrap();
+    append_to_log_files(0, &path, key, b"secret 2\nsecret 3\n", b"error\n").unwrap();
+
+    // Nothing is stored in plain text.
+    let (stdout_path, stderr_path) = get_log_paths(0, &path);
+    let raw = |path| String::from_utf8_lossy(&fs::read(path).unwrap()).to_string();
+    assert!(!raw(&stdout_path).contains("secret"));
+    assert!(!raw(&stderr_path).contains("error"));
+
+    let logs = read_log_files(0, &path, key).unwrap();
+    assert_eq!(logs.0, "secret 1\nsecret 2\nsecret 3\n");
+    assert_eq!(logs.1, "error\n");
+    assert_eq!(read_last_line(0, &path, key), Some("secret 3".to_string()));
+
+    // Trimmed logs stay encrypted.
+    trim_log_files(0, &path, key, 1).unwrap();
+    let logs = read_log_files(0, &path, key).unwrap();
+    assert_eq!(logs.0, "[pueue] 2 lines trimmed\nsecret 3\n");
+    assert!(!raw(&stdout_path).contains("secret"));
+
+    // Without the key, the logs can't be read.
+    assert!(read_log_files(0, &path, None).is_err());
+    assert!(LogKey::from_hex("abc").is_err());
+}
+
+#[test]
+fn test_strip_ansi() {
+    assert_eq!(
+        strip_ansi("\x1b[1;31merror\x1b[0m:\tfailed\n 10%\r 50%\r100%\r\ndone"),
+        "error:\tfailed\n100%\ndone"
+    );
+    assert_eq!(strip_ansi("plain\n\n"), "plain\n\n");
+}
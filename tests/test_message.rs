@@ -0,0 +1,140 @@
+use chrono::prelude::*;
+use chrono::Duration;
+
+use pueue::message::{
+    client_instant, EnqueueMessage, ExitCodeFilter, Message, StatusPageMessage, TaskSelection,
+};
+use pueue::task::{parse_cpu_list, Progress, TaskResult, TaskStatus};
+
+mod helper;
+use helper::task;
+
+#[test]
+fn test_status_page_time_range() {
+    let mut task = task("true", TaskStatus::Done);
+    let mut message = StatusPageMessage {
+        group: None,
+        query: None,
+        pipeline: None,
+        offset: 0,
+        limit: None,
+        added_after: Some(Local::now() - Duration::hours(1)),
+        added_before: None,
+        finished_after: None,
+        finished_before: None,
+        exit_code: None,
+    };
+    assert!(message.matches_time_range(&task));
+
+    // Unfinished tasks never match a finished filter.
+    message.finished_after = Some(Local::now() - Duration::hours(1));
+    assert!(!message.matches_time_range(&task));
+
+    task.end = Some(Local::now());
+    assert!(message.matches_time_range(&task));
+
+    message.added_before = Some(Local::now() - Duration::minutes(30));
+    assert!(!message.matches_time_range(&task));
+}
+
+#[test]
+fn test_exit_code_filter() {
+    let mut task = task("false", TaskStatus::Done);
+    let failed: ExitCodeFilter = "!=0".parse().unwrap();
+    let two: ExitCodeFilter = "2".parse().unwrap();
+    let at_least: ExitCodeFilter = ">= 2".parse().unwrap();
+    assert!("!=zero".parse::<ExitCodeFilter>().is_err());
+
+    // Tasks without an exit code never match.
+    assert!(!failed.matches(&task));
+    task.result = Some(TaskResult::Killed);
+    assert!(!failed.matches(&task));
+
+    task.result = Some(TaskResult::Failed(2));
+    assert!(failed.matches(&task) && two.matches(&task) && at_least.matches(&task));
+    task.result = Some(TaskResult::Success);
+    assert!(!failed.matches(&task) && !two.matches(&task) && !at_least.matches(&task));
+}
+
+#[test]
+fn test_task_selection() {
+    let mut task = task("ffmpeg -i in.mkv out.mp4", TaskStatus::Queued);
+    let selection = TaskSelection::from_ranges(&[(1, 1), (3, 12)], None, None);
+    assert_eq!(selection.task_ids, vec![1]);
+    assert_eq!(selection.ranges, vec![(3, 12)]);
+    assert!(!selection.is_plain());
+    assert!(TaskSelection::from(vec![1, 2]).is_plain());
+
+    let default = TaskSelection::from_ranges(&[], Some("default".into()), Some("ffmpeg*".into()));
+    assert!(default.matches(&task));
+    task.group = Some("encode".into());
+    assert!(!default.matches(&task));
+
+    let encode = TaskSelection::from_ranges(&[], Some("encode".into()), Some("*.mp3".into()));
+    assert!(!encode.matches(&task));
+}
+
+#[test]
+fn test_parse_progress() {
+    assert_eq!("42%".parse(), Ok(Progress::Percentage(42.0)));
+    assert_eq!(" 5 / 120 ".parse(), Ok(Progress::Count(5, 120)));
+    assert!("120%".parse::<Progress>().is_err());
+    assert!("five".parse::<Progress>().is_err());
+    assert_eq!(Progress::Percentage(42.4).to_string(), "42%");
+}
+
+#[test]
+fn test_parse_cpu_list() {
+    assert_eq!(parse_cpu_list("0-3"), Ok(vec![0, 1, 2, 3]));
+    assert_eq!(parse_cpu_list("6, 1-2,2"), Ok(vec![1, 2, 6]));
+    assert!(parse_cpu_list("3-1").is_err());
+    assert!(parse_cpu_list("").is_err());
+}
+
+#[test]
+fn test_client_instant() {
+    let time = FixedOffset::east(2 * 3600)
+        .ymd(2026, 3, 29)
+        .and_hms(9, 0, 0);
+    let message = EnqueueMessage {
+        task_ids: vec![0],
+        enqueue_at: Some(time),
+    };
+
+    // The daemon can show the time in the client's timezone, while the instant stays the same.
+    let encoded = bincode::serialize(&message).unwrap();
+    let decoded: EnqueueMessage = bincode::deserialize(&encoded).unwrap();
+    let enqueue_at = decoded.enqueue_at.unwrap();
+    assert_eq!(enqueue_at.format("%H:%M %:z").to_string(), "09:00 +02:00");
+    assert_eq!(enqueue_at.with_timezone(&Utc).hour(), 7);
+
+    let now = Local::now();
+    assert_eq!(client_instant(now), now);
+}
+
+/// The handshake and error responses have to be understood by clients and daemons of all versions,
+/// so their encoding must never change.
+#[test]
+fn test_handshake_variant_indices() {
+    // The variant index as u32, followed by the length of the string as u64.
+    let encoded = |index: u32, text: &str| -> Vec<u8> {
+        let mut bytes = index.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(text.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(text.as_bytes());
+        bytes
+    };
+
+    let messages = vec![
+        (Message::Version("0.9.0".into()), encoded(0, "0.9.0")),
+        (Message::Success("ok".into()), encoded(1, "ok")),
+        (Message::Failure("error".into()), encoded(2, "error")),
+    ];
+    for (message, bytes) in messages {
+        assert_eq!(bincode::serialize(&message).unwrap(), bytes);
+    }
+
+    match bincode::deserialize(&encoded(0, "1.2.3")).unwrap() {
+        Message::Version(version) => assert_eq!(version, "1.2.3"),
+        message => panic!("Decoded unexpected message {:?}", message),
+    }
+}
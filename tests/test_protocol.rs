@@ -41,3 +41,14 @@ async fn test_single_huge_payload() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_version_compatibility() {
+    assert!(versions_are_compatible("1.2.3", "1.0.0"));
+    assert!(versions_are_compatible("0.8.2-alpha.0", "0.8.1"));
+    assert!(!versions_are_compatible("0.8.2", "0.9.0"));
+    assert!(!versions_are_compatible("1.0.0", "2.0.0"));
+
+    // The bincode indices of the messages changed after 0.8.1.
+    assert!(!versions_are_compatible(env!("CARGO_PKG_VERSION"), "0.8.1"));
+}
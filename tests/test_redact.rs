@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+
+use pueue::redact::*;
+use pueue::task::TaskStatus;
+
+mod helper;
+use helper::task;
+
+#[test]
+fn test_redact_task() {
+    let patterns = vec!["*_TOKEN".to_string(), "PASSWORD".to_string()];
+    let mut envs = HashMap::new();
+    envs.insert("API_TOKEN".to_string(), "abc123".to_string());
+    envs.insert("PASSWORD".to_string(), "".to_string());
+    envs.insert("HOME".to_string(), "/home/user".to_string());
+
+    let mut task = task(
+        "PASSWORD=hunter2 curl -H 'Bearer abc123' $HOME",
+        TaskStatus::Queued,
+    );
+    task.envs = envs;
+    redact_task(&mut task, &patterns);
+
+    assert_eq!(
+        task.command,
+        format!("PASSWORD={} curl -H 'Bearer {}' $HOME", MASK, MASK)
+    );
+    assert_eq!(task.envs["API_TOKEN"], MASK);
+    assert_eq!(task.envs["PASSWORD"], MASK);
+    assert_eq!(task.envs["HOME"], "/home/user");
+}
@@ -0,0 +1,80 @@
+use pueue::settings::{PathMapping, Shared};
+
+mod helper;
+use helper::client;
+
+fn shared(host: &str) -> Shared {
+    Shared {
+        host: host.to_string(),
+        port: "6924".into(),
+        secret: "secret".into(),
+        pueue_directory: "/tmp".into(),
+        use_unix_socket: false,
+        unix_socket_path: "/tmp/pueue.socket".into(),
+        unix_socket_mode: None,
+        unix_socket_group: None,
+        redacted_envs: Vec::new(),
+        tls_ca: None,
+    }
+}
+
+#[test]
+fn test_tcp_address() {
+    assert_eq!(shared("127.0.0.1").tcp_address("6924"), "127.0.0.1:6924");
+    assert_eq!(shared("::1").tcp_address("6924"), "[::1]:6924");
+    assert_eq!(shared("[::1]").tcp_address("6924"), "[::1]:6924");
+
+    // Clients can't connect to unspecified addresses everywhere.
+    assert_eq!(shared("0.0.0.0").client_tcp_address("1"), "127.0.0.1:1");
+    assert_eq!(shared("::").client_tcp_address("1"), "[::1]:1");
+    assert_eq!(shared("10.0.0.2").client_tcp_address("1"), "10.0.0.2:1");
+}
+
+#[test]
+fn test_map_path() {
+    let mapping = |client: &str, daemon: &str| PathMapping {
+        client: client.into(),
+        daemon: daemon.into(),
+    };
+    let mut client = client();
+    // Without any mappings, the daemon runs on the same machine.
+    assert_eq!(client.map_path("/home/me").unwrap(), "/home/me");
+
+    client.path_mappings = vec![
+        mapping("/Users/me", "/home/me"),
+        mapping("/Users/me/data/", "/mnt/data"),
+        mapping("C:\\Projects", "/srv/projects"),
+    ];
+    assert_eq!(client.map_path("/Users/me").unwrap(), "/home/me");
+    assert_eq!(client.map_path("/Users/me/code").unwrap(), "/home/me/code");
+    // The longest prefix wins.
+    assert_eq!(client.map_path("/Users/me/data/x").unwrap(), "/mnt/data/x");
+    assert_eq!(
+        client.map_path("C:\\Projects\\app\\src").unwrap(),
+        "/srv/projects/app/src"
+    );
+
+    // Prefixes have to match whole path components.
+    assert!(client.map_path("/Users/meow").is_err());
+    assert!(client
+        .map_path("/tmp")
+        .unwrap_err()
+        .to_string()
+        .contains("/tmp"));
+}
+
+#[test]
+fn test_unmap_path() {
+    let mut client = client();
+    assert_eq!(client.unmap_path("/home/me").unwrap(), "/home/me");
+
+    client.path_mappings = vec![PathMapping {
+        client: "C:\\Users\\me".into(),
+        daemon: "/home/me".into(),
+    }];
+    assert_eq!(
+        client.unmap_path("/home/me/code/app").unwrap(),
+        "C:\\Users\\me\\code\\app"
+    );
+    assert_eq!(client.unmap_path("/srv"), None);
+}
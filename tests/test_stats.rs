@@ -0,0 +1,20 @@
+use pueue::stats::RunStatistics;
+
+#[test]
+fn test_run_statistics() {
+    let mut statistics = RunStatistics::default();
+    assert_eq!(statistics.mean(), None);
+    assert_eq!(statistics.p95(), None);
+
+    for runtime in 1..=20 {
+        statistics.record(runtime, true);
+    }
+    statistics.record(100, false);
+
+    assert_eq!(statistics.runs, 21);
+    assert_eq!(statistics.mean(), Some(10));
+    assert_eq!(statistics.p95(), Some(19));
+    assert!((statistics.failure_rate() - 1.0 / 21.0).abs() < f64::EPSILON);
+    // Only the last ten successful runs are used for estimates.
+    assert_eq!(statistics.expected_runtime(), Some(15));
+}
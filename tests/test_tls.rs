@@ -0,0 +1,111 @@
+#![cfg(not(windows))]
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_std::os::unix::net::{UnixListener, UnixStream};
+use async_std::task;
+use futures_rustls::rustls::crypto::ring;
+use futures_rustls::rustls::{ClientConfig, RootCertStore};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa, KeyPair};
+
+use pueue::protocol::*;
+use pueue::tls::{
+    build_client_config, build_server_config, connect, load_certificates, TlsListener,
+};
+
+/// Create a certificate for `localhost` and write it and its key to the directory.
+/// Certificates without a CA are signed by themselves.
+fn write_certificate(
+    directory: &Path,
+    name: &str,
+    ca: Option<&(Certificate, KeyPair)>,
+) -> Result<(Certificate, KeyPair)> {
+    let key = KeyPair::generate()?;
+    let certificate = match ca {
+        Some((ca_certificate, ca_key)) => CertificateParams::new(vec!["localhost".into()])?
+            .signed_by(&key, ca_certificate, ca_key)?,
+        None => {
+            let mut params = CertificateParams::new(Vec::<String>::new())?;
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params.self_signed(&key)?
+        }
+    };
+    fs::write(directory.join(format!("{}.pem", name)), certificate.pem())?;
+    fs::write(directory.join(format!("{}.key", name)), key.serialize_pem())?;
+
+    Ok((certificate, key))
+}
+
+/// Send some bytes through the connection and return the daemon's answer.
+async fn echo(socket: &mut Socket) -> Result<Vec<u8>> {
+    send_bytes(b"secret", socket).await?;
+    receive_bytes(socket).await
+}
+
+fn path(directory: &Path, file: &str) -> String {
+    directory.join(file).to_string_lossy().to_string()
+}
+
+#[async_std::test]
+async fn test_client_certificates() -> Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    let dir = tempdir.path();
+    let ca = write_certificate(dir, "ca", None)?;
+    write_certificate(dir, "daemon", Some(&ca))?;
+    write_certificate(dir, "client", Some(&ca))?;
+    // A client, whose certificate has been signed by another CA.
+    let other_ca = write_certificate(dir, "other_ca", None)?;
+    write_certificate(dir, "intruder", Some(&other_ca))?;
+
+    let ca_path = path(dir, "ca.pem");
+    let server_config =
+        build_server_config(&ca_path, &path(dir, "daemon.pem"), &path(dir, "daemon.key"))?;
+    let socket_path = dir.join("pueue.socket");
+    let listener: Listener = Box::new(UnixListener::bind(&socket_path).await?);
+    let listener = TlsListener::new(listener, server_config);
+
+    // Echo the bytes of each client, that passed the handshake.
+    task::spawn(async move {
+        loop {
+            let mut socket = listener.accept().await.unwrap();
+            let bytes = receive_bytes(&mut socket).await.unwrap();
+            send_bytes(&bytes, &mut socket).await.unwrap();
+        }
+    });
+
+    // The daemon rejects the client, since it doesn't trust its certificate.
+    let config = build_client_config(
+        &ca_path,
+        &path(dir, "intruder.pem"),
+        &path(dir, "intruder.key"),
+    )?;
+    let socket: Socket = Box::new(UnixStream::connect(&socket_path).await?);
+    let mut socket = connect(socket, config, "localhost").await?;
+    assert!(echo(&mut socket).await.is_err());
+
+    // Clients without any certificate are rejected as well.
+    let mut roots = RootCertStore::empty();
+    roots.add_parsable_certificates(load_certificates(&ca_path)?);
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let socket: Socket = Box::new(UnixStream::connect(&socket_path).await?);
+    let mut socket = connect(socket, config, "localhost").await?;
+    assert!(echo(&mut socket).await.is_err());
+
+    // Trusted clients can still connect afterwards.
+    let config = build_client_config(&ca_path, &path(dir, "client.pem"), &path(dir, "client.key"))?;
+    let socket: Socket = Box::new(UnixStream::connect(&socket_path).await?);
+    let mut socket = connect(socket, config, "localhost").await?;
+    assert_eq!(echo(&mut socket).await?, b"secret");
+
+    // The client refuses daemons, whose certificate isn't valid for the host.
+    let config = build_client_config(&ca_path, &path(dir, "client.pem"), &path(dir, "client.key"))?;
+    let socket: Socket = Box::new(UnixStream::connect(&socket_path).await?);
+    assert!(connect(socket, config, "example.org").await.is_err());
+
+    Ok(())
+}
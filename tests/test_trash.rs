@@ -0,0 +1,24 @@
+use chrono::{Duration, Local};
+
+use pueue::task::TaskStatus;
+use pueue::trash::{expire_trash, Trash, TrashedTask};
+
+mod helper;
+use helper::task;
+
+#[test]
+fn test_expire_trash() {
+    let now = Local::now();
+    let mut trash = Trash::new();
+    for (id, age) in [(0, 30), (1, 5)] {
+        let mut task = task("ls", TaskStatus::Done);
+        task.id = id;
+        let removed_at = now - Duration::seconds(age);
+        trash.insert(id, TrashedTask { task, removed_at });
+    }
+
+    assert!(!expire_trash(&mut trash, 60, now));
+    assert_eq!(trash.len(), 2);
+    assert!(expire_trash(&mut trash, 10, now));
+    assert_eq!(trash.keys().collect::<Vec<_>>(), vec![&1]);
+}
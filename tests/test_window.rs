@@ -0,0 +1,51 @@
+use chrono::prelude::*;
+
+use pueue::window::{RunWindow, WindowEndAction};
+
+fn window(start: &str, end: &str, days: Option<Vec<&str>>) -> RunWindow {
+    RunWindow {
+        start: start.into(),
+        end: end.into(),
+        days: days.map(|days| days.into_iter().map(String::from).collect()),
+        at_end: None,
+    }
+}
+
+#[test]
+fn test_run_window() {
+    // 2021-01-04 is a monday.
+    let monday = |hour, minute| Local.ymd(2021, 1, 4).and_hms(hour, minute, 0);
+    let tuesday = |hour, minute| Local.ymd(2021, 1, 5).and_hms(hour, minute, 0);
+
+    let nightly = window(
+        "01:00",
+        "06:00",
+        Some(vec!["Mon", "Tue", "Wed", "Thu", "Fri"]),
+    );
+    assert!(nightly.check().is_ok());
+    assert!(nightly.contains(monday(1, 0)));
+    assert!(nightly.contains(monday(5, 59)));
+    assert!(!nightly.contains(monday(6, 0)));
+    assert!(!nightly.contains(Local.ymd(2021, 1, 3).and_hms(2, 0, 0)));
+
+    // Windows that span midnight belong to the day they start on.
+    let overnight = window("22:00", "02:00", Some(vec!["Mon"]));
+    assert!(overnight.contains(monday(23, 0)));
+    assert!(overnight.contains(tuesday(1, 0)));
+    assert!(!overnight.contains(tuesday(23, 0)));
+    assert!(!overnight.contains(monday(1, 0)));
+}
+
+#[test]
+fn test_invalid_run_window() {
+    assert!(window("1am", "06:00", None).check().is_err());
+    assert!(window("01:00", "06:00", Some(vec!["Someday"]))
+        .check()
+        .is_err());
+
+    let mut killing = window("01:00", "06:00", None);
+    killing.at_end = Some("kill".into());
+    assert_eq!(killing.end_action().unwrap(), WindowEndAction::Kill);
+    killing.at_end = Some("explode".into());
+    assert!(killing.check().is_err());
+}
@@ -0,0 +1,27 @@
+# Dynamic completion of task ids and group names for bash.
+# Source this file after the completion script generated by `pueue completions bash`.
+# Task ids and group names are requested from the running daemon via `pueue __complete`.
+
+_pueue_dynamic() {
+    local current previous
+    current="${COMP_WORDS[COMP_CWORD]}"
+    previous="${COMP_WORDS[COMP_CWORD-1]}"
+
+    if [[ "${previous}" == "-g" || "${previous}" == "--group" ]]; then
+        COMPREPLY=($(compgen -W "$(pueue __complete groups 2>/dev/null | cut -f1)" -- "${current}"))
+        return 0
+    fi
+
+    case "${COMP_WORDS[1]}" in
+        kill|remove|start|pause|stash|enqueue|restart|edit|log|follow|switch|send|artifacts)
+            if [[ "${current}" != -* ]]; then
+                COMPREPLY=($(compgen -W "$(pueue __complete tasks 2>/dev/null | cut -f1)" -- "${current}"))
+                return 0
+            fi
+            ;;
+    esac
+
+    _pueue "$@"
+}
+
+complete -F _pueue_dynamic -o bashdefault -o default pueue